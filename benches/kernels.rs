@@ -0,0 +1,99 @@
+//! Criterion benchmarks of the core kernels: Pasta keystream blocks, polynomial multiplication at several
+//! ring dimensions and the BFV operations at the `Bfv128Depth1` preset. Compare two runs with criterion's
+//! baselines, e.g. before and after an NTT or reduction change:
+//!
+//! ```text
+//! cargo bench --bench kernels -- --save-baseline before
+//! cargo bench --bench kernels -- --baseline before
+//! ```
+//!
+//! `bench-report` keeps coarser snapshots for CI, this suite is for local comparisons.
+
+use std::hint::black_box;
+
+use criterion::{BenchmarkId, Criterion, criterion_group, criterion_main};
+use rlattice::bfv_params::{Bfv128Depth1, BfvParams};
+use rlattice::bfv_pke::Evaluator;
+use rlattice::ntt::NttTable;
+use rlattice::pasta_plain::{Pasta, Pasta4, PastaKey};
+use rlattice::polynomial::Polynomial;
+
+const P: u64 = 65_537;
+/// Prime, 1 mod 8192, so every size below is NTT friendly.
+const Q: u64 = 18_014_398_509_309_953;
+
+fn keystream(c: &mut Criterion) {
+    let mut group = c.benchmark_group("keystream");
+    let mut pasta = Pasta::new(PastaKey::generate(&mut rand::rng(), P), P);
+    let mut ctr = 0u64;
+    group.bench_function("pasta3/derive", |b| {
+        b.iter(|| {
+            ctr += 1;
+            pasta.keystream(1, black_box(ctr))
+        })
+    });
+    group.bench_function("pasta3/fused", |b| {
+        b.iter(|| {
+            ctr += 1;
+            pasta.keystream_fused(1, black_box(ctr))
+        })
+    });
+    let mut pasta4 = Pasta4::with_key(PastaKey::generate(&mut rand::rng(), P), P);
+    group.bench_function("pasta4/derive", |b| {
+        b.iter(|| {
+            ctr += 1;
+            pasta4.keystream(1, black_box(ctr))
+        })
+    });
+    group.finish();
+}
+
+fn poly_mul_at<const N: usize>(c: &mut Criterion) {
+    let mut group = c.benchmark_group("poly_mul");
+    group.sample_size(20);
+    let (a, b) = (Polynomial::<N, Q>::rand(), Polynomial::<N, Q>::rand());
+    group.bench_with_input(
+        BenchmarkId::new("karatsuba", N),
+        &(a, b),
+        |bench, (a, b)| bench.iter(|| black_box(a).mul_karatsuba(black_box(b))),
+    );
+    let table = NttTable::<N, Q>::new().expect("q is 1 mod 2n");
+    group.bench_with_input(BenchmarkId::new("ntt", N), &(a, b), |bench, (a, b)| {
+        bench.iter(|| table.mul(black_box(a), black_box(b)))
+    });
+    if N <= 1024 {
+        group.bench_with_input(
+            BenchmarkId::new("schoolbook", N),
+            &(a, b),
+            |bench, (a, b)| bench.iter(|| black_box(a).mul_schoolbook(black_box(b))),
+        );
+    }
+    group.finish();
+}
+
+fn poly_mul(c: &mut Criterion) {
+    poly_mul_at::<256>(c);
+    poly_mul_at::<1024>(c);
+    poly_mul_at::<4096>(c);
+}
+
+fn bfv(c: &mut Criterion) {
+    let params = BfvParams::default_128bit_depth1();
+    let mut group = c.benchmark_group(format!("bfv/{}", params.n));
+    group.sample_size(20);
+    let (bfv, sk) = Bfv128Depth1::keygen().unwrap();
+    let evaluator = Evaluator::new().with_relin_key(bfv.gen_relin_key(&sk, params.relin_base_log));
+    let m = Polynomial::rand();
+    let ct = bfv.encrypt(m);
+
+    group.bench_function("encrypt", |b| b.iter(|| bfv.encrypt(black_box(m))));
+    group.bench_function("add", |b| b.iter(|| black_box(ct.clone()) + ct.clone()));
+    group.bench_function("mul", |b| {
+        b.iter(|| evaluator.mul(black_box(&ct), &ct).unwrap())
+    });
+    group.bench_function("decrypt", |b| b.iter(|| black_box(&ct).decrypt(&sk)));
+    group.finish();
+}
+
+criterion_group!(benches, keystream, poly_mul, bfv);
+criterion_main!(benches);
@@ -0,0 +1,131 @@
+//! Pasta keystream throughput and peak memory over long messages, for picking a keystream mode:
+//!
+//! - `derive`: `Pasta::keystream`, round materials derived per block and dropped right away.
+//! - `cached`: materials for every block derived once and kept, encrypt and decrypt both reuse them.
+//! - `fused`:  `Pasta::keystream_fused`, each affine layer squeezed right before it is applied.
+//!
+//! Each mode encrypts then decrypts the message (two keystream passes). Output is one csv row per run so
+//! two runs can be diffed or joined directly. Message sizes in MiB come from `RLATTICE_BENCH_MB` (default `1,2`).
+//!
+//! `cargo bench --bench keystream`
+
+use std::time::Instant;
+
+use rand::{Rng, rng};
+use rlattice::pasta_plain::{PASTA_R, PASTA_T, Pasta, PastaKey, RoundMaterials};
+
+const P: u64 = 65_537;
+const NONCE: u64 = 123_456_789;
+
+/// Encrypts then decrypts a message with one keystream mode.
+type Mode = fn(&mut Pasta, &[u64]) -> Vec<u64>;
+
+/// Resets the peak resident set size of this process (linux only, no-op elsewhere).
+fn reset_peak_rss() {
+    let _ = std::fs::write("/proc/self/clear_refs", "5");
+}
+
+/// Peak resident set size in KiB since the last reset, `None` when /proc isn't available.
+fn peak_rss_kib() -> Option<u64> {
+    let status = std::fs::read_to_string("/proc/self/status").ok()?;
+    let line = status.lines().find(|l| l.starts_with("VmHWM:"))?;
+    line.split_whitespace().nth(1)?.parse().ok()
+}
+
+fn add_block(out: &mut [u64], ks: &[u64]) {
+    for (w, k) in out.iter_mut().zip(ks) {
+        *w = (*w + k) % P;
+    }
+}
+
+fn sub_block(out: &mut [u64], ks: &[u64]) {
+    for (w, k) in out.iter_mut().zip(ks) {
+        *w = (*w + P - k) % P;
+    }
+}
+
+fn run_derive(pasta: &mut Pasta, message: &[u64]) -> Vec<u64> {
+    let mut ct = message.to_vec();
+    for (b, chunk) in ct.chunks_mut(PASTA_T).enumerate() {
+        add_block(chunk, &pasta.keystream(NONCE, b as u64));
+    }
+    let mut pt = ct;
+    for (b, chunk) in pt.chunks_mut(PASTA_T).enumerate() {
+        sub_block(chunk, &pasta.keystream(NONCE, b as u64));
+    }
+    pt
+}
+
+fn run_cached(pasta: &mut Pasta, message: &[u64]) -> Vec<u64> {
+    let cache = (0..message.len().div_ceil(PASTA_T))
+        .map(|b| RoundMaterials::derive(P, NONCE, b as u64, PASTA_T, PASTA_R))
+        .collect::<Vec<_>>();
+    let mut ct = message.to_vec();
+    for (chunk, materials) in ct.chunks_mut(PASTA_T).zip(&cache) {
+        add_block(chunk, &pasta.keystream_with(materials));
+    }
+    let mut pt = ct;
+    for (chunk, materials) in pt.chunks_mut(PASTA_T).zip(&cache) {
+        sub_block(chunk, &pasta.keystream_with(materials));
+    }
+    pt
+}
+
+fn run_fused(pasta: &mut Pasta, message: &[u64]) -> Vec<u64> {
+    let mut ct = message.to_vec();
+    for (b, chunk) in ct.chunks_mut(PASTA_T).enumerate() {
+        add_block(chunk, &pasta.keystream_fused(NONCE, b as u64));
+    }
+    let mut pt = ct;
+    for (b, chunk) in pt.chunks_mut(PASTA_T).enumerate() {
+        sub_block(chunk, &pasta.keystream_fused(NONCE, b as u64));
+    }
+    pt
+}
+
+fn main() {
+    let sizes = std::env::var("RLATTICE_BENCH_MB")
+        .unwrap_or_else(|_| "1,2".to_string())
+        .split(',')
+        .map(|s| {
+            s.trim()
+                .parse::<usize>()
+                .expect("RLATTICE_BENCH_MB is a list of MiB")
+        })
+        .collect::<Vec<_>>();
+
+    let mut rng = rng();
+    let mut pasta = Pasta::new(PastaKey::generate(&mut rng, P), P);
+
+    let modes: [(&str, Mode); 3] = [
+        ("derive", run_derive),
+        ("cached", run_cached),
+        ("fused", run_fused),
+    ];
+
+    println!("mode,t,rounds,message_bytes,blocks,seconds,mib_per_s,peak_rss_kib");
+    for mib in sizes {
+        let words = mib * (1 << 20) / 8;
+        let message = (0..words)
+            .map(|_| rng.random_range(0..P))
+            .collect::<Vec<_>>();
+
+        for (name, run) in modes {
+            reset_peak_rss();
+            let start = Instant::now();
+            let out = run(&mut pasta, &message);
+            let elapsed = start.elapsed();
+            assert_eq!(out, message, "{name} did not roundtrip");
+
+            let secs = elapsed.as_secs_f64();
+            println!(
+                "{name},{PASTA_T},{PASTA_R},{},{},{secs:.4},{:.3},{}",
+                words * 8,
+                words.div_ceil(PASTA_T),
+                // two passes over the message
+                2.0 * mib as f64 / secs,
+                peak_rss_kib().map_or("na".to_string(), |k| k.to_string()),
+            );
+        }
+    }
+}
@@ -1,6 +1,11 @@
 fn main() {
     println!("cargo::rerun-if-changed=src/main.rs");
 
+    // only diamond-io (the `bgg` feature) needs openFHE
+    if std::env::var_os("CARGO_FEATURE_BGG").is_none() {
+        return;
+    }
+
     // linking openFHE
     println!("cargo::rustc-link-arg=-L/usr/local/lib");
     println!("cargo::rustc-link-arg=-lOPENFHEpke");
@@ -0,0 +1,57 @@
+//! BFV with a vector packed into the coefficients of one plaintext polynomial.
+//!
+//! `cargo run --example bfv_packed_arithmetic`
+//!
+//! Addition is slot wise. Multiplying by a plaintext polynomial is a negacyclic convolution, so a constant
+//! scales every slot and x^k shifts the slots by k, negating the ones that wrap around.
+
+use rlattice::bfv_pke::Bfv;
+use rlattice::polynomial::{Element, Polynomial};
+
+const N: usize = 16;
+const Q: u64 = 12_289;
+const T: u64 = 17;
+
+fn pack(values: &[i64]) -> Polynomial<N, T> {
+    let mut inner = [Element::new(0); N];
+    for (c, v) in inner.iter_mut().zip(values) {
+        *c = Element::new(*v);
+    }
+    Polynomial::new(inner)
+}
+
+fn monomial(k: usize, c: i64) -> Polynomial<N, Q> {
+    let mut inner = [Element::new(0); N];
+    inner[k] = Element::new(c);
+    Polynomial::new(inner)
+}
+
+fn main() {
+    let (bfv, sk) = Bfv::<N, Q, T>::keygen().unwrap();
+
+    let a = pack(&[1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16]);
+    let b = pack(&[16, 15, 14, 13, 12, 11, 10, 9, 8, 7, 6, 5, 4, 3, 2, 1]);
+    let ct_a = bfv.encrypt(a);
+    let ct_b = bfv.encrypt(b);
+
+    let sum = (ct_a.clone() + ct_b).decrypt(&sk);
+    println!("a + b = {:?}", sum);
+    assert_eq!(sum, a + b);
+
+    let tripled = (&ct_a * monomial(0, 3)).decrypt(&sk);
+    println!("3a    = {:?}", tripled);
+    assert_eq!(tripled, a * pack(&[3]));
+
+    let shifted = (&ct_a * monomial(1, 1)).decrypt(&sk);
+    println!("x * a = {:?}", shifted);
+    assert_eq!(shifted.inner[1], a.inner[0]);
+    assert_eq!(shifted.inner[0], -a.inner[N - 1]);
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn run() {
+        super::main();
+    }
+}
@@ -0,0 +1,47 @@
+//! The client/server flow through `session::Session`, over `hybrid::Client` and `hybrid::Server`.
+//!
+//! `cargo run --example hybrid_session`
+//!
+//! The client uploads its data Pasta encrypted, one word per data word, the server transciphers it into
+//! BFV, doubles every word slot by slot and sends the result back. The reduced Pasta<2, 2> and n = 8
+//! keep the run short, they are not secure.
+
+use rlattice::session::{ClientKeys, ServerBundle, Session};
+
+const N: usize = 8;
+const Q: u64 = 1 << 59;
+// 17 ≡ 1 mod 16, so t allows batching
+const T: u64 = 17;
+
+fn main() {
+    let message = [7, 10, 16, 3, 0, 12];
+
+    let keys = ClientKeys::<N, Q, T, 2, 2>::generate().unwrap();
+    let bundle = ServerBundle::new(&keys, 8);
+    let session = Session::new(keys, &bundle).unwrap();
+
+    let uploaded = session.upload(&message).unwrap();
+    println!(
+        "setup: {} bytes, upload: {} bytes for {} words",
+        bundle.as_bytes().len(),
+        uploaded.state.message.len(),
+        message.len()
+    );
+    // decrypting checks the result against the blocks the client uploaded
+    let result = uploaded
+        .transcipher()
+        .unwrap()
+        .compute(|_, cts| cts.iter().map(|ct| ct.clone() + ct.clone()).collect())
+        .decrypt()
+        .unwrap();
+    println!("2 * message mod t = {result:?}");
+    assert_eq!(result, message.map(|m| 2 * m % T));
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn run() {
+        super::main();
+    }
+}
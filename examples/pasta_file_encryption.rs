@@ -0,0 +1,58 @@
+//! Encrypts a file (or a built in sample) with plain Pasta over p = 65537 and decrypts it again.
+//!
+//! `cargo run --example pasta_file_encryption -- [path]`
+//!
+//! Two bytes go into one field element, so the ciphertext is (len / 2) words of 17 bits.
+
+use rlattice::pasta_plain::{Pasta, PastaKey};
+
+const P: u64 = 65_537;
+
+fn to_words(bytes: &[u8]) -> Vec<u64> {
+    bytes
+        .chunks(2)
+        .map(|c| c[0] as u64 | (*c.get(1).unwrap_or(&0) as u64) << 8)
+        .collect()
+}
+
+fn from_words(words: &[u64], len: usize) -> Vec<u8> {
+    let mut out = words
+        .iter()
+        .flat_map(|w| [*w as u8, (*w >> 8) as u8])
+        .collect::<Vec<_>>();
+    out.truncate(len);
+    out
+}
+
+const SAMPLE: &[u8] = b"hybrid homomorphic encryption, the client side";
+
+fn roundtrip(data: &[u8]) {
+    let key = PastaKey::new(vec![1_234, 56_789], P);
+    let mut pasta = Pasta::new(key, P);
+
+    // never reuse a nonce under the same key, it travels with the ciphertext
+    let nonce = rand::random();
+    let ct = pasta.encrypt_with_nonce(nonce, &to_words(data));
+    println!("{} bytes -> {} words, nonce {nonce}", data.len(), ct.len());
+
+    let dec = from_words(&pasta.decrypt_with_nonce(nonce, &ct), data.len());
+    assert_eq!(dec, data);
+    println!("decrypted {} bytes", dec.len());
+}
+
+fn main() {
+    match std::env::args().nth(1) {
+        Some(path) => roundtrip(&std::fs::read(&path).unwrap_or_else(|e| panic!("{path}: {e}"))),
+        None => roundtrip(SAMPLE),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn run() {
+        super::roundtrip(super::SAMPLE);
+        // odd length, the last word carries a single byte
+        super::roundtrip(&super::SAMPLE[..9]);
+    }
+}
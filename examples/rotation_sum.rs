@@ -0,0 +1,55 @@
+//! Sum of all slots of a batched BFV ciphertext by rotations.
+//!
+//! `cargo run --example rotation_sum`
+//!
+//! With t ≡ 1 mod 2n the plaintext holds two rows of n/2 slots, and x -> x^(3^k) rotates both rows left by
+//! k. Adding the ciphertext to its rotation by n/4, then by n/8, ..., then by 1 leaves the sum of each row
+//! in every slot of that row, in log2(n/2) rotations. `fold::fold_slots` is the same loop for any combiner.
+
+use rlattice::batch::BatchEncoder;
+use rlattice::bfv_pke::{Bfv, Evaluator, galois_element};
+use rlattice::encoding::Encoder;
+
+const N: usize = 16;
+const Q: u64 = 1 << 50;
+// 97 ≡ 1 mod 32
+const T: u64 = 97;
+
+fn main() {
+    let (bfv, sk) = Bfv::<N, Q, T>::keygen().unwrap();
+    let encoder = BatchEncoder::<N, T>::new().unwrap();
+
+    let steps = (0..(N / 2).trailing_zeros())
+        .map(|i| 1usize << i)
+        .collect::<Vec<_>>();
+    let elements = steps
+        .iter()
+        .map(|&k| galois_element::<N>(k as i64))
+        .collect::<Vec<_>>();
+    let evaluator =
+        Evaluator::new().with_galois_keys(bfv.gen_galois_keys(&sk, &elements, 8).unwrap());
+
+    let row_0 = [3, 1, 4, 1, 5, 9, 2, 6];
+    let row_1 = [2, 7, 1, 8, 2, 8, 1, 8];
+    let mut ct = bfv.encrypt(encoder.encode(&[row_0, row_1].concat()).unwrap());
+    for &k in steps.iter().rev() {
+        let rotated = evaluator
+            .rotate(&ct, galois_element::<N>(k as i64))
+            .unwrap();
+        ct = ct + rotated;
+    }
+
+    let slots = encoder.decode(&ct.decrypt(&sk));
+    println!("slots after {} rotations: {slots:?}", steps.len());
+    let sums = [row_0, row_1].map(|row| row.iter().sum::<u64>() % T);
+    assert_eq!(slots[..N / 2], [sums[0]; N / 2]);
+    assert_eq!(slots[N / 2..], [sums[1]; N / 2]);
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn run() {
+        super::main();
+    }
+}
@@ -0,0 +1,64 @@
+//! Pasta ciphertext in, BFV ciphertexts of the plaintext out, with `pasta_bfv` directly.
+//!
+//! `cargo run --example transcipher_roundtrip`
+//!
+//! The client Pasta encrypts its data and BFV encrypts its Pasta key once. The server evaluates Pasta
+//! decryption under BFV, which leaves word i of block b in slot b of the i-th ciphertext, and the client
+//! decrypts those. `hybrid` wraps the same steps in byte messages. The reduced Pasta<2, 2> and n = 8 keep
+//! the run short, they are not secure.
+
+use rlattice::batch::BatchEncoder;
+use rlattice::bfv_pke::{Bfv, Evaluator};
+use rlattice::encoding::Encoder;
+use rlattice::pasta_bfv::{EncryptedPastaKey, Transcipher};
+use rlattice::pasta_plain::{Pasta, PastaKey};
+
+const N: usize = 8;
+const Q: u64 = 1 << 59;
+// the Pasta modulus, 17 ≡ 1 mod 16 so t allows batching
+const T: u64 = 17;
+const W: usize = 2;
+const R: usize = 2;
+
+fn main() {
+    let message = [1, 2, 3, 4, 5, 6, 7];
+
+    // client
+    let key = PastaKey::<W>::generate(&mut rand::rng(), T);
+    let ciphertext = Pasta::<W, R>::with_key(key.clone(), T).encrypt_with_nonce(9, &message);
+    let (bfv, sk) = Bfv::<N, Q, T>::keygen().unwrap();
+    let encrypted_key = EncryptedPastaKey::encrypt(&bfv, &key).unwrap();
+    let evaluator = Evaluator::new().with_relin_key(bfv.gen_relin_key(&sk, 8));
+
+    // server
+    let server =
+        Transcipher::<N, Q, T, Pasta<W, R>>::new(encrypted_key.into_words(), evaluator).unwrap();
+    let circuit = server.circuit(9, 0, &ciphertext).unwrap();
+    println!(
+        "{} blocks, circuit of depth {} with {} relinearizations",
+        message.len().div_ceil(W),
+        circuit.depth(),
+        circuit.relinearizations()
+    );
+    let data = server.decrypt(9, 0, &ciphertext).unwrap();
+
+    // client
+    let encoder = BatchEncoder::<N, T>::new().unwrap();
+    let words = data
+        .iter()
+        .map(|ct| encoder.decode(&ct.decrypt(&sk)))
+        .collect::<Vec<_>>();
+    let result = (0..message.len())
+        .map(|k| words[k % W][k / W])
+        .collect::<Vec<_>>();
+    println!("transciphered: {result:?}");
+    assert_eq!(result, message);
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn run() {
+        super::main();
+    }
+}
@@ -0,0 +1,98 @@
+//! Backend for the ring operations the schemes are built from.
+//!
+//! Every method has a pure Rust default, so a backend only overrides what it can do faster
+//! (e.g. hand `mul` and the NTT to concrete-ntt, tfhe-rs or a gpu). Schemes take the backend as a
+//! type parameter that defaults to [`NativeBackend`].
+
+use std::fmt::Debug;
+
+use crate::ntt::NttTable;
+use crate::polynomial::Polynomial;
+
+pub trait RingBackend: Debug + Clone + Copy + Default + Send + Sync {
+    fn add<const N: usize, const A: u64>(
+        a: &Polynomial<N, A>,
+        b: &Polynomial<N, A>,
+    ) -> Polynomial<N, A> {
+        *a + *b
+    }
+
+    fn sub<const N: usize, const A: u64>(
+        a: &Polynomial<N, A>,
+        b: &Polynomial<N, A>,
+    ) -> Polynomial<N, A> {
+        *a - *b
+    }
+
+    fn mul<const N: usize, const A: u64>(
+        a: &Polynomial<N, A>,
+        b: &Polynomial<N, A>,
+    ) -> Polynomial<N, A> {
+        *a * *b
+    }
+
+    fn ntt_forward<const N: usize, const A: u64>(
+        table: &NttTable<N, A>,
+        a: &Polynomial<N, A>,
+    ) -> Polynomial<N, A> {
+        table.forward(a)
+    }
+
+    fn ntt_inverse<const N: usize, const A: u64>(
+        table: &NttTable<N, A>,
+        a: &Polynomial<N, A>,
+    ) -> Polynomial<N, A> {
+        table.inverse(a)
+    }
+
+    /// x -> x^k
+    fn automorphism<const N: usize, const A: u64>(
+        a: &Polynomial<N, A>,
+        k: usize,
+    ) -> Polynomial<N, A> {
+        a.automorphism(k)
+    }
+}
+
+/// The in-crate implementation.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NativeBackend;
+
+impl RingBackend for NativeBackend {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Backend that routes multiplication through the NTT whenever the ring allows it.
+    #[derive(Debug, Clone, Copy, Default)]
+    struct NttBackend;
+
+    impl RingBackend for NttBackend {
+        fn mul<const N: usize, const A: u64>(
+            a: &Polynomial<N, A>,
+            b: &Polynomial<N, A>,
+        ) -> Polynomial<N, A> {
+            match NttTable::<N, A>::new() {
+                Some(table) => table.mul(a, b),
+                None => *a * *b,
+            }
+        }
+    }
+
+    #[test]
+    fn test_backends_agree() {
+        let a = Polynomial::<64, 12_289>::rand();
+        let b = Polynomial::<64, 12_289>::rand();
+        assert_eq!(NativeBackend::mul(&a, &b), NttBackend::mul(&a, &b));
+        assert_eq!(NativeBackend::add(&a, &b), NttBackend::add(&a, &b));
+        assert_eq!(
+            NativeBackend::automorphism(&a, 3),
+            NttBackend::automorphism(&a, 3)
+        );
+
+        let table = NttTable::<64, 12_289>::new().unwrap();
+        let a_hat = NttBackend::ntt_forward(&table, &a);
+        assert_eq!(NativeBackend::ntt_inverse(&table, &a_hat), a);
+    }
+}
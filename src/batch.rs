@@ -0,0 +1,211 @@
+//! CRT batching: n values mod t packed into one plaintext, so one homomorphic add or multiply acts on all of
+//! them slot by slot.
+//!
+//! With t prime and t ≡ 1 mod 2n, x^n + 1 splits into n linear factors x - ψ^e (ψ a primitive 2n-th root,
+//! e odd) and Z_t[x]/(x^n + 1) is isomorphic to Z_t^n by evaluating at the roots, which is exactly the
+//! negacyclic NTT mod t. The slots are the evaluations in the order
+//!
+//! row 0: ψ^(3^j), row 1: ψ^(-3^j), j = 0..n/2
+//!
+//! so x -> x^3 (`galois_element(1)`) rotates both rows left by one and x -> x^(2n-1) swaps them. Slot i is
+//! row i / (n/2), column i % (n/2).
+
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::bfv_pke::ParamError;
+use crate::encoding::Encoder;
+use crate::ntt::{NttTable, is_ntt_friendly};
+use crate::polynomial::{Element, Polynomial};
+
+#[derive(Debug, Clone)]
+pub struct BatchEncoder<const N: usize, const T: u64> {
+    table: NttTable<N, T>,
+    /// Slot i is entry `slot_index[i]` of the (bit reversed) NTT output.
+    slot_index: Vec<usize>,
+}
+
+impl<const N: usize, const T: u64> BatchEncoder<N, T> {
+    pub fn new() -> Result<Self, ParamError> {
+        if N < 2 || !is_ntt_friendly(N, T) {
+            return Err(ParamError::BatchingUnsupported { t: T, n: N });
+        }
+        let table = NttTable::<N, T>::new().expect("checked NTT friendly");
+
+        // the transform of x lists the root behind every output entry
+        let mut x = Polynomial::<N, T>::new([Element::new(0); N]);
+        x.inner[1] = Element::new(1);
+        let roots = table.forward(&x);
+        let position = roots
+            .inner
+            .iter()
+            .enumerate()
+            .map(|(i, r)| (r.value(), i))
+            .collect::<HashMap<_, _>>();
+        let psi = roots.inner[0];
+        let pow = |e: usize| (0..e).fold(Element::<T>::new(1), |acc, _| acc * psi);
+
+        let half = N / 2;
+        let mut slot_index = vec![0; N];
+        let mut e = 1;
+        for j in 0..half {
+            slot_index[j] = position[&pow(e).value()];
+            slot_index[half + j] = position[&pow(2 * N - e).value()];
+            e = e * 3 % (2 * N);
+        }
+        Ok(Self { table, slot_index })
+    }
+
+    /// Number of slots, n.
+    pub fn slots(&self) -> usize {
+        N
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TooManyValues {
+    pub values: usize,
+    pub slots: usize,
+}
+
+impl fmt::Display for TooManyValues {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} values for {} slots", self.values, self.slots)
+    }
+}
+
+impl std::error::Error for TooManyValues {}
+
+impl<const N: usize, const T: u64> Encoder<N, T> for BatchEncoder<N, T> {
+    type Value = [u64];
+    type Error = TooManyValues;
+
+    /// Plaintext whose slot i holds `values[i]` mod t, slots past `values.len()` are 0.
+    fn encode(&self, values: &[u64]) -> Result<Polynomial<N, T>, TooManyValues> {
+        if values.len() > N {
+            return Err(TooManyValues {
+                values: values.len(),
+                slots: N,
+            });
+        }
+        let mut evals = [Element::new(0); N];
+        for (&i, &v) in self.slot_index.iter().zip(values) {
+            evals[i] = Element::new((v % T) as i64);
+        }
+        Ok(self.table.inverse(&Polynomial::new(evals)))
+    }
+
+    /// All n slots of `pt`.
+    fn decode(&self, pt: &Polynomial<N, T>) -> Vec<u64> {
+        let evals = self.table.forward(pt);
+        self.slot_index
+            .iter()
+            .map(|&i| evals.inner[i].value())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{Rng, rng};
+
+    use super::*;
+    use crate::bfv_pke::{Bfv, Evaluator, galois_element};
+
+    const N: usize = 16;
+    // 97 ≡ 1 mod 32
+    const T: u64 = 97;
+
+    fn rand_slots() -> Vec<u64> {
+        let mut rng = rng();
+        (0..N).map(|_| rng.random_range(0..T)).collect()
+    }
+
+    #[test]
+    fn test_roundtrip_and_slotwise_ops() {
+        let encoder = BatchEncoder::<N, T>::new().unwrap();
+        let a = rand_slots();
+        let b = rand_slots();
+        assert_eq!(encoder.decode(&encoder.encode(&a).unwrap()), a);
+        assert_eq!(
+            encoder.decode(&encoder.encode(&a[..5]).unwrap())[5..],
+            [0; N - 5]
+        );
+        assert_eq!(
+            encoder.encode(&[1; N + 1]),
+            Err(TooManyValues {
+                values: N + 1,
+                slots: N
+            })
+        );
+
+        let (pa, pb) = (encoder.encode(&a).unwrap(), encoder.encode(&b).unwrap());
+        let sum = a
+            .iter()
+            .zip(&b)
+            .map(|(x, y)| (x + y) % T)
+            .collect::<Vec<_>>();
+        let prod = a.iter().zip(&b).map(|(x, y)| x * y % T).collect::<Vec<_>>();
+        assert_eq!(encoder.decode(&(pa + pb)), sum);
+        assert_eq!(encoder.decode(&(pa * pb)), prod);
+    }
+
+    #[test]
+    fn test_rotations() {
+        let encoder = BatchEncoder::<N, T>::new().unwrap();
+        let a = rand_slots();
+        let pt = encoder.encode(&a).unwrap();
+        let (row_0, row_1) = a.split_at(N / 2);
+
+        let mut rotated = row_0.to_vec();
+        rotated.rotate_left(1);
+        let mut rotated_1 = row_1.to_vec();
+        rotated_1.rotate_left(1);
+        rotated.extend(rotated_1);
+        assert_eq!(
+            encoder.decode(&pt.automorphism(galois_element::<N>(1))),
+            rotated
+        );
+
+        let swapped = [row_1, row_0].concat();
+        assert_eq!(encoder.decode(&pt.automorphism(2 * N - 1)), swapped);
+    }
+
+    #[test]
+    fn test_encrypted_slots() {
+        const Q: u64 = 1 << 40;
+        let encoder = BatchEncoder::<N, T>::new().unwrap();
+        let (bfv, sk) = Bfv::<N, Q, T>::keygen().unwrap();
+        let evaluator = Evaluator::new()
+            .with_relin_key(bfv.gen_relin_key(&sk, 10))
+            .with_galois_keys(
+                bfv.gen_galois_keys(&sk, &[galois_element::<N>(2)], 10)
+                    .unwrap(),
+            );
+
+        let a = rand_slots();
+        let b = rand_slots();
+        let ct_a = bfv.encrypt(encoder.encode(&a).unwrap());
+        let ct_b = bfv.encrypt(encoder.encode(&b).unwrap());
+        let prod = evaluator.mul(&ct_a, &ct_b).unwrap();
+        let rotated = evaluator.rotate(&prod, galois_element::<N>(2)).unwrap();
+
+        let expected = (0..N)
+            .map(|i| {
+                let (row, col) = (i / (N / 2), i % (N / 2));
+                let j = row * N / 2 + (col + 2) % (N / 2);
+                a[j] * b[j] % T
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(encoder.decode(&rotated.decrypt(&sk)), expected);
+    }
+
+    #[test]
+    fn test_unsupported_t() {
+        assert_eq!(BatchEncoder::<N, 257>::new().map(|_| ()), Ok(()));
+        assert_eq!(
+            BatchEncoder::<N, 256>::new().unwrap_err(),
+            ParamError::BatchingUnsupported { t: 256, n: N }
+        );
+    }
+}
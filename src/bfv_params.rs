@@ -0,0 +1,339 @@
+//! Preset (N, Q, T) for `bfv_pke::Bfv` that pass the `security` tables, so callers don't hand-pick const
+//! generics. Every preset has a type alias, e.g. `Bfv128Depth2::keygen()`, and a [`BfvParams`] describing
+//! it at runtime.
+//!
+//! q is an NTT friendly prime below 2^54: the tensor product keeps n q^2 t below 2^127 so it fits in
+//! `i128`, which caps q and t together. Depths are for squaring chains relinearized with
+//! `relin_base_log`, the worst case of a product of fresh ciphertexts, and keep a few bits of budget
+//! spare. Plaintext moduli this small rule out batching, which needs t ≡ 1 mod 2n.
+//!
+//! [`BfvParams::generate`] derives parameters for other (security, depth, t) instead: the smallest n
+//! whose largest NTT friendly q within the security tables, the [`estimator`] and the tensor bound leaves
+//! budget for the depth under the noise estimates `bfv_pke` traces. Those estimates are worst case, so
+//! generated depths can fall short of what the parameters really reach, e.g. the depth 3 preset comes out
+//! at depth 2. q stays a single word, deeper circuits need `bfv_rns`.
+//!
+//! Polynomials are arrays on the stack, tens of KiB each at these degrees; unoptimized builds can need
+//! more than the 2 MiB of a spawned thread.
+
+use std::fmt;
+
+use crate::bfv_pke::{Bfv, fresh_noise_bits, switch_noise_bits, tensor_growth_bits};
+use crate::estimator::{self, CostModel};
+use crate::keyswitch::Gadget;
+use crate::ntt::largest_ntt_prime;
+use crate::polynomial::{ErrorDist, Ternary};
+use crate::security::{self, SecretDist, SecurityLevel};
+
+/// The largest prime below 2^54 that is 1 mod 4096.
+const Q_2048: u64 = 18_014_398_509_404_161;
+/// The largest prime below 2^54 that is 1 mod 8192.
+const Q_4096: u64 = 18_014_398_509_309_953;
+/// `relin_base_log` of every preset and generated parameter set.
+const RELIN_BASE_LOG: u32 = 16;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GenerateError {
+    /// Below 128 bits, which `security::check` rejects.
+    Insecure { bits: u32 },
+    /// t has to be at least 2.
+    PlaintextModulus { t: u64 },
+    /// No degree up to 32768 has a modulus with budget for the depth.
+    Unreachable { depth: u32, t: u64 },
+}
+
+impl fmt::Display for GenerateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GenerateError::Insecure { bits } => {
+                write!(f, "{bits} bits of security requested, at least 128 needed")
+            }
+            GenerateError::PlaintextModulus { t } => {
+                write!(f, "plaintext modulus {t} must be at least 2")
+            }
+            GenerateError::Unreachable { depth, t } => write!(
+                f,
+                "no single word modulus reaches depth {depth} with t = {t}, see bfv_rns"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for GenerateError {}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BfvParams {
+    pub n: usize,
+    pub q: u64,
+    pub t: u64,
+    /// `base_log` for `Bfv::gen_relin_key`.
+    pub relin_base_log: u32,
+    /// Per `security::check` with `Ternary::UNIFORM` secrets.
+    pub security: SecurityLevel,
+    /// Sequential relinearized ciphertext multiplications that still decrypt.
+    pub depth: u32,
+}
+
+impl BfvParams {
+    pub const fn default_128bit_depth1() -> Self {
+        Self {
+            n: 2048,
+            q: Q_2048,
+            t: 251,
+            relin_base_log: RELIN_BASE_LOG,
+            security: SecurityLevel::Bits128,
+            depth: 1,
+        }
+    }
+
+    pub const fn default_128bit_depth2() -> Self {
+        Self {
+            n: 2048,
+            q: Q_2048,
+            t: 17,
+            relin_base_log: RELIN_BASE_LOG,
+            security: SecurityLevel::Bits128,
+            depth: 2,
+        }
+    }
+
+    /// Binary plaintexts.
+    pub const fn default_128bit_depth3() -> Self {
+        Self {
+            n: 2048,
+            q: Q_2048,
+            t: 2,
+            relin_base_log: RELIN_BASE_LOG,
+            security: SecurityLevel::Bits128,
+            depth: 3,
+        }
+    }
+
+    pub const fn default_256bit_depth2() -> Self {
+        Self {
+            n: 4096,
+            q: Q_4096,
+            t: 17,
+            relin_base_log: RELIN_BASE_LOG,
+            security: SecurityLevel::Bits256,
+            depth: 2,
+        }
+    }
+
+    pub const ALL: [Self; 4] = [
+        Self::default_128bit_depth1(),
+        Self::default_128bit_depth2(),
+        Self::default_128bit_depth3(),
+        Self::default_256bit_depth2(),
+    ];
+
+    /// The smallest preset with at least `security` and `depth` and a plaintext modulus of at least `t`,
+    /// ties going to the larger t.
+    pub fn select(security: SecurityLevel, depth: u32, t: u64) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .filter(|p| p.security >= security && p.depth >= depth && p.t >= t)
+            .min_by_key(|p| (p.n, std::cmp::Reverse(p.t)))
+    }
+
+    /// Parameters with at least `security_bits` of security, by both the standard tables and the
+    /// estimator's BDGL16 model, and estimated depth at least `mult_depth` for plaintext modulus t.
+    /// Ternary secrets and `ErrorDist::STANDARD` errors, as `Bfv::keygen` uses.
+    ///
+    /// The result is only a description: N, Q and T of `Bfv<N, Q, T>` are const generics, so n, q and t have
+    /// to be copied into them by hand (e.g. printed once and pasted into a type alias like the presets'),
+    /// nothing checks that a `Bfv` type matches a generated `BfvParams`.
+    pub fn generate(security_bits: u32, mult_depth: u32, t: u64) -> Result<Self, GenerateError> {
+        if t < 2 {
+            return Err(GenerateError::PlaintextModulus { t });
+        }
+        let level = SecurityLevel::ALL
+            .into_iter()
+            .rev()
+            .find(|level| level.bits() <= security_bits)
+            .ok_or(GenerateError::Insecure {
+                bits: security_bits,
+            })?;
+        (10..=15)
+            .map(|log_n| 1usize << log_n)
+            .find_map(|n| {
+                let table = security::max_log_q(n, level, SecretDist::Ternary)?;
+                let estimated = estimator::max_log_q(
+                    n,
+                    ErrorDist::STANDARD,
+                    &Ternary::UNIFORM,
+                    security_bits as f64,
+                    CostModel::Bdgl16,
+                )?;
+                // n q^2 t < 2^127 keeps the tensor product in i128
+                let tensor = ((127.0 - (n as f64 * t as f64).log2()) / 2.0).floor() as u32;
+                let q = largest_ntt_prime(n, table.min(estimated).min(tensor))?;
+                let depth = estimated_depth(n, q, t)?;
+                let security = security::classify(n, 64 - q.leading_zeros(), SecretDist::Ternary)?;
+                (q > t && depth >= mult_depth).then_some(Self {
+                    n,
+                    q,
+                    t,
+                    relin_base_log: RELIN_BASE_LOG,
+                    security,
+                    depth,
+                })
+            })
+            .ok_or(GenerateError::Unreachable {
+                depth: mult_depth,
+                t,
+            })
+    }
+}
+
+/// Relinearized squarings of a fresh ciphertext before the estimated noise reaches Δ/2, `None` if even
+/// fresh ciphertexts don't decrypt.
+fn estimated_depth(n: usize, q: u64, t: u64) -> Option<u32> {
+    let budget = ((q / t / 2) as f64).log2();
+    let growth = tensor_growth_bits(n, t);
+    let relin = switch_noise_bits(n, &Gadget::new(q, RELIN_BASE_LOG));
+    let mut noise = fresh_noise_bits(n, ErrorDist::STANDARD);
+    if noise >= budget {
+        return None;
+    }
+    let mut depth = 0;
+    loop {
+        // log2(2^(noise + growth) + 2^relin)
+        let grown = noise + growth;
+        noise = grown.max(relin) + (1.0 + 2f64.powf(-(grown - relin).abs())).log2();
+        if noise >= budget {
+            return Some(depth);
+        }
+        depth += 1;
+    }
+}
+
+pub type Bfv128Depth1 = Bfv<
+    { BfvParams::default_128bit_depth1().n },
+    { BfvParams::default_128bit_depth1().q },
+    { BfvParams::default_128bit_depth1().t },
+>;
+pub type Bfv128Depth2 = Bfv<
+    { BfvParams::default_128bit_depth2().n },
+    { BfvParams::default_128bit_depth2().q },
+    { BfvParams::default_128bit_depth2().t },
+>;
+pub type Bfv128Depth3 = Bfv<
+    { BfvParams::default_128bit_depth3().n },
+    { BfvParams::default_128bit_depth3().q },
+    { BfvParams::default_128bit_depth3().t },
+>;
+pub type Bfv256Depth2 = Bfv<
+    { BfvParams::default_256bit_depth2().n },
+    { BfvParams::default_256bit_depth2().q },
+    { BfvParams::default_256bit_depth2().t },
+>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bfv_pke::Evaluator;
+    use crate::ntt::is_ntt_friendly;
+    use crate::polynomial::{Polynomial, Ternary};
+    use crate::security;
+
+    #[test]
+    fn test_presets_are_sound() {
+        for p in BfvParams::ALL {
+            assert!(is_ntt_friendly(p.n, p.q), "{p:?}");
+            // |tensor coefficient| * 2t < n q^2 t
+            let bound = (p.n as u128) * (p.q as u128).pow(2) * p.t as u128;
+            assert!(bound < 1 << 127, "{p:?}");
+        }
+        let level = |p: BfvParams| match p.n {
+            2048 => security::check::<2048, Q_2048>(&Ternary::UNIFORM),
+            _ => security::check::<4096, Q_4096>(&Ternary::UNIFORM),
+        };
+        for p in BfvParams::ALL {
+            assert_eq!(level(p), Ok(p.security), "{p:?}");
+        }
+    }
+
+    fn check_depth<const N: usize, const Q: u64, const T: u64>(p: BfvParams) {
+        let (bfv, sk) = Bfv::<N, Q, T>::keygen().unwrap();
+        let evaluator = Evaluator::new().with_relin_key(bfv.gen_relin_key(&sk, p.relin_base_log));
+        let mut m = Polynomial::<N, T>::rand();
+        let mut ct = bfv.encrypt(m);
+        for _ in 0..p.depth {
+            ct = evaluator.mul(&ct, &ct).unwrap();
+            m = m * m;
+        }
+        assert_eq!(ct.decrypt(&sk), m, "{p:?}");
+        assert!(ct.noise_budget(&sk) > 0, "{p:?}");
+    }
+
+    #[test]
+    fn test_preset_depths() {
+        // polynomials of this size live on the stack, more than the 2 MiB of a test thread in debug builds
+        std::thread::Builder::new()
+            .stack_size(64 << 20)
+            .spawn(|| {
+                check_depth::<2048, Q_2048, 251>(BfvParams::default_128bit_depth1());
+                check_depth::<2048, Q_2048, 17>(BfvParams::default_128bit_depth2());
+                check_depth::<2048, Q_2048, 2>(BfvParams::default_128bit_depth3());
+                check_depth::<4096, Q_4096, 17>(BfvParams::default_256bit_depth2());
+                // the aliases are the same types
+                let _: Bfv128Depth2 = Bfv::<2048, Q_2048, 17>::keygen().unwrap().0;
+            })
+            .unwrap()
+            .join()
+            .unwrap();
+    }
+
+    #[test]
+    fn test_select() {
+        let select = BfvParams::select;
+        assert_eq!(
+            select(SecurityLevel::Bits128, 2, 2),
+            Some(BfvParams::default_128bit_depth2())
+        );
+        assert_eq!(
+            select(SecurityLevel::Bits192, 1, 2),
+            Some(BfvParams::default_256bit_depth2())
+        );
+        assert_eq!(
+            select(SecurityLevel::Bits128, 3, 2),
+            Some(BfvParams::default_128bit_depth3())
+        );
+        assert_eq!(select(SecurityLevel::Bits128, 1, 1000), None);
+        assert_eq!(select(SecurityLevel::Bits256, 3, 2), None);
+    }
+
+    #[test]
+    fn test_generate() {
+        // the noise estimates are worst case, never above what the presets are tested at
+        for p in BfvParams::ALL {
+            assert!(estimated_depth(p.n, p.q, p.t).unwrap() <= p.depth, "{p:?}");
+        }
+        assert_eq!(
+            BfvParams::generate(128, 1, 251),
+            Ok(BfvParams::default_128bit_depth1())
+        );
+        assert_eq!(
+            BfvParams::generate(128, 2, 17),
+            Ok(BfvParams::default_128bit_depth2())
+        );
+        let p = BfvParams::generate(192, 1, 17).unwrap();
+        // the tensor bound caps q below the 192 bit maximum, so it reaches the next level
+        assert_eq!((p.n, p.security), (4096, SecurityLevel::Bits256));
+        assert!(is_ntt_friendly(p.n, p.q), "{p:?}");
+        assert!((p.n as u128) * (p.q as u128).pow(2) * (p.t as u128) < 1 << 127);
+        assert_eq!(
+            BfvParams::generate(100, 1, 17),
+            Err(GenerateError::Insecure { bits: 100 })
+        );
+        assert_eq!(
+            BfvParams::generate(128, 1, 1),
+            Err(GenerateError::PlaintextModulus { t: 1 })
+        );
+        assert_eq!(
+            BfvParams::generate(128, 6, 2),
+            Err(GenerateError::Unreachable { depth: 6, t: 2 })
+        );
+    }
+}
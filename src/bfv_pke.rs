@@ -5,106 +5,1266 @@
 //! q = ciphertext modulus
 //! t = plaintext modulus
 //! n = ring dimension
+//!
+//! The roles are split as in SEAL: [`Encryptor`] holds only the public key,
+//! [`Decryptor`] the secret key and [`Evaluator`] the relinearization and Galois keys, so a server
+//! holding an `Evaluator` never sees a secret.
+//!
+//! With the `tracing` feature every operation runs in a `tracing` span (`bfv.encrypt`, `bfv.mul`,
+//! `bfv.relinearize`, ...) carrying its duration in `elapsed_us` and an estimate of the noise it adds,
+//! `noise_growth_bits` for multiplicative growth and `noise_added_bits` for additive noise. Decryption
+//! measures the remaining budget and warns once it is gone.
 
-use crate::polynomial::{Element, Polynomial};
+use crate::backend::{NativeBackend, RingBackend};
+use crate::keyswitch::{Gadget, KeySwitchKey};
+use crate::lwe::{LweCipher, extract_lwe};
+use crate::ntt::{Domain, NttPolynomial, NttTable, is_ntt_friendly};
+use crate::parallel::{self, Op};
+use crate::polynomial::{
+    DecodeError, Element, ErrorDist, Polynomial, SigmaError, Ternary, TernaryError,
+};
+use crate::security::{self, SecurityError};
+use sha3::{
+    Shake128,
+    digest::{ExtendableOutput, Update, XofReader},
+};
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::marker::PhantomData;
 use std::ops::{Add, Mul};
+use std::path::Path;
+use std::sync::Arc;
+use zeroize::{Zeroize, ZeroizeOnDrop, Zeroizing};
+
+/// Runs `$body` in a `tracing` span of level `$level` named `$name` with the given fields, recording its
+/// duration in `elapsed_us`. Fields are only evaluated when the span is enabled. Without the `tracing`
+/// feature it is just `$body`.
+macro_rules! traced {
+    ($level:ident, $name:literal, $body:expr $(, $($field:tt)*)?) => {{
+        #[cfg(feature = "tracing")]
+        let span = tracing::span!(
+            tracing::Level::$level,
+            $name,
+            $($($field)*,)?
+            elapsed_us = tracing::field::Empty
+        );
+        #[cfg(feature = "tracing")]
+        let (_entered, start) = (span.enter(), std::time::Instant::now());
+        let out = $body;
+        #[cfg(feature = "tracing")]
+        span.record("elapsed_us", start.elapsed().as_micros() as u64);
+        out
+    }};
+}
+
+/// Relinearizations since the start of the process, reported by the `bfv.relinearize` span.
+#[cfg(feature = "tracing")]
+static RELINEARIZATIONS: std::sync::atomic::AtomicU64 = std::sync::atomic::AtomicU64::new(0);
+
+/// log2 of a bound on the noise of a fresh encryption: |e u + e_2 s + e_1| <= (2n + 1) B for ternary u
+/// and s and errors below B. An estimate for tracing and `BfvParams::generate`, `BfvCiphertext::noise`
+/// measures the real value.
+pub(crate) fn fresh_noise_bits(n: usize, error: ErrorDist) -> f64 {
+    ((2 * n + 1) as f64 * error.bound().max(1) as f64).log2()
+}
 
-pub struct Bfv<const N: usize, const Q: u64, const T: u64> {
-    pk: (Polynomial<N, Q>, Polynomial<N, Q>),
+/// log2 of the noise a key switch through `gadget` adds, about n * digits * 2^base_log * B / 2 (see
+/// `keyswitch`), with the standard error since keys don't record theirs.
+pub(crate) fn switch_noise_bits(n: usize, gadget: &Gadget) -> f64 {
+    let bound = ErrorDist::STANDARD.bound() as f64;
+    (n as f64 * gadget.digits() as f64 * 2f64.powi(gadget.base_log() as i32) * bound / 2.0).log2()
 }
 
+/// log2 of the factor a tensor product multiplies the larger input noise by, about n t.
+pub(crate) fn tensor_growth_bits(n: usize, t: u64) -> f64 {
+    (n as f64 * t as f64).log2()
+}
+
+/// Data shared by a key pair and every ciphertext under it. Ciphertexts hold it behind an `Arc`,
+/// so cloning them doesn't copy the tables.
 #[derive(Debug)]
-pub struct BfvCipher<const N: usize, const Q: u64, const T: u64> {
+pub struct BfvContext<const N: usize, const Q: u64, const T: u64, B: RingBackend = NativeBackend> {
+    /// Δ = floor(q / t)
+    pub delta: u64,
+    /// Present when q is NTT friendly for n, then ring products go through the NTT.
+    pub ntt: Option<NttTable<N, Q>>,
+    /// Hash of (n, q, t, pk), two ciphertexts can only be combined if these match.
+    fingerprint: u64,
+    _backend: PhantomData<B>,
+}
+
+impl<const N: usize, const Q: u64, const T: u64, B: RingBackend> BfvContext<N, Q, T, B> {
+    fn new(pk: &BfvPublicKey<N, Q>) -> Self {
+        let mut hasher = Shake128::default();
+        hasher.update(&(N as u64).to_le_bytes());
+        hasher.update(&Q.to_le_bytes());
+        hasher.update(&T.to_le_bytes());
+        for e in pk.p_0.inner.iter().chain(pk.p_1.inner.iter()) {
+            hasher.update(&e.value().to_le_bytes());
+        }
+        let mut buf = [0u8; 8];
+        hasher.finalize_xof().read(&mut buf);
+
+        Self {
+            delta: Q / T,
+            ntt: NttTable::new(),
+            fingerprint: u64::from_le_bytes(buf),
+            _backend: PhantomData,
+        }
+    }
+
+    pub fn fingerprint(&self) -> u64 {
+        self.fingerprint
+    }
+
+    /// Whether t allows packing plaintexts into slots (CRT batching, see [`crate::batch`]). Everything else
+    /// works for any t >= 2.
+    pub fn check_batching(&self) -> Result<(), ParamError> {
+        if is_ntt_friendly(N, T) {
+            Ok(())
+        } else {
+            Err(ParamError::BatchingUnsupported { t: T, n: N })
+        }
+    }
+
+    /// Ring product, through the NTT when the table exists.
+    pub fn mul(&self, a: &Polynomial<N, Q>, b: &Polynomial<N, Q>) -> Polynomial<N, Q> {
+        match &self.ntt {
+            Some(table) => {
+                let a_hat = B::ntt_forward(table, a);
+                let b_hat = B::ntt_forward(table, b);
+                B::ntt_inverse(table, &crate::ntt::pointwise(&a_hat, &b_hat))
+            }
+            None => B::mul(a, b),
+        }
+    }
+
+    /// Moves `a` to the evaluation domain when the NTT is available, so it can be reused by `mul_cached`
+    /// without another forward transform.
+    pub fn cache(&self, a: &Polynomial<N, Q>) -> NttPolynomial<N, Q> {
+        match &self.ntt {
+            Some(table) => NttPolynomial::evaluation(B::ntt_forward(table, a)),
+            None => NttPolynomial::coefficient(*a),
+        }
+    }
+
+    /// Ring product of two cached operands, one inverse NTT and forward ones only for operands not cached yet.
+    pub fn mul_cached(&self, a: &NttPolynomial<N, Q>, b: &NttPolynomial<N, Q>) -> Polynomial<N, Q> {
+        match &self.ntt {
+            Some(table) => {
+                let to_eval = |p: &NttPolynomial<N, Q>| match p.domain() {
+                    Domain::Evaluation => *p.as_polynomial(),
+                    Domain::Coefficient => B::ntt_forward(table, p.as_polynomial()),
+                };
+                B::ntt_inverse(table, &crate::ntt::pointwise(&to_eval(a), &to_eval(b)))
+            }
+            None => B::mul(a.as_polynomial(), b.as_polynomial()),
+        }
+    }
+}
+
+impl<const N: usize, const Q: u64, const T: u64, B: RingBackend> PartialEq
+    for BfvContext<N, Q, T, B>
+{
+    fn eq(&self, other: &Self) -> bool {
+        self.fingerprint == other.fingerprint
+    }
+}
+
+/// round(t * c / q) mod t. Unlike dividing by Δ this stays exact when t doesn't divide q (composite or
+/// power of two t with a prime q): every wrap mod t only adds q mod t < t to the noise.
+#[cfg(not(feature = "constant-time"))]
+pub(crate) fn decode<const Q: u64, const T: u64>(c: u64) -> u64 {
+    ((c as u128 * T as u128 + Q as u128 / 2) / Q as u128 % T as u128) as u64
+}
+
+/// `decode` without the variable time u128 division, the rounded value is at most t.
+#[cfg(feature = "constant-time")]
+pub(crate) fn decode<const Q: u64, const T: u64>(c: u64) -> u64 {
+    let x = crate::ct::div(c as u128 * T as u128 + Q as u128 / 2, Q as u128);
+    crate::ct::reduce_once(x as u64, T)
+}
+
+/// `decode` of every coefficient of c_1 + c_2*s.
+pub(crate) fn decode_phase<const N: usize, const Q: u64, const T: u64>(
+    phase: &Polynomial<N, Q>,
+) -> Polynomial<N, T> {
+    Polynomial::new(core::array::from_fn(|i| {
+        Element::new(decode::<Q, T>(phase.inner[i].value()) as i64)
+    }))
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParamError {
+    /// Slot batching needs a prime t with t ≡ 1 mod 2n.
+    BatchingUnsupported { t: u64, n: usize },
+}
+
+impl fmt::Display for ParamError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParamError::BatchingUnsupported { t, n } => write!(
+                f,
+                "batching needs a prime plaintext modulus t ≡ 1 mod 2n, got t = {t} with n = {n}; \
+                 coefficient encoding works for any t"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ParamError {}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum KeyGenError {
+    /// Refused under `SecurityPolicy::Refuse`.
+    Security(SecurityError),
+    Sigma(SigmaError),
+    Ternary(TernaryError),
+}
+
+impl fmt::Display for KeyGenError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            KeyGenError::Security(e) => write!(f, "refusing keygen: {e}"),
+            KeyGenError::Sigma(e) => write!(f, "{e}"),
+            KeyGenError::Ternary(e) => write!(f, "{e}"),
+        }
+    }
+}
+
+impl std::error::Error for KeyGenError {}
+
+impl From<SecurityError> for KeyGenError {
+    fn from(e: SecurityError) -> Self {
+        KeyGenError::Security(e)
+    }
+}
+
+impl From<SigmaError> for KeyGenError {
+    fn from(e: SigmaError) -> Self {
+        KeyGenError::Sigma(e)
+    }
+}
+
+impl From<TernaryError> for KeyGenError {
+    fn from(e: TernaryError) -> Self {
+        KeyGenError::Ternary(e)
+    }
+}
+
+/// The ternary secret s. Zeroized on drop and never printed.
+#[derive(Clone, PartialEq)]
+pub struct BfvSecretKey<const N: usize> {
+    s: Polynomial<N, 3>,
+}
+
+impl<const N: usize> BfvSecretKey<N> {
+    /// Length of `to_bytes`.
+    pub const BYTES: usize = Polynomial::<N, 3>::BYTES;
+
+    pub fn new(s: Polynomial<N, 3>) -> Self {
+        Self { s }
+    }
+
+    /// Coefficients in {0, 1, 2}, 2 standing for -1.
+    pub fn poly(&self) -> &Polynomial<N, 3> {
+        &self.s
+    }
+
+    /// s mod q with -1 as q - 1, wiped when dropped like the key itself.
+    pub fn lift_centered<const Q: u64>(&self) -> Zeroizing<Polynomial<N, Q>> {
+        Zeroizing::new(self.s.lift_centered::<Q>())
+    }
+
+    /// `Polynomial::to_bytes`, two bits per coefficient.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        self.s.to_bytes()
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, DecodeError> {
+        Polynomial::from_bytes(bytes).map(Self::new)
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        fs::write(path, self.to_bytes())
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+        Self::from_bytes(&fs::read(path)?).map_err(invalid_data)
+    }
+}
+
+impl<const N: usize> Drop for BfvSecretKey<N> {
+    fn drop(&mut self) {
+        self.s.zeroize();
+    }
+}
+
+impl<const N: usize> ZeroizeOnDrop for BfvSecretKey<N> {}
+
+impl<const N: usize> fmt::Debug for BfvSecretKey<N> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "BfvSecretKey<{N}>(..)")
+    }
+}
+
+/// (p_0, p_1) = (-(a s + e), a).
+#[derive(Debug, Clone, PartialEq)]
+pub struct BfvPublicKey<const N: usize, const Q: u64> {
+    p_0: Polynomial<N, Q>,
+    p_1: Polynomial<N, Q>,
+}
+
+impl<const N: usize, const Q: u64> BfvPublicKey<N, Q> {
+    /// Length of `to_bytes`.
+    pub const BYTES: usize = 2 * Polynomial::<N, Q>::BYTES;
+
+    pub fn new(p_0: Polynomial<N, Q>, p_1: Polynomial<N, Q>) -> Self {
+        Self { p_0, p_1 }
+    }
+
+    pub fn p_0(&self) -> &Polynomial<N, Q> {
+        &self.p_0
+    }
+
+    pub fn p_1(&self) -> &Polynomial<N, Q> {
+        &self.p_1
+    }
+
+    /// `p_0` then `p_1`, each as `Polynomial::to_bytes`.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = self.p_0.to_bytes();
+        out.extend(self.p_1.to_bytes());
+        out
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, DecodeError> {
+        let (p_0, p_1) = split_pair::<N, Q>(bytes)?;
+        Ok(Self { p_0, p_1 })
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        fs::write(path, self.to_bytes())
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+        Self::from_bytes(&fs::read(path)?).map_err(invalid_data)
+    }
+}
+
+/// Two polynomials of `Polynomial::<N, Q>::BYTES` each.
+fn split_pair<const N: usize, const Q: u64>(
+    bytes: &[u8],
+) -> Result<(Polynomial<N, Q>, Polynomial<N, Q>), DecodeError> {
+    let len = Polynomial::<N, Q>::BYTES;
+    if bytes.len() != 2 * len {
+        return Err(DecodeError::Length {
+            expected: 2 * len,
+            got: bytes.len(),
+        });
+    }
+    let (a, b) = bytes.split_at(len);
+    Ok((Polynomial::from_bytes(a)?, Polynomial::from_bytes(b)?))
+}
+
+fn invalid_data(e: DecodeError) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, e.to_string())
+}
+
+pub struct Bfv<const N: usize, const Q: u64, const T: u64, B: RingBackend = NativeBackend> {
+    pk: BfvPublicKey<N, Q>,
+    /// `pk` in the evaluation domain (when the NTT is available), transformed once.
+    pk_cached: (NttPolynomial<N, Q>, NttPolynomial<N, Q>),
+    /// Errors of encryption and of generated switching keys.
+    error: ErrorDist,
+    ctx: Arc<BfvContext<N, Q, T, B>>,
+}
+
+/// A message, coefficients mod t.
+pub type Plaintext<const N: usize, const T: u64> = Polynomial<N, T>;
+
+#[derive(Debug, Clone)]
+pub struct BfvCiphertext<const N: usize, const Q: u64, const T: u64, B: RingBackend = NativeBackend>
+{
     c_1: Polynomial<N, Q>,
     c_2: Polynomial<N, Q>,
+    ctx: Arc<BfvContext<N, Q, T, B>>,
 }
 
-impl<const N: usize, const Q: u64, const T: u64> Bfv<N, Q, T> {
-    pub fn keygen() -> (Self, Polynomial<N, 2>) {
+impl<const N: usize, const Q: u64, const T: u64, B: RingBackend> Bfv<N, Q, T, B> {
+    pub fn keygen() -> Result<(Self, BfvSecretKey<N>), KeyGenError> {
+        Self::keygen_with(Ternary::UNIFORM)
+    }
+
+    /// Keygen with the secret drawn from `secret_dist`, e.g. `Ternary::HammingWeight(h)` for sparse secrets.
+    /// Parameters below 128 bits go through `security::enforce`, see `security::set_policy`.
+    pub fn keygen_with(secret_dist: Ternary) -> Result<(Self, BfvSecretKey<N>), KeyGenError> {
+        Self::keygen_with_error(secret_dist, ErrorDist::STANDARD)
+    }
+
+    /// Keygen with errors from `error`, for the key and everything generated or encrypted with it.
+    /// `ErrorDist::Zero` gives exact phases for debugging.
+    pub fn keygen_with_error(
+        secret_dist: Ternary,
+        error: ErrorDist,
+    ) -> Result<(Self, BfvSecretKey<N>), KeyGenError> {
+        error.validate()?;
+        secret_dist.validate(N)?;
+        security::enforce::<N, Q>(&secret_dist)?;
         /*
             a <- R_q
             e <- X
             pk[0] <- (-(a*sk)+e) mod q
             pk[1] <- a
         */
-        let sk = Polynomial::<N, 2>::rand();
+        // straight into the key, so the only copy of s is the one wiped on drop
+        let sk = BfvSecretKey::new(Polynomial::<N, 3>::ternary_valid(secret_dist));
         let a = Polynomial::<N, Q>::rand();
-        let e = Polynomial::<N, Q>::ternary_error();
-        println!("e {:?}", e);
-        let pk1 = -(a * sk.lift::<Q>() + e);
-        (Self { pk: (pk1, a) }, sk)
+        let e = Polynomial::<N, Q>::error_valid(error);
+        let p_0 = -B::add(&B::mul(&a, &sk.lift_centered::<Q>()), &e);
+        let pk = BfvPublicKey { p_0, p_1: a };
+        let mut bfv = Self::from_public_key(pk);
+        bfv.error = error;
+        Ok((bfv, sk))
     }
 
-    pub fn encrypt(&self, message: Polynomial<N, T>) -> BfvCipher<N, Q, T> {
-        let delta_elem = Element::<Q>::new(Q.div_ceil(T) as i64);
-        let delta_m = message.lift::<Q>() * delta_elem;
-        let u = Polynomial::<N, 2>::rand();
-        let e_1 = Polynomial::<N, Q>::ternary_error();
-        let e_2 = Polynomial::<N, Q>::ternary_error();
-        println!("e_1 {:?}", e_1);
-        println!("e_2 {:?}", e_2);
-        let u = u.lift::<Q>();
+    /// Encryption side only, e.g. from a key received with `BfvPublicKey::from_bytes`. The context is
+    /// derived from the key, so ciphertexts made here combine with those of the key owner.
+    pub fn from_public_key(pk: BfvPublicKey<N, Q>) -> Self {
+        let ctx = Arc::new(BfvContext::new(&pk));
+        let pk_cached = (ctx.cache(&pk.p_0), ctx.cache(&pk.p_1));
+        Self {
+            pk,
+            pk_cached,
+            error: ErrorDist::STANDARD,
+            ctx,
+        }
+    }
 
-        let c_1 = self.pk.0 * u + e_1 + delta_m;
-        let c_2 = self.pk.1 * u + e_2;
+    /// Encrypts and generates keys with errors from `error`, `ErrorDist::STANDARD` by default.
+    pub fn with_error(mut self, error: ErrorDist) -> Result<Self, SigmaError> {
+        error.validate()?;
+        self.error = error;
+        Ok(self)
+    }
 
-        BfvCipher { c_1, c_2 }
+    pub fn error_dist(&self) -> ErrorDist {
+        self.error
+    }
+
+    pub fn public_key(&self) -> &BfvPublicKey<N, Q> {
+        &self.pk
+    }
+
+    /// Inverse of `BfvCiphertext::to_bytes` for a ciphertext under this key.
+    pub fn ciphertext_from_bytes(
+        &self,
+        bytes: &[u8],
+    ) -> Result<BfvCiphertext<N, Q, T, B>, DecodeError> {
+        let (c_1, c_2) = split_pair::<N, Q>(bytes)?;
+        Ok(BfvCiphertext::new(c_1, c_2, self.ctx.clone()))
+    }
+
+    pub fn context(&self) -> &Arc<BfvContext<N, Q, T, B>> {
+        &self.ctx
+    }
+
+    /// Relinearization key, switches s^2 to s. See [`crate::keyswitch`] for the choice of `base_log`.
+    pub fn gen_relin_key(&self, sk: &BfvSecretKey<N>, base_log: u32) -> KeySwitchKey<N, Q, T, B> {
+        let s = Zeroizing::new(self.ctx.cache(&sk.lift_centered::<Q>()));
+        let s2 = Zeroizing::new(self.ctx.mul_cached(&s, &s));
+        KeySwitchKey::new_valid(self.ctx.clone(), &s2, sk.poly(), base_log, self.error)
+    }
+
+    /// Key moving ciphertexts under `old_sk` to `sk`, the secret of this key pair (secret key rotation).
+    pub fn gen_switch_key(
+        &self,
+        old_sk: &BfvSecretKey<N>,
+        sk: &BfvSecretKey<N>,
+        base_log: u32,
+    ) -> KeySwitchKey<N, Q, T, B> {
+        KeySwitchKey::new_valid(
+            self.ctx.clone(),
+            &old_sk.lift_centered::<Q>(),
+            sk.poly(),
+            base_log,
+            self.error,
+        )
     }
-}
 
-impl<const N: usize, const Q: u64, const T: u64> BfvCipher<N, Q, T> {
-    pub fn decrypt(self, sk: Polynomial<N, 2>) -> Polynomial<N, T> {
-        let ct = self.c_1 + self.c_2 * sk.lift::<Q>();
-        let delta: u64 = Q.div_ceil(T);
-        // (ct + Δ/2) / Δ  mod t
-        let p_inner: [_; N] = ct
-            .inner
+    /// Proxy re-encryption key from `sk_from` to this key pair. Needs only the public key here, so the
+    /// owner of `sk_from` can make it with `Bfv::from_public_key` of the recipient's key.
+    pub fn gen_reencryption_key(
+        &self,
+        sk_from: &BfvSecretKey<N>,
+        base_log: u32,
+    ) -> ReEncryptionKey<N, Q, T, B> {
+        ReEncryptionKey {
+            ksk: KeySwitchKey::from_public_key_valid(
+                self.ctx.clone(),
+                &sk_from.lift_centered::<Q>(),
+                &self.pk,
+                base_log,
+                self.error,
+            ),
+        }
+    }
+
+    /// Galois keys for the automorphisms x -> x^k, k in `elements` (odd, taken mod 2n), each switching
+    /// s(x^k) back to s.
+    pub fn gen_galois_keys(
+        &self,
+        sk: &BfvSecretKey<N>,
+        elements: &[usize],
+        base_log: u32,
+    ) -> Result<GaloisKeys<N, Q, T, B>, EvalError> {
+        let s = sk.lift_centered::<Q>();
+        let keys = elements
             .iter()
-            .map(|e| {
-                let rounded = (e.value() as u64 + delta / 2) / delta;
-                println!("{}", rounded);
-                Element::<T>::new(rounded as i64)
+            .map(|&k| {
+                let k = k % (2 * N);
+                if k.is_multiple_of(2) {
+                    return Err(EvalError::EvenGaloisElement { element: k });
+                }
+                let s_k = Zeroizing::new(B::automorphism(&s, k));
+                let ksk = KeySwitchKey::new_valid(
+                    self.ctx.clone(),
+                    &s_k,
+                    sk.poly(),
+                    base_log,
+                    self.error,
+                );
+                Ok((k, ksk))
             })
-            .collect::<Vec<_>>()
-            .try_into()
-            .unwrap();
-        Polynomial::new(p_inner)
-        // ct.msb()
+            .collect::<Result<_, _>>()?;
+        Ok(GaloisKeys { keys })
+    }
+
+    pub fn encrypt(&self, message: Polynomial<N, T>) -> BfvCiphertext<N, Q, T, B> {
+        traced!(
+            DEBUG,
+            "bfv.encrypt",
+            self.encrypt_inner(message),
+            noise_bits = fresh_noise_bits(N, self.error),
+            budget_bits = ((self.ctx.delta / 2) as f64).log2() - fresh_noise_bits(N, self.error)
+        )
+    }
+
+    fn encrypt_inner(&self, message: Polynomial<N, T>) -> BfvCiphertext<N, Q, T, B> {
+        let delta_elem = Element::<Q>::new(self.ctx.delta as i64);
+        let delta_m = message.lift::<Q>() * delta_elem;
+        // u decrypts the ciphertext as well as the secret key does
+        let u = Zeroizing::new(Polynomial::<N, 3>::ternary_error());
+        let e_1 = Polynomial::<N, Q>::error_valid(self.error);
+        let e_2 = Polynomial::<N, Q>::error_valid(self.error);
+        let ctx = &self.ctx;
+        let u = Zeroizing::new(ctx.cache(&Zeroizing::new(u.lift_centered::<Q>())));
+
+        let (pk_0_u, pk_1_u) = parallel::join(
+            Op::Bfv,
+            || ctx.mul_cached(&self.pk_cached.0, &u),
+            || ctx.mul_cached(&self.pk_cached.1, &u),
+        );
+        let c_1 = B::add(&B::add(&pk_0_u, &e_1), &delta_m);
+        let c_2 = B::add(&pk_1_u, &e_2);
+
+        BfvCiphertext::new(c_1, c_2, self.ctx.clone())
     }
 }
 
-impl<const N: usize, const Q: u64, const T: u64> Add for BfvCipher<N, Q, T> {
+impl<const N: usize, const Q: u64, const T: u64, B: RingBackend> BfvCiphertext<N, Q, T, B> {
+    /// Length of `to_bytes`.
+    pub const BYTES: usize = 2 * Polynomial::<N, Q>::BYTES;
+
+    pub(crate) fn new(
+        c_1: Polynomial<N, Q>,
+        c_2: Polynomial<N, Q>,
+        ctx: Arc<BfvContext<N, Q, T, B>>,
+    ) -> Self {
+        Self { c_1, c_2, ctx }
+    }
+
+    pub fn context(&self) -> &Arc<BfvContext<N, Q, T, B>> {
+        &self.ctx
+    }
+
+    /// Errors if `other` was produced under a different context (other params or key pair).
+    fn check_context(&self, other: &Self) -> Result<(), EvalError> {
+        if Arc::ptr_eq(&self.ctx, &other.ctx) || self.ctx == other.ctx {
+            Ok(())
+        } else {
+            Err(EvalError::ContextMismatch)
+        }
+    }
+
+    /// `self + rhs`, or `EvalError::ContextMismatch` if the two belong to different contexts.
+    pub fn try_add(&self, rhs: &Self) -> Result<Self, EvalError> {
+        self.check_context(rhs)?;
+        Ok(traced!(
+            TRACE,
+            "bfv.add",
+            {
+                let c_1 = B::add(&self.c_1, &rhs.c_1);
+                let c_2 = B::add(&self.c_2, &rhs.c_2);
+                Self::new(c_1, c_2, self.ctx.clone())
+            },
+            noise_growth_bits = 1.0
+        ))
+    }
+
+    pub fn c_1(&self) -> &Polynomial<N, Q> {
+        &self.c_1
+    }
+
+    pub fn c_2(&self) -> &Polynomial<N, Q> {
+        &self.c_2
+    }
+
+    /// `c_1` then `c_2`, each as `Polynomial::to_bytes`. Read back with `Bfv::ciphertext_from_bytes`.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = self.c_1.to_bytes();
+        out.extend(self.c_2.to_bytes());
+        out
+    }
+
+    /// LWE encryption of plaintext coefficient `index` under the coefficient vector of the same secret, see
+    /// [`lwe::extract_lwe`](crate::lwe::extract_lwe).
+    pub fn extract_lwe(&self, index: usize) -> LweCipher<N, Q, T> {
+        extract_lwe(&self.c_1, &self.c_2, index)
+    }
+
+    /// Same plaintext under the target secret of `ksk`: (c_1 + k_1, k_2) with (k_1, k_2) the switch of c_2.
+    /// The result belongs to the context of `ksk`.
+    pub fn switch_key(&self, ksk: &KeySwitchKey<N, Q, T, B>) -> Self {
+        traced!(
+            DEBUG,
+            "bfv.switch_key",
+            {
+                let (k_1, k_2) = ksk.switch(&self.c_2);
+                Self::new(B::add(&self.c_1, &k_1), k_2, ksk.context().clone())
+            },
+            noise_added_bits = switch_noise_bits(N, ksk.gadget())
+        )
+    }
+
+    /// Encryption of m(x^k): applies the automorphism to both components, which moves the ciphertext to
+    /// s(x^k), and switches back to s with `key` (the Galois key of k).
+    pub fn apply_galois(&self, k: usize, key: &KeySwitchKey<N, Q, T, B>) -> Self {
+        traced!(
+            DEBUG,
+            "bfv.apply_galois",
+            {
+                let c_1 = B::automorphism(&self.c_1, k);
+                let c_2 = B::automorphism(&self.c_2, k);
+                let (k_1, k_2) = key.switch(&c_2);
+                Self::new(B::add(&c_1, &k_1), k_2, key.context().clone())
+            },
+            k,
+            noise_added_bits = switch_noise_bits(N, key.gadget())
+        )
+    }
+
+    /// c_1 + c_2 * s
+    fn phase(&self, sk: &BfvSecretKey<N>) -> Polynomial<N, Q> {
+        B::add(
+            &self.c_1,
+            &self.ctx.mul(&self.c_2, &sk.lift_centered::<Q>()),
+        )
+    }
+
+    pub fn decrypt(&self, sk: &BfvSecretKey<N>) -> Polynomial<N, T> {
+        traced!(DEBUG, "bfv.decrypt", {
+            #[cfg(feature = "tracing")]
+            if tracing::enabled!(tracing::Level::DEBUG) {
+                match self.noise_budget(sk) {
+                    0 => tracing::warn!("noise budget exhausted, the decryption is likely wrong"),
+                    budget => tracing::debug!(budget_bits = budget),
+                }
+            }
+            decode_phase::<N, Q, T>(&self.phase(sk))
+        })
+    }
+
+    /// Infinity norm of the invariant noise, i.e. |c_1 + c_2*s - Δm| centered mod q.
+    pub fn noise(&self, sk: &BfvSecretKey<N>) -> u64 {
+        let ct = self.phase(sk);
+        let delta: u64 = self.ctx.delta;
+        let delta_m = Polynomial::<N, Q>::new(core::array::from_fn(|i| {
+            let m = decode::<Q, T>(ct.inner[i].value());
+            Element::new((delta * m) as i64)
+        }));
+        (ct - delta_m).linf_norm()
+    }
+
+    /// Bits of noise left before decryption breaks, log2(Δ / (2 * noise)). 0 once exhausted.
+    pub fn noise_budget(&self, sk: &BfvSecretKey<N>) -> u32 {
+        let half_delta = self.ctx.delta / 2;
+        match self.noise(sk) {
+            0 => half_delta.checked_ilog2().unwrap_or(0),
+            noise => (half_delta / noise).checked_ilog2().unwrap_or(0),
+        }
+    }
+
+    /// Encryption of m + `pt`, adding Δ`pt` to c_1. The noise grows by at most q mod t, from slots where
+    /// the sum wraps mod t.
+    pub fn add_plain(&self, pt: &Plaintext<N, T>) -> Self {
+        traced!(
+            TRACE,
+            "bfv.add_plain",
+            {
+                let delta_pt = pt.lift::<Q>() * Element::new(self.ctx.delta as i64);
+                Self::new(B::add(&self.c_1, &delta_pt), self.c_2, self.ctx.clone())
+            },
+            noise_added_bits = ((Q % T).max(1) as f64).log2()
+        )
+    }
+
+    /// Encryption of m * `pt`, multiplying by the centered lift of `pt`. The noise is multiplied by up to
+    /// its l1 norm, at most n t / 2, so a dense plaintext costs about log2(n t) bits of budget and a
+    /// monomial almost none.
+    pub fn mul_plain(&self, pt: &Plaintext<N, T>) -> Self {
+        self * pt.lift_centered::<Q>()
+    }
+
+    /// Encryption of k m for k in Z_t, multiplying both components by k's representative in (-t/2, t/2].
+    /// The noise grows by that factor, at most t / 2.
+    pub fn mul_scalar(&self, k: u64) -> Self {
+        let k = k % T;
+        let centered = if k > T / 2 {
+            k as i64 - T as i64
+        } else {
+            k as i64
+        };
+        traced!(
+            TRACE,
+            "bfv.mul_scalar",
+            {
+                let k = Element::<Q>::new(centered);
+                Self::new(self.c_1 * k, self.c_2 * k, self.ctx.clone())
+            },
+            noise_growth_bits = (centered.unsigned_abs().max(1) as f64).log2()
+        )
+    }
+}
+
+/// # Panics
+///
+/// If the two ciphertexts belong to different contexts, see `try_add`.
+impl<const N: usize, const Q: u64, const T: u64, B: RingBackend> Add for BfvCiphertext<N, Q, T, B> {
     type Output = Self;
 
     fn add(self, rhs: Self) -> Self::Output {
-        let c_1 = self.c_1 + rhs.c_1;
-        let c_2 = self.c_2 + rhs.c_2;
-        Self { c_1, c_2 }
+        self.try_add(&rhs).unwrap_or_else(|e| panic!("{e}"))
     }
 }
 
-/// plaintext * ciphertext
-impl<const N: usize, const Q: u64, const T: u64> Mul<Polynomial<N, Q>> for &BfvCipher<N, Q, T> {
-    type Output = BfvCipher<N, Q, T>;
+/// plaintext * ciphertext with the plaintext already lifted to Z_q, see `mul_plain` for mod t plaintexts.
+impl<const N: usize, const Q: u64, const T: u64, B: RingBackend> Mul<Polynomial<N, Q>>
+    for &BfvCiphertext<N, Q, T, B>
+{
+    type Output = BfvCiphertext<N, Q, T, B>;
 
     fn mul(self, pt: Polynomial<N, Q>) -> Self::Output {
-        let c0 = self.c_1 * pt;
-        let c1 = self.c_2 * pt;
+        let ctx = &self.ctx;
+        let (c0, c1) = traced!(
+            DEBUG,
+            "bfv.mul_plain",
+            parallel::join(
+                Op::Bfv,
+                || ctx.mul(&self.c_1, &pt),
+                || ctx.mul(&self.c_2, &pt),
+            ),
+            // the noise is multiplied by at most the l1 norm of the plaintext
+            noise_growth_bits = (pt
+                .to_centered()
+                .iter()
+                .map(|c| c.unsigned_abs())
+                .sum::<u64>()
+                .max(1) as f64)
+                .log2()
+        );
 
-        BfvCipher { c_1: c0, c_2: c1 }
+        BfvCiphertext::new(c0, c1, self.ctx.clone())
     }
 }
 
-// todo fix
-// impl<const N: usize, const Q: u64, const T: u64> Mul for BfvCipher<N, Q, T> {
-//     type Output = Self;
+/// Negacyclic product over the integers, no reduction. |coefficients| stay below n * (q/2)^2.
+fn mul_wide<const N: usize>(a: &[i64; N], b: &[i64; N]) -> [i128; N] {
+    let mut out = [0i128; N];
+    for i in 0..N {
+        for j in 0..N {
+            let p = a[i] as i128 * b[j] as i128;
+            if i + j < N {
+                out[i + j] += p;
+            } else {
+                out[i + j - N] -= p;
+            }
+        }
+    }
+    out
+}
 
-//     fn mul(self, rhs: Self) -> Self::Output {
-//         let c_1 = self.c_1 * rhs.c_1;
-//         let c_2 = self.c_2 * rhs.c_2;
-//         Self { c_1, c_2 }
-//     }
-// }
+/// Whether `scale_round` of a tensor product stays in i128: centered coefficients are at most q/2, so a
+/// product coefficient is at most 2n (q/2)^2 and the numerator 2t x + q at most about n q^2 t.
+const fn tensor_fits(n: usize, q: u64, t: u64) -> bool {
+    let h = (q / 2) as u128;
+    let Some(x) = h.checked_mul(h) else {
+        return false;
+    };
+    let Some(x) = x.checked_mul(4 * n as u128) else {
+        return false;
+    };
+    match x.checked_mul(t as u128) {
+        Some(num) => num < i128::MAX as u128 - q as u128,
+        None => false,
+    }
+}
+
+/// round(t * x / q) mod q for every coefficient.
+fn scale_round<const N: usize, const Q: u64, const T: u64>(x: &[i128; N]) -> Polynomial<N, Q> {
+    Polynomial::new(core::array::from_fn(|i| {
+        let num = 2 * T as i128 * x[i] + Q as i128;
+        let rounded = num.div_euclid(2 * Q as i128);
+        Element::new(rounded.rem_euclid(Q as i128) as i64)
+    }))
+}
+
+/// Product of two ciphertexts before relinearization, its phase is d_0 + d_1 s + d_2 s^2 = Δ m m' + e.
+#[derive(Debug, Clone)]
+pub struct TensoredCipher<
+    const N: usize,
+    const Q: u64,
+    const T: u64,
+    B: RingBackend = NativeBackend,
+> {
+    pub d_0: Polynomial<N, Q>,
+    pub d_1: Polynomial<N, Q>,
+    pub d_2: Polynomial<N, Q>,
+    ctx: Arc<BfvContext<N, Q, T, B>>,
+}
+
+impl<const N: usize, const Q: u64, const T: u64, B: RingBackend> BfvCiphertext<N, Q, T, B> {
+    /// Tensors the two ciphertexts over the integers (centered lifts) and scales every component by t/q
+    /// with rounding. The products are exact in i128 as long as n * q^2 * t stays below 2^127, which is
+    /// checked when the parameters are compiled.
+    ///
+    /// # Panics
+    ///
+    /// If the two ciphertexts belong to different contexts, see `try_tensor`.
+    pub fn tensor(&self, rhs: &Self) -> TensoredCipher<N, Q, T, B> {
+        self.try_tensor(rhs).unwrap_or_else(|e| panic!("{e}"))
+    }
+
+    /// `tensor`, or `EvalError::ContextMismatch` if the two belong to different contexts.
+    pub fn try_tensor(&self, rhs: &Self) -> Result<TensoredCipher<N, Q, T, B>, EvalError> {
+        const {
+            assert!(
+                tensor_fits(N, Q, T),
+                "n q^2 t has to stay below 2^127 for the tensor product"
+            )
+        };
+        self.check_context(rhs)?;
+        // the larger input noise times about n t
+        Ok(traced!(
+            DEBUG,
+            "bfv.tensor",
+            self.tensor_inner(rhs),
+            noise_growth_bits = tensor_growth_bits(N, T)
+        ))
+    }
+
+    fn tensor_inner(&self, rhs: &Self) -> TensoredCipher<N, Q, T, B> {
+        let (a0, a1) = (self.c_1.to_centered(), self.c_2.to_centered());
+        let (b0, b1) = (rhs.c_1.to_centered(), rhs.c_2.to_centered());
+
+        let d_0 = mul_wide(&a0, &b0);
+        let cross = (mul_wide(&a0, &b1), mul_wide(&a1, &b0));
+        let d_1 = core::array::from_fn(|i| cross.0[i] + cross.1[i]);
+        let d_2 = mul_wide(&a1, &b1);
+
+        TensoredCipher {
+            d_0: scale_round::<N, Q, T>(&d_0),
+            d_1: scale_round::<N, Q, T>(&d_1),
+            d_2: scale_round::<N, Q, T>(&d_2),
+            ctx: self.ctx.clone(),
+        }
+    }
+}
+
+impl<const N: usize, const Q: u64, const T: u64, B: RingBackend> TensoredCipher<N, Q, T, B> {
+    pub fn context(&self) -> &Arc<BfvContext<N, Q, T, B>> {
+        &self.ctx
+    }
+
+    /// Back to a regular ciphertext under s, switching d_2 from s^2 to s with `rlk` from `Bfv::gen_relin_key`.
+    pub fn relinearize(
+        &self,
+        rlk: &KeySwitchKey<N, Q, T, B>,
+    ) -> Result<BfvCiphertext<N, Q, T, B>, EvalError> {
+        if !(Arc::ptr_eq(&self.ctx, rlk.context()) || *self.ctx == **rlk.context()) {
+            return Err(EvalError::ContextMismatch);
+        }
+        #[cfg(feature = "tracing")]
+        let total = RELINEARIZATIONS.fetch_add(1, std::sync::atomic::Ordering::Relaxed) + 1;
+        traced!(
+            DEBUG,
+            "bfv.relinearize",
+            {
+                let (k_1, k_2) = rlk.switch(&self.d_2);
+                Ok(BfvCiphertext::new(
+                    B::add(&self.d_0, &k_1),
+                    B::add(&self.d_1, &k_2),
+                    self.ctx.clone(),
+                ))
+            },
+            noise_added_bits = switch_noise_bits(N, rlk.gadget()),
+            total
+        )
+    }
+
+    /// d_0 + d_1 s + d_2 s^2
+    fn phase(&self, sk: &BfvSecretKey<N>) -> Polynomial<N, Q> {
+        let s = sk.lift_centered::<Q>();
+        let s2 = Zeroizing::new(self.ctx.mul(&s, &s));
+        B::add(
+            &B::add(&self.d_0, &self.ctx.mul(&self.d_1, &s)),
+            &self.ctx.mul(&self.d_2, &s2),
+        )
+    }
+
+    /// Decrypts with s and s^2 directly, without relinearizing first.
+    pub fn decrypt(&self, sk: &BfvSecretKey<N>) -> Polynomial<N, T> {
+        decode_phase::<N, Q, T>(&self.phase(sk))
+    }
+}
+
+/// A ciphertext as a degree 2 one with d_2 = 0, to add to products before relinearizing.
+impl<const N: usize, const Q: u64, const T: u64, B: RingBackend> From<BfvCiphertext<N, Q, T, B>>
+    for TensoredCipher<N, Q, T, B>
+{
+    fn from(ct: BfvCiphertext<N, Q, T, B>) -> Self {
+        Self {
+            d_0: ct.c_1,
+            d_1: ct.c_2,
+            d_2: Polynomial::new([Element::new(0); N]),
+            ctx: ct.ctx,
+        }
+    }
+}
+
+/// Sums of products relinearize once.
+impl<const N: usize, const Q: u64, const T: u64, B: RingBackend> Add
+    for TensoredCipher<N, Q, T, B>
+{
+    type Output = Self;
+
+    fn add(self, rhs: Self) -> Self::Output {
+        assert!(
+            Arc::ptr_eq(&self.ctx, &rhs.ctx) || self.ctx == rhs.ctx,
+            "ciphertexts belong to different contexts ({:#x} vs {:#x})",
+            self.ctx.fingerprint,
+            rhs.ctx.fingerprint
+        );
+        Self {
+            d_0: B::add(&self.d_0, &rhs.d_0),
+            d_1: B::add(&self.d_1, &rhs.d_1),
+            d_2: B::add(&self.d_2, &rhs.d_2),
+            ctx: self.ctx,
+        }
+    }
+}
+
+/// ciphertext * ciphertext, see [`BfvCiphertext::tensor`].
+impl<const N: usize, const Q: u64, const T: u64, B: RingBackend> Mul
+    for &BfvCiphertext<N, Q, T, B>
+{
+    type Output = TensoredCipher<N, Q, T, B>;
+
+    fn mul(self, rhs: Self) -> Self::Output {
+        self.tensor(rhs)
+    }
+}
+
+/// Galois element 3^step mod 2n of a rotation by `step`. 3 generates a cyclic group of order n / 2 mod 2n,
+/// the rows of batched slots, so steps are taken mod n / 2 and negative steps rotate the other way.
+/// 2n - 1 swaps the two rows.
+pub fn galois_element<const N: usize>(step: i64) -> usize {
+    let half = (N / 2) as i64;
+    let mut k = 1;
+    for _ in 0..step.rem_euclid(half.max(1)) {
+        k = k * 3 % (2 * N);
+    }
+    k
+}
+
+/// Switching keys by Galois element, from `Bfv::gen_galois_keys`.
+#[derive(Debug, Clone)]
+pub struct GaloisKeys<const N: usize, const Q: u64, const T: u64, B: RingBackend = NativeBackend> {
+    keys: HashMap<usize, KeySwitchKey<N, Q, T, B>>,
+}
+
+impl<const N: usize, const Q: u64, const T: u64, B: RingBackend> GaloisKeys<N, Q, T, B> {
+    /// Keys received from elsewhere, by Galois element. Elements are taken mod 2n.
+    pub fn from_keys(keys: impl IntoIterator<Item = (usize, KeySwitchKey<N, Q, T, B>)>) -> Self {
+        let keys = keys
+            .into_iter()
+            .map(|(k, key)| (k % (2 * N), key))
+            .collect();
+        Self { keys }
+    }
+
+    pub fn get(&self, k: usize) -> Option<&KeySwitchKey<N, Q, T, B>> {
+        self.keys.get(&(k % (2 * N)))
+    }
+
+    /// The Galois elements there are keys for, in no particular order.
+    pub fn elements(&self) -> impl Iterator<Item = usize> + '_ {
+        self.keys.keys().copied()
+    }
+}
+
+/// Moves ciphertexts from one key pair to another without decrypting, from `Bfv::gen_reencryption_key`.
+/// Holds no secret, a proxy can keep it.
+#[derive(Debug, Clone)]
+pub struct ReEncryptionKey<
+    const N: usize,
+    const Q: u64,
+    const T: u64,
+    B: RingBackend = NativeBackend,
+> {
+    ksk: KeySwitchKey<N, Q, T, B>,
+}
+
+impl<const N: usize, const Q: u64, const T: u64, B: RingBackend> ReEncryptionKey<N, Q, T, B> {
+    /// The same plaintext under the recipient's key, in the recipient's context. `ct` must be under the
+    /// key the re-encryption key was made from, anything else decrypts to garbage.
+    pub fn re_encrypt(&self, ct: &BfvCiphertext<N, Q, T, B>) -> BfvCiphertext<N, Q, T, B> {
+        ct.switch_key(&self.ksk)
+    }
+
+    pub fn key_switch_key(&self) -> &KeySwitchKey<N, Q, T, B> {
+        &self.ksk
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EvalError {
+    MissingRelinKey,
+    MissingGaloisKey {
+        element: usize,
+    },
+    /// Galois elements are units mod 2n, so odd.
+    EvenGaloisElement {
+        element: usize,
+    },
+    /// A key or ciphertext of another key pair or parameter set.
+    ContextMismatch,
+    /// `pack_lwes` takes 1 to n ciphertexts.
+    PackCount {
+        count: usize,
+        n: usize,
+    },
+}
+
+impl fmt::Display for EvalError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EvalError::MissingRelinKey => write!(f, "evaluator has no relinearization key"),
+            EvalError::MissingGaloisKey { element } => {
+                write!(f, "evaluator has no galois key for x -> x^{element}")
+            }
+            EvalError::EvenGaloisElement { element } => {
+                write!(f, "galois element {element} must be odd")
+            }
+            EvalError::ContextMismatch => {
+                write!(f, "key or ciphertext belongs to a different context")
+            }
+            EvalError::PackCount { count, n } => {
+                write!(f, "can pack 1 to {n} ciphertexts, got {count}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for EvalError {}
+
+/// Public evaluation keys of one key pair, everything a server needs to compute on its ciphertexts.
+#[derive(Debug, Clone)]
+pub struct Evaluator<const N: usize, const Q: u64, const T: u64, B: RingBackend = NativeBackend> {
+    relin_key: Option<KeySwitchKey<N, Q, T, B>>,
+    galois_keys: Option<GaloisKeys<N, Q, T, B>>,
+}
+
+impl<const N: usize, const Q: u64, const T: u64, B: RingBackend> Default for Evaluator<N, Q, T, B> {
+    fn default() -> Self {
+        Self {
+            relin_key: None,
+            galois_keys: None,
+        }
+    }
+}
+
+impl<const N: usize, const Q: u64, const T: u64, B: RingBackend> Evaluator<N, Q, T, B> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_relin_key(mut self, rlk: KeySwitchKey<N, Q, T, B>) -> Self {
+        self.relin_key = Some(rlk);
+        self
+    }
+
+    pub fn with_galois_keys(mut self, keys: GaloisKeys<N, Q, T, B>) -> Self {
+        self.galois_keys = Some(keys);
+        self
+    }
+
+    /// Tensor and relinearize, `EvalError::ContextMismatch` if `a` and `b` belong to different contexts.
+    pub fn mul(
+        &self,
+        a: &BfvCiphertext<N, Q, T, B>,
+        b: &BfvCiphertext<N, Q, T, B>,
+    ) -> Result<BfvCiphertext<N, Q, T, B>, EvalError> {
+        traced!(DEBUG, "bfv.mul", self.relinearize(&a.try_tensor(b)?))
+    }
+
+    pub fn relinearize(
+        &self,
+        ct: &TensoredCipher<N, Q, T, B>,
+    ) -> Result<BfvCiphertext<N, Q, T, B>, EvalError> {
+        let rlk = self.relin_key.as_ref().ok_or(EvalError::MissingRelinKey)?;
+        ct.relinearize(rlk)
+    }
+
+    /// Applies x -> x^k to the plaintext of `ct`. With batched plaintexts k = `galois_element(step)` rotates
+    /// the rows of slots by `step` and k = 2n - 1 swaps them.
+    pub fn rotate(
+        &self,
+        ct: &BfvCiphertext<N, Q, T, B>,
+        k: usize,
+    ) -> Result<BfvCiphertext<N, Q, T, B>, EvalError> {
+        let k = k % (2 * N);
+        if k == 1 {
+            return Ok(ct.clone());
+        }
+        Ok(ct.apply_galois(k, self.galois_key(k)?))
+    }
+
+    /// The key of x -> x^k, k already reduced mod 2n.
+    pub(crate) fn galois_key(&self, k: usize) -> Result<&KeySwitchKey<N, Q, T, B>, EvalError> {
+        self.galois_keys
+            .as_ref()
+            .and_then(|keys| keys.get(k))
+            .ok_or(EvalError::MissingGaloisKey { element: k })
+    }
+}
+
+/// The encryption side: the public key and nothing to generate keys or evaluate with.
+pub struct Encryptor<const N: usize, const Q: u64, const T: u64, B: RingBackend = NativeBackend> {
+    bfv: Bfv<N, Q, T, B>,
+}
+
+impl<const N: usize, const Q: u64, const T: u64, B: RingBackend> Encryptor<N, Q, T, B> {
+    pub fn new(pk: BfvPublicKey<N, Q>) -> Self {
+        Self {
+            bfv: Bfv::from_public_key(pk),
+        }
+    }
+
+    /// Encrypts with errors from `error`, `ErrorDist::STANDARD` by default.
+    pub fn with_error(self, error: ErrorDist) -> Result<Self, SigmaError> {
+        Ok(Self {
+            bfv: self.bfv.with_error(error)?,
+        })
+    }
+
+    pub fn encrypt(&self, message: Polynomial<N, T>) -> BfvCiphertext<N, Q, T, B> {
+        self.bfv.encrypt(message)
+    }
+
+    pub fn public_key(&self) -> &BfvPublicKey<N, Q> {
+        self.bfv.public_key()
+    }
+
+    pub fn context(&self) -> &Arc<BfvContext<N, Q, T, B>> {
+        self.bfv.context()
+    }
+}
+
+/// The secret key holder: decrypts and measures noise.
+#[derive(Debug, Clone)]
+pub struct Decryptor<const N: usize> {
+    sk: BfvSecretKey<N>,
+}
+
+impl<const N: usize> Decryptor<N> {
+    pub fn new(sk: BfvSecretKey<N>) -> Self {
+        Self { sk }
+    }
+
+    pub fn secret_key(&self) -> &BfvSecretKey<N> {
+        &self.sk
+    }
+
+    pub fn decrypt<const Q: u64, const T: u64, B: RingBackend>(
+        &self,
+        ct: &BfvCiphertext<N, Q, T, B>,
+    ) -> Plaintext<N, T> {
+        ct.decrypt(&self.sk)
+    }
+
+    pub fn noise_budget<const Q: u64, const T: u64, B: RingBackend>(
+        &self,
+        ct: &BfvCiphertext<N, Q, T, B>,
+    ) -> u32 {
+        ct.noise_budget(&self.sk)
+    }
+}
+
+/// Keys serialize as their `to_bytes`. Ciphertexts only serialize, deserialize the bytes and read them with
+/// `Bfv::ciphertext_from_bytes`, which attaches the context of the key they were made under.
+#[cfg(feature = "serde")]
+mod serde_impls {
+    use serde::{Deserialize, Deserializer, Serialize, Serializer, de::Error};
+
+    use super::*;
+
+    impl<const N: usize> Serialize for BfvSecretKey<N> {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            serializer.serialize_bytes(&self.to_bytes())
+        }
+    }
+
+    impl<'de, const N: usize> Deserialize<'de> for BfvSecretKey<N> {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            let bytes = Vec::<u8>::deserialize(deserializer)?;
+            Self::from_bytes(&bytes).map_err(D::Error::custom)
+        }
+    }
+
+    impl<const N: usize, const Q: u64> Serialize for BfvPublicKey<N, Q> {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            serializer.serialize_bytes(&self.to_bytes())
+        }
+    }
+
+    impl<'de, const N: usize, const Q: u64> Deserialize<'de> for BfvPublicKey<N, Q> {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            let bytes = Vec::<u8>::deserialize(deserializer)?;
+            Self::from_bytes(&bytes).map_err(D::Error::custom)
+        }
+    }
+
+    impl<const N: usize, const Q: u64, const T: u64, B: RingBackend> Serialize
+        for BfvCiphertext<N, Q, T, B>
+    {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            serializer.serialize_bytes(&self.to_bytes())
+        }
+    }
+}
 
 #[cfg(test)]
 mod tests {
@@ -115,9 +1275,15 @@ mod tests {
         const T: u64 = 2;
         type E = Element<T>;
         const N: usize = 4;
-        const Q: u64 = 32;
+        // ternary s, u and e give noise up to ~2N per ciphertext, q = 32 leaves no room for that and q = 128
+        // none for Gaussian errors
+        const Q: u64 = 128;
 
-        let (bfv, sk) = Bfv::<N, Q, T>::keygen();
+        let (bfv, sk) = Bfv::<N, Q, T>::keygen_with_error(
+            Ternary::UNIFORM,
+            ErrorDist::CenteredBinomial { eta: 1 },
+        )
+        .unwrap();
 
         let m_a_1 = E::new(1);
         let m_a_2 = E::new(0);
@@ -126,7 +1292,7 @@ mod tests {
         let m_a = Polynomial::<N, T>::new([m_a_1, m_a_2, m_a_3, m_a_4]);
         println!("m_a {:?}", m_a);
         let enc_a = bfv.encrypt(m_a);
-        let enc_a_ct = enc_a.c_1 + enc_a.c_2 * sk.lift::<Q>();
+        let enc_a_ct = enc_a.c_1 + enc_a.c_2 * *sk.lift_centered::<Q>();
         println!("enc_a_ct {:?}", enc_a_ct);
 
         let m_b_1 = E::new(0);
@@ -136,15 +1302,15 @@ mod tests {
         let m_b = Polynomial::<N, T>::new([m_b_1, m_b_2, m_b_3, m_b_4]);
         println!("m_b {:?}", m_b);
         let enc_b = bfv.encrypt(m_b);
-        let enc_b_ct = enc_b.c_1 + enc_b.c_2 * sk.lift::<Q>();
+        let enc_b_ct = enc_b.c_1 + enc_b.c_2 * *sk.lift_centered::<Q>();
         println!("enc_b_ct {:?}", enc_b_ct);
 
         /* Homomorphic */
         let enc_3 = enc_a + enc_b;
-        let enc_3_ct = enc_3.c_1 + enc_3.c_2 * sk.lift::<Q>();
+        let enc_3_ct = enc_3.c_1 + enc_3.c_2 * *sk.lift_centered::<Q>();
         println!("enc_3_ct {:?}", enc_3_ct);
 
-        let dec = enc_3.decrypt(sk);
+        let dec = enc_3.decrypt(&sk);
         /* Decryption */
         // expect 1, 1, 0, 1
         println!("dec d      = {:?}", dec);
@@ -160,7 +1326,11 @@ mod tests {
         const N: usize = 4;
         const Q: u64 = 128;
 
-        let (bfv, sk) = Bfv::<N, Q, T>::keygen();
+        let (bfv, sk) = Bfv::<N, Q, T>::keygen_with_error(
+            Ternary::UNIFORM,
+            ErrorDist::CenteredBinomial { eta: 1 },
+        )
+        .unwrap();
 
         let m_a_1 = E::new(1);
         let m_a_2 = E::new(2);
@@ -169,7 +1339,7 @@ mod tests {
         let m_a = Polynomial::<N, T>::new([m_a_1, m_a_2, m_a_3, m_a_4]);
         println!("m_a {:?}", m_a);
         let enc_a = bfv.encrypt(m_a);
-        let enc_a_ct = enc_a.c_1 + enc_a.c_2 * sk.lift::<Q>();
+        let enc_a_ct = enc_a.c_1 + enc_a.c_2 * *sk.lift_centered::<Q>();
         println!("enc_a_ct {:?}", enc_a_ct);
 
         let m_b_1 = E::new(0);
@@ -179,15 +1349,15 @@ mod tests {
         let m_b = Polynomial::<N, T>::new([m_b_1, m_b_2, m_b_3, m_b_4]);
         println!("m_b {:?}", m_b);
         let enc_b = bfv.encrypt(m_b);
-        let enc_b_ct = enc_b.c_1 + enc_b.c_2 * sk.lift::<Q>();
+        let enc_b_ct = enc_b.c_1 + enc_b.c_2 * *sk.lift_centered::<Q>();
         println!("enc_b_ct {:?}", enc_b_ct);
 
         /* Homomorphic */
         let enc_3 = enc_a + enc_b;
-        let enc_3_ct = enc_3.c_1 + enc_3.c_2 * sk.lift::<Q>();
+        let enc_3_ct = enc_3.c_1 + enc_3.c_2 * *sk.lift_centered::<Q>();
         println!("enc_3_ct {:?}", enc_3_ct);
 
-        let dec = enc_3.decrypt(sk);
+        let dec = enc_3.decrypt(&sk);
         /* Decryption */
         // expect 1, 1, 0, 1
         println!("dec d      = {:?}", dec);
@@ -195,4 +1365,475 @@ mod tests {
         println!("raw = {:?}", raw_add);
         assert_eq!(raw_add, dec);
     }
+
+    #[test]
+    fn test_bfv_sparse_secret() {
+        const T: u64 = 4;
+        const N: usize = 16;
+        const Q: u64 = 1 << 12;
+
+        let (bfv, sk) = Bfv::<N, Q, T>::keygen_with(Ternary::HammingWeight(4)).unwrap();
+        assert_eq!(sk.poly().inner.iter().filter(|e| e.value() != 0).count(), 4);
+        assert!(matches!(
+            Bfv::<N, Q, T>::keygen_with(Ternary::HammingWeight(N + 1)),
+            Err(KeyGenError::Ternary(TernaryError::HammingWeight {
+                h: 17,
+                n: N
+            }))
+        ));
+
+        let m = Polynomial::<N, T>::rand();
+        assert_eq!(bfv.encrypt(m).decrypt(&sk), m);
+    }
+
+    #[test]
+    fn test_zero_error() {
+        const T: u64 = 4;
+        const N: usize = 16;
+        const Q: u64 = 1 << 12;
+
+        let (bfv, sk) =
+            Bfv::<N, Q, T>::keygen_with_error(Ternary::UNIFORM, ErrorDist::Zero).unwrap();
+        assert_eq!(bfv.error_dist(), ErrorDist::Zero);
+        let m = Polynomial::<N, T>::rand();
+        let ct = bfv.encrypt(m);
+        assert_eq!(ct.noise(&sk), 0);
+        assert_eq!(ct.decrypt(&sk), m);
+
+        let (bfv, sk) = Bfv::<N, Q, T>::keygen().unwrap();
+        assert_eq!(bfv.error_dist(), ErrorDist::STANDARD);
+        assert_eq!(bfv.encrypt(m).decrypt(&sk), m);
+    }
+
+    #[test]
+    fn test_shared_context() {
+        const T: u64 = 4;
+        const N: usize = 16;
+        // 12289 = 1 mod 32, products go through the NTT
+        const Q: u64 = 12_289;
+
+        let (bfv, sk) = Bfv::<N, Q, T>::keygen().unwrap();
+        assert!(bfv.context().ntt.is_some());
+
+        let m_a = Polynomial::<N, T>::rand();
+        let m_b = Polynomial::<N, T>::rand();
+        let enc_a = bfv.encrypt(m_a);
+        let enc_b = bfv.encrypt(m_b);
+        let copy = enc_a.clone();
+        assert!(Arc::ptr_eq(copy.context(), bfv.context()));
+        assert_eq!(Arc::strong_count(bfv.context()), 4);
+
+        assert_eq!((enc_a + enc_b).decrypt(&sk), m_a + m_b);
+    }
+
+    #[test]
+    #[should_panic(expected = "different context")]
+    fn test_add_across_keys_panics() {
+        const T: u64 = 2;
+        const N: usize = 4;
+        const Q: u64 = 128;
+
+        let (bfv_a, _) = Bfv::<N, Q, T>::keygen().unwrap();
+        let (bfv_b, _) = Bfv::<N, Q, T>::keygen().unwrap();
+        let m = Polynomial::<N, T>::rand();
+        let _ = bfv_a.encrypt(m) + bfv_b.encrypt(m);
+    }
+
+    #[test]
+    fn test_context_mismatch_errors() {
+        const T: u64 = 2;
+        const N: usize = 4;
+        const Q: u64 = 1 << 20;
+
+        let (bfv_a, sk_a) = Bfv::<N, Q, T>::keygen().unwrap();
+        let (bfv_b, _) = Bfv::<N, Q, T>::keygen().unwrap();
+        let m = Polynomial::<N, T>::rand();
+        let (a, b) = (bfv_a.encrypt(m), bfv_b.encrypt(m));
+        assert_eq!(a.try_add(&b).unwrap_err(), EvalError::ContextMismatch);
+        assert_eq!(a.try_tensor(&b).unwrap_err(), EvalError::ContextMismatch);
+        let evaluator = Evaluator::new().with_relin_key(bfv_a.gen_relin_key(&sk_a, 4));
+        assert_eq!(
+            evaluator.mul(&a, &b).unwrap_err(),
+            EvalError::ContextMismatch
+        );
+        assert_eq!(a.try_add(&a).unwrap().decrypt(&sk_a), m + m);
+    }
+
+    #[test]
+    fn test_composite_t_wraps() {
+        fn check<const Q: u64, const T: u64>() {
+            const N: usize = 16;
+            let (bfv, sk) = Bfv::<N, Q, T>::keygen().unwrap();
+            let m = Polynomial::<N, T>::rand();
+            let ct = bfv.encrypt(m);
+
+            // eight additions wrap most coefficients mod t several times
+            let mut acc = ct.clone();
+            let mut expected = m;
+            for _ in 0..7 {
+                acc = acc + ct.clone();
+                expected = expected + m;
+            }
+            assert_eq!(acc.decrypt(&sk), expected);
+        }
+        // power of two t under a prime q, t doesn't divide q
+        check::<65_537, 16>();
+        check::<{ (1 << 31) - 1 }, 256>();
+        // composite t under a power of two q
+        check::<{ 1 << 30 }, 100>();
+        // power of two both
+        check::<{ 1 << 30 }, { 1 << 10 }>();
+    }
+
+    #[test]
+    fn test_plain_ops() {
+        const N: usize = 16;
+        const Q: u64 = 1 << 40;
+        const T: u64 = 17;
+        let (bfv, sk) = Bfv::<N, Q, T>::keygen().unwrap();
+        let m = Polynomial::<N, T>::rand();
+        let pt = Polynomial::<N, T>::rand();
+        let ct = bfv.encrypt(m);
+        let budget = ct.noise_budget(&sk);
+
+        let sum = ct.add_plain(&pt);
+        assert_eq!(sum.decrypt(&sk), m + pt);
+        assert!(sum.noise_budget(&sk) + 1 >= budget);
+
+        let prod = ct.mul_plain(&pt);
+        assert_eq!(prod.decrypt(&sk), m * pt);
+        let cost = (N as u64 * T).ilog2() + 1;
+        assert!(prod.noise_budget(&sk) + cost >= budget);
+
+        // x^3 only rotates the noise, wrapped coefficients pick up q mod t
+        let mut x_3 = Polynomial::<N, T>::new([Element::new(0); N]);
+        x_3.inner[3] = Element::new(1);
+        let shifted = ct.mul_plain(&x_3);
+        assert_eq!(shifted.decrypt(&sk), m * x_3);
+        assert!(shifted.noise(&sk) <= ct.noise(&sk) + Q % T);
+    }
+
+    #[test]
+    fn test_mul_scalar() {
+        const N: usize = 16;
+        const Q: u64 = 1 << 40;
+        const T: u64 = 17;
+        let (bfv, sk) = Bfv::<N, Q, T>::keygen().unwrap();
+        let m = Polynomial::<N, T>::rand();
+        let ct = bfv.encrypt(m);
+        for k in [0, 1, 2, 8, 9, 16, 17 + 3, u64::MAX] {
+            let scaled = ct.mul_scalar(k);
+            assert_eq!(
+                scaled.decrypt(&sk),
+                m * Element::new((k % T) as i64),
+                "k = {k}"
+            );
+        }
+        // 16 = -1 mod 17, so the noise keeps its size up to the q mod t of the wrapped slots
+        assert!(ct.mul_scalar(16).noise(&sk) <= ct.noise(&sk) + Q % T);
+
+        // weighted sum 3 a + 5 b
+        let b = Polynomial::<N, T>::rand();
+        let sum = ct.mul_scalar(3) + bfv.encrypt(b).mul_scalar(5);
+        assert_eq!(sum.decrypt(&sk), m * Element::new(3) + b * Element::new(5));
+    }
+
+    #[test]
+    fn test_ciphertext_mul() {
+        fn check<const Q: u64, const T: u64>() {
+            const N: usize = 16;
+            let (bfv, sk) = Bfv::<N, Q, T>::keygen().unwrap();
+            let m_a = Polynomial::<N, T>::rand();
+            let m_b = Polynomial::<N, T>::rand();
+            let prod = &bfv.encrypt(m_a) * &bfv.encrypt(m_b);
+            assert_eq!(prod.decrypt(&sk), m_a * m_b);
+        }
+        check::<{ 1 << 40 }, 257>();
+        check::<{ 1 << 30 }, 2>();
+        // t not dividing q
+        check::<{ (1 << 31) - 1 }, 16>();
+    }
+
+    #[test]
+    fn test_relinearize() {
+        const N: usize = 16;
+        // depth 2 needs more room than a single product
+        const Q: u64 = 1 << 50;
+        const T: u64 = 17;
+        let (bfv, sk) = Bfv::<N, Q, T>::keygen().unwrap();
+        let m_a = Polynomial::<N, T>::rand();
+        let m_b = Polynomial::<N, T>::rand();
+        let m_c = Polynomial::<N, T>::rand();
+        for base_log in [8, 20] {
+            let rlk = bfv.gen_relin_key(&sk, base_log);
+            let ab = (&bfv.encrypt(m_a) * &bfv.encrypt(m_b))
+                .relinearize(&rlk)
+                .unwrap();
+            assert_eq!(ab.decrypt(&sk), m_a * m_b);
+            // depth 2
+            let abc = (&ab * &bfv.encrypt(m_c)).relinearize(&rlk).unwrap();
+            assert_eq!(abc.decrypt(&sk), m_a * m_b * m_c);
+        }
+    }
+
+    #[test]
+    fn test_roles() {
+        const N: usize = 16;
+        const Q: u64 = 1 << 50;
+        const T: u64 = 17;
+        let (bfv, sk) = Bfv::<N, Q, T>::keygen().unwrap();
+        // the server only gets evaluation keys
+        let evaluator = Evaluator::new().with_relin_key(bfv.gen_relin_key(&sk, 16));
+        let encryptor = Encryptor::new(bfv.public_key().clone())
+            .with_error(bfv.error_dist())
+            .unwrap();
+        assert_eq!(
+            encryptor.context().fingerprint(),
+            bfv.context().fingerprint()
+        );
+        let decryptor = Decryptor::new(sk);
+
+        let m_a = Polynomial::<N, T>::rand();
+        let m_b = Polynomial::<N, T>::rand();
+        let ct = evaluator
+            .mul(&encryptor.encrypt(m_a), &encryptor.encrypt(m_b))
+            .unwrap();
+        assert_eq!(decryptor.decrypt(&ct), m_a * m_b);
+        assert!(decryptor.noise_budget(&ct) > 0);
+        // the ciphertext is still there to compute on
+        let ab = m_a * m_b;
+        assert_eq!(decryptor.decrypt(&(ct.clone() + ct)), ab + ab);
+    }
+
+    #[test]
+    fn test_secret_key_rotation() {
+        const N: usize = 16;
+        const Q: u64 = 1 << 30;
+        const T: u64 = 17;
+        let (old, old_sk) = Bfv::<N, Q, T>::keygen().unwrap();
+        let (new, new_sk) = Bfv::<N, Q, T>::keygen().unwrap();
+        let m = Polynomial::<N, T>::rand();
+
+        let ksk = new.gen_switch_key(&old_sk, &new_sk, 6);
+        let moved = old.encrypt(m).switch_key(&ksk);
+        assert!(Arc::ptr_eq(moved.context(), new.context()));
+        assert_eq!((moved + new.encrypt(m)).decrypt(&new_sk), m + m);
+    }
+
+    #[test]
+    fn test_re_encrypt() {
+        const N: usize = 16;
+        const Q: u64 = 1 << 50;
+        const T: u64 = 17;
+        let (alice, alice_sk) = Bfv::<N, Q, T>::keygen().unwrap();
+        let (bob, bob_sk) = Bfv::<N, Q, T>::keygen().unwrap();
+        let m = Polynomial::<N, T>::rand();
+
+        // alice only sees bob's public key
+        let to_bob = Bfv::<N, Q, T>::from_public_key(bob.public_key().clone());
+        let rk = to_bob.gen_reencryption_key(&alice_sk, 8);
+        let ct = alice.encrypt(m);
+        let moved = rk.re_encrypt(&ct);
+        assert_eq!(moved.decrypt(&bob_sk), m);
+        assert!(moved.noise_budget(&bob_sk) > 0);
+        assert_ne!(moved.decrypt(&alice_sk), m);
+        // and it computes with bob's own ciphertexts
+        assert_eq!((moved + bob.encrypt(m)).decrypt(&bob_sk), m + m);
+    }
+
+    #[test]
+    fn test_rotate() {
+        const N: usize = 16;
+        const Q: u64 = 1 << 40;
+        const T: u64 = 257;
+        let (bfv, sk) = Bfv::<N, Q, T>::keygen().unwrap();
+        let elements = [galois_element::<N>(1), galois_element::<N>(-1), 2 * N - 1];
+        assert_eq!(elements[..2], [3, 11]);
+        let evaluator =
+            Evaluator::new().with_galois_keys(bfv.gen_galois_keys(&sk, &elements, 10).unwrap());
+
+        let m = Polynomial::<N, T>::rand();
+        let ct = bfv.encrypt(m);
+        for k in elements {
+            let rotated = evaluator.rotate(&ct, k).unwrap();
+            assert_eq!(rotated.decrypt(&sk), m.automorphism(k), "k = {k}");
+        }
+        // x -> x^3 twice is x -> x^9
+        let twice = evaluator
+            .rotate(&evaluator.rotate(&ct, 3).unwrap(), 3)
+            .unwrap();
+        assert_eq!(twice.decrypt(&sk), m.automorphism(9));
+        assert_eq!(evaluator.rotate(&ct, 1).unwrap().decrypt(&sk), m);
+
+        assert_eq!(
+            evaluator.rotate(&ct, 5).unwrap_err(),
+            EvalError::MissingGaloisKey { element: 5 }
+        );
+        assert_eq!(
+            evaluator.mul(&ct, &ct).unwrap_err(),
+            EvalError::MissingRelinKey
+        );
+    }
+
+    #[test]
+    fn test_mul_wide_matches_ring() {
+        const N: usize = 8;
+        const Q: u64 = 97;
+        let a = Polynomial::<N, Q>::rand();
+        let b = Polynomial::<N, Q>::rand();
+        let wide = mul_wide(&a.to_centered(), &b.to_centered());
+        let reduced = Polynomial::<N, Q>::new(core::array::from_fn(|i| {
+            Element::new(wide[i].rem_euclid(Q as i128) as i64)
+        }));
+        assert_eq!(reduced, a * b);
+    }
+
+    #[test]
+    fn test_key_and_ciphertext_bytes() {
+        const N: usize = 16;
+        const Q: u64 = 12_289;
+        const T: u64 = 4;
+        let (bfv, sk) = Bfv::<N, Q, T>::keygen().unwrap();
+        let m = Polynomial::<N, T>::rand();
+        let ct = bfv.encrypt(m);
+
+        // the client ships pk, the server encrypts under the rebuilt key
+        let pk_bytes = bfv.public_key().to_bytes();
+        assert_eq!(pk_bytes.len(), BfvPublicKey::<N, Q>::BYTES);
+        let server = Bfv::<N, Q, T>::from_public_key(BfvPublicKey::from_bytes(&pk_bytes).unwrap());
+        assert_eq!(server.context().fingerprint(), bfv.context().fingerprint());
+        let sum = server.encrypt(m) + ct.clone();
+
+        let loaded = bfv.ciphertext_from_bytes(&sum.to_bytes()).unwrap();
+        assert_eq!((loaded.c_1(), loaded.c_2()), (sum.c_1(), sum.c_2()));
+        let sk = BfvSecretKey::<N>::from_bytes(&sk.to_bytes()).unwrap();
+        assert_eq!(loaded.decrypt(&sk), m + m);
+        assert_eq!(format!("{sk:?}"), "BfvSecretKey<16>(..)");
+
+        assert_eq!(
+            bfv.ciphertext_from_bytes(&pk_bytes[1..]).unwrap_err(),
+            DecodeError::Length {
+                expected: pk_bytes.len(),
+                got: pk_bytes.len() - 1
+            }
+        );
+    }
+
+    #[test]
+    fn test_save_load() {
+        let dir = std::env::temp_dir().join(format!("rlattice-keys-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let (bfv, sk) = Bfv::<16, 12_289, 4>::keygen().unwrap();
+        bfv.public_key().save(dir.join("pk")).unwrap();
+        sk.save(dir.join("sk")).unwrap();
+        assert_eq!(
+            &BfvPublicKey::load(dir.join("pk")).unwrap(),
+            bfv.public_key()
+        );
+        assert!(BfvSecretKey::<16>::load(dir.join("sk")).unwrap() == sk);
+
+        fs::write(dir.join("bad"), [0xff; 3]).unwrap();
+        let err = BfvPublicKey::<16, 12_289>::load(dir.join("bad")).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde() {
+        let (bfv, sk) = Bfv::<16, 12_289, 4>::keygen().unwrap();
+        let m = Polynomial::<16, 4>::rand();
+        let json = serde_json::to_string(&(bfv.public_key(), &sk, bfv.encrypt(m))).unwrap();
+        let (pk, sk, ct): (BfvPublicKey<16, 12_289>, BfvSecretKey<16>, Vec<u8>) =
+            serde_json::from_str(&json).unwrap();
+        assert_eq!(&pk, bfv.public_key());
+        assert_eq!(bfv.ciphertext_from_bytes(&ct).unwrap().decrypt(&sk), m);
+    }
+
+    #[test]
+    fn test_tensor_fits() {
+        assert!(tensor_fits(8, 1 << 59, 17));
+        assert!(!tensor_fits(8, 1 << 60, 17));
+        // the largest preset, 2^11 (2^54)^2 251 is just below 2^127
+        assert!(tensor_fits(2048, 18_014_398_509_404_161, 251));
+        assert!(!tensor_fits(2048, 18_014_398_509_404_161, 257));
+        assert!(!tensor_fits(1 << 15, u64::MAX, 2));
+    }
+
+    #[test]
+    fn test_check_batching() {
+        let (bfv, _) = Bfv::<16, 12_289, 257>::keygen().unwrap();
+        assert_eq!(bfv.context().check_batching(), Ok(()));
+
+        let (bfv, _) = Bfv::<16, 12_289, 256>::keygen().unwrap();
+        let err = bfv.context().check_batching().unwrap_err();
+        assert_eq!(err, ParamError::BatchingUnsupported { t: 256, n: 16 });
+    }
+
+    #[cfg(feature = "tracing")]
+    #[test]
+    fn test_tracing_spans() {
+        use std::sync::Mutex;
+        use tracing::span::{Attributes, Id, Record};
+        use tracing::{Event, Level, Metadata, Subscriber};
+
+        /// Names of the spans opened and levels of the events, in order.
+        #[derive(Clone, Default)]
+        struct Collector(Arc<Mutex<Vec<String>>>);
+
+        impl Subscriber for Collector {
+            fn enabled(&self, _: &Metadata<'_>) -> bool {
+                true
+            }
+            fn new_span(&self, span: &Attributes<'_>) -> Id {
+                let mut seen = self.0.lock().unwrap();
+                seen.push(span.metadata().name().to_string());
+                Id::from_u64(seen.len() as u64)
+            }
+            fn record(&self, _: &Id, _: &Record<'_>) {}
+            fn record_follows_from(&self, _: &Id, _: &Id) {}
+            fn event(&self, event: &Event<'_>) {
+                let level = *event.metadata().level();
+                self.0.lock().unwrap().push(level.to_string());
+            }
+            fn enter(&self, _: &Id) {}
+            fn exit(&self, _: &Id) {}
+        }
+
+        const N: usize = 16;
+        const Q: u64 = 1 << 50;
+        const T: u64 = 17;
+        let (bfv, sk) = Bfv::<N, Q, T>::keygen().unwrap();
+        let evaluator = Evaluator::new().with_relin_key(bfv.gen_relin_key(&sk, 16));
+        let collector = Collector::default();
+        let m = Polynomial::<N, T>::rand();
+        tracing::subscriber::with_default(collector.clone(), || {
+            let ct = evaluator.mul(&bfv.encrypt(m), &bfv.encrypt(m)).unwrap();
+            assert_eq!(ct.decrypt(&sk), m * m);
+            // no budget left after squaring 40 times
+            let mut ct = ct;
+            for _ in 0..40 {
+                ct = evaluator.mul(&ct, &ct).unwrap();
+            }
+            ct.decrypt(&sk);
+        });
+
+        let seen = collector.0.lock().unwrap();
+        let position = |name: &str| seen.iter().position(|s| s == name);
+        for name in [
+            "bfv.encrypt",
+            "bfv.mul",
+            "bfv.tensor",
+            "bfv.relinearize",
+            "bfv.decrypt",
+        ] {
+            assert!(position(name).is_some(), "no {name} span in {seen:?}");
+        }
+        assert!(position("bfv.tensor") > position("bfv.mul"));
+        assert_eq!(
+            seen[position("bfv.decrypt").unwrap() + 1],
+            Level::DEBUG.to_string()
+        );
+        assert_eq!(seen.last().unwrap(), &Level::WARN.to_string());
+    }
 }
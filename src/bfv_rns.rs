@@ -0,0 +1,559 @@
+//! BFV over an RNS ciphertext modulus Q = q_0 * ... * q_{k-1}, the HPS variant
+//! (https://eprint.iacr.org/2018/117.pdf). Unlike `bfv_pke`, where q is a single `u64` const parameter,
+//! Q is only bounded by the number of primes, and nothing needs wider than `u128` arithmetic.
+//!
+//! Multiplication follows HPS:
+//! 1. both ciphertexts are extended from Q to QP with the exact [`BaseConverter`],
+//! 2. tensored in QP, where P > n t Q leaves room for the full integer products,
+//! 3. scaled by t/Q with rounding directly in P: for x in QP with CRT digits y_r,
+//!    round(t x / Q) = sum_i y_i floor(tP/q_i) + round(sum_i y_i frac(tP/q_i)) + y_{p_j} tP/p_j mod p_j,
+//!    the fractional sum in floating point,
+//! 4. converted back from P to Q.
+//!
+//! Decryption is the same rounding with P replaced by t. Relinearization uses the RNS gadget, the digits
+//! of d are its CRT digits [d (Q/q_i)^-1]_{q_i}, so there is one key row per prime.
+//!
+//! Products run through a per prime NTT when the primes are ≡ 1 mod 2n, see [`RnsBasis::with_ntt`]. Keygen
+//! checks n and the bit length of Q against `security` like `Bfv::keygen`.
+
+use std::fmt;
+use std::ops::Add;
+use std::sync::Arc;
+
+use zeroize::Zeroizing;
+
+use crate::bfv_pke::BfvSecretKey;
+use crate::field::{LazyAcc, PrimeField, Ring};
+use crate::polynomial::{ErrorDist, Polynomial, SigmaError, Ternary};
+use crate::rns::{BaseConverter, RnsBasis, RnsError, RnsPolynomial};
+use crate::security::{self, SecurityError};
+
+/// The primes of Q, those of the auxiliary basis P, the plaintext modulus and the distribution of the
+/// errors of keys and encryptions.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BfvRnsParams {
+    pub q: Vec<u64>,
+    pub p: Vec<u64>,
+    pub t: u64,
+    pub error: ErrorDist,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum BfvRnsError {
+    Basis(RnsError),
+    /// t has to be at least 2 and below every prime of Q.
+    PlaintextModulus {
+        t: u64,
+    },
+    /// P has to exceed n t Q for the tensor products to be exact.
+    AuxiliaryTooSmall {
+        need_bits: u32,
+        got_bits: u32,
+    },
+    Sigma(SigmaError),
+    /// Refused under `SecurityPolicy::Refuse`.
+    Security(SecurityError),
+}
+
+impl fmt::Display for BfvRnsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BfvRnsError::Basis(e) => write!(f, "{e}"),
+            BfvRnsError::PlaintextModulus { t } => {
+                write!(f, "plaintext modulus {t} must be in 2..min(q_i)")
+            }
+            BfvRnsError::AuxiliaryTooSmall {
+                need_bits,
+                got_bits,
+            } => write!(
+                f,
+                "auxiliary basis has {got_bits} bits, multiplication needs {need_bits}"
+            ),
+            BfvRnsError::Sigma(e) => write!(f, "{e}"),
+            BfvRnsError::Security(e) => write!(f, "refusing keygen: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for BfvRnsError {}
+
+impl From<SigmaError> for BfvRnsError {
+    fn from(e: SigmaError) -> Self {
+        BfvRnsError::Sigma(e)
+    }
+}
+
+impl From<SecurityError> for BfvRnsError {
+    fn from(e: SecurityError) -> Self {
+        BfvRnsError::Security(e)
+    }
+}
+
+/// Bases, converters and the precomputed constants of one parameter set.
+#[derive(Debug)]
+pub struct BfvRnsContext<const N: usize> {
+    pub q: RnsBasis,
+    pub p: RnsBasis,
+    qp: RnsBasis,
+    pub t: u64,
+    pub error: ErrorDist,
+    /// [floor(Q/t)]_{q_i}
+    delta: Vec<u64>,
+    /// [Q/q_i]_{q_i}
+    q_hat: Vec<u64>,
+    q_to_p: BaseConverter,
+    p_to_q: BaseConverter,
+    /// [floor(tP/q_i)]_{p_j}, indexed [j][i]
+    scale_int: Vec<Vec<u64>>,
+    /// [tP]_{q_i}, the numerators of frac(tP/q_i)
+    scale_rem: Vec<u64>,
+    /// [tP/p_j]_{p_j}
+    scale_p: Vec<u64>,
+}
+
+impl<const N: usize> BfvRnsContext<N> {
+    pub fn new(params: &BfvRnsParams) -> Result<Self, BfvRnsError> {
+        params.error.validate()?;
+        let q = RnsBasis::new(&params.q).map_err(BfvRnsError::Basis)?;
+        let p = RnsBasis::new(&params.p).map_err(BfvRnsError::Basis)?;
+        let qp = q.concat(&p).map_err(BfvRnsError::Basis)?.with_ntt(N);
+        let (q, p) = (q.with_ntt(N), p.with_ntt(N));
+        let t = params.t;
+        if t < 2 || params.q.iter().any(|&q_i| q_i <= t) {
+            return Err(BfvRnsError::PlaintextModulus { t });
+        }
+        let need_bits = (q.bits() + (N as f64).log2() + (t as f64).log2()).ceil() as u32 + 2;
+        let got_bits = p.bits().floor() as u32;
+        if got_bits < need_bits {
+            return Err(BfvRnsError::AuxiliaryTooSmall {
+                need_bits,
+                got_bits,
+            });
+        }
+
+        // floor(Q/t) = (Q - [Q]_t) / t
+        let q_mod_t = q.product_mod(t);
+        let delta = (0..q.len())
+            .map(|i| {
+                let q_i = q.modulus(i);
+                q_i.mul(q_i.neg(q_mod_t), q_i.inv(t).expect("t < q_i"))
+            })
+            .collect();
+        let q_hat = (0..q.len())
+            .map(|i| {
+                let q_i = q.modulus(i);
+                q_i.inv(q.hat_inv()[i]).expect("nonzero")
+            })
+            .collect();
+
+        let scale_rem = (0..q.len())
+            .map(|i| {
+                let q_i = q.modulus(i);
+                q_i.mul(t % q_i.modulus(), p.product_mod(q_i.modulus()))
+            })
+            .collect::<Vec<_>>();
+        // floor(tP/q_i) = (tP - r_i) / q_i, and tP = 0 mod p_j
+        let scale_int = (0..p.len())
+            .map(|j| {
+                let p_j = p.modulus(j);
+                (0..q.len())
+                    .map(|i| {
+                        let q_inv = p_j.inv(q.modulus(i).modulus()).expect("distinct primes");
+                        p_j.mul(p_j.neg(scale_rem[i] % p_j.modulus()), q_inv)
+                    })
+                    .collect()
+            })
+            .collect();
+        let scale_p = (0..p.len())
+            .map(|j| {
+                let p_j = p.modulus(j);
+                p_j.mul(t % p_j.modulus(), p_j.inv(p.hat_inv()[j]).expect("nonzero"))
+            })
+            .collect();
+
+        Ok(Self {
+            q_to_p: BaseConverter::new(&q, &p),
+            p_to_q: BaseConverter::new(&p, &q),
+            q,
+            p,
+            qp,
+            t,
+            error: params.error,
+            delta,
+            q_hat,
+            scale_int,
+            scale_rem,
+            scale_p,
+        })
+    }
+
+    /// round(t x / Q) mod Q for `x` given in QP.
+    fn scale_round(&self, x: &RnsPolynomial<N>) -> RnsPolynomial<N> {
+        let k = self.q.len();
+        let mut out = self.p.zero::<N>();
+        for c in 0..N {
+            let y = self.qp.crt_digits(x, c);
+            let mut int_part = 0u128;
+            let mut frac = 0f64;
+            for (i, (&y_i, &rem)) in y[..k].iter().zip(&self.scale_rem).enumerate() {
+                let q_i = self.q.modulus(i).modulus() as u128;
+                let num = y_i as u128 * rem as u128;
+                int_part += num / q_i;
+                frac += (num % q_i) as f64 / q_i as f64;
+            }
+            let int_part = int_part + frac.round() as u128;
+            for j in 0..self.p.len() {
+                let p_j = self.p.modulus(j);
+                let mut acc = LazyAcc::new(p_j);
+                for (&y_i, &int) in y[..k].iter().zip(&self.scale_int[j]) {
+                    acc.add_mul(y_i % p_j.modulus(), int);
+                }
+                acc.add_mul(y[k + j], self.scale_p[j]);
+                acc.add(p_j.reduce(int_part));
+                out.residues[j][c] = acc.finish();
+            }
+        }
+        self.p_to_q.convert(&out)
+    }
+
+    /// round(t x / Q) mod t for `x` given in Q.
+    fn decode(&self, x: &RnsPolynomial<N>) -> [u64; N] {
+        let t = self.t as u128;
+        core::array::from_fn(|c| {
+            let y = self.q.crt_digits(x, c);
+            let mut int_part = 0u128;
+            let mut frac = 0f64;
+            for (i, &y_i) in y.iter().enumerate() {
+                let q_i = self.q.modulus(i).modulus() as u128;
+                let num = y_i as u128 * t;
+                int_part += num / q_i;
+                frac += (num % q_i) as f64 / q_i as f64;
+            }
+            ((int_part + frac.round() as u128) % t) as u64
+        })
+    }
+
+    fn ternary(&self) -> RnsPolynomial<N> {
+        self.q
+            .from_signed(&Polynomial::<N, 3>::ternary_error().to_centered())
+    }
+
+    /// s in every residue of Q, wiped when dropped.
+    fn secret(&self, sk: &BfvSecretKey<N>) -> Zeroizing<RnsPolynomial<N>> {
+        Zeroizing::new(self.q.from_signed(&Zeroizing::new(sk.poly().to_centered())))
+    }
+
+    fn error(&self) -> RnsPolynomial<N> {
+        let mut rng = rand::rng();
+        self.q
+            .from_signed(&core::array::from_fn(|_| self.error.sample_valid(&mut rng)))
+    }
+}
+
+pub struct BfvRns<const N: usize> {
+    pk: (RnsPolynomial<N>, RnsPolynomial<N>),
+    ctx: Arc<BfvRnsContext<N>>,
+}
+
+#[derive(Debug, Clone)]
+pub struct BfvRnsCipher<const N: usize> {
+    c_1: RnsPolynomial<N>,
+    c_2: RnsPolynomial<N>,
+    ctx: Arc<BfvRnsContext<N>>,
+}
+
+/// Product of two ciphertexts before relinearization, phase d_0 + d_1 s + d_2 s^2.
+#[derive(Debug, Clone)]
+pub struct BfvRnsTensored<const N: usize> {
+    d_0: RnsPolynomial<N>,
+    d_1: RnsPolynomial<N>,
+    d_2: RnsPolynomial<N>,
+    ctx: Arc<BfvRnsContext<N>>,
+}
+
+/// One row (b_i, a_i) per prime, b_i + a_i s = [Q/q_i]_Q s^2 + e_i.
+#[derive(Debug, Clone)]
+pub struct BfvRnsRelinKey<const N: usize> {
+    rows: Vec<(RnsPolynomial<N>, RnsPolynomial<N>)>,
+}
+
+impl<const N: usize> BfvRns<N> {
+    pub fn keygen(params: &BfvRnsParams) -> Result<(Self, BfvSecretKey<N>), BfvRnsError> {
+        let ctx = Arc::new(BfvRnsContext::new(params)?);
+        security::enforce_log_q(N, ctx.q.bits().floor() as u32 + 1, &Ternary::UNIFORM)?;
+        let sk = BfvSecretKey::new(Polynomial::<N, 3>::ternary_error());
+        let q = &ctx.q;
+        let s = ctx.secret(&sk);
+        let a = q.rand();
+        let pk_1 = q.neg(&q.add(&q.mul(&a, &s), &ctx.error()));
+        Ok((Self { pk: (pk_1, a), ctx }, sk))
+    }
+
+    pub fn context(&self) -> &Arc<BfvRnsContext<N>> {
+        &self.ctx
+    }
+
+    pub fn gen_relin_key(&self, sk: &BfvSecretKey<N>) -> BfvRnsRelinKey<N> {
+        let q = &self.ctx.q;
+        let s = self.ctx.secret(sk);
+        let s2 = Zeroizing::new(q.mul(&s, &s));
+        let rows = (0..q.len())
+            .map(|i| {
+                // Q/q_i is 0 mod every other prime
+                let mut hat = vec![0; q.len()];
+                hat[i] = self.ctx.q_hat[i];
+                let a = q.rand();
+                let b = q.sub(
+                    &q.add(&q.mul_scalars(&s2, &hat), &self.ctx.error()),
+                    &q.mul(&a, &s),
+                );
+                (b, a)
+            })
+            .collect();
+        BfvRnsRelinKey { rows }
+    }
+
+    /// Encrypts the coefficients `message`, reduced mod t.
+    pub fn encrypt(&self, message: &[u64; N]) -> BfvRnsCipher<N> {
+        let ctx = &self.ctx;
+        let q = &ctx.q;
+        let m = q.from_signed(&message.map(|c| (c % ctx.t) as i64));
+        let delta_m = q.mul_scalars(&m, &ctx.delta);
+        let u = ctx.ternary();
+        let c_1 = q.add(&q.add(&q.mul(&self.pk.0, &u), &ctx.error()), &delta_m);
+        let c_2 = q.add(&q.mul(&self.pk.1, &u), &ctx.error());
+        BfvRnsCipher {
+            c_1,
+            c_2,
+            ctx: ctx.clone(),
+        }
+    }
+}
+
+impl<const N: usize> BfvRnsCipher<N> {
+    fn check_context(&self, other: &Self) {
+        assert!(
+            Arc::ptr_eq(&self.ctx, &other.ctx),
+            "ciphertexts belong to different contexts"
+        );
+    }
+
+    pub fn decrypt(&self, sk: &BfvSecretKey<N>) -> [u64; N] {
+        let q = &self.ctx.q;
+        let s = self.ctx.secret(sk);
+        self.ctx.decode(&q.add(&self.c_1, &q.mul(&self.c_2, &s)))
+    }
+
+    /// HPS tensoring, see the module docs.
+    pub fn tensor(&self, rhs: &Self) -> BfvRnsTensored<N> {
+        self.check_context(rhs);
+        let ctx = &self.ctx;
+        let qp = &ctx.qp;
+        let extend = |x: &RnsPolynomial<N>| x.concat(&ctx.q_to_p.convert(x));
+        let (a_1, a_2) = (extend(&self.c_1), extend(&self.c_2));
+        let (b_1, b_2) = (extend(&rhs.c_1), extend(&rhs.c_2));
+        let d_1 = qp.add(&qp.mul(&a_1, &b_2), &qp.mul(&a_2, &b_1));
+        BfvRnsTensored {
+            d_0: ctx.scale_round(&qp.mul(&a_1, &b_1)),
+            d_1: ctx.scale_round(&d_1),
+            d_2: ctx.scale_round(&qp.mul(&a_2, &b_2)),
+            ctx: ctx.clone(),
+        }
+    }
+}
+
+impl<const N: usize> Add for BfvRnsCipher<N> {
+    type Output = Self;
+
+    fn add(self, rhs: Self) -> Self::Output {
+        self.check_context(&rhs);
+        let q = &self.ctx.q;
+        Self {
+            c_1: q.add(&self.c_1, &rhs.c_1),
+            c_2: q.add(&self.c_2, &rhs.c_2),
+            ctx: self.ctx,
+        }
+    }
+}
+
+impl<const N: usize> BfvRnsTensored<N> {
+    pub fn relinearize(&self, rlk: &BfvRnsRelinKey<N>) -> BfvRnsCipher<N> {
+        let q = &self.ctx.q;
+        let digits = (0..N)
+            .map(|c| q.crt_digits(&self.d_2, c))
+            .collect::<Vec<_>>();
+        let (mut c_1, mut c_2) = (self.d_0.clone(), self.d_1.clone());
+        for (i, (b, a)) in rlk.rows.iter().enumerate() {
+            // CRT digit i of d_2, an integer below q_i, in every residue
+            let digit = RnsPolynomial {
+                residues: (0..q.len())
+                    .map(|j| {
+                        let q_j = q.modulus(j).modulus();
+                        core::array::from_fn(|c| digits[c][i] % q_j)
+                    })
+                    .collect(),
+            };
+            c_1 = q.add(&c_1, &q.mul(&digit, b));
+            c_2 = q.add(&c_2, &q.mul(&digit, a));
+        }
+        BfvRnsCipher {
+            c_1,
+            c_2,
+            ctx: self.ctx.clone(),
+        }
+    }
+
+    pub fn decrypt(&self, sk: &BfvSecretKey<N>) -> [u64; N] {
+        let q = &self.ctx.q;
+        let s = self.ctx.secret(sk);
+        let s2 = Zeroizing::new(q.mul(&s, &s));
+        let phase = q.add(
+            &q.add(&self.d_0, &q.mul(&self.d_1, &s)),
+            &q.mul(&self.d_2, &s2),
+        );
+        self.ctx.decode(&phase)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{Rng, rng};
+
+    const N: usize = 16;
+    const T: u64 = 257;
+
+    /// Q of 90 bits, P of 120 bits.
+    fn params() -> BfvRnsParams {
+        BfvRnsParams {
+            q: vec![1_073_741_789, 1_073_741_783, 1_073_741_741],
+            p: vec![1_073_741_723, 1_073_741_719, 1_073_741_717, 1_073_741_689],
+            t: T,
+            error: ErrorDist::STANDARD,
+        }
+    }
+
+    fn rand_message() -> [u64; N] {
+        let mut rng = rng();
+        core::array::from_fn(|_| rng.random_range(0..T))
+    }
+
+    /// Negacyclic product mod t.
+    fn plain_mul(a: &[u64; N], b: &[u64; N]) -> [u64; N] {
+        let mut out = [0i128; N];
+        for i in 0..N {
+            for j in 0..N {
+                let p = (a[i] * b[j]) as i128;
+                if i + j < N {
+                    out[i + j] += p;
+                } else {
+                    out[i + j - N] -= p;
+                }
+            }
+        }
+        out.map(|c| c.rem_euclid(T as i128) as u64)
+    }
+
+    #[test]
+    fn test_encrypt_add() {
+        let (bfv, sk) = BfvRns::<N>::keygen(&params()).unwrap();
+        let (m_a, m_b) = (rand_message(), rand_message());
+        let ct_a = bfv.encrypt(&m_a);
+        assert_eq!(ct_a.decrypt(&sk), m_a);
+        let sum = (ct_a + bfv.encrypt(&m_b)).decrypt(&sk);
+        assert_eq!(sum, core::array::from_fn(|i| (m_a[i] + m_b[i]) % T));
+    }
+
+    #[test]
+    fn test_error_dist() {
+        // without errors the phase of a fresh ciphertext is exactly Δ m
+        let exact = BfvRnsParams {
+            error: ErrorDist::Zero,
+            ..params()
+        };
+        let (bfv, sk) = BfvRns::<N>::keygen(&exact).unwrap();
+        let q = &bfv.context().q;
+        let m = rand_message();
+        let ct = bfv.encrypt(&m);
+        let s = bfv.context().secret(&sk);
+        let phase = q.add(&ct.c_1, &q.mul(&ct.c_2, &s));
+        let delta_m = q.mul_scalars(&q.from_signed(&m.map(|c| c as i64)), &bfv.context().delta);
+        assert_eq!(phase, delta_m);
+
+        let binomial = BfvRnsParams {
+            error: ErrorDist::CenteredBinomial { eta: 4 },
+            ..params()
+        };
+        let (bfv, sk) = BfvRns::<N>::keygen(&binomial).unwrap();
+        let rlk = bfv.gen_relin_key(&sk);
+        let ct = bfv.encrypt(&m).tensor(&bfv.encrypt(&m)).relinearize(&rlk);
+        assert_eq!(ct.decrypt(&sk), plain_mul(&m, &m));
+    }
+
+    #[test]
+    fn test_mul_and_relinearize() {
+        let (bfv, sk) = BfvRns::<N>::keygen(&params()).unwrap();
+        let rlk = bfv.gen_relin_key(&sk);
+        let (m_a, m_b, m_c) = (rand_message(), rand_message(), rand_message());
+
+        let tensored = bfv.encrypt(&m_a).tensor(&bfv.encrypt(&m_b));
+        let ab = plain_mul(&m_a, &m_b);
+        assert_eq!(tensored.decrypt(&sk), ab);
+        let ct_ab = tensored.relinearize(&rlk);
+        assert_eq!(ct_ab.decrypt(&sk), ab);
+
+        let abc = ct_ab.tensor(&bfv.encrypt(&m_c)).relinearize(&rlk);
+        assert_eq!(abc.decrypt(&sk), plain_mul(&ab, &m_c));
+    }
+
+    #[test]
+    fn test_ntt_primes() {
+        // every prime 1 mod 32, so all products go through the NTT
+        let params = BfvRnsParams {
+            q: vec![1_073_741_441, 1_073_740_609, 1_073_739_937],
+            p: vec![1_073_739_649, 1_073_739_617, 1_073_739_361, 1_073_739_169],
+            t: T,
+            error: ErrorDist::STANDARD,
+        };
+        let (bfv, sk) = BfvRns::<N>::keygen(&params).unwrap();
+        assert_eq!(bfv.context().q.ntt_primes(), 3);
+        assert_eq!(bfv.context().p.ntt_primes(), 4);
+        let rlk = bfv.gen_relin_key(&sk);
+        let (m_a, m_b) = (rand_message(), rand_message());
+        let ab = bfv
+            .encrypt(&m_a)
+            .tensor(&bfv.encrypt(&m_b))
+            .relinearize(&rlk);
+        assert_eq!(ab.decrypt(&sk), plain_mul(&m_a, &m_b));
+    }
+
+    #[test]
+    fn test_params_errors() {
+        let small_p = BfvRnsParams {
+            p: vec![1_073_741_723, 1_073_741_719],
+            ..params()
+        };
+        assert!(matches!(
+            BfvRnsContext::<N>::new(&small_p),
+            Err(BfvRnsError::AuxiliaryTooSmall { got_bits: 59, .. })
+        ));
+        let big_t = BfvRnsParams {
+            t: 1 << 40,
+            ..params()
+        };
+        assert_eq!(
+            BfvRnsContext::<N>::new(&big_t).unwrap_err(),
+            BfvRnsError::PlaintextModulus { t: 1 << 40 }
+        );
+        let overlap = BfvRnsParams {
+            p: vec![1_073_741_789, 1_073_741_719, 1_073_741_717, 1_073_741_689],
+            ..params()
+        };
+        assert_eq!(
+            BfvRnsContext::<N>::new(&overlap).unwrap_err(),
+            BfvRnsError::Basis(RnsError::Duplicate {
+                modulus: 1_073_741_789
+            })
+        );
+    }
+}
@@ -1,53 +1,126 @@
-use crate::polynomial::{Element, Polynomial};
-use std::ops::Add;
-
-pub struct Bfv<const N: usize, const Q: u64, const T: u64> {}
-
-#[derive(Debug)]
-pub struct BfvCipher<const N: usize, const Q: u64, const T: u64> {
-    c_1: Polynomial<N, Q>,
-    c_2: Polynomial<N, Q>,
+//! Secret key BFV encryption, (s a + Δm + e, -a). The ciphertexts are [`bfv_pke`](crate::bfv_pke) ones
+//! under the key pair's context, so they combine with public key encryptions and go through the same
+//! `Evaluator`, with the noise of a single error term.
+
+use crate::backend::{NativeBackend, RingBackend};
+use crate::bfv_pke::{Bfv, BfvCiphertext, BfvContext, BfvSecretKey};
+use crate::polynomial::{DecodeError, Element, ErrorDist, Polynomial};
+use rand::Rng;
+use std::marker::PhantomData;
+use std::sync::Arc;
+
+/// Encrypts with the secret key of a `bfv_pke` key pair.
+pub struct SymmetricEncryptor<
+    const N: usize,
+    const Q: u64,
+    const T: u64,
+    B: RingBackend = NativeBackend,
+> {
+    sk: BfvSecretKey<N>,
+    error: ErrorDist,
+    ctx: Arc<BfvContext<N, Q, T, B>>,
 }
 
-impl<const N: usize, const Q: u64, const T: u64> Bfv<N, Q, T> {
-    pub fn keygen() -> (Self, Polynomial<N, 2>) {
-        let sk = Polynomial::<N, 2>::rand();
-        (Self {}, sk)
+impl<const N: usize, const Q: u64, const T: u64, B: RingBackend> SymmetricEncryptor<N, Q, T, B> {
+    /// `sk` must be the secret of `bfv`. Errors follow `bfv.error_dist()`.
+    pub fn new(bfv: &Bfv<N, Q, T, B>, sk: BfvSecretKey<N>) -> Self {
+        Self {
+            sk,
+            error: bfv.error_dist(),
+            ctx: bfv.context().clone(),
+        }
     }
 
-    pub fn encrypt(&self, message: Polynomial<N, T>, sk: Polynomial<N, 2>) -> BfvCipher<N, Q, T> {
-        let delta_elem = Element::<Q>::new(Q.div_ceil(T) as i64);
-        let delta_m = message.lift::<Q>() * delta_elem;
+    pub fn context(&self) -> &Arc<BfvContext<N, Q, T, B>> {
+        &self.ctx
+    }
 
+    pub fn encrypt(&self, message: Polynomial<N, T>) -> BfvCiphertext<N, Q, T, B> {
         let a = Polynomial::<N, Q>::rand();
-        let e = Polynomial::<N, Q>::ternary_error();
-        let c_1 = sk.lift::<Q>() * a + delta_m + e;
-        let c_2 = -a;
+        BfvCiphertext::new(self.mask(message, &a), -a, self.ctx.clone())
+    }
 
-        BfvCipher { c_1, c_2 }
+    /// Like `encrypt`, but `a` is expanded from a fresh seed and only the seed is kept, see
+    /// [`CompressedCipher`].
+    pub fn encrypt_compressed(&self, message: Polynomial<N, T>) -> CompressedCipher<N, Q, T, B> {
+        let seed: [u8; 32] = rand::rng().random();
+        let a = Polynomial::<N, Q>::rand_from_seed(seed);
+        CompressedCipher {
+            seed,
+            c_1: self.mask(message, &a),
+            _backend: PhantomData,
+        }
     }
-}
 
-impl<const N: usize, const Q: u64, const T: u64> BfvCipher<N, Q, T> {
-    pub fn decrypt(self, sk: Polynomial<N, 2>) -> Polynomial<N, T> {
-        let ct = self.c_1 + self.c_2 * sk.lift::<Q>();
-        ct.msb()
+    /// s * a + Δm + e
+    fn mask(&self, message: Polynomial<N, T>, a: &Polynomial<N, Q>) -> Polynomial<N, Q> {
+        let delta_elem = Element::<Q>::new(self.ctx.delta as i64);
+        let delta_m = message.lift::<Q>() * delta_elem;
+        let e = Polynomial::<N, Q>::error_valid(self.error);
+        let s = self.sk.lift_centered::<Q>();
+        B::add(&B::add(&self.ctx.mul(&s, a), &delta_m), &e)
     }
 }
 
-impl<const N: usize, const Q: u64, const T: u64> Add for BfvCipher<N, Q, T> {
-    type Output = Self;
+/// A fresh symmetric encryption with c_2 = -a replaced by the 32 byte seed `a` was expanded from
+/// (`Polynomial::rand_from_seed`), about half the size of a full ciphertext. Only fresh ciphertexts can be
+/// compressed, anything computed on has no seed for its second component.
+#[derive(Debug, Clone)]
+pub struct CompressedCipher<
+    const N: usize,
+    const Q: u64,
+    const T: u64,
+    B: RingBackend = NativeBackend,
+> {
+    seed: [u8; 32],
+    c_1: Polynomial<N, Q>,
+    _backend: PhantomData<B>,
+}
+
+impl<const N: usize, const Q: u64, const T: u64, B: RingBackend> CompressedCipher<N, Q, T, B> {
+    /// Length of `to_bytes`: the seed, then `c_1` as `Polynomial::to_bytes`.
+    pub const BYTES: usize = 32 + Polynomial::<N, Q>::BYTES;
+
+    pub fn seed(&self) -> &[u8; 32] {
+        &self.seed
+    }
+
+    /// The full ciphertext under `ctx`, the context of the encrypting key pair, re-expanding a from
+    /// the seed.
+    pub fn expand(&self, ctx: &Arc<BfvContext<N, Q, T, B>>) -> BfvCiphertext<N, Q, T, B> {
+        BfvCiphertext::new(
+            self.c_1,
+            -Polynomial::<N, Q>::rand_from_seed(self.seed),
+            ctx.clone(),
+        )
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = self.seed.to_vec();
+        out.extend(self.c_1.to_bytes());
+        out
+    }
 
-    fn add(self, rhs: Self) -> Self::Output {
-        let c_1 = self.c_1 + rhs.c_1;
-        let c_2 = self.c_2 + rhs.c_2;
-        Self { c_1, c_2 }
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, DecodeError> {
+        if bytes.len() != Self::BYTES {
+            return Err(DecodeError::Length {
+                expected: Self::BYTES,
+                got: bytes.len(),
+            });
+        }
+        let (seed, c_1) = bytes.split_at(32);
+        Ok(Self {
+            seed: seed.try_into().unwrap(),
+            c_1: Polynomial::from_bytes(c_1)?,
+            _backend: PhantomData,
+        })
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::polynomial::Ternary;
 
     #[test]
     fn test_bfv_add_t_2_example() {
@@ -55,15 +128,21 @@ mod tests {
         const N: usize = 4;
         const Q: u64 = 32;
 
-        let (bfv, sk) = Bfv::<N, Q, T>::keygen();
+        // q = 32 has no room for Gaussian errors
+        let (bfv, sk) = Bfv::<N, Q, T>::keygen_with_error(
+            Ternary::UNIFORM,
+            ErrorDist::CenteredBinomial { eta: 1 },
+        )
+        .unwrap();
+        let encryptor = SymmetricEncryptor::new(&bfv, sk.clone());
 
         let m_a = Polynomial::<N, T>::rand();
         println!("m_a {:?}", m_a);
-        let enc_a = bfv.encrypt(m_a, sk);
+        let enc_a = encryptor.encrypt(m_a);
 
         let m_b = Polynomial::<N, T>::rand();
         println!("m_b {:?}", m_b);
-        let enc_b = bfv.encrypt(m_b, sk);
+        let enc_b = encryptor.encrypt(m_b);
 
         /* Homomorphic */
         let enc_3 = enc_a + enc_b;
@@ -71,27 +150,66 @@ mod tests {
         /* Decryption */
         let raw_add = m_a + m_b;
         println!("expected = {:?}", raw_add);
-        let dec = enc_3.decrypt(sk);
+        let dec = enc_3.decrypt(&sk);
         println!("actual = {:?}", dec);
         assert_eq!(raw_add, dec);
     }
 
+    #[test]
+    fn test_compressed_cipher() {
+        const T: u64 = 16;
+        const N: usize = 256;
+        const Q: u64 = 1 << 20;
+
+        let (bfv, sk) = Bfv::<N, Q, T>::keygen().unwrap();
+        let encryptor = SymmetricEncryptor::new(&bfv, sk.clone());
+        let m_a = Polynomial::<N, T>::rand();
+        let m_b = Polynomial::<N, T>::rand();
+        let compressed = encryptor.encrypt_compressed(m_a);
+
+        let bytes = compressed.to_bytes();
+        assert_eq!(bytes.len(), CompressedCipher::<N, Q, T>::BYTES);
+        assert!(bytes.len() < 2 * Polynomial::<N, Q>::BYTES * 6 / 10);
+        let loaded = CompressedCipher::<N, Q, T>::from_bytes(&bytes).unwrap();
+        assert_eq!(loaded.seed(), compressed.seed());
+        assert_eq!(loaded.to_bytes(), bytes);
+
+        // expands to an ordinary ciphertext, which adds to public key encryptions
+        let sum = loaded.expand(bfv.context()) + encryptor.encrypt(m_b) + bfv.encrypt(m_b);
+        assert_eq!(sum.decrypt(&sk), m_a + m_b + m_b);
+        assert_eq!(compressed.expand(encryptor.context()).decrypt(&sk), m_a);
+
+        assert_eq!(
+            CompressedCipher::<N, Q, T>::from_bytes(&bytes[1..]).unwrap_err(),
+            DecodeError::Length {
+                expected: bytes.len(),
+                got: bytes.len() - 1
+            }
+        );
+    }
+
     #[test]
     fn test_bfv_add_t_4_example() {
         const T: u64 = 4;
         const N: usize = 4;
         const Q: u64 = 32;
 
-        let (bfv, sk) = Bfv::<N, Q, T>::keygen();
+        // q = 32 has no room for Gaussian errors
+        let (bfv, sk) = Bfv::<N, Q, T>::keygen_with_error(
+            Ternary::UNIFORM,
+            ErrorDist::CenteredBinomial { eta: 1 },
+        )
+        .unwrap();
+        let encryptor = SymmetricEncryptor::new(&bfv, sk.clone());
 
         let m_a = Polynomial::<N, T>::rand();
         println!("m_a {:?}", m_a);
-        let enc_a = bfv.encrypt(m_a, sk);
+        let enc_a = encryptor.encrypt(m_a);
         println!("enc_a {:?}", enc_a);
 
         let m_b = Polynomial::<N, T>::rand();
         println!("m_b {:?}", m_b);
-        let enc_b = bfv.encrypt(m_b, sk);
+        let enc_b = encryptor.encrypt(m_b);
         println!("enc_b {:?}", enc_b);
 
         /* Homomorphic */
@@ -101,8 +219,25 @@ mod tests {
         /* Decryption */
         let raw_add = m_a + m_b;
         println!("expected = {:?}", raw_add);
-        let dec = enc_3.decrypt(sk);
+        let dec = enc_3.decrypt(&sk);
         println!("actual = {:?}", dec);
         assert_eq!(raw_add, dec);
     }
+
+    #[test]
+    fn test_decrypt_keeps_ciphertext() {
+        const T: u64 = 16;
+        const N: usize = 256;
+        const Q: u64 = 1 << 20;
+
+        let (bfv, sk) = Bfv::<N, Q, T>::keygen().unwrap();
+        let encryptor = SymmetricEncryptor::new(&bfv, sk.clone());
+        let m = Polynomial::<N, T>::rand();
+        let ct = encryptor.encrypt(m);
+        assert_eq!(ct.decrypt(&sk), m);
+        // the ciphertext and key are still usable
+        let sum = ct.clone() + ct;
+        assert_eq!(sum.decrypt(&sk), m + m);
+        assert_eq!(encryptor.encrypt(m).decrypt(&sk), m);
+    }
 }
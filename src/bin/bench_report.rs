@@ -0,0 +1,292 @@
+//! Performance snapshots of a fixed suite, and regression checks between two snapshots.
+//!
+//! ```text
+//! cargo run --release --features bench-report --bin bench-report -- run [--out snapshot.json]
+//! cargo run --release --features bench-report --bin bench-report -- compare old.json new.json [--threshold 0.1]
+//! ```
+//!
+//! `run` writes the mean time per iteration of every benchmark, with the git hash and machine info.
+//! `compare` lists every benchmark slower than `threshold` (relative) and exits with 1 if there is any.
+
+use std::hint::black_box;
+use std::process::{Command, ExitCode};
+use std::time::{Duration, Instant};
+
+use rlattice::bfv_pke::Bfv;
+use rlattice::hybrid::{Client, Server};
+use rlattice::kreyvium::Kreyvium;
+use rlattice::ntt::NttTable;
+use rlattice::pasta_plain::{PASTA_T, Pasta, PastaKey};
+use rlattice::polynomial::Polynomial;
+use serde_json::{Value, json};
+
+/// Each benchmark runs at least this long (and at least `MIN_ITERS` times).
+const TARGET_TIME: Duration = Duration::from_millis(300);
+const MIN_ITERS: u64 = 10;
+
+fn measure(mut f: impl FnMut()) -> f64 {
+    // warm up
+    f();
+    let start = Instant::now();
+    let mut iters = 0u64;
+    while iters < MIN_ITERS || start.elapsed() < TARGET_TIME {
+        f();
+        iters += 1;
+    }
+    start.elapsed().as_nanos() as f64 / iters as f64
+}
+
+fn poly_mul<const N: usize, const Q: u64>(results: &mut Vec<(String, f64)>) {
+    let a = Polynomial::<N, Q>::rand();
+    let b = Polynomial::<N, Q>::rand();
+    results.push((
+        format!("poly_mul/schoolbook/{N}"),
+        measure(|| {
+            black_box(black_box(&a).mul_schoolbook(black_box(&b)));
+        }),
+    ));
+    results.push((
+        format!("poly_mul/karatsuba/{N}"),
+        measure(|| {
+            black_box(black_box(&a).mul_karatsuba(black_box(&b)));
+        }),
+    ));
+    if let Some(table) = NttTable::<N, Q>::new() {
+        results.push((
+            format!("poly_mul/ntt/{N}"),
+            measure(|| {
+                black_box(table.mul(black_box(&a), black_box(&b)));
+            }),
+        ));
+    }
+}
+
+fn keystream(results: &mut Vec<(String, f64)>) {
+    const P: u64 = 65_537;
+    let key = PastaKey::new((0..2 * PASTA_T as u64).collect(), P);
+    let mut pasta = Pasta::new(key, P);
+    let mut ctr = 0u64;
+    results.push((
+        "keystream/derive".to_string(),
+        measure(|| {
+            ctr += 1;
+            black_box(pasta.keystream(1, ctr));
+        }),
+    ));
+    results.push((
+        "keystream/fused".to_string(),
+        measure(|| {
+            ctr += 1;
+            black_box(pasta.keystream_fused(1, ctr));
+        }),
+    ));
+    // 17 bits, about one Pasta word
+    let mut kreyvium = Kreyvium::new(&[true; 128], &[false; 128]);
+    results.push((
+        "keystream/kreyvium".to_string(),
+        measure(|| {
+            black_box(kreyvium.keystream(17));
+        }),
+    ));
+}
+
+fn bfv(results: &mut Vec<(String, f64)>) {
+    const N: usize = 1024;
+    const Q: u64 = 12_289;
+    const T: u64 = 2;
+
+    let (bfv, sk) = Bfv::<N, Q, T>::keygen().unwrap();
+    let m = Polynomial::<N, T>::rand();
+    let ct = bfv.encrypt(m);
+    let pt = Polynomial::<N, Q>::rand();
+
+    results.push((
+        format!("bfv/encrypt/{N}"),
+        measure(|| {
+            black_box(bfv.encrypt(black_box(m)));
+        }),
+    ));
+    results.push((
+        format!("bfv/add/{N}"),
+        measure(|| {
+            black_box(ct.clone() + ct.clone());
+        }),
+    ));
+    results.push((
+        format!("bfv/mul_plain/{N}"),
+        measure(|| {
+            black_box(&ct * black_box(pt));
+        }),
+    ));
+    results.push((
+        format!("bfv/decrypt/{N}"),
+        measure(|| {
+            black_box(ct.decrypt(&sk));
+        }),
+    ));
+}
+
+/// Server side transciphering of one chunk (n blocks) at the reduced Pasta<2, 2> of the tests, Pasta-3
+/// doesn't fit a single 64 bit q.
+fn transcipher(results: &mut Vec<(String, f64)>) {
+    const N: usize = 8;
+    const Q: u64 = 1 << 59;
+    const T: u64 = 17;
+
+    let client = Client::<N, Q, T, 2, 2>::generate().unwrap();
+    let server = Server::<N, Q, T, 2, 2>::from_setup(&client.setup(8)).unwrap();
+    let upload = client
+        .upload(&(0..2 * N as u64).map(|i| i % T).collect::<Vec<_>>())
+        .unwrap();
+    results.push((
+        format!("transcipher/pasta_2_2/{N}"),
+        measure(|| {
+            black_box(server.transcipher(black_box(&upload)).unwrap());
+        }),
+    ));
+}
+
+fn git_hash() -> String {
+    Command::new("git")
+        .args(["rev-parse", "--short", "HEAD"])
+        .output()
+        .ok()
+        .filter(|o| o.status.success())
+        .map(|o| String::from_utf8_lossy(&o.stdout).trim().to_string())
+        .unwrap_or_else(|| "unknown".to_string())
+}
+
+fn machine() -> Value {
+    let cpu = std::fs::read_to_string("/proc/cpuinfo")
+        .ok()
+        .and_then(|info| {
+            info.lines()
+                .find(|l| l.starts_with("model name"))
+                .and_then(|l| l.split(':').nth(1))
+                .map(|m| m.trim().to_string())
+        })
+        .unwrap_or_else(|| "unknown".to_string());
+    json!({
+        "os": std::env::consts::OS,
+        "arch": std::env::consts::ARCH,
+        "cpu": cpu,
+        "threads": std::thread::available_parallelism().map_or(1, |n| n.get()),
+        "features": {
+            "simd": cfg!(feature = "simd"),
+            "parallel": cfg!(feature = "parallel"),
+        },
+    })
+}
+
+fn run(out: Option<&str>) -> ExitCode {
+    let mut results = Vec::new();
+    poly_mul::<64, 12_289>(&mut results);
+    poly_mul::<256, 12_289>(&mut results);
+    poly_mul::<1024, 12_289>(&mut results);
+    keystream(&mut results);
+    bfv(&mut results);
+    transcipher(&mut results);
+
+    for (name, ns) in results.iter() {
+        eprintln!("{name:<32} {ns:>14.1} ns/iter");
+    }
+    let snapshot = json!({
+        "git": git_hash(),
+        "machine": machine(),
+        "results": results
+            .iter()
+            .map(|(name, ns)| (name.clone(), json!(ns)))
+            .collect::<serde_json::Map<_, _>>(),
+    });
+    let text = serde_json::to_string_pretty(&snapshot).unwrap();
+    match out {
+        Some(path) => std::fs::write(path, text).expect("failed to write snapshot"),
+        None => println!("{text}"),
+    }
+    ExitCode::SUCCESS
+}
+
+fn load(path: &str) -> Value {
+    let text = std::fs::read_to_string(path).unwrap_or_else(|e| panic!("{path}: {e}"));
+    serde_json::from_str(&text).unwrap_or_else(|e| panic!("{path}: {e}"))
+}
+
+/// Benchmarks in both snapshots whose time grew by more than `threshold`, as (name, old, new).
+fn regressions(old: &Value, new: &Value, threshold: f64) -> Vec<(String, f64, f64)> {
+    let (Some(old), Some(new)) = (old["results"].as_object(), new["results"].as_object()) else {
+        return Vec::new();
+    };
+    new.iter()
+        .filter_map(|(name, new_ns)| {
+            let old_ns = old.get(name)?.as_f64()?;
+            let new_ns = new_ns.as_f64()?;
+            (new_ns > old_ns * (1.0 + threshold)).then(|| (name.clone(), old_ns, new_ns))
+        })
+        .collect()
+}
+
+fn compare(old_path: &str, new_path: &str, threshold: f64) -> ExitCode {
+    let old = load(old_path);
+    let new = load(new_path);
+    println!(
+        "{} ({}) -> {} ({})",
+        old_path, old["git"], new_path, new["git"]
+    );
+
+    let slower = regressions(&old, &new, threshold);
+    for (name, old_ns, new_ns) in slower.iter() {
+        println!(
+            "REGRESSION {name}: {old_ns:.1} -> {new_ns:.1} ns/iter (+{:.1}%)",
+            (new_ns / old_ns - 1.0) * 100.0
+        );
+    }
+    if slower.is_empty() {
+        println!("no regressions above {:.1}%", threshold * 100.0);
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    }
+}
+
+fn usage() -> ExitCode {
+    eprintln!("usage: bench-report run [--out FILE]");
+    eprintln!("       bench-report compare OLD NEW [--threshold FRACTION]");
+    ExitCode::from(2)
+}
+
+fn main() -> ExitCode {
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+    let flag = |name: &str| {
+        args.iter()
+            .position(|a| a == name)
+            .and_then(|i| args.get(i + 1))
+            .map(String::as_str)
+    };
+
+    match args.first().map(String::as_str) {
+        Some("run") => run(flag("--out")),
+        Some("compare") if args.len() >= 3 => {
+            let threshold = flag("--threshold").map_or(0.1, |t| {
+                t.parse().expect("--threshold is a fraction, e.g. 0.1")
+            });
+            compare(&args[1], &args[2], threshold)
+        }
+        _ => usage(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_regressions() {
+        let old = json!({ "results": { "a": 100.0, "b": 100.0, "gone": 1.0 } });
+        let new = json!({ "results": { "a": 105.0, "b": 150.0, "added": 1.0 } });
+        assert_eq!(
+            regressions(&old, &new, 0.1),
+            vec![("b".to_string(), 100.0, 150.0)]
+        );
+        assert!(regressions(&old, &new, 0.6).is_empty());
+    }
+}
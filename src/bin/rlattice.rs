@@ -0,0 +1,430 @@
+//! Key generation, Pasta and BFV encryption and transciphering from the command line, on files in the
+//! crate's serialized formats.
+//!
+//! ```text
+//! rlattice [--insecure-toy] keygen DIR
+//! rlattice --insecure-toy pasta encrypt DIR OUT WORD...
+//! rlattice --insecure-toy pasta decrypt DIR IN
+//! rlattice bfv encrypt DIR OUT WORD...
+//! rlattice bfv decrypt DIR IN
+//! rlattice bfv add|mul DIR A B OUT
+//! rlattice --insecure-toy toy transcipher DIR IN OUT
+//! rlattice --insecure-toy toy decrypt DIR IN
+//! ```
+//!
+//! The `bfv` commands run at the `Bfv128Depth2` preset: 128 bit secure, two sequential
+//! multiplications, words below t = 17. No single word modulus is both secure and batching, so `bfv
+//! encrypt` puts up to n words into the coefficients of one ciphertext, `add` adds them word by word and
+//! `mul` multiplies the word polynomials mod x^n + 1. `bfv decrypt` prints the coefficients of every
+//! ciphertext on its own line, trailing zeros dropped.
+//!
+//! Pasta and transciphering only run at the toy sizes the `hybrid` tests use, n = 8 and Pasta<2, 2>,
+//! which give no security; those commands refuse to run without `--insecure-toy` and print a warning
+//! with it. Pasta-3 is out of reach of a single 64 bit q, see `pasta_bfv`.
+//!
+//! `keygen` writes `bfv.sk`, `bfv.pk`, `bfv.rlk` (`to_bytes` of the preset keys), and with `--insecure-toy`
+//! `pasta.key` (the 2W key words), `toy.sk`, `toy.pk`, `toy.rlk` (the toy BFV keys) and `pasta.key.bfv`
+//! (the key words encrypted under the toy key, what a server gets). Secret key files are only readable
+//! by their owner. A Pasta ciphertext file is the `hybrid` upload message: nonce,
+//! word count, words, little endian u64. A BFV file is one or more `BfvCiphertext::to_bytes`. `toy
+//! transcipher` writes W ciphertexts per n blocks, word i of block b in slot b of the i-th, as
+//! `hybrid::EncryptedData`, and `toy decrypt` prints their slots.
+
+use std::error::Error;
+use std::fs;
+use std::io::Write;
+#[cfg(unix)]
+use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+use std::path::Path;
+use std::process::ExitCode;
+
+use rlattice::batch::BatchEncoder;
+use rlattice::bfv_params::{Bfv128Depth2, BfvParams};
+use rlattice::bfv_pke::{Bfv, BfvCiphertext, BfvPublicKey, BfvSecretKey, Evaluator};
+use rlattice::encoding::Encoder;
+use rlattice::keyswitch::KeySwitchKey;
+use rlattice::pasta_bfv::{EncryptedPastaKey, Transcipher};
+use rlattice::pasta_plain::{Pasta, PastaKey};
+use rlattice::polynomial::{DecodeError, Element, Polynomial};
+use rlattice::security;
+use zeroize::Zeroizing;
+
+const PARAMS: BfvParams = BfvParams::default_128bit_depth2();
+const N: usize = PARAMS.n;
+const T: u64 = PARAMS.t;
+
+/// Toy ring dimension of the transciphering instance, not secure.
+const TOY_N: usize = 8;
+const TOY_Q: u64 = 1 << 59;
+/// 1 mod 2n, so BFV can batch and Pasta runs mod t. Equal to the preset t, so words fit both.
+const TOY_T: u64 = T;
+const W: usize = 2;
+const R: usize = 2;
+const TOY_RELIN_BASE_LOG: u32 = 8;
+const INSECURE_TOY: &str = "--insecure-toy";
+
+type Result<T> = std::result::Result<T, Box<dyn Error>>;
+
+fn words_to_bytes(words: &[u64]) -> Vec<u8> {
+    words.iter().flat_map(|w| w.to_le_bytes()).collect()
+}
+
+fn bytes_to_words(bytes: &[u8]) -> Result<Vec<u64>> {
+    if !bytes.len().is_multiple_of(8) {
+        return Err(DecodeError::Length {
+            expected: bytes.len().next_multiple_of(8),
+            got: bytes.len(),
+        }
+        .into());
+    }
+    Ok(bytes
+        .chunks(8)
+        .map(|w| u64::from_le_bytes(w.try_into().unwrap()))
+        .collect())
+}
+
+fn parse_words(args: &[&str]) -> Result<Vec<u64>> {
+    args.iter()
+        .map(|a| match a.parse::<u64>() {
+            Ok(w) if w < T => Ok(w),
+            _ => Err(format!("{a} is not a word below t = {T}").into()),
+        })
+        .collect()
+}
+
+/// `fs::write` for key material, the file is created with (or reset to) mode 0600 on unix.
+fn write_secret(path: &Path, bytes: &[u8]) -> Result<()> {
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    options.mode(0o600);
+    let mut file = options.open(path)?;
+    // `mode` only applies when the file is created
+    #[cfg(unix)]
+    file.set_permissions(fs::Permissions::from_mode(0o600))?;
+    Ok(file.write_all(bytes)?)
+}
+
+fn warn_toy() {
+    eprintln!(
+        "rlattice: warning: toy parameters (n = {TOY_N}, Pasta<{W}, {R}>), this is NOT secure"
+    );
+}
+
+/// The preset instance behind `bfv.pk`.
+fn bfv(dir: &Path) -> Result<Bfv128Depth2> {
+    Ok(Bfv::from_public_key(BfvPublicKey::load(
+        dir.join("bfv.pk"),
+    )?))
+}
+
+/// The toy instance behind `toy.pk`.
+fn toy(dir: &Path) -> Result<Bfv<TOY_N, TOY_Q, TOY_T>> {
+    Ok(Bfv::from_public_key(BfvPublicKey::load(
+        dir.join("toy.pk"),
+    )?))
+}
+
+fn pasta(dir: &Path) -> Result<Pasta<W, R>> {
+    let key = bytes_to_words(&fs::read(dir.join("pasta.key"))?)?;
+    Ok(Pasta::with_key(PastaKey::try_new(key, TOY_T)?, TOY_T))
+}
+
+fn read_ciphertexts<const N: usize, const Q: u64, const T: u64>(
+    bfv: &Bfv<N, Q, T>,
+    path: &Path,
+) -> Result<Vec<BfvCiphertext<N, Q, T>>> {
+    let bytes = fs::read(path)?;
+    let len = BfvCiphertext::<N, Q, T>::BYTES;
+    if bytes.is_empty() || !bytes.len().is_multiple_of(len) {
+        return Err(DecodeError::Length {
+            expected: len * bytes.len().div_ceil(len).max(1),
+            got: bytes.len(),
+        }
+        .into());
+    }
+    Ok(bytes
+        .chunks(len)
+        .map(|ct| bfv.ciphertext_from_bytes(ct))
+        .collect::<std::result::Result<_, _>>()?)
+}
+
+fn write_ciphertexts<const N: usize, const Q: u64, const T: u64>(
+    path: &Path,
+    cts: &[BfvCiphertext<N, Q, T>],
+) -> Result<()> {
+    Ok(fs::write(
+        path,
+        cts.iter().flat_map(|ct| ct.to_bytes()).collect::<Vec<_>>(),
+    )?)
+}
+
+/// The preset keys, and the toy ones with `toy`.
+fn keygen(dir: &Path, toy: bool) -> Result<()> {
+    fs::create_dir_all(dir)?;
+    let (bfv, sk) = Bfv128Depth2::keygen()?;
+    write_secret(&dir.join("bfv.sk"), &Zeroizing::new(sk.to_bytes()))?;
+    bfv.public_key().save(dir.join("bfv.pk"))?;
+    fs::write(
+        dir.join("bfv.rlk"),
+        bfv.gen_relin_key(&sk, PARAMS.relin_base_log).to_bytes(),
+    )?;
+    if !toy {
+        return Ok(());
+    }
+
+    warn_toy();
+    let (toy, sk) = Bfv::<TOY_N, TOY_Q, TOY_T>::keygen()?;
+    let key = PastaKey::<W>::generate(&mut rand::rng(), TOY_T);
+    let encrypted = EncryptedPastaKey::<TOY_N, TOY_Q, TOY_T, W>::encrypt(&toy, &key)?;
+    write_secret(&dir.join("toy.sk"), &Zeroizing::new(sk.to_bytes()))?;
+    toy.public_key().save(dir.join("toy.pk"))?;
+    fs::write(
+        dir.join("toy.rlk"),
+        toy.gen_relin_key(&sk, TOY_RELIN_BASE_LOG).to_bytes(),
+    )?;
+    write_secret(
+        &dir.join("pasta.key"),
+        &Zeroizing::new(words_to_bytes(key.words())),
+    )?;
+    write_ciphertexts(&dir.join("pasta.key.bfv"), encrypted.words())
+}
+
+fn pasta_encrypt(dir: &Path, out: &Path, words: &[u64]) -> Result<()> {
+    warn_toy();
+    let nonce = rand::random();
+    let ciphertext = pasta(dir)?.encrypt_with_nonce(nonce, words);
+    let mut message = words_to_bytes(&[nonce, words.len() as u64]);
+    message.extend(words_to_bytes(&ciphertext));
+    Ok(fs::write(out, message)?)
+}
+
+/// (nonce, words) of a Pasta ciphertext file.
+fn read_pasta(path: &Path) -> Result<(u64, Vec<u64>)> {
+    let words = bytes_to_words(&fs::read(path)?)?;
+    match words.as_slice() {
+        [nonce, count, rest @ ..] if *count as usize == rest.len() => Ok((*nonce, rest.to_vec())),
+        _ => Err(format!("{} is not a Pasta ciphertext", path.display()).into()),
+    }
+}
+
+fn pasta_decrypt(dir: &Path, input: &Path) -> Result<Vec<u64>> {
+    warn_toy();
+    let (nonce, ciphertext) = read_pasta(input)?;
+    Ok(pasta(dir)?.decrypt_with_nonce(nonce, &ciphertext))
+}
+
+fn bfv_encrypt(dir: &Path, out: &Path, words: &[u64]) -> Result<()> {
+    if words.len() > N {
+        return Err(format!("{} words, at most n = {N} fit", words.len()).into());
+    }
+    let plaintext = Polynomial::new(core::array::from_fn(|i| {
+        Element::new(words.get(i).map_or(0, |&w| w as i64))
+    }));
+    write_ciphertexts(out, &[bfv(dir)?.encrypt(plaintext)])
+}
+
+fn bfv_decrypt(dir: &Path, input: &Path) -> Result<Vec<Vec<u64>>> {
+    let sk = BfvSecretKey::<N>::load(dir.join("bfv.sk"))?;
+    Ok(read_ciphertexts(&bfv(dir)?, input)?
+        .iter()
+        .map(|ct| {
+            let coeffs = ct.decrypt(&sk).inner.map(|c| c.value());
+            let len = coeffs.iter().rposition(|&c| c != 0).map_or(0, |i| i + 1);
+            coeffs[..len].to_vec()
+        })
+        .collect())
+}
+
+/// `a + b` or `a * b` ciphertext by ciphertext.
+fn bfv_binary(dir: &Path, mul: bool, a: &Path, b: &Path, out: &Path) -> Result<()> {
+    let bfv = bfv(dir)?;
+    let (a, b) = (read_ciphertexts(&bfv, a)?, read_ciphertexts(&bfv, b)?);
+    if a.len() != b.len() {
+        return Err(format!("{} against {} ciphertexts", a.len(), b.len()).into());
+    }
+    let result = if mul {
+        let rlk = KeySwitchKey::from_bytes(bfv.context().clone(), &fs::read(dir.join("bfv.rlk"))?)?;
+        let evaluator = Evaluator::new().with_relin_key(rlk);
+        a.iter()
+            .zip(&b)
+            .map(|(x, y)| evaluator.mul(x, y))
+            .collect::<std::result::Result<Vec<_>, _>>()?
+    } else {
+        a.into_iter().zip(b).map(|(x, y)| x + y).collect()
+    };
+    write_ciphertexts(out, &result)
+}
+
+fn transcipher(dir: &Path, input: &Path, out: &Path) -> Result<()> {
+    warn_toy();
+    let toy = toy(dir)?;
+    let key = read_ciphertexts(&toy, &dir.join("pasta.key.bfv"))?;
+    let rlk = KeySwitchKey::from_bytes(toy.context().clone(), &fs::read(dir.join("toy.rlk"))?)?;
+    let transcipher = Transcipher::<TOY_N, TOY_Q, TOY_T, Pasta<W, R>>::new(
+        key,
+        Evaluator::new().with_relin_key(rlk),
+    )?;
+    let (nonce, ciphertext) = read_pasta(input)?;
+    let mut cts = Vec::new();
+    for (chunk, words) in ciphertext.chunks(TOY_N * W).enumerate() {
+        cts.extend(transcipher.decrypt(nonce, (chunk * TOY_N) as u64, words)?);
+    }
+    write_ciphertexts(out, &cts)
+}
+
+/// The slots of every transciphered ciphertext.
+fn toy_decrypt(dir: &Path, input: &Path) -> Result<Vec<Vec<u64>>> {
+    warn_toy();
+    let sk = BfvSecretKey::<TOY_N>::load(dir.join("toy.sk"))?;
+    let encoder = BatchEncoder::<TOY_N, TOY_T>::new()?;
+    Ok(read_ciphertexts(&toy(dir)?, input)?
+        .iter()
+        .map(|ct| encoder.decode(&ct.decrypt(&sk)))
+        .collect())
+}
+
+fn print_words(words: &[u64]) {
+    let words = words.iter().map(u64::to_string).collect::<Vec<_>>();
+    println!("{}", words.join(" "));
+}
+
+fn run(args: &[String]) -> Option<Result<()>> {
+    let insecure_toy = args.iter().any(|a| a == INSECURE_TOY);
+    let args = args
+        .iter()
+        .map(String::as_str)
+        .filter(|&a| a != INSECURE_TOY)
+        .collect::<Vec<_>>();
+    let path = |i: usize| Path::new(args[i]);
+    Some(match args[..] {
+        ["pasta", "encrypt", _, _, ..]
+        | ["pasta", "decrypt", _, _]
+        | ["toy", "transcipher", _, _, _]
+        | ["toy", "decrypt", _, _]
+            if !insecure_toy =>
+        {
+            Err(format!(
+                "`{} {}` runs at toy parameters that are NOT secure, pass {INSECURE_TOY} to run it",
+                args[0], args[1]
+            )
+            .into())
+        }
+        ["keygen", _] => keygen(path(1), insecure_toy),
+        ["pasta", "encrypt", _, _, ..] => {
+            parse_words(&args[4..]).and_then(|w| pasta_encrypt(path(2), path(3), &w))
+        }
+        ["pasta", "decrypt", _, _] => pasta_decrypt(path(2), path(3)).map(|w| print_words(&w)),
+        ["bfv", "encrypt", _, _, ..] => {
+            parse_words(&args[4..]).and_then(|w| bfv_encrypt(path(2), path(3), &w))
+        }
+        ["bfv", "decrypt", _, _] => {
+            bfv_decrypt(path(2), path(3)).map(|slots| slots.iter().for_each(|s| print_words(s)))
+        }
+        ["bfv", op @ ("add" | "mul"), _, _, _, _] => {
+            bfv_binary(path(2), op == "mul", path(3), path(4), path(5))
+        }
+        ["toy", "transcipher", _, _, _] => transcipher(path(2), path(3), path(4)),
+        ["toy", "decrypt", _, _] => {
+            toy_decrypt(path(2), path(3)).map(|slots| slots.iter().for_each(|s| print_words(s)))
+        }
+        _ => return None,
+    })
+}
+
+fn usage() -> ExitCode {
+    eprintln!("usage: rlattice [{INSECURE_TOY}] keygen DIR");
+    eprintln!("       rlattice {INSECURE_TOY} pasta encrypt DIR OUT WORD...");
+    eprintln!("       rlattice {INSECURE_TOY} pasta decrypt DIR IN");
+    eprintln!("       rlattice bfv encrypt DIR OUT WORD...");
+    eprintln!("       rlattice bfv decrypt DIR IN");
+    eprintln!("       rlattice bfv add|mul DIR A B OUT");
+    eprintln!("       rlattice {INSECURE_TOY} toy transcipher DIR IN OUT");
+    eprintln!("       rlattice {INSECURE_TOY} toy decrypt DIR IN");
+    ExitCode::from(2)
+}
+
+fn main() -> ExitCode {
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+    let result = run(&args);
+    if let Some(e) = security::take_warning() {
+        eprintln!("rlattice: warning: {e}");
+    }
+    match result {
+        Some(Ok(())) => ExitCode::SUCCESS,
+        Some(Err(e)) => {
+            eprintln!("rlattice: {e}");
+            ExitCode::FAILURE
+        }
+        None => usage(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pipeline() {
+        // preset polynomials live on the stack, more than the 2 MiB of a test thread in debug builds
+        std::thread::Builder::new()
+            .stack_size(64 << 20)
+            .spawn(pipeline)
+            .unwrap()
+            .join()
+            .unwrap();
+    }
+
+    fn pipeline() {
+        let dir = std::env::temp_dir().join(format!("rlattice-cli-{}", std::process::id()));
+        keygen(&dir, true).unwrap();
+        #[cfg(unix)]
+        for key in ["bfv.sk", "toy.sk", "pasta.key"] {
+            let mode = fs::metadata(dir.join(key)).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600, "{key}");
+        }
+
+        let data = (0..21).map(|i| i * 5 % T).collect::<Vec<_>>();
+        pasta_encrypt(&dir, &dir.join("data.pasta"), &data).unwrap();
+        assert_eq!(pasta_decrypt(&dir, &dir.join("data.pasta")).unwrap(), data);
+
+        // two chunks of n blocks, W ciphertexts each
+        transcipher(&dir, &dir.join("data.pasta"), &dir.join("data.bfv")).unwrap();
+        let slots = toy_decrypt(&dir, &dir.join("data.bfv")).unwrap();
+        assert_eq!(slots.len(), 4);
+        for (k, &w) in data.iter().enumerate() {
+            let (chunk, k) = (k / (TOY_N * W), k % (TOY_N * W));
+            assert_eq!(slots[chunk * W + k % W][k / W], w);
+        }
+
+        bfv_encrypt(&dir, &dir.join("x.bfv"), &[3, 2]).unwrap();
+        bfv_binary(
+            &dir,
+            true,
+            &dir.join("x.bfv"),
+            &dir.join("x.bfv"),
+            &dir.join("sq.bfv"),
+        )
+        .unwrap();
+        bfv_binary(
+            &dir,
+            false,
+            &dir.join("sq.bfv"),
+            &dir.join("x.bfv"),
+            &dir.join("s.bfv"),
+        )
+        .unwrap();
+        // (3 + 2x)^2 + 3 + 2x = 12 + 14x + 4x^2
+        assert_eq!(
+            bfv_decrypt(&dir, &dir.join("s.bfv")).unwrap(),
+            [vec![12, 14, 4]]
+        );
+
+        assert!(parse_words(&["17"]).is_err());
+        assert!(bfv_encrypt(&dir, &dir.join("y.bfv"), &[1; N + 1]).is_err());
+        assert!(run(&["pasta".to_string()]).is_none());
+        let decrypt = ["toy", "decrypt", dir.to_str().unwrap(), "data.bfv"].map(String::from);
+        assert!(run(&decrypt).unwrap().is_err());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,120 @@
+//! Blind rotation, the core of FHEW / TFHE bootstrapping: turns an LWE ciphertext into an RLWE encryption
+//! of test * x^-k, k its phase switched to Z_2n, without learning k.
+//!
+//! The accumulator starts as the trivial encryption (test * x^-b, 0) and every coefficient a_i of the
+//! ciphertext multiplies it by x^(-a_i s_i) through a CMux on an RGSW encryption of the secret bit s_i. The
+//! constant coefficient of the result is then test[k] for k < n and -test[k - n] above, so the test
+//! polynomial decides which function of the phase comes out (see `tfhe`).
+
+use std::sync::Arc;
+
+use zeroize::Zeroizing;
+
+use crate::lwe::{LweCipher, LweSecretKey};
+use crate::polynomial::{Element, Polynomial};
+use crate::rgsw::{RgswCiphertext, RgswContext, Rlwe};
+
+/// round(x * 2n / q) mod 2n, the exponent of x a phase of x turns into.
+fn switch_to_2n<const N: usize, const Q: u64>(x: Element<Q>) -> usize {
+    let two_n = 2 * N as u128;
+    ((x.value() as u128 * two_n + Q as u128 / 2) / Q as u128 % two_n) as usize
+}
+
+/// RGSW encryptions of the l bits of a binary LWE secret under a ring secret of degree n, decomposed in
+/// base 2^`base_log`. (n, q) has to be NTT friendly.
+#[derive(Debug, Clone)]
+pub struct BlindRotationKey<const L: usize, const N: usize, const Q: u64> {
+    bits: Vec<RgswCiphertext<N, Q>>,
+}
+
+impl<const L: usize, const N: usize, const Q: u64> BlindRotationKey<L, N, Q> {
+    pub fn new(lwe_sk: &LweSecretKey<L>, ring_sk: &LweSecretKey<N>, base_log: u32) -> Self {
+        assert!(
+            lwe_sk.poly().inner.iter().all(|c| c.value() < 2),
+            "blind rotation needs a binary LWE secret"
+        );
+        let ctx = Arc::new(
+            RgswContext::new(base_log)
+                .unwrap_or_else(|| panic!("(n = {N}, q = {Q}) is not NTT friendly")),
+        );
+        let s = Zeroizing::new(lwe_sk.poly().lift::<Q>());
+        let bits = s
+            .inner
+            .iter()
+            .map(|&s_i| {
+                let mut m = Zeroizing::new(Polynomial::new([Element::new(0); N]));
+                m.inner[0] = s_i;
+                RgswCiphertext::encrypt(&ctx, ring_sk.poly(), &m)
+            })
+            .collect();
+        Self { bits }
+    }
+
+    /// RLWE encryption of test * x^-k under the ring secret, k the phase of `ct` switched to 2n. Each of the
+    /// l CMuxes adds the noise of one external product.
+    pub fn rotate<const T: u64>(
+        &self,
+        ct: &LweCipher<L, Q, T>,
+        test: &Polynomial<N, Q>,
+    ) -> Rlwe<N, Q> {
+        let two_n = 2 * N;
+        let mut acc = Rlwe::trivial(*test).mul_monomial(two_n - switch_to_2n::<N, Q>(ct.b));
+        for (a_i, bit) in ct.a.inner.iter().zip(&self.bits) {
+            let k = switch_to_2n::<N, Q>(*a_i);
+            if k != 0 {
+                acc = bit.cmux(&acc, &acc.mul_monomial(two_n - k));
+            }
+        }
+        acc
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const L: usize = 16;
+    const N: usize = 64;
+    /// Prime, 1 mod 128.
+    const Q: u64 = 4_294_957_057;
+
+    #[test]
+    fn test_rotate() {
+        let (lwe_sk, ring_sk) = (LweSecretKey::<L>::binary(), LweSecretKey::<N>::generate());
+        let key = BlindRotationKey::<L, N, Q>::new(&lwe_sk, &ring_sk, 8);
+        let test = Polynomial::<N, Q>::rand();
+        for _ in 0..4 {
+            // any (a, b) works, the phase does not have to be a valid encryption
+            let ct = LweCipher::<L, Q, 2>::new(
+                Polynomial::rand(),
+                Element::new(rand::random_range(0..Q as i64)),
+            );
+            let k = lwe_sk
+                .poly()
+                .inner
+                .iter()
+                .zip(&ct.a.inner)
+                .fold(switch_to_2n::<N, Q>(ct.b), |k, (s_i, a_i)| {
+                    k + s_i.value() as usize * switch_to_2n::<N, Q>(*a_i)
+                });
+            let expected = Rlwe::trivial(test).mul_monomial(2 * N - k % (2 * N)).c_1;
+            let noise = (key.rotate(&ct, &test).phase(ring_sk.poly()) - expected)
+                .inner
+                .iter()
+                .map(|c| c.value().min(Q - c.value()))
+                .max()
+                .unwrap();
+            assert!(noise < 1 << 20, "noise {noise}");
+        }
+    }
+
+    #[test]
+    fn test_binary_secret_required() {
+        let ring_sk = LweSecretKey::<N>::generate();
+        let ternary = LweSecretKey::new(Polynomial::new([Element::new(2); L]));
+        assert!(
+            std::panic::catch_unwind(|| BlindRotationKey::<L, N, Q>::new(&ternary, &ring_sk, 8))
+                .is_err()
+        );
+    }
+}
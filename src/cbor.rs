@@ -0,0 +1,417 @@
+//! Structured encoding of BFV keys and ciphertexts (`cbor` feature), for embedding them in RPC messages
+//! instead of the opaque `to_bytes` blobs.
+//!
+//! Each object converts to a record of named fields, its parameters and its coefficients as integer
+//! arrays, e.g. a ciphertext is `{"n": .., "q": .., "t": .., "c_1": [..], "c_2": [..]}`. The records are
+//! `Serialize` / `Deserialize`, so they nest as fields of any serde message (CBOR, JSON, MessagePack), and
+//! [`to_cbor`] / [`from_cbor`] encode them standalone. Converting back checks the parameters against the
+//! receiving types and every coefficient against its modulus.
+
+use std::fmt;
+
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use zeroize::{Zeroize, ZeroizeOnDrop};
+
+use crate::backend::RingBackend;
+use crate::bfv_pke::{Bfv, BfvCiphertext, BfvPublicKey, BfvSecretKey, GaloisKeys};
+use crate::keyswitch::KeySwitchKey;
+use crate::polynomial::{DecodeError, Element, Polynomial};
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CiphertextRecord {
+    pub n: u64,
+    pub q: u64,
+    pub t: u64,
+    pub c_1: Vec<u64>,
+    pub c_2: Vec<u64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PublicKeyRecord {
+    pub n: u64,
+    pub q: u64,
+    pub p_0: Vec<u64>,
+    pub p_1: Vec<u64>,
+}
+
+/// A relinearization key or one Galois key: its gadget base and the rows (b_j, a_j) in the coefficient
+/// domain, least significant digit first.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KeySwitchKeyRecord {
+    pub n: u64,
+    pub q: u64,
+    pub t: u64,
+    pub base_log: u32,
+    pub b: Vec<Vec<u64>>,
+    pub a: Vec<Vec<u64>>,
+}
+
+/// Galois keys as (Galois element, key) pairs, ordered by element.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GaloisKeysRecord {
+    pub keys: Vec<(u64, KeySwitchKeyRecord)>,
+}
+
+/// Coefficients of s in {-1, 0, 1}. Zeroized on drop and never printed, like the key.
+#[derive(Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SecretKeyRecord {
+    pub n: u64,
+    pub s: Vec<i8>,
+}
+
+impl Drop for SecretKeyRecord {
+    fn drop(&mut self) {
+        self.s.zeroize();
+    }
+}
+
+impl ZeroizeOnDrop for SecretKeyRecord {}
+
+impl fmt::Debug for SecretKeyRecord {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "SecretKeyRecord {{ n: {}, .. }}", self.n)
+    }
+}
+
+/// `field` has to equal the const parameter `expected`.
+fn check_param(field: &'static str, value: u64, expected: u64) -> Result<(), DecodeError> {
+    if value == expected {
+        Ok(())
+    } else {
+        Err(DecodeError::InvalidField { field, value })
+    }
+}
+
+fn coeffs<const N: usize, const A: u64>(poly: &Polynomial<N, A>) -> Vec<u64> {
+    poly.inner.iter().map(Element::value).collect()
+}
+
+fn poly<const N: usize, const A: u64>(
+    field: &'static str,
+    coeffs: &[u64],
+) -> Result<Polynomial<N, A>, DecodeError> {
+    if coeffs.len() != N {
+        return Err(DecodeError::InvalidField {
+            field,
+            value: coeffs.len() as u64,
+        });
+    }
+    if let Some((index, &value)) = coeffs.iter().enumerate().find(|(_, c)| **c >= A) {
+        return Err(DecodeError::OutOfRange { index, value });
+    }
+    Ok(Polynomial::new(core::array::from_fn(|i| {
+        Element::new(coeffs[i] as i64)
+    })))
+}
+
+impl<const N: usize, const Q: u64, const T: u64, B: RingBackend> From<&BfvCiphertext<N, Q, T, B>>
+    for CiphertextRecord
+{
+    fn from(ct: &BfvCiphertext<N, Q, T, B>) -> Self {
+        CiphertextRecord {
+            n: N as u64,
+            q: Q,
+            t: T,
+            c_1: coeffs(ct.c_1()),
+            c_2: coeffs(ct.c_2()),
+        }
+    }
+}
+
+impl CiphertextRecord {
+    /// The ciphertext under the key of `bfv`, like `Bfv::ciphertext_from_bytes`.
+    pub fn to_ciphertext<const N: usize, const Q: u64, const T: u64, B: RingBackend>(
+        &self,
+        bfv: &Bfv<N, Q, T, B>,
+    ) -> Result<BfvCiphertext<N, Q, T, B>, DecodeError> {
+        check_param("n", self.n, N as u64)?;
+        check_param("q", self.q, Q)?;
+        check_param("t", self.t, T)?;
+        Ok(BfvCiphertext::new(
+            poly("c_1", &self.c_1)?,
+            poly("c_2", &self.c_2)?,
+            bfv.context().clone(),
+        ))
+    }
+}
+
+impl<const N: usize, const Q: u64, const T: u64, B: RingBackend> From<&KeySwitchKey<N, Q, T, B>>
+    for KeySwitchKeyRecord
+{
+    fn from(key: &KeySwitchKey<N, Q, T, B>) -> Self {
+        let (b, a) = key
+            .rows()
+            .iter()
+            .map(|(b, a)| (coeffs(b), coeffs(a)))
+            .unzip();
+        KeySwitchKeyRecord {
+            n: N as u64,
+            q: Q,
+            t: T,
+            base_log: key.gadget().base_log(),
+            b,
+            a,
+        }
+    }
+}
+
+impl KeySwitchKeyRecord {
+    /// The key into the key pair of `bfv`, like `KeySwitchKey::from_bytes`.
+    pub fn to_key<const N: usize, const Q: u64, const T: u64, B: RingBackend>(
+        &self,
+        bfv: &Bfv<N, Q, T, B>,
+    ) -> Result<KeySwitchKey<N, Q, T, B>, DecodeError> {
+        check_param("n", self.n, N as u64)?;
+        check_param("q", self.q, Q)?;
+        check_param("t", self.t, T)?;
+        check_param("a", self.a.len() as u64, self.b.len() as u64)?;
+        let rows = self
+            .b
+            .iter()
+            .zip(&self.a)
+            .map(|(b, a)| Ok((poly("b", b)?, poly("a", a)?)))
+            .collect::<Result<Vec<_>, DecodeError>>()?;
+        KeySwitchKey::from_rows(bfv.context().clone(), self.base_log, &rows)
+    }
+}
+
+impl<const N: usize, const Q: u64, const T: u64, B: RingBackend> From<&GaloisKeys<N, Q, T, B>>
+    for GaloisKeysRecord
+{
+    fn from(keys: &GaloisKeys<N, Q, T, B>) -> Self {
+        let mut elements = keys.elements().collect::<Vec<_>>();
+        elements.sort_unstable();
+        GaloisKeysRecord {
+            keys: elements
+                .into_iter()
+                .map(|k| {
+                    (
+                        k as u64,
+                        KeySwitchKeyRecord::from(keys.get(k).expect("listed")),
+                    )
+                })
+                .collect(),
+        }
+    }
+}
+
+impl GaloisKeysRecord {
+    /// The keys into the key pair of `bfv`, every element has to be odd and below 2n.
+    pub fn to_keys<const N: usize, const Q: u64, const T: u64, B: RingBackend>(
+        &self,
+        bfv: &Bfv<N, Q, T, B>,
+    ) -> Result<GaloisKeys<N, Q, T, B>, DecodeError> {
+        let keys = self
+            .keys
+            .iter()
+            .map(|(k, key)| {
+                if k % 2 == 0 || *k >= 2 * N as u64 {
+                    return Err(DecodeError::InvalidField {
+                        field: "element",
+                        value: *k,
+                    });
+                }
+                Ok((*k as usize, key.to_key(bfv)?))
+            })
+            .collect::<Result<Vec<_>, DecodeError>>()?;
+        Ok(GaloisKeys::from_keys(keys))
+    }
+}
+
+impl<const N: usize, const Q: u64> From<&BfvPublicKey<N, Q>> for PublicKeyRecord {
+    fn from(pk: &BfvPublicKey<N, Q>) -> Self {
+        PublicKeyRecord {
+            n: N as u64,
+            q: Q,
+            p_0: coeffs(pk.p_0()),
+            p_1: coeffs(pk.p_1()),
+        }
+    }
+}
+
+impl<const N: usize, const Q: u64> TryFrom<&PublicKeyRecord> for BfvPublicKey<N, Q> {
+    type Error = DecodeError;
+
+    fn try_from(record: &PublicKeyRecord) -> Result<Self, Self::Error> {
+        check_param("n", record.n, N as u64)?;
+        check_param("q", record.q, Q)?;
+        Ok(BfvPublicKey::new(
+            poly("p_0", &record.p_0)?,
+            poly("p_1", &record.p_1)?,
+        ))
+    }
+}
+
+impl<const N: usize> From<&BfvSecretKey<N>> for SecretKeyRecord {
+    fn from(sk: &BfvSecretKey<N>) -> Self {
+        SecretKeyRecord {
+            n: N as u64,
+            s: sk.poly().to_centered().iter().map(|&c| c as i8).collect(),
+        }
+    }
+}
+
+impl<const N: usize> TryFrom<&SecretKeyRecord> for BfvSecretKey<N> {
+    type Error = DecodeError;
+
+    fn try_from(record: &SecretKeyRecord) -> Result<Self, Self::Error> {
+        check_param("n", record.n, N as u64)?;
+        if record.s.len() != N {
+            return Err(DecodeError::InvalidField {
+                field: "s",
+                value: record.s.len() as u64,
+            });
+        }
+        if let Some((index, &value)) = record.s.iter().enumerate().find(|(_, c)| c.abs() > 1) {
+            return Err(DecodeError::NotTernary {
+                index,
+                value: value as i64,
+            });
+        }
+        Ok(BfvSecretKey::new(Polynomial::new(core::array::from_fn(
+            |i| Element::new(record.s[i] as i64),
+        ))))
+    }
+}
+
+/// Not well formed CBOR, or not the expected record.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CborError(String);
+
+impl fmt::Display for CborError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "cbor: {}", self.0)
+    }
+}
+
+impl std::error::Error for CborError {}
+
+pub fn to_cbor<V: Serialize>(value: &V) -> Vec<u8> {
+    let mut out = Vec::new();
+    ciborium::into_writer(value, &mut out).expect("writing to a Vec can't fail");
+    out
+}
+
+pub fn from_cbor<V: DeserializeOwned>(bytes: &[u8]) -> Result<V, CborError> {
+    ciborium::from_reader(bytes).map_err(|e| CborError(e.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    type Params = Bfv<16, 12_289, 4>;
+
+    #[test]
+    fn test_roundtrip() {
+        let (bfv, sk) = Params::keygen().unwrap();
+        let m = Polynomial::<16, 4>::rand();
+        let ct = bfv.encrypt(m);
+
+        let bytes = to_cbor(&CiphertextRecord::from(&ct));
+        let record: CiphertextRecord = from_cbor(&bytes).unwrap();
+        assert_eq!(record.to_ciphertext(&bfv).unwrap().decrypt(&sk), m);
+
+        let record: PublicKeyRecord =
+            from_cbor(&to_cbor(&PublicKeyRecord::from(bfv.public_key()))).unwrap();
+        assert_eq!(&BfvPublicKey::try_from(&record).unwrap(), bfv.public_key());
+
+        let record: SecretKeyRecord = from_cbor(&to_cbor(&SecretKeyRecord::from(&sk))).unwrap();
+        assert!(BfvSecretKey::<16>::try_from(&record).unwrap() == sk);
+
+        let rlk = bfv.gen_relin_key(&sk, 4);
+        let record: KeySwitchKeyRecord =
+            from_cbor(&to_cbor(&KeySwitchKeyRecord::from(&rlk))).unwrap();
+        assert_eq!(record.to_key(&bfv).unwrap().to_bytes(), rlk.to_bytes());
+
+        let galois = bfv.gen_galois_keys(&sk, &[3, 31], 4).unwrap();
+        let record: GaloisKeysRecord =
+            from_cbor(&to_cbor(&GaloisKeysRecord::from(&galois))).unwrap();
+        assert_eq!(
+            record.keys.iter().map(|(k, _)| *k).collect::<Vec<_>>(),
+            [3, 31]
+        );
+        let back = record.to_keys(&bfv).unwrap();
+        for k in [3, 31] {
+            assert_eq!(
+                back.get(k).unwrap().to_bytes(),
+                galois.get(k).unwrap().to_bytes()
+            );
+        }
+
+        // records nest in other serde messages
+        let json = serde_json::to_string(&("id", CiphertextRecord::from(&ct))).unwrap();
+        assert!(json.contains("\"c_1\":["));
+    }
+
+    #[test]
+    fn test_rejects_mismatches() {
+        let (bfv, _) = Params::keygen().unwrap();
+        let record = CiphertextRecord::from(&bfv.encrypt(Polynomial::rand()));
+        let wrong_q = CiphertextRecord {
+            q: 7681,
+            ..record.clone()
+        };
+        assert_eq!(
+            wrong_q.to_ciphertext(&bfv).unwrap_err(),
+            DecodeError::InvalidField {
+                field: "q",
+                value: 7681
+            }
+        );
+        let mut big = record.clone();
+        big.c_2[3] = 12_289;
+        assert_eq!(
+            big.to_ciphertext(&bfv).unwrap_err(),
+            DecodeError::OutOfRange {
+                index: 3,
+                value: 12_289
+            }
+        );
+        let short = CiphertextRecord {
+            c_1: vec![0; 8],
+            ..record
+        };
+        assert!(short.to_ciphertext(&bfv).is_err());
+        assert!(from_cbor::<CiphertextRecord>(&[0xff, 0x00]).is_err());
+
+        let (bfv, sk) = Params::keygen().unwrap();
+        let mut record = KeySwitchKeyRecord::from(&bfv.gen_relin_key(&sk, 4));
+        record.a.pop();
+        assert_eq!(
+            record.to_key(&bfv).unwrap_err(),
+            DecodeError::InvalidField {
+                field: "a",
+                value: record.a.len() as u64
+            }
+        );
+        record.b.pop();
+        assert_eq!(
+            record.to_key(&bfv).unwrap_err(),
+            DecodeError::InvalidField {
+                field: "rows",
+                value: record.b.len() as u64
+            }
+        );
+        let mut record = GaloisKeysRecord::from(&bfv.gen_galois_keys(&sk, &[3], 4).unwrap());
+        record.keys[0].0 = 4;
+        assert_eq!(
+            record.to_keys(&bfv).unwrap_err(),
+            DecodeError::InvalidField {
+                field: "element",
+                value: 4
+            }
+        );
+
+        let mut record = SecretKeyRecord::from(&sk);
+        record.s[5] = -2;
+        assert_eq!(
+            BfvSecretKey::<16>::try_from(&record).unwrap_err(),
+            DecodeError::NotTernary {
+                index: 5,
+                value: -2
+            }
+        );
+    }
+}
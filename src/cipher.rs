@@ -0,0 +1,189 @@
+//! One interface over the HE-friendly block ciphers, so transciphering code is written once.
+//!
+//! Every cipher is a keystream generator over (nonce, block counter) with words in Z_p, the binary ones
+//! (Rasta) use p = 2 and 0/1 words. Encryption adds the keystream word by word mod p, block b of the
+//! message takes the keystream of (nonce, b).
+//! FiLIP and Kreyvium produce one sequential stream per nonce/IV without random access to a block, so they
+//! stay outside the trait.
+
+use rand::Rng;
+use zeroize::Zeroizing;
+
+use crate::field::{Modulus, Ring};
+use crate::masta::{Masta, MastaError};
+use crate::pasta_plain::{Pasta, PastaError, PastaKey};
+use crate::rasta::{Rasta, RastaError};
+
+pub trait HeFriendlyCipher: Sized {
+    type Error: std::error::Error;
+
+    /// Cipher under a fresh uniform key, `modulus` is the plaintext modulus. Fails on a modulus the
+    /// cipher can't run over.
+    fn keygen(rng: &mut impl Rng, modulus: u64) -> Result<Self, Self::Error>;
+
+    fn plaintext_modulus(&self) -> u64;
+
+    /// Keystream words per (nonce, block counter).
+    fn block_size(&self) -> usize;
+
+    /// `block_size` words, each below `plaintext_modulus`.
+    fn keystream(&mut self, nonce: u64, block_counter: u64) -> Vec<u64>;
+
+    fn encrypt(&mut self, nonce: u64, plaintext: &[u64]) -> Vec<u64> {
+        let field = Modulus::new(self.plaintext_modulus());
+        let mut out = plaintext.to_vec();
+        for (b, block) in out.chunks_mut(self.block_size()).enumerate() {
+            let ks = self.keystream(nonce, b as u64);
+            for (w, k) in block.iter_mut().zip(ks) {
+                *w = field.add(*w, k);
+            }
+        }
+        out
+    }
+
+    fn decrypt(&mut self, nonce: u64, ciphertext: &[u64]) -> Vec<u64> {
+        let field = Modulus::new(self.plaintext_modulus());
+        let mut out = ciphertext.to_vec();
+        for (b, block) in out.chunks_mut(self.block_size()).enumerate() {
+            let ks = self.keystream(nonce, b as u64);
+            for (w, k) in block.iter_mut().zip(ks) {
+                *w = field.sub(*w, k);
+            }
+        }
+        out
+    }
+}
+
+impl<const T: usize, const R: usize> HeFriendlyCipher for Pasta<T, R> {
+    type Error = PastaError;
+
+    fn keygen(rng: &mut impl Rng, modulus: u64) -> Result<Self, PastaError> {
+        Pasta::try_with_key(PastaKey::try_generate(rng, modulus)?, modulus)
+    }
+
+    fn plaintext_modulus(&self) -> u64 {
+        self.modulus()
+    }
+
+    fn block_size(&self) -> usize {
+        T
+    }
+
+    fn keystream(&mut self, nonce: u64, block_counter: u64) -> Vec<u64> {
+        Pasta::keystream(self, nonce, block_counter).to_vec()
+    }
+
+    fn encrypt(&mut self, nonce: u64, plaintext: &[u64]) -> Vec<u64> {
+        self.encrypt_with_nonce(nonce, plaintext)
+    }
+
+    fn decrypt(&mut self, nonce: u64, ciphertext: &[u64]) -> Vec<u64> {
+        self.decrypt_with_nonce(nonce, ciphertext)
+    }
+}
+
+impl<const N: usize, const R: usize> HeFriendlyCipher for Masta<N, R> {
+    type Error = MastaError;
+
+    fn keygen(rng: &mut impl Rng, modulus: u64) -> Result<Self, MastaError> {
+        if modulus < 2 {
+            return Err(MastaError::Modulus { modulus });
+        }
+        Masta::try_new(
+            (0..N).map(|_| rng.random_range(0..modulus)).collect(),
+            modulus,
+        )
+    }
+
+    fn plaintext_modulus(&self) -> u64 {
+        self.modulus()
+    }
+
+    fn block_size(&self) -> usize {
+        N
+    }
+
+    fn keystream(&mut self, nonce: u64, block_counter: u64) -> Vec<u64> {
+        Masta::keystream(self, nonce, block_counter).to_vec()
+    }
+}
+
+impl<const N: usize, const R: usize> HeFriendlyCipher for Rasta<N, R> {
+    type Error = RastaError;
+
+    /// `modulus` has to be 2.
+    fn keygen(rng: &mut impl Rng, modulus: u64) -> Result<Self, RastaError> {
+        if modulus != 2 {
+            return Err(RastaError::Modulus { modulus });
+        }
+        Rasta::try_new(&Zeroizing::new(
+            (0..N).map(|_| rng.random()).collect::<Vec<bool>>(),
+        ))
+    }
+
+    fn plaintext_modulus(&self) -> u64 {
+        2
+    }
+
+    fn block_size(&self) -> usize {
+        N
+    }
+
+    fn keystream(&mut self, nonce: u64, block_counter: u64) -> Vec<u64> {
+        Rasta::keystream(self, nonce, block_counter)
+            .into_iter()
+            .map(u64::from)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::rng;
+
+    use super::*;
+
+    /// Written once against the trait.
+    fn roundtrip<C: HeFriendlyCipher>(modulus: u64) {
+        let mut rng = rng();
+        let mut cipher = C::keygen(&mut rng, modulus).unwrap();
+        assert_eq!(cipher.plaintext_modulus(), modulus);
+        let len = 2 * cipher.block_size() + 1;
+        let plain = (0..len)
+            .map(|_| rng.random_range(0..modulus))
+            .collect::<Vec<_>>();
+        let ct = cipher.encrypt(3, &plain);
+        assert_eq!(cipher.decrypt(3, &ct), plain);
+
+        // block b is keyed by (nonce, b)
+        let ks = cipher.keystream(3, 1);
+        assert_eq!(ks.len(), cipher.block_size());
+        let b = cipher.block_size();
+        let field = Modulus::new(modulus);
+        for i in 0..b {
+            assert_eq!(ct[b + i], field.add(plain[b + i], ks[i]));
+        }
+    }
+
+    #[test]
+    fn test_ciphers() {
+        roundtrip::<Pasta>(65_537);
+        roundtrip::<Pasta<4, 2>>(17);
+        roundtrip::<Masta<8, 3>>(65_537);
+        roundtrip::<Rasta<21, 2>>(2);
+
+        let mut rng = rng();
+        assert_eq!(
+            Pasta::<4, 2>::keygen(&mut rng, 1 << 60).err(),
+            Some(PastaError::Modulus { modulus: 1 << 60 })
+        );
+        assert_eq!(
+            Masta::<8, 3>::keygen(&mut rng, 0).err(),
+            Some(MastaError::Modulus { modulus: 0 })
+        );
+        assert_eq!(
+            Rasta::<21, 2>::keygen(&mut rng, 3).err(),
+            Some(RastaError::Modulus { modulus: 3 })
+        );
+    }
+}
@@ -0,0 +1,375 @@
+//! A small arithmetic circuit IR over BFV ciphertexts. [`Circuit`] records gates and the multiplicative
+//! level of every wire, [`Circuit::evaluate`] runs them with an [`Evaluator`].
+//!
+//! Products stay tensored until something needs a regular ciphertext: another product, a plaintext
+//! operation, a rotation or an output. Sums of products relinearize once, a*b + c*d costs one
+//! relinearization instead of two.
+
+use std::fmt;
+
+use crate::backend::RingBackend;
+use crate::bfv_pke::{BfvCiphertext, EvalError, Evaluator, Plaintext, TensoredCipher};
+use crate::cancel::{CancellationToken, Cancelled};
+
+/// Output of a gate, only meaningful in the circuit that returned it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Wire(usize);
+
+#[derive(Debug, Clone)]
+pub enum Gate<const N: usize, const T: u64> {
+    /// The i-th ciphertext passed to `evaluate`.
+    Input(usize),
+    Add(Wire, Wire),
+    Mul(Wire, Wire),
+    AddPlain(Wire, Plaintext<N, T>),
+    MulPlain(Wire, Plaintext<N, T>),
+    /// x -> x^k, see `Evaluator::rotate`.
+    Rotate(Wire, usize),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CircuitError {
+    InputCount {
+        expected: usize,
+        got: usize,
+    },
+    Eval(EvalError),
+    /// The token was cancelled between two gates.
+    Cancelled,
+}
+
+impl fmt::Display for CircuitError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CircuitError::InputCount { expected, got } => {
+                write!(f, "circuit takes {expected} inputs, got {got}")
+            }
+            CircuitError::Eval(e) => write!(f, "{e}"),
+            CircuitError::Cancelled => write!(f, "{Cancelled}"),
+        }
+    }
+}
+
+impl std::error::Error for CircuitError {}
+
+impl From<EvalError> for CircuitError {
+    fn from(e: EvalError) -> Self {
+        CircuitError::Eval(e)
+    }
+}
+
+impl From<Cancelled> for CircuitError {
+    fn from(_: Cancelled) -> Self {
+        CircuitError::Cancelled
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Circuit<const N: usize, const T: u64> {
+    gates: Vec<Gate<N, T>>,
+    /// Ciphertext products on the longest path to each wire.
+    levels: Vec<u32>,
+    inputs: usize,
+    outputs: Vec<Wire>,
+}
+
+impl<const N: usize, const T: u64> Circuit<N, T> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn input(&mut self) -> Wire {
+        self.inputs += 1;
+        self.push(Gate::Input(self.inputs - 1), 0)
+    }
+
+    pub fn add(&mut self, a: Wire, b: Wire) -> Wire {
+        self.push(Gate::Add(a, b), self.level(a).max(self.level(b)))
+    }
+
+    pub fn mul(&mut self, a: Wire, b: Wire) -> Wire {
+        self.push(Gate::Mul(a, b), self.level(a).max(self.level(b)) + 1)
+    }
+
+    pub fn add_plain(&mut self, a: Wire, pt: Plaintext<N, T>) -> Wire {
+        self.push(Gate::AddPlain(a, pt), self.level(a))
+    }
+
+    pub fn mul_plain(&mut self, a: Wire, pt: Plaintext<N, T>) -> Wire {
+        self.push(Gate::MulPlain(a, pt), self.level(a))
+    }
+
+    pub fn rotate(&mut self, a: Wire, k: usize) -> Wire {
+        self.push(Gate::Rotate(a, k), self.level(a))
+    }
+
+    /// Marks `w` as a result, `evaluate` returns results in the order they were marked.
+    pub fn output(&mut self, w: Wire) {
+        self.level(w);
+        self.outputs.push(w);
+    }
+
+    fn push(&mut self, gate: Gate<N, T>, level: u32) -> Wire {
+        self.gates.push(gate);
+        self.levels.push(level);
+        Wire(self.gates.len() - 1)
+    }
+
+    /// Panics on a wire from another circuit.
+    pub fn level(&self, w: Wire) -> u32 {
+        *self
+            .levels
+            .get(w.0)
+            .unwrap_or_else(|| panic!("wire {} is not in this circuit", w.0))
+    }
+
+    /// Multiplicative depth, the highest level of an output.
+    pub fn depth(&self) -> u32 {
+        self.outputs
+            .iter()
+            .map(|&w| self.level(w))
+            .max()
+            .unwrap_or(0)
+    }
+
+    pub fn gates(&self) -> &[Gate<N, T>] {
+        &self.gates
+    }
+
+    pub fn inputs(&self) -> usize {
+        self.inputs
+    }
+
+    pub fn outputs(&self) -> &[Wire] {
+        &self.outputs
+    }
+
+    /// For every wire, whether `evaluate` relinearizes it right after computing it: tensored wires that
+    /// feed a product, a plaintext operation, a rotation or an output.
+    fn relin_plan(&self) -> Vec<bool> {
+        let mut needed = vec![false; self.gates.len()];
+        for gate in &self.gates {
+            match *gate {
+                Gate::Input(_) | Gate::Add(..) => {}
+                Gate::Mul(a, b) => {
+                    needed[a.0] = true;
+                    needed[b.0] = true;
+                }
+                Gate::AddPlain(a, _) | Gate::MulPlain(a, _) | Gate::Rotate(a, _) => {
+                    needed[a.0] = true
+                }
+            }
+        }
+        for w in &self.outputs {
+            needed[w.0] = true;
+        }
+        let mut tensored = vec![false; self.gates.len()];
+        let mut plan = vec![false; self.gates.len()];
+        for (i, gate) in self.gates.iter().enumerate() {
+            tensored[i] = match *gate {
+                // an add of a relinearized wire is no longer tensored
+                Gate::Add(a, b) => (tensored[a.0] && !plan[a.0]) || (tensored[b.0] && !plan[b.0]),
+                Gate::Mul(..) => true,
+                _ => false,
+            };
+            plan[i] = tensored[i] && needed[i];
+        }
+        plan
+    }
+
+    /// Relinearizations `evaluate` performs.
+    pub fn relinearizations(&self) -> usize {
+        self.relin_plan().into_iter().filter(|&r| r).count()
+    }
+
+    /// Runs the circuit on `inputs`, one per `input` wire in order. `evaluator` needs a relinearization key
+    /// if there are products and Galois keys for the rotations.
+    pub fn evaluate<const Q: u64, B: RingBackend>(
+        &self,
+        evaluator: &Evaluator<N, Q, T, B>,
+        inputs: &[BfvCiphertext<N, Q, T, B>],
+    ) -> Result<Vec<BfvCiphertext<N, Q, T, B>>, CircuitError> {
+        self.evaluate_cancellable(evaluator, inputs, &CancellationToken::new())
+    }
+
+    /// `evaluate` that checks `token` before every gate and returns `CircuitError::Cancelled` once it is
+    /// cancelled.
+    pub fn evaluate_cancellable<const Q: u64, B: RingBackend>(
+        &self,
+        evaluator: &Evaluator<N, Q, T, B>,
+        inputs: &[BfvCiphertext<N, Q, T, B>],
+        token: &CancellationToken,
+    ) -> Result<Vec<BfvCiphertext<N, Q, T, B>>, CircuitError> {
+        if inputs.len() != self.inputs {
+            return Err(CircuitError::InputCount {
+                expected: self.inputs,
+                got: inputs.len(),
+            });
+        }
+        let plan = self.relin_plan();
+        let mut values: Vec<Value<N, Q, T, B>> = Vec::with_capacity(self.gates.len());
+        for (gate, &relin) in self.gates.iter().zip(&plan) {
+            token.check()?;
+            let ct = |w: Wire| values[w.0].ciphertext();
+            let value = match *gate {
+                Gate::Input(i) => Value::Ciphertext(inputs[i].clone()),
+                Gate::Add(a, b) => values[a.0].add(&values[b.0]),
+                Gate::Mul(a, b) => Value::Tensored(ct(a).tensor(ct(b))),
+                Gate::AddPlain(a, ref pt) => Value::Ciphertext(ct(a).add_plain(pt)),
+                Gate::MulPlain(a, ref pt) => Value::Ciphertext(ct(a).mul_plain(pt)),
+                Gate::Rotate(a, k) => Value::Ciphertext(evaluator.rotate(ct(a), k)?),
+            };
+            values.push(match value {
+                Value::Tensored(t) if relin => Value::Ciphertext(evaluator.relinearize(&t)?),
+                value => value,
+            });
+        }
+        Ok(self
+            .outputs
+            .iter()
+            .map(|w| values[w.0].ciphertext().clone())
+            .collect())
+    }
+}
+
+enum Value<const N: usize, const Q: u64, const T: u64, B: RingBackend> {
+    Ciphertext(BfvCiphertext<N, Q, T, B>),
+    Tensored(TensoredCipher<N, Q, T, B>),
+}
+
+impl<const N: usize, const Q: u64, const T: u64, B: RingBackend> Value<N, Q, T, B> {
+    /// Wires the plan relinearizes are never left tensored.
+    fn ciphertext(&self) -> &BfvCiphertext<N, Q, T, B> {
+        match self {
+            Value::Ciphertext(ct) => ct,
+            Value::Tensored(_) => unreachable!("tensored wire was not relinearized"),
+        }
+    }
+
+    fn tensored(&self) -> TensoredCipher<N, Q, T, B> {
+        match self {
+            Value::Ciphertext(ct) => ct.clone().into(),
+            Value::Tensored(t) => t.clone(),
+        }
+    }
+
+    fn add(&self, rhs: &Self) -> Self {
+        match (self, rhs) {
+            (Value::Ciphertext(a), Value::Ciphertext(b)) => {
+                Value::Ciphertext(a.clone() + b.clone())
+            }
+            _ => Value::Tensored(self.tensored() + rhs.tensored()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bfv_pke::Bfv;
+    use crate::polynomial::Polynomial;
+
+    const N: usize = 16;
+    const Q: u64 = 1 << 50;
+    const T: u64 = 17;
+
+    #[test]
+    fn test_sum_of_products() {
+        let (bfv, sk) = Bfv::<N, Q, T>::keygen().unwrap();
+        let evaluator = Evaluator::new().with_relin_key(bfv.gen_relin_key(&sk, 16));
+
+        let mut circuit = Circuit::<N, T>::new();
+        let [a, b, c, d] = [(); 4].map(|_| circuit.input());
+        let ab = circuit.mul(a, b);
+        let cd = circuit.mul(c, d);
+        let sum = circuit.add(ab, cd);
+        let plus_a = circuit.add(sum, a);
+        circuit.output(plus_a);
+        assert_eq!(circuit.depth(), 1);
+        assert_eq!(circuit.relinearizations(), 1);
+
+        let m = [(); 4].map(|_| Polynomial::<N, T>::rand());
+        let inputs = m.map(|m| bfv.encrypt(m));
+        let out = circuit.evaluate(&evaluator, &inputs).unwrap();
+        assert_eq!(out.len(), 1);
+        assert_eq!(out[0].decrypt(&sk), m[0] * m[1] + m[2] * m[3] + m[0]);
+
+        // (ab + cd) * a relinearizes the sum before the product and the product for the output
+        let prod = circuit.mul(sum, a);
+        circuit.output(prod);
+        assert_eq!(circuit.level(prod), 2);
+        assert_eq!(circuit.relinearizations(), 2);
+        let out = circuit.evaluate(&evaluator, &inputs).unwrap();
+        assert_eq!(out[1].decrypt(&sk), (m[0] * m[1] + m[2] * m[3]) * m[0]);
+    }
+
+    #[test]
+    fn test_plain_and_rotate() {
+        let (bfv, sk) = Bfv::<N, Q, T>::keygen().unwrap();
+        let evaluator = Evaluator::new()
+            .with_relin_key(bfv.gen_relin_key(&sk, 16))
+            .with_galois_keys(bfv.gen_galois_keys(&sk, &[3], 16).unwrap());
+
+        let pt = Polynomial::<N, T>::rand();
+        let mut circuit = Circuit::<N, T>::new();
+        let a = circuit.input();
+        let sq = circuit.mul(a, a);
+        let scaled = circuit.mul_plain(sq, pt);
+        let shifted = circuit.add_plain(scaled, pt);
+        let rotated = circuit.rotate(shifted, 3);
+        circuit.output(rotated);
+        assert_eq!(circuit.depth(), 1);
+
+        let m = Polynomial::<N, T>::rand();
+        let out = circuit.evaluate(&evaluator, &[bfv.encrypt(m)]).unwrap();
+        assert_eq!(out[0].decrypt(&sk), (m * m * pt + pt).automorphism(3));
+    }
+
+    #[test]
+    fn test_errors() {
+        let (bfv, _) = Bfv::<N, Q, T>::keygen().unwrap();
+        let mut circuit = Circuit::<N, T>::new();
+        let a = circuit.input();
+        let sq = circuit.mul(a, a);
+        circuit.output(sq);
+
+        let ct = bfv.encrypt(Polynomial::rand());
+        let evaluator = Evaluator::new();
+        assert_eq!(
+            circuit.evaluate(&evaluator, &[]).unwrap_err(),
+            CircuitError::InputCount {
+                expected: 1,
+                got: 0
+            }
+        );
+        assert_eq!(
+            circuit.evaluate(&evaluator, &[ct]).unwrap_err(),
+            CircuitError::Eval(EvalError::MissingRelinKey)
+        );
+        assert!(std::panic::catch_unwind(|| Circuit::<N, T>::new().level(Wire(0))).is_err());
+    }
+
+    #[test]
+    fn test_cancelled() {
+        let (bfv, _) = Bfv::<N, Q, T>::keygen().unwrap();
+        let mut circuit = Circuit::<N, T>::new();
+        let a = circuit.input();
+        let sum = circuit.add(a, a);
+        circuit.output(sum);
+
+        let inputs = [bfv.encrypt(Polynomial::rand())];
+        let token = CancellationToken::new();
+        assert!(
+            circuit
+                .evaluate_cancellable(&Evaluator::new(), &inputs, &token)
+                .is_ok()
+        );
+        token.cancel();
+        assert_eq!(
+            circuit
+                .evaluate_cancellable(&Evaluator::new(), &inputs, &token)
+                .unwrap_err(),
+            CircuitError::Cancelled
+        );
+    }
+}
@@ -0,0 +1,223 @@
+//! Constant-time replacements for the secret-dependent branches of the crate (`constant-time` feature),
+//! built on `subtle`'s selects and comparisons. With the feature on:
+//!
+//! - [`Ring::add`], [`Ring::sub`] and [`Ring::neg`] select instead of branching, and [`Ring::mul`],
+//!   [`Ring::reduce`] and [`Ring::reduce_i64`] go through a Barrett reduction ([`reduce`]) instead of the
+//!   hardware `%`, whose latency depends on the operands on some CPUs. That covers Pasta and MASTA
+//!   encryption and decryption (keystream words added or subtracted mod p), their keystreams (the S-boxes
+//!   and affine layers multiply secret state), and `Element` arithmetic.
+//! - BFV decoding, round(t c / q) mod t in every decryption, divides with [`div`] instead of the u128
+//!   division, which is a library call whose time depends on its operands.
+//! - Gaussian errors ([`ErrorDist::sample`]) are drawn by a full scan of a cumulative table
+//!   ([`gaussian`]) instead of rejection sampling, whose candidates and float weights depend on the
+//!   value drawn.
+//!
+//! Still variable time: the scalar fallback of the `simd` kernels, key switching and relinearization digit
+//! decomposition, and everything outside `field`, `polynomial`, `pasta_plain` and BFV decryption. Branch free source is no guarantee either, the compiler may still put branches
+//! back, so check the generated code for the targets you ship.
+//!
+//! [`Ring::add`]: crate::field::Ring::add
+//! [`Ring::sub`]: crate::field::Ring::sub
+//! [`Ring::neg`]: crate::field::Ring::neg
+//! [`Ring::mul`]: crate::field::Ring::mul
+//! [`Ring::reduce`]: crate::field::Ring::reduce
+//! [`Ring::reduce_i64`]: crate::field::Ring::reduce_i64
+//! [`ErrorDist::sample`]: crate::polynomial::ErrorDist::sample
+
+#[cfg(feature = "std")]
+use alloc::{rc::Rc, vec::Vec};
+#[cfg(feature = "std")]
+use core::cell::RefCell;
+
+#[cfg(feature = "std")]
+use rand::Rng;
+#[cfg(feature = "std")]
+use subtle::ConditionallyNegatable;
+use subtle::{Choice, ConditionallySelectable, ConstantTimeLess};
+
+/// a + b mod m for a, b < m.
+pub fn add_mod(a: u64, b: u64, m: u64) -> u64 {
+    let s = a as u128 + b as u128;
+    let m = m as u128;
+    u128::conditional_select(&s, &s.wrapping_sub(m), !s.ct_lt(&m)) as u64
+}
+
+/// a - b mod m for a, b < m.
+pub fn sub_mod(a: u64, b: u64, m: u64) -> u64 {
+    let d = a.wrapping_sub(b);
+    u64::conditional_select(&d, &d.wrapping_add(m), a.ct_lt(&b))
+}
+
+/// x mod m for x < 2m.
+pub fn reduce_once(x: u64, m: u64) -> u64 {
+    u64::conditional_select(&x, &x.wrapping_sub(m), !x.ct_lt(&m))
+}
+
+/// x mod m for any x and m > 0, by Barrett reduction with mu = floor((2^128 - 1) / m). mu only depends on
+/// the public modulus, and the quotient estimate is at most one short, which a select fixes.
+pub fn reduce(x: u128, m: u64) -> u64 {
+    let m = m as u128;
+    let q = mul_hi(x, u128::MAX / m);
+    let r = x - q * m;
+    u128::conditional_select(&r, &r.wrapping_sub(m), !r.ct_lt(&m)) as u64
+}
+
+/// x mod m for m > 0, negative values wrap around.
+pub fn reduce_i64(x: i64, m: u64) -> u64 {
+    let negative = Choice::from((x as u64 >> 63) as u8);
+    let abs = u64::conditional_select(&(x as u64), &(x as u64).wrapping_neg(), negative);
+    let r = reduce(abs as u128, m);
+    u64::conditional_select(&r, &sub_mod(0, r, m), negative)
+}
+
+/// High 128 bits of the 256 bit product a b, from 64 bit limbs.
+fn mul_hi(a: u128, b: u128) -> u128 {
+    let (a1, a0) = (a >> 64, a as u64 as u128);
+    let (b1, b0) = (b >> 64, b as u64 as u128);
+    let (lo, mid_a, mid_b) = (a0 * b0, a0 * b1, a1 * b0);
+    let carry = (lo >> 64) + (mid_a as u64 as u128) + (mid_b as u64 as u128);
+    a1 * b1 + (mid_a >> 64) + (mid_b >> 64) + (carry >> 64)
+}
+
+/// n / d by restoring division over all 128 bits, for 0 < d < 2^127.
+pub fn div(n: u128, d: u128) -> u128 {
+    let (mut q, mut r) = (0u128, 0u128);
+    for i in (0..128).rev() {
+        r = (r << 1) | (n >> i & 1);
+        let fits = !r.ct_lt(&d);
+        r = u128::conditional_select(&r, &r.wrapping_sub(d), fits);
+        q |= (fits.unwrap_u8() as u128) << i;
+    }
+    q
+}
+
+/// Discrete Gaussian with parameter sigma on [-6 sigma, 6 sigma], the distribution of the rejection
+/// sampler. |x| is the number of cumulative table entries a 63 bit uniform value reaches, every entry
+/// is compared, and the sign is a separate bit.
+#[cfg(feature = "std")]
+pub fn gaussian(rng: &mut impl Rng, sigma: f64) -> i64 {
+    let u = rng.random::<u64>() >> 1;
+    let mut x = 0i64;
+    for &c in cached_cdt(sigma).iter() {
+        x += (!u.ct_lt(&c)).unwrap_u8() as i64;
+    }
+    x.conditional_negate(Choice::from(rng.random::<bool>() as u8));
+    x
+}
+
+#[cfg(feature = "std")]
+std::thread_local! {
+    /// Tables by the bits of sigma, errors are drawn a polynomial at a time with one or two widths.
+    static CDT: RefCell<Vec<(u64, Rc<[u64]>)>> = const { RefCell::new(Vec::new()) };
+}
+
+/// [`cdt`] built once per sigma and thread.
+#[cfg(feature = "std")]
+fn cached_cdt(sigma: f64) -> Rc<[u64]> {
+    CDT.with(|cache| {
+        let mut cache = cache.borrow_mut();
+        if let Some((_, table)) = cache.iter().find(|(bits, _)| *bits == sigma.to_bits()) {
+            return table.clone();
+        }
+        let table: Rc<[u64]> = cdt(sigma).into();
+        cache.push((sigma.to_bits(), table.clone()));
+        table
+    })
+}
+
+/// Pr[|x| <= k] scaled to 2^63, for k below the 6 sigma cut (the last entry would be 2^63).
+#[cfg(feature = "std")]
+fn cdt(sigma: f64) -> Vec<u64> {
+    assert!(sigma > 0.0, "sigma {sigma} must be positive");
+    let bound = (6.0 * sigma).ceil() as i64;
+    // |x| = k > 0 covers both signs
+    let weights = (0..=bound)
+        .map(|k| {
+            let w = (-((k * k) as f64) / (2.0 * sigma * sigma)).exp();
+            if k == 0 { w } else { 2.0 * w }
+        })
+        .collect::<Vec<_>>();
+    let total: f64 = weights.iter().sum();
+    let mut acc = 0.0;
+    weights[..bound as usize]
+        .iter()
+        .map(|w| {
+            acc += w;
+            (acc / total * (1u64 << 63) as f64) as u64
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_matches_branchy_arithmetic() {
+        let m = (1u64 << 61) - 1;
+        for (a, b) in [
+            (0, 0),
+            (0, m - 1),
+            (m - 1, m - 1),
+            (5, 7),
+            (m - 3, 2),
+            (1 << 60, 1 << 60),
+        ] {
+            assert_eq!(
+                add_mod(a, b, m),
+                ((a as u128 + b as u128) % m as u128) as u64
+            );
+            assert_eq!(
+                sub_mod(a, b, m),
+                ((a as u128 + m as u128 - b as u128) % m as u128) as u64
+            );
+        }
+        assert_eq!(add_mod(u64::MAX - 1, u64::MAX - 1, u64::MAX), u64::MAX - 2);
+        assert_eq!(reduce_once(17, 17), 0);
+        assert_eq!(reduce_once(16, 17), 16);
+        for (n, d) in [
+            (0, 1),
+            (7, 3),
+            (u128::MAX, 1 << 59),
+            (123 << 70, 12_289),
+            (5, 9),
+        ] {
+            assert_eq!(div(n, d), n / d);
+        }
+        for m in [1, 2, 17, 12_289, (1 << 61) - 1, 1 << 63, u64::MAX] {
+            for x in [
+                0,
+                1,
+                m as u128 - 1,
+                m as u128,
+                u64::MAX as u128,
+                u128::MAX,
+                3 << 100,
+            ] {
+                assert_eq!(reduce(x, m), (x % m as u128) as u64, "{x} mod {m}");
+            }
+            for x in [0, -1, 5, -17, i64::MIN, i64::MAX] {
+                assert_eq!(
+                    reduce_i64(x, m),
+                    (x as i128).rem_euclid(m as i128) as u64,
+                    "{x} mod {m}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_gaussian_moments() {
+        let (sigma, n) = (3.2, 20_000);
+        let mut rng = rand::rng();
+        let xs = (0..n)
+            .map(|_| gaussian(&mut rng, sigma))
+            .collect::<Vec<_>>();
+        assert!(xs.iter().all(|x| x.abs() <= 20));
+        let mean = xs.iter().sum::<i64>() as f64 / n as f64;
+        let var = xs.iter().map(|&x| (x * x) as f64).sum::<f64>() / n as f64;
+        assert!(mean.abs() < 0.2, "mean {mean}");
+        assert!((var - sigma * sigma).abs() < 1.0, "variance {var}");
+        assert!(Rc::ptr_eq(&cached_cdt(sigma), &cached_cdt(sigma)));
+    }
+}
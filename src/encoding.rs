@@ -0,0 +1,301 @@
+//! Integer encoding into plaintext polynomials, and the [`Encoder`] trait shared with
+//! `batch::BatchEncoder`.
+//!
+//! An integer of `bits` bits (two's complement when signed) is written as base-t digits, least significant
+//! digit in the constant coefficient. Decoding evaluates the digits at t and reads the result back mod 2^bits,
+//! so digit wise sums decode to the sum mod 2^bits as long as no digit wrapped mod t.
+//!
+//! With t = 2^bits the value is a single coefficient and wraps are exactly Z_{2^bits} arithmetic, e.g.
+//! u8 / u16 counters under BFV with t = 256 / 65536 (the ciphertext side handles any t, see `bfv_pke`).
+//!
+//! [`IntegerEncoder::Binary`] instead writes the signed binary digits of the value and decodes by
+//! evaluating at 2 over the integers, so products decode as well as sums.
+
+use std::fmt;
+
+use crate::polynomial::{Element, Polynomial, RingKind};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IntEncoding {
+    /// Bit width, 1..=64.
+    pub bits: u32,
+    /// Two's complement, values in [-2^(bits-1), 2^(bits-1)).
+    pub signed: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum IntEncodeError {
+    OutOfRange {
+        value: i128,
+        encoding: IntEncoding,
+    },
+    /// t^n < 2^bits, the ring has too few coefficients for the digits.
+    TooManyDigits {
+        digits: usize,
+        ring_dim: usize,
+    },
+}
+
+impl fmt::Display for IntEncodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            IntEncodeError::OutOfRange { value, encoding } => write!(
+                f,
+                "{value} does not fit in {} {} bits",
+                encoding.bits,
+                if encoding.signed {
+                    "signed"
+                } else {
+                    "unsigned"
+                }
+            ),
+            IntEncodeError::TooManyDigits { digits, ring_dim } => write!(
+                f,
+                "encoding needs {digits} digits but the ring dimension is {ring_dim}"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for IntEncodeError {}
+
+impl IntEncoding {
+    pub fn unsigned(bits: u32) -> Self {
+        assert!((1..=64).contains(&bits), "bit width {bits} not in 1..=64");
+        Self {
+            bits,
+            signed: false,
+        }
+    }
+
+    pub fn signed(bits: u32) -> Self {
+        assert!((1..=64).contains(&bits), "bit width {bits} not in 1..=64");
+        Self { bits, signed: true }
+    }
+
+    pub fn min(&self) -> i128 {
+        if self.signed {
+            -(1i128 << (self.bits - 1))
+        } else {
+            0
+        }
+    }
+
+    pub fn max(&self) -> i128 {
+        if self.signed {
+            (1i128 << (self.bits - 1)) - 1
+        } else {
+            (1i128 << self.bits) - 1
+        }
+    }
+
+    /// Number of base-t digits, the smallest d with t^d >= 2^bits.
+    pub fn digits(&self, t: u64) -> usize {
+        assert!(t >= 2, "plaintext modulus must be at least 2");
+        let mut d = 0;
+        let mut reach = 1u128;
+        while reach < 1u128 << self.bits {
+            reach = reach.saturating_mul(t as u128);
+            d += 1;
+        }
+        d
+    }
+}
+
+impl<const N: usize, const T: u64, R: RingKind> Polynomial<N, T, R> {
+    pub fn from_int(value: i128, encoding: IntEncoding) -> Result<Self, IntEncodeError> {
+        if value < encoding.min() || value > encoding.max() {
+            return Err(IntEncodeError::OutOfRange { value, encoding });
+        }
+        let digits = encoding.digits(T);
+        if digits > N {
+            return Err(IntEncodeError::TooManyDigits {
+                digits,
+                ring_dim: N,
+            });
+        }
+
+        // two's complement inside `bits`
+        let mut rest = (value as u128) & ((1u128 << encoding.bits) - 1);
+        let mut inner = [Element::new(0); N];
+        for c in inner.iter_mut().take(digits) {
+            *c = Element::new((rest % T as u128) as i64);
+            rest /= T as u128;
+        }
+        Ok(Self::new(inner))
+    }
+
+    pub fn to_int(&self, encoding: IntEncoding) -> i128 {
+        let mask = (1u128 << encoding.bits) - 1;
+        // evaluate at t mod 2^bits, Horner from the top digit
+        let mut acc = 0u128;
+        for c in self.inner.iter().rev() {
+            acc = acc.wrapping_mul(T as u128).wrapping_add(c.value() as u128) & mask;
+        }
+        if encoding.signed && acc > encoding.max() as u128 {
+            acc as i128 - (1i128 << encoding.bits)
+        } else {
+            acc as i128
+        }
+    }
+}
+
+/// Moves values in and out of plaintexts `Polynomial<N, T>`.
+pub trait Encoder<const N: usize, const T: u64> {
+    /// What `encode` takes, `decode` returns the owned form.
+    type Value: ?Sized + ToOwned;
+    type Error;
+
+    fn encode(&self, value: &Self::Value) -> Result<Polynomial<N, T>, Self::Error>;
+
+    fn decode(&self, pt: &Polynomial<N, T>) -> <Self::Value as ToOwned>::Owned;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IntegerEncoder {
+    /// `Polynomial::from_int`, read back mod 2^bits.
+    BaseT(IntEncoding),
+    /// Digits of |v| in {0, 1}, negated for v < 0 (t >= 3), read back by evaluating the centered
+    /// coefficients at 2. Sums and products decode while no coefficient reaches t/2 in absolute value and
+    /// the degree stays below n.
+    Binary,
+}
+
+impl<const N: usize, const T: u64> Encoder<N, T> for IntegerEncoder {
+    type Value = i128;
+    type Error = IntEncodeError;
+
+    fn encode(&self, value: &i128) -> Result<Polynomial<N, T>, IntEncodeError> {
+        match self {
+            IntegerEncoder::BaseT(encoding) => Polynomial::from_int(*value, *encoding),
+            IntegerEncoder::Binary => {
+                let abs = value.unsigned_abs();
+                let digits = (u128::BITS - abs.leading_zeros()) as usize;
+                if digits > N {
+                    return Err(IntEncodeError::TooManyDigits {
+                        digits,
+                        ring_dim: N,
+                    });
+                }
+                let sign = value.signum() as i64;
+                Ok(Polynomial::new(core::array::from_fn(|i| {
+                    let bit = i < digits && (abs >> i) & 1 == 1;
+                    Element::new(if bit { sign } else { 0 })
+                })))
+            }
+        }
+    }
+
+    fn decode(&self, pt: &Polynomial<N, T>) -> i128 {
+        match self {
+            IntegerEncoder::BaseT(encoding) => pt.to_int(*encoding),
+            IntegerEncoder::Binary => pt.inner.iter().rev().fold(0i128, |acc, c| {
+                let c = c.value();
+                let centered = if c > T / 2 {
+                    c as i128 - T as i128
+                } else {
+                    c as i128
+                };
+                acc.wrapping_mul(2).wrapping_add(centered)
+            }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_roundtrip() {
+        let u16_bits = IntEncoding::unsigned(16);
+        assert_eq!(u16_bits.digits(2), 16);
+        assert_eq!(u16_bits.digits(257), 2);
+        for v in [0, 1, 12_345, 65_535] {
+            let p = Polynomial::<16, 2>::from_int(v, u16_bits).unwrap();
+            assert_eq!(p.to_int(u16_bits), v);
+        }
+
+        let i8_bits = IntEncoding::signed(8);
+        for v in [-128, -1, 0, 5, 127] {
+            let p = Polynomial::<8, 3>::from_int(v, i8_bits).unwrap();
+            assert_eq!(p.to_int(i8_bits), v);
+        }
+
+        let i64_bits = IntEncoding::signed(64);
+        let v = i64::MIN as i128;
+        let p = Polynomial::<32, 65_537>::from_int(v, i64_bits).unwrap();
+        assert_eq!(p.to_int(i64_bits), v);
+    }
+
+    #[test]
+    fn test_digitwise_add_decodes_to_sum() {
+        // fine as long as no digit wraps mod t
+        let enc = IntEncoding::signed(16);
+        let a = Polynomial::<4, 257>::from_int(258, enc).unwrap();
+        let b = Polynomial::<4, 257>::from_int(2, enc).unwrap();
+        assert_eq!((a + b).to_int(enc), 260);
+
+        // the sum is read mod 2^bits, so -1 + 1 comes back as 0
+        let m1 = Polynomial::<4, 257>::from_int(-1, enc).unwrap();
+        let p1 = Polynomial::<4, 257>::from_int(1, enc).unwrap();
+        assert_eq!((m1 + p1).to_int(enc), 0);
+    }
+
+    #[test]
+    fn test_power_of_two_t_wraps() {
+        let enc = IntEncoding::unsigned(8);
+        assert_eq!(enc.digits(256), 1);
+        let a = Polynomial::<4, 256>::from_int(200, enc).unwrap();
+        let b = Polynomial::<4, 256>::from_int(100, enc).unwrap();
+        assert_eq!((a + b).to_int(enc), (200 + 100) % 256);
+        assert_eq!((a - b - b - b).to_int(enc), (200 - 300i128).rem_euclid(256));
+    }
+
+    #[test]
+    fn test_integer_encoder() {
+        let base_t = IntegerEncoder::BaseT(IntEncoding::signed(16));
+        let pt: Polynomial<4, 257> = base_t.encode(&-300).unwrap();
+        assert_eq!(
+            pt,
+            Polynomial::from_int(-300, IntEncoding::signed(16)).unwrap()
+        );
+        assert_eq!(Encoder::<4, 257>::decode(&base_t, &pt), -300);
+
+        let binary = IntegerEncoder::Binary;
+        let encode = |v: i128| -> Polynomial<16, 1024> { binary.encode(&v).unwrap() };
+        for v in [0, 1, -1, 13, -300, 1 << 15] {
+            assert_eq!(binary.decode(&encode(v)), v);
+        }
+        // 13 = x^3 + x^2 + 1, -6 = -(x^2 + x)
+        assert_eq!(encode(13).inner.map(|c| c.value())[..4], [1, 0, 1, 1]);
+        assert_eq!(binary.decode(&(encode(13) * encode(-6))), -78);
+        assert_eq!(binary.decode(&(encode(13) + encode(-6) + encode(-6))), 1);
+        assert_eq!(
+            Encoder::<16, 1024>::encode(&binary, &(1 << 16)),
+            Err(IntEncodeError::TooManyDigits {
+                digits: 17,
+                ring_dim: 16
+            })
+        );
+    }
+
+    #[test]
+    fn test_errors() {
+        assert_eq!(
+            Polynomial::<8, 2>::from_int(256, IntEncoding::unsigned(8)),
+            Err(IntEncodeError::OutOfRange {
+                value: 256,
+                encoding: IntEncoding::unsigned(8)
+            })
+        );
+        assert!(Polynomial::<8, 2>::from_int(-1, IntEncoding::unsigned(8)).is_err());
+        assert_eq!(
+            Polynomial::<4, 2>::from_int(1, IntEncoding::unsigned(8)),
+            Err(IntEncodeError::TooManyDigits {
+                digits: 8,
+                ring_dim: 4
+            })
+        );
+    }
+}
@@ -0,0 +1,149 @@
+//! The crate-wide error. Modules keep their own error enums, which all convert into [`Error`], so code
+//! mixing several of them can use `?` with one return type. Panics are left for broken internal invariants
+//! and for const generic parameters that no input can fix.
+
+use thiserror::Error;
+
+#[cfg(feature = "bfv")]
+use crate::batch::TooManyValues;
+#[cfg(feature = "bfv")]
+use crate::bfv_params::GenerateError;
+#[cfg(feature = "bfv")]
+use crate::bfv_pke::{EvalError, KeyGenError, ParamError};
+#[cfg(feature = "bfv")]
+use crate::bfv_rns::BfvRnsError;
+#[cfg(feature = "cbor")]
+use crate::cbor::CborError;
+#[cfg(feature = "bfv")]
+use crate::circuit::CircuitError;
+#[cfg(feature = "bfv")]
+use crate::encoding::IntEncodeError;
+#[cfg(feature = "pasta")]
+use crate::filip::FilipError;
+#[cfg(feature = "bfv")]
+use crate::fold::FoldError;
+use crate::frodo::FrodoError;
+#[cfg(all(feature = "pasta", feature = "bfv"))]
+use crate::hybrid::HybridError;
+#[cfg(feature = "pasta")]
+use crate::masta::MastaError;
+#[cfg(feature = "parallel")]
+use crate::parallel::ThreadPoolError;
+#[cfg(all(feature = "pasta", feature = "bfv"))]
+use crate::pasta_bfv::TranscipherError;
+#[cfg(feature = "pasta")]
+use crate::pasta_kat::KatError;
+#[cfg(feature = "pasta")]
+use crate::pasta_plain::PastaError;
+use crate::polynomial::{DecodeError, SigmaError, TernaryError};
+#[cfg(feature = "pasta")]
+use crate::rasta::RastaError;
+use crate::rns::RnsError;
+#[cfg(feature = "bfv")]
+use crate::seal::SealError;
+#[cfg(feature = "bfv")]
+use crate::security::SecurityError;
+#[cfg(feature = "tfhe")]
+use crate::tfhe::BootstrapError;
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[cfg(feature = "bfv")]
+    #[error(transparent)]
+    Param(#[from] ParamError),
+    #[cfg(feature = "bfv")]
+    #[error(transparent)]
+    Generate(#[from] GenerateError),
+    #[cfg(feature = "bfv")]
+    #[error(transparent)]
+    Eval(#[from] EvalError),
+    #[cfg(feature = "bfv")]
+    #[error(transparent)]
+    KeyGen(#[from] KeyGenError),
+    #[cfg(feature = "bfv")]
+    #[error(transparent)]
+    BfvRns(#[from] BfvRnsError),
+    #[cfg(feature = "cbor")]
+    #[error(transparent)]
+    Cbor(#[from] CborError),
+    #[cfg(feature = "bfv")]
+    #[error(transparent)]
+    Circuit(#[from] CircuitError),
+    #[cfg(feature = "pasta")]
+    #[error(transparent)]
+    Filip(#[from] FilipError),
+    #[cfg(feature = "bfv")]
+    #[error(transparent)]
+    Fold(#[from] FoldError),
+    #[error(transparent)]
+    Frodo(#[from] FrodoError),
+    #[cfg(feature = "bfv")]
+    #[error(transparent)]
+    IntEncode(#[from] IntEncodeError),
+    #[cfg(feature = "bfv")]
+    #[error(transparent)]
+    TooManyValues(#[from] TooManyValues),
+    #[cfg(all(feature = "pasta", feature = "bfv"))]
+    #[error(transparent)]
+    Hybrid(#[from] HybridError),
+    #[cfg(feature = "parallel")]
+    #[error(transparent)]
+    ThreadPool(#[from] ThreadPoolError),
+    #[cfg(all(feature = "pasta", feature = "bfv"))]
+    #[error(transparent)]
+    Transcipher(#[from] TranscipherError),
+    #[cfg(feature = "pasta")]
+    #[error(transparent)]
+    Kat(#[from] KatError),
+    #[cfg(feature = "pasta")]
+    #[error(transparent)]
+    Masta(#[from] MastaError),
+    #[cfg(feature = "pasta")]
+    #[error(transparent)]
+    Pasta(#[from] PastaError),
+    #[cfg(feature = "pasta")]
+    #[error(transparent)]
+    Rasta(#[from] RastaError),
+    #[error(transparent)]
+    Decode(#[from] DecodeError),
+    #[error(transparent)]
+    Sigma(#[from] SigmaError),
+    #[error(transparent)]
+    Ternary(#[from] TernaryError),
+    #[error(transparent)]
+    Rns(#[from] RnsError),
+    #[cfg(feature = "bfv")]
+    #[error(transparent)]
+    Seal(#[from] SealError),
+    #[cfg(feature = "bfv")]
+    #[error(transparent)]
+    Security(#[from] SecurityError),
+    #[cfg(feature = "tfhe")]
+    #[error(transparent)]
+    Bootstrap(#[from] BootstrapError),
+}
+
+#[cfg(all(test, feature = "pasta"))]
+mod tests {
+    use super::*;
+    use crate::pasta_plain::PastaKey;
+
+    #[test]
+    fn test_conversions() {
+        fn key(words: Vec<u64>) -> Result<PastaKey> {
+            Ok(PastaKey::try_new(words, 17)?)
+        }
+        assert!(key(vec![1, 2]).is_ok());
+        let e = key(vec![1, 2, 3]).unwrap_err();
+        assert!(matches!(
+            e,
+            Error::Pasta(PastaError::KeyLength {
+                expected: 2,
+                got: 3
+            })
+        ));
+        assert_eq!(e.to_string(), "key must be 2T = 2 words, got 3");
+    }
+}
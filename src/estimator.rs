@@ -0,0 +1,272 @@
+//! Lattice attack cost estimates for LWE and RLWE parameters, for the (n, q, sigma, secret) combinations the
+//! tables in `security` don't cover: other error widths, binary or sparse secrets, moduli
+//! between the tabulated degrees.
+//!
+//! Only the primal uSVP attack is modelled (Alkim, Ducas, Pöppelmann and Schwabe, 2016): embed m samples
+//! into a lattice of dimension d = m + n + 1 and find the smallest BKZ block size beta whose last
+//! Gram-Schmidt vector is longer than the projected error, scanning beta upward from 40. The cost of that
+//! BKZ tour is then priced by a [`CostModel`]. Rescaling small secrets by sigma / sigma_s is included,
+//! hybrid and combinatorial attacks on sparse secrets are not, so estimates for small Hamming weights are
+//! upper bounds. Under [`CostModel::Bdgl16`] the moduli of the Homomorphic Encryption Standard tables come
+//! out at or slightly above their level.
+
+use std::f64::consts::{E, PI};
+
+use crate::polynomial::{ErrorDist, Ternary};
+
+/// Smallest block size scanned. The root Hermite factor formula is meaningless below it.
+const MIN_BETA: usize = 40;
+
+/// Distribution of the secret, by its standard deviation.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Secret {
+    /// Uniform mod q, estimated as the normal form secret (distributed like the error).
+    Uniform,
+    /// Small secret with the given per coefficient standard deviation.
+    Small { std_dev: f64 },
+}
+
+impl Secret {
+    /// Uniform over {-1, 0, 1}.
+    pub const TERNARY: Self = Secret::Small {
+        std_dev: 0.816_496_580_927_726,
+    };
+    /// Uniform over {0, 1}.
+    pub const BINARY: Self = Secret::Small { std_dev: 0.5 };
+
+    /// The secret `dist` samples in dimension n.
+    pub fn from_ternary(dist: &Ternary, n: usize) -> Self {
+        let variance = match *dist {
+            Ternary::Probability(p) => p,
+            Ternary::HammingWeight(h) => h as f64 / n as f64,
+        };
+        Secret::Small {
+            std_dev: variance.sqrt(),
+        }
+    }
+
+    fn std_dev(&self, sigma: f64) -> f64 {
+        match *self {
+            Secret::Uniform => sigma,
+            Secret::Small { std_dev } => std_dev,
+        }
+    }
+}
+
+/// Price of one BKZ tour with block size beta in dimension d, in log2 operations.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CostModel {
+    /// 0.292 beta, one classical sieve call (core-SVP).
+    ClassicalCoreSvp,
+    /// 0.265 beta, one quantum sieve call (core-SVP).
+    QuantumCoreSvp,
+    /// 0.292 beta + 16.4 + log2(8d), the sieve of Becker, Ducas, Gama and Laarhoven with d BKZ calls,
+    /// the model of the Homomorphic Encryption Standard.
+    Bdgl16,
+}
+
+impl CostModel {
+    pub fn cost(&self, beta: usize, d: usize) -> f64 {
+        let beta = beta as f64;
+        match self {
+            CostModel::ClassicalCoreSvp => 0.292 * beta,
+            CostModel::QuantumCoreSvp => 0.265 * beta,
+            CostModel::Bdgl16 => 0.292 * beta + 16.4 + (8.0 * d as f64).log2(),
+        }
+    }
+}
+
+/// An LWE instance: dimension n, modulus 2^log_q, errors of standard deviation sigma and at most `samples`
+/// samples available to the attacker.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LweParams {
+    pub n: usize,
+    /// Fractional so moduli chains above 64 bits fit.
+    pub log_q: f64,
+    pub sigma: f64,
+    pub secret: Secret,
+    pub samples: usize,
+}
+
+impl LweParams {
+    /// n samples, as many as one RLWE sample gives.
+    pub fn new(n: usize, log_q: f64, sigma: f64, secret: Secret) -> Self {
+        assert!(sigma > 0.0, "sigma {sigma} must be positive");
+        LweParams {
+            n,
+            log_q,
+            sigma,
+            secret,
+            samples: n,
+        }
+    }
+
+    /// RLWE in degree n with the crate's error and secret distributions.
+    pub fn rlwe(n: usize, log_q: f64, error: ErrorDist, secret: &Ternary) -> Self {
+        Self::new(n, log_q, error.std_dev(), Secret::from_ternary(secret, n))
+    }
+
+    pub fn with_samples(self, samples: usize) -> Self {
+        LweParams { samples, ..self }
+    }
+}
+
+/// Cheapest primal uSVP attack found.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Estimate {
+    pub beta: usize,
+    /// Lattice dimension d = m + n + 1.
+    pub dimension: usize,
+    /// Samples m used.
+    pub samples: usize,
+    /// log2 of the attack cost.
+    pub bits: f64,
+}
+
+/// log delta_beta, the root Hermite factor BKZ-beta reaches.
+fn log_delta(beta: usize) -> f64 {
+    let b = beta as f64;
+    ((PI * b).powf(1.0 / b) * b / (2.0 * PI * E)).ln() / (2.0 * (b - 1.0))
+}
+
+/// Primal uSVP attack on `params`. `None` if no block size up to the full dimension succeeds, i.e. the
+/// instance is not breakable by this attack.
+pub fn primal_usvp(params: &LweParams, model: CostModel) -> Option<Estimate> {
+    let n = params.n as f64;
+    let log_q = params.log_q * 2f64.ln();
+    // rescaling the secret by xi balances it against the error
+    let log_xi = (params.sigma / params.secret.std_dev(params.sigma)).ln();
+    let max_d = params.n + params.samples + 1;
+    (MIN_BETA..=max_d).find_map(|beta| {
+        let log_delta = log_delta(beta);
+        // dimension maximizing the last Gram-Schmidt length, m clamped to the available samples
+        let optimal = ((log_q * (n + 1.0) - n * log_xi) / log_delta)
+            .max(1.0)
+            .sqrt();
+        let m = (optimal - n - 1.0)
+            .round()
+            .clamp(0.0, params.samples as f64) as usize;
+        let d = m + params.n + 1;
+        if beta > d {
+            return None;
+        }
+        let log_volume = m as f64 * log_q + n * log_xi;
+        let gso = (2.0 * beta as f64 - d as f64 - 1.0) * log_delta + log_volume / d as f64;
+        let projected = params.sigma.ln() + 0.5 * (beta as f64).ln();
+        (projected <= gso).then(|| Estimate {
+            beta,
+            dimension: d,
+            samples: m,
+            bits: model.cost(beta, d),
+        })
+    })
+}
+
+/// Security of `params` in bits under `model`, infinite if the attack never succeeds.
+pub fn security_bits(params: &LweParams, model: CostModel) -> f64 {
+    primal_usvp(params, model).map_or(f64::INFINITY, |e| e.bits)
+}
+
+/// Largest integer log q for which RLWE in degree n with `error` and `secret` keeps `bits` bits of
+/// security, `None` if not even log q = 1 does.
+pub fn max_log_q(
+    n: usize,
+    error: ErrorDist,
+    secret: &Ternary,
+    bits: f64,
+    model: CostModel,
+) -> Option<u32> {
+    let secure =
+        |log_q: u32| security_bits(&LweParams::rlwe(n, log_q as f64, error, secret), model) >= bits;
+    if !secure(1) {
+        return None;
+    }
+    // security falls as q grows, so bisect on [lo secure, hi insecure)
+    let (mut lo, mut hi) = (1u32, 2u32);
+    while secure(hi) {
+        lo = hi;
+        hi *= 2;
+    }
+    while hi - lo > 1 {
+        let mid = lo + (hi - lo) / 2;
+        if secure(mid) {
+            lo = mid;
+        } else {
+            hi = mid;
+        }
+    }
+    Some(lo)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[cfg(feature = "bfv")]
+    fn test_matches_standard_tables() {
+        use crate::security::{self, SecretDist, SecurityLevel};
+
+        for n in [1024, 2048, 4096, 8192] {
+            for level in SecurityLevel::ALL {
+                let table = security::max_log_q(n, level, SecretDist::Ternary).unwrap();
+                let params =
+                    LweParams::rlwe(n, table as f64, ErrorDist::STANDARD, &Ternary::UNIFORM);
+                let bits = security_bits(&params, CostModel::Bdgl16);
+                let target = level.bits() as f64;
+                // the tables round log q down, so the estimate may overshoot the level
+                assert!(
+                    bits > target - 3.0 && bits < target + 12.0,
+                    "n = {n}, log q = {table}: {bits} bits, table says {target}"
+                );
+            }
+        }
+        let uniform =
+            security::max_log_q(2048, SecurityLevel::Bits128, SecretDist::Uniform).unwrap();
+        let params = LweParams::new(2048, uniform as f64, 3.2, Secret::Uniform);
+        assert!((security_bits(&params, CostModel::Bdgl16) - 128.0).abs() < 8.0);
+    }
+
+    #[test]
+    fn test_monotone() {
+        let at = |n: usize, log_q: f64, secret: Secret| {
+            security_bits(
+                &LweParams::new(n, log_q, 3.2, secret),
+                CostModel::ClassicalCoreSvp,
+            )
+        };
+        assert!(at(2048, 40.0, Secret::TERNARY) > at(2048, 54.0, Secret::TERNARY));
+        assert!(at(4096, 54.0, Secret::TERNARY) > at(2048, 54.0, Secret::TERNARY));
+        assert!(at(2048, 54.0, Secret::TERNARY) > at(2048, 54.0, Secret::BINARY));
+        let quantum = LweParams::new(2048, 54.0, 3.2, Secret::TERNARY);
+        assert!(
+            security_bits(&quantum, CostModel::QuantumCoreSvp)
+                < security_bits(&quantum, CostModel::ClassicalCoreSvp)
+        );
+        // toy parameters fall at the smallest block size
+        let toy = primal_usvp(
+            &LweParams::new(64, 30.0, 3.2, Secret::TERNARY),
+            CostModel::Bdgl16,
+        );
+        assert_eq!(toy.map(|e| e.beta), Some(MIN_BETA));
+
+        let max = max_log_q(
+            4096,
+            ErrorDist::STANDARD,
+            &Ternary::UNIFORM,
+            128.0,
+            CostModel::Bdgl16,
+        )
+        .unwrap();
+        assert!((100..=115).contains(&max), "{max}");
+        let sparse = max_log_q(
+            4096,
+            ErrorDist::STANDARD,
+            &Ternary::HammingWeight(64),
+            128.0,
+            CostModel::Bdgl16,
+        )
+        .unwrap();
+        assert!(sparse < max);
+    }
+}
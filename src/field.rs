@@ -0,0 +1,296 @@
+//! Arithmetic mod a single modulus, shared by the schemes.
+//!
+//! BFV's `Element<A>` fixes the modulus in the type ([`ConstModulus`]), Pasta picks its prime at runtime
+//! ([`Modulus`]); both reduce through [`Ring`], and so do the XOF samplers and the NTT. Values are the
+//! canonical representatives in [0, m).
+
+use sha3::digest::XofReader;
+
+use crate::polynomial::sample_mod;
+
+pub trait Ring: Copy {
+    fn modulus(&self) -> u64;
+
+    #[cfg(feature = "constant-time")]
+    fn reduce(&self, x: u128) -> u64 {
+        crate::ct::reduce(x, self.modulus())
+    }
+
+    #[cfg(not(feature = "constant-time"))]
+    fn reduce(&self, x: u128) -> u64 {
+        (x % self.modulus() as u128) as u64
+    }
+
+    /// x mod m, negative values wrap around.
+    #[cfg(feature = "constant-time")]
+    fn reduce_i64(&self, x: i64) -> u64 {
+        crate::ct::reduce_i64(x, self.modulus())
+    }
+
+    /// x mod m, negative values wrap around.
+    #[cfg(not(feature = "constant-time"))]
+    fn reduce_i64(&self, x: i64) -> u64 {
+        let m = self.modulus();
+        if m <= i64::MAX as u64 {
+            x.rem_euclid(m as i64) as u64
+        } else {
+            (x as i128).rem_euclid(m as i128) as u64
+        }
+    }
+
+    #[cfg(feature = "constant-time")]
+    fn add(&self, a: u64, b: u64) -> u64 {
+        crate::ct::add_mod(a, b, self.modulus())
+    }
+
+    #[cfg(not(feature = "constant-time"))]
+    fn add(&self, a: u64, b: u64) -> u64 {
+        let m = self.modulus() as u128;
+        let s = a as u128 + b as u128;
+        (if s >= m { s - m } else { s }) as u64
+    }
+
+    #[cfg(feature = "constant-time")]
+    fn sub(&self, a: u64, b: u64) -> u64 {
+        crate::ct::sub_mod(a, b, self.modulus())
+    }
+
+    #[cfg(not(feature = "constant-time"))]
+    fn sub(&self, a: u64, b: u64) -> u64 {
+        if a >= b {
+            a - b
+        } else {
+            (a as u128 + self.modulus() as u128 - b as u128) as u64
+        }
+    }
+
+    #[cfg(feature = "constant-time")]
+    fn neg(&self, a: u64) -> u64 {
+        crate::ct::sub_mod(0, a, self.modulus())
+    }
+
+    #[cfg(not(feature = "constant-time"))]
+    fn neg(&self, a: u64) -> u64 {
+        if a == 0 { 0 } else { self.modulus() - a }
+    }
+
+    #[cfg(feature = "constant-time")]
+    fn mul(&self, a: u64, b: u64) -> u64 {
+        self.reduce(a as u128 * b as u128)
+    }
+
+    #[cfg(not(feature = "constant-time"))]
+    fn mul(&self, a: u64, b: u64) -> u64 {
+        let m = self.modulus();
+        // the product fits in a u64 for 32 bit moduli, which avoids the u128 division
+        if m <= u32::MAX as u64 {
+            (a * b) % m
+        } else {
+            self.reduce(a as u128 * b as u128)
+        }
+    }
+
+    fn pow(&self, mut base: u64, mut exp: u64) -> u64 {
+        let mut acc = 1 % self.modulus();
+        base = self.reduce(base as u128);
+        while exp > 0 {
+            if exp & 1 == 1 {
+                acc = self.mul(acc, base);
+            }
+            base = self.mul(base, base);
+            exp >>= 1;
+        }
+        acc
+    }
+
+    /// Uniform element squeezed from `xof`, see [`sample_mod`].
+    fn sample(&self, xof: &mut impl XofReader, allow_zero: bool) -> u64 {
+        sample_mod(xof, self.modulus(), allow_zero)
+    }
+}
+
+pub trait PrimeField: Ring {
+    /// a^-1 by Fermat, `None` for 0.
+    fn inv(&self, a: u64) -> Option<u64> {
+        let a = a % self.modulus();
+        (a != 0).then(|| self.pow(a, self.modulus() - 2))
+    }
+}
+
+/// Modulus fixed at compile time, the ring of `Element<A>`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ConstModulus<const A: u64>;
+
+impl<const A: u64> Ring for ConstModulus<A> {
+    #[inline(always)]
+    fn modulus(&self) -> u64 {
+        A
+    }
+}
+
+/// Modulus chosen at runtime.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Modulus(u64);
+
+impl Modulus {
+    pub fn new(m: u64) -> Self {
+        assert!(m > 0, "modulus must be positive");
+        Self(m)
+    }
+}
+
+impl Ring for Modulus {
+    #[inline(always)]
+    fn modulus(&self) -> u64 {
+        self.0
+    }
+}
+
+/// Runtime modulus checked to be prime.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PrimeModulus(u64);
+
+impl PrimeModulus {
+    /// `None` if `p` is not prime.
+    pub fn new(p: u64) -> Option<Self> {
+        is_prime(p).then_some(Self(p))
+    }
+}
+
+impl Ring for PrimeModulus {
+    #[inline(always)]
+    fn modulus(&self) -> u64 {
+        self.0
+    }
+}
+
+impl PrimeField for PrimeModulus {}
+
+/// Sum of products mod m with a single reduction at the end instead of one per term. Inputs are reduced,
+/// so a u128 holds u128::MAX / (m-1)^2 products before it has to be reduced early, which only happens
+/// for moduli above 2^32.
+#[derive(Debug, Clone, Copy)]
+pub struct LazyAcc<R: Ring> {
+    ring: R,
+    acc: u128,
+    pending: u128,
+    budget: u128,
+}
+
+impl<R: Ring> LazyAcc<R> {
+    pub fn new(ring: R) -> Self {
+        let max = ring.modulus().saturating_sub(1).max(1) as u128;
+        Self {
+            ring,
+            acc: 0,
+            pending: 0,
+            budget: u128::MAX / (max * max),
+        }
+    }
+
+    #[inline(always)]
+    pub fn add_mul(&mut self, a: u64, b: u64) {
+        if self.pending >= self.budget {
+            // the residue is below m <= (m-1)^2, it counts as one term
+            self.acc = self.ring.reduce(self.acc) as u128;
+            self.pending = 1;
+        }
+        self.acc += a as u128 * b as u128;
+        self.pending += 1;
+    }
+
+    #[inline(always)]
+    pub fn add(&mut self, a: u64) {
+        self.add_mul(a, 1);
+    }
+
+    pub fn finish(self) -> u64 {
+        self.ring.reduce(self.acc)
+    }
+}
+
+pub fn is_prime(q: u64) -> bool {
+    if q < 2 {
+        return false;
+    }
+    let ring = Modulus::new(q);
+    // deterministic Miller-Rabin bases for u64
+    let d = (q - 1) >> (q - 1).trailing_zeros();
+    let s = (q - 1).trailing_zeros();
+    [2u64, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37]
+        .iter()
+        .all(|&a| {
+            if a % q == 0 {
+                return true;
+            }
+            let mut x = ring.pow(a, d);
+            if x == 1 || x == q - 1 {
+                return true;
+            }
+            for _ in 1..s {
+                x = ring.mul(x, x);
+                if x == q - 1 {
+                    return true;
+                }
+            }
+            false
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ring_ops() {
+        fn check(ring: impl Ring) {
+            let m = ring.modulus();
+            for (a, b) in [(0, 0), (1, m - 1), (m - 1, m - 1), (m / 2, m / 3 + 1)] {
+                let (wa, wb, wm) = (a as u128, b as u128, m as u128);
+                assert_eq!(ring.add(a, b) as u128, (wa + wb) % wm);
+                assert_eq!(ring.sub(a, b) as u128, (wa + wm - wb) % wm);
+                assert_eq!(ring.mul(a, b) as u128, wa * wb % wm);
+                assert_eq!(ring.add(ring.neg(a), a), 0);
+            }
+            assert_eq!(ring.reduce_i64(-1), m - 1);
+            assert_eq!(ring.pow(m - 1, 2), 1);
+        }
+        check(ConstModulus::<97>);
+        check(ConstModulus::<{ 1 << 32 }>);
+        check(Modulus::new(65_537));
+        check(Modulus::new(u64::MAX - 58));
+    }
+
+    #[test]
+    fn test_lazy_acc() {
+        fn check(ring: impl Ring, len: usize) {
+            let m = ring.modulus();
+            let (mut lazy, mut eager) = (LazyAcc::new(ring), 0);
+            for i in 0..len as u64 {
+                let (a, b) = ((m - 1).saturating_sub(i % 7), (m - 1).saturating_sub(i % 5));
+                lazy.add_mul(a, b);
+                eager = ring.add(eager, ring.mul(a, b));
+            }
+            lazy.add(m - 1);
+            assert_eq!(lazy.finish(), ring.add(eager, m - 1));
+        }
+        check(ConstModulus::<12_289>, 1024);
+        check(ConstModulus::<2>, 9);
+        // (m-1)^2 close to 2^126, reduces every few terms
+        check(Modulus::new((1 << 63) + 1), 100);
+        check(Modulus::new(u64::MAX - 58), 100);
+    }
+
+    #[test]
+    fn test_prime_field() {
+        assert!(PrimeModulus::new(12_289).is_some());
+        assert!(PrimeModulus::new(12_288).is_none());
+        assert!(is_prime((1 << 61) - 1));
+
+        let f = PrimeModulus::new(65_537).unwrap();
+        assert_eq!(f.inv(0), None);
+        for a in [1, 2, 3, 65_536] {
+            assert_eq!(f.mul(a, f.inv(a).unwrap()), 1);
+        }
+    }
+}
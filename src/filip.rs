@@ -0,0 +1,272 @@
+//! FiLIP (https://eprint.iacr.org/2019/483.pdf), a filter permutator: every keystream bit is a fixed boolean
+//! filter evaluated on a fresh selection of key bits, so decrypting homomorphically costs one filter
+//! evaluation per bit and the depth never grows with the message length.
+//!
+//! For output bit i a forward PRNG (here SHAKE128 seeded with the nonce, the paper uses AES) picks n of the
+//! N key bits in a random order (subset selection and wire-cut permutation in one ordered draw) and a
+//! whitening vector w of n bits. The bit is f(selection ^ w), with f a direct sum of monomials (DSM):
+//! the descriptor [m_1, m_2, ...] asks for m_d monomials of degree d over disjoint variables, XORed
+//! together, so n = sum d * m_d.
+
+use sha3::{
+    Shake128, Shake128Reader,
+    digest::{ExtendableOutput, Update, XofReader},
+};
+use zeroize::{Zeroize, ZeroizeOnDrop, Zeroizing};
+
+use std::fmt;
+
+use crate::polynomial::sample_mod;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FilipError {
+    /// The key has to be `key_bits` bits.
+    KeyLength { expected: usize, got: usize },
+    /// The filter reads more bits than the register holds.
+    FilterTooWide { filter_bits: usize, key_bits: usize },
+}
+
+impl fmt::Display for FilipError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FilipError::KeyLength { expected, got } => {
+                write!(f, "key must be {expected} bits, got {got}")
+            }
+            FilipError::FilterTooWide {
+                filter_bits,
+                key_bits,
+            } => write!(
+                f,
+                "filter takes {filter_bits} bits, the register only has {key_bits}"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for FilipError {}
+
+/// Register size and DSM filter of one FiLIP instance.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FilipParams {
+    pub key_bits: usize,
+    /// `descriptor[d - 1]` monomials of degree d.
+    pub descriptor: Vec<usize>,
+}
+
+impl FilipParams {
+    /// FiLIP-1216: N = 16384, filter DSM [128, 64, 0, 80, 0, 0, 0, 80].
+    pub fn filip_1216() -> Self {
+        Self {
+            key_bits: 16_384,
+            descriptor: vec![128, 64, 0, 80, 0, 0, 0, 80],
+        }
+    }
+
+    /// Filter inputs n, the bits drawn per output bit.
+    pub fn filter_bits(&self) -> usize {
+        self.descriptor
+            .iter()
+            .enumerate()
+            .map(|(d, m)| (d + 1) * m)
+            .sum()
+    }
+}
+
+pub struct Filip {
+    key: Vec<bool>,
+    params: FilipParams,
+}
+
+impl Drop for Filip {
+    fn drop(&mut self) {
+        self.key.zeroize();
+    }
+}
+
+impl ZeroizeOnDrop for Filip {}
+
+/// Forward PRNG of the selection, permutation and whitening.
+struct FilipPrng {
+    shake: Shake128Reader,
+}
+
+impl FilipPrng {
+    fn seeded(nonce: u64) -> Self {
+        let mut shake = Shake128::default();
+        shake.update(&nonce.to_be_bytes());
+        Self {
+            shake: shake.finalize_xof(),
+        }
+    }
+
+    /// `n` distinct indices below `len` in draw order.
+    fn ordered_subset(&mut self, n: usize, len: usize, taken: &mut [bool]) -> Vec<usize> {
+        let mut out = Vec::with_capacity(n);
+        while out.len() < n {
+            let i = sample_mod(&mut self.shake, len as u64, true) as usize;
+            if !taken[i] {
+                taken[i] = true;
+                out.push(i);
+            }
+        }
+        for &i in out.iter() {
+            taken[i] = false;
+        }
+        out
+    }
+
+    fn bits(&mut self, n: usize) -> Vec<bool> {
+        let mut bytes = vec![0u8; n.div_ceil(8)];
+        self.shake.read(&mut bytes);
+        (0..n).map(|i| bytes[i / 8] >> (i % 8) & 1 == 1).collect()
+    }
+}
+
+impl Filip {
+    /// `try_new` that panics on invalid input.
+    pub fn new(key: Vec<bool>, params: FilipParams) -> Self {
+        Self::try_new(key, params).unwrap_or_else(|e| panic!("{e}"))
+    }
+
+    /// `key` holds `params.key_bits` bits.
+    pub fn try_new(key: Vec<bool>, params: FilipParams) -> Result<Self, FilipError> {
+        if key.len() != params.key_bits {
+            return Err(FilipError::KeyLength {
+                expected: params.key_bits,
+                got: key.len(),
+            });
+        }
+        if params.filter_bits() > params.key_bits {
+            return Err(FilipError::FilterTooWide {
+                filter_bits: params.filter_bits(),
+                key_bits: params.key_bits,
+            });
+        }
+        Ok(Self { key, params })
+    }
+
+    pub fn params(&self) -> &FilipParams {
+        &self.params
+    }
+
+    /// The first `len` keystream bits under `nonce`.
+    pub fn keystream(&self, nonce: u64, len: usize) -> Vec<bool> {
+        let mut prng = FilipPrng::seeded(nonce);
+        let n = self.params.filter_bits();
+        let mut taken = vec![false; self.params.key_bits];
+        (0..len)
+            .map(|_| {
+                let selection = prng.ordered_subset(n, self.params.key_bits, &mut taken);
+                let whitening = prng.bits(n);
+                let input = Zeroizing::new(
+                    selection
+                        .iter()
+                        .zip(whitening)
+                        .map(|(&i, w)| self.key[i] ^ w)
+                        .collect::<Vec<_>>(),
+                );
+                dsm(&self.params.descriptor, &input)
+            })
+            .collect()
+    }
+
+    /// XORs the keystream onto `bits`, decryption is the same call.
+    pub fn encrypt_with_nonce(&self, nonce: u64, bits: &[bool]) -> Vec<bool> {
+        let ks = Zeroizing::new(self.keystream(nonce, bits.len()));
+        bits.iter().zip(ks.iter()).map(|(m, k)| m ^ k).collect()
+    }
+
+    pub fn decrypt_with_nonce(&self, nonce: u64, bits: &[bool]) -> Vec<bool> {
+        self.encrypt_with_nonce(nonce, bits)
+    }
+}
+
+/// Direct sum of monomials: the inputs are consumed in order, degree 1 monomials first.
+fn dsm(descriptor: &[usize], input: &[bool]) -> bool {
+    let mut vars = input.iter();
+    let mut out = false;
+    for (d, &m) in descriptor.iter().enumerate() {
+        for _ in 0..m {
+            // fold, not `all`: every variable of the monomial has to be consumed
+            out ^= vars.by_ref().take(d + 1).fold(true, |acc, &x| acc & x);
+        }
+    }
+    debug_assert!(vars.next().is_none());
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{Rng, rng};
+
+    use super::*;
+
+    fn small() -> FilipParams {
+        FilipParams {
+            key_bits: 64,
+            descriptor: vec![2, 1, 1],
+        }
+    }
+
+    fn rand_bits(n: usize) -> Vec<bool> {
+        let mut rng = rng();
+        (0..n).map(|_| rng.random()).collect()
+    }
+
+    #[test]
+    fn test_roundtrip() {
+        let filip = Filip::new(rand_bits(64), small());
+        let msg = rand_bits(100);
+        let ct = filip.encrypt_with_nonce(4, &msg);
+        assert_eq!(filip.decrypt_with_nonce(4, &ct), msg);
+        assert_eq!(filip.keystream(4, 100), filip.keystream(4, 100));
+        assert_ne!(filip.keystream(4, 100), filip.keystream(5, 100));
+    }
+
+    #[test]
+    fn test_try_new() {
+        assert!(matches!(
+            Filip::try_new(rand_bits(63), small()),
+            Err(FilipError::KeyLength {
+                expected: 64,
+                got: 63
+            })
+        ));
+        let narrow = FilipParams {
+            key_bits: 4,
+            descriptor: vec![2, 1, 1],
+        };
+        assert!(matches!(
+            Filip::try_new(rand_bits(4), narrow),
+            Err(FilipError::FilterTooWide {
+                filter_bits: 7,
+                key_bits: 4
+            })
+        ));
+    }
+
+    #[test]
+    fn test_dsm() {
+        assert_eq!(small().filter_bits(), 2 + 2 + 3);
+        assert_eq!(FilipParams::filip_1216().filter_bits(), 1216);
+        // x0 ^ x1 ^ x2 x3 ^ x4 x5 x6
+        let f = |x: [u8; 7]| x[0] ^ x[1] ^ (x[2] & x[3]) ^ (x[4] & x[5] & x[6]) == 1;
+        for v in 0..128u8 {
+            let x: [u8; 7] = core::array::from_fn(|i| v >> i & 1);
+            let input = x.map(|b| b == 1);
+            assert_eq!(dsm(&[2, 1, 1], &input), f(x), "input {v:07b}");
+        }
+    }
+
+    #[test]
+    fn test_ordered_subset() {
+        let mut prng = FilipPrng::seeded(1);
+        let mut taken = vec![false; 20];
+        let s = prng.ordered_subset(20, 20, &mut taken);
+        let mut sorted = s.clone();
+        sorted.sort();
+        assert_eq!(sorted, (0..20).collect::<Vec<_>>());
+        assert!(taken.iter().all(|t| !t));
+        assert_ne!(s, sorted);
+    }
+}
@@ -0,0 +1,286 @@
+//! Rotate-and-reduce over packed slots.
+//!
+//! `fold_slots` combines all `n` slots in log2(n) steps: `x = op(x, rotate(x, 2^i))`, after which every slot
+//! holds the combination of all of them. With `op` add that is a slot sum, with mul a slot product (depth
+//! log2 n), with a masked select or `a + b - ab` an OR over indicator vectors. `prefix_slots` is the
+//! Hillis-Steele scan: the rotated copy is masked so slot i only sees slots < i.
+//!
+//! Only [`SlotRotate`] is needed, so the same code runs on plaintext vectors (the reference `PlainSlots`)
+//! and on batched BFV ciphertexts ([`BfvSlots`]).
+
+#[cfg(feature = "bfv")]
+use crate::backend::{NativeBackend, RingBackend};
+#[cfg(feature = "bfv")]
+use crate::batch::BatchEncoder;
+#[cfg(feature = "bfv")]
+use crate::bfv_pke::{BfvCiphertext, EvalError, Evaluator, ParamError, galois_element};
+#[cfg(feature = "bfv")]
+use crate::encoding::Encoder;
+
+/// Cyclic rotations of a vector of slots.
+pub trait SlotRotate<C> {
+    /// Power of two.
+    fn slots(&self) -> usize;
+    /// Slot i of the result is slot (i + k) mod n of `x`.
+    fn rotate(&self, x: &C, k: usize) -> C;
+    /// Zero every slot where `keep` is false (a plaintext mask multiplication).
+    fn mask(&self, x: &C, keep: &[bool]) -> C;
+}
+
+/// Every slot ends up holding `op` over all slots. `op` has to be associative and commutative.
+pub fn fold_slots<C: Clone, R: SlotRotate<C>>(rot: &R, x: &C, op: impl Fn(&C, &C) -> C) -> C {
+    let n = rot.slots();
+    assert!(n.is_power_of_two(), "slot count {n} is not a power of two");
+    let mut acc = x.clone();
+    let mut k = n;
+    while k > 1 {
+        k >>= 1;
+        acc = op(&acc, &rot.rotate(&acc, k));
+    }
+    acc
+}
+
+/// Slot i ends up holding `op` over slots 0..=i. `op` has to be associative with 0 as identity
+/// (sums, OR, XOR), the slots shifted in are zeros.
+pub fn prefix_slots<C: Clone, R: SlotRotate<C>>(rot: &R, x: &C, op: impl Fn(&C, &C) -> C) -> C {
+    let n = rot.slots();
+    assert!(n.is_power_of_two(), "slot count {n} is not a power of two");
+    let mut acc = x.clone();
+    let mut k = 1;
+    while k < n {
+        acc = op(&acc, &shift_right(rot, &acc, k));
+        k <<= 1;
+    }
+    acc
+}
+
+/// Slot i of the result is slot i - k of `x`, zero for i < k.
+fn shift_right<C, R: SlotRotate<C>>(rot: &R, x: &C, k: usize) -> C {
+    let n = rot.slots();
+    let keep = (0..n).map(|i| i >= k).collect::<Vec<_>>();
+    rot.mask(&rot.rotate(x, n - k), &keep)
+}
+
+/// Number of rotations either operation needs for `slots` slots, also its depth in `op`.
+pub fn fold_steps(slots: usize) -> usize {
+    slots.trailing_zeros() as usize
+}
+
+/// Plaintext slots, the reference the encrypted ones have to agree with.
+#[derive(Debug, Clone, Copy)]
+pub struct PlainSlots {
+    pub slots: usize,
+}
+
+impl SlotRotate<Vec<u64>> for PlainSlots {
+    fn slots(&self) -> usize {
+        self.slots
+    }
+
+    fn rotate(&self, x: &Vec<u64>, k: usize) -> Vec<u64> {
+        let mut out = x.clone();
+        out.rotate_left(k % self.slots);
+        out
+    }
+
+    fn mask(&self, x: &Vec<u64>, keep: &[bool]) -> Vec<u64> {
+        x.iter()
+            .zip(keep)
+            .map(|(&v, &k)| if k { v } else { 0 })
+            .collect()
+    }
+}
+
+/// Ciphertext slots under [`BatchEncoder`]. x -> x^(3^k) rotates both rows of n/2 slots together, so every
+/// row is a vector of n/2 slots, and an operation acts on the two rows independently.
+#[cfg(feature = "bfv")]
+#[derive(Debug, Clone)]
+pub struct BfvSlots<'a, const N: usize, const Q: u64, const T: u64, B: RingBackend = NativeBackend>
+{
+    evaluator: &'a Evaluator<N, Q, T, B>,
+    encoder: BatchEncoder<N, T>,
+}
+
+#[cfg(feature = "bfv")]
+impl<'a, const N: usize, const Q: u64, const T: u64, B: RingBackend> BfvSlots<'a, N, Q, T, B> {
+    /// Galois elements of the rotations `fold_slots` and `prefix_slots` use, for `Bfv::gen_galois_keys`.
+    pub fn galois_elements() -> Vec<usize> {
+        let n = N / 2;
+        let mut elements = (0..fold_steps(n))
+            .flat_map(|i| [1 << i, n - (1 << i)])
+            .map(|k| galois_element::<N>(k as i64))
+            .collect::<Vec<_>>();
+        elements.sort_unstable();
+        elements.dedup();
+        elements
+    }
+
+    /// Fails if t doesn't allow batching or `evaluator` lacks a key of `galois_elements`.
+    pub fn new(evaluator: &'a Evaluator<N, Q, T, B>) -> Result<Self, FoldError> {
+        let encoder = BatchEncoder::new()?;
+        for k in Self::galois_elements() {
+            evaluator.galois_key(k)?;
+        }
+        Ok(Self { evaluator, encoder })
+    }
+}
+
+#[cfg(feature = "bfv")]
+impl<const N: usize, const Q: u64, const T: u64, B: RingBackend>
+    SlotRotate<BfvCiphertext<N, Q, T, B>> for BfvSlots<'_, N, Q, T, B>
+{
+    fn slots(&self) -> usize {
+        N / 2
+    }
+
+    /// Panics for rotations outside `galois_elements`.
+    fn rotate(&self, x: &BfvCiphertext<N, Q, T, B>, k: usize) -> BfvCiphertext<N, Q, T, B> {
+        self.evaluator
+            .rotate(x, galois_element::<N>(k as i64))
+            .unwrap_or_else(|e| panic!("rotation by {k}: {e}"))
+    }
+
+    /// The same mask on both rows.
+    fn mask(&self, x: &BfvCiphertext<N, Q, T, B>, keep: &[bool]) -> BfvCiphertext<N, Q, T, B> {
+        let row = keep.iter().map(|&k| k as u64);
+        let mask = row.clone().chain(row).collect::<Vec<_>>();
+        x.mul_plain(&self.encoder.encode(&mask).expect("n slots"))
+    }
+}
+
+#[cfg(feature = "bfv")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FoldError {
+    /// t doesn't allow batching.
+    Batching(ParamError),
+    /// A Galois key of `BfvSlots::galois_elements` is missing.
+    Eval(EvalError),
+}
+
+#[cfg(feature = "bfv")]
+impl std::fmt::Display for FoldError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FoldError::Batching(e) => write!(f, "{e}"),
+            FoldError::Eval(e) => write!(f, "{e}"),
+        }
+    }
+}
+
+#[cfg(feature = "bfv")]
+impl std::error::Error for FoldError {}
+
+#[cfg(feature = "bfv")]
+impl From<ParamError> for FoldError {
+    fn from(e: ParamError) -> Self {
+        FoldError::Batching(e)
+    }
+}
+
+#[cfg(feature = "bfv")]
+impl From<EvalError> for FoldError {
+    fn from(e: EvalError) -> Self {
+        FoldError::Eval(e)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const T: u64 = 257;
+
+    fn add(a: &[u64], b: &[u64]) -> Vec<u64> {
+        a.iter().zip(b).map(|(x, y)| (x + y) % T).collect()
+    }
+
+    fn mul(a: &[u64], b: &[u64]) -> Vec<u64> {
+        a.iter().zip(b).map(|(x, y)| x * y % T).collect()
+    }
+
+    #[test]
+    fn test_fold() {
+        let rot = PlainSlots { slots: 8 };
+        let x = vec![3, 1, 4, 1, 5, 9, 2, 6];
+        let sum = x.iter().sum::<u64>() % T;
+        let prod = x.iter().product::<u64>() % T;
+        assert_eq!(fold_slots(&rot, &x, |a, b| add(a, b)), vec![sum; 8]);
+        assert_eq!(fold_slots(&rot, &x, |a, b| mul(a, b)), vec![prod; 8]);
+
+        // OR over 0/1 indicators as a + b - ab
+        let or = |a: &Vec<u64>, b: &Vec<u64>| {
+            a.iter()
+                .zip(b)
+                .map(|(x, y)| (x + y + T - x * y % T) % T)
+                .collect()
+        };
+        assert_eq!(
+            fold_slots(&rot, &vec![0, 0, 0, 1, 0, 0, 0, 0], or),
+            vec![1; 8]
+        );
+        assert_eq!(fold_slots(&rot, &vec![0; 8], or), vec![0; 8]);
+        assert_eq!(fold_steps(8), 3);
+    }
+
+    #[test]
+    fn test_prefix() {
+        let rot = PlainSlots { slots: 8 };
+        let x = vec![3, 1, 4, 1, 5, 9, 2, 6];
+        let expected = x
+            .iter()
+            .scan(0, |acc, v| {
+                *acc = (*acc + v) % T;
+                Some(*acc)
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(prefix_slots(&rot, &x, |a, b| add(a, b)), expected);
+
+        let single = PlainSlots { slots: 1 };
+        assert_eq!(fold_slots(&single, &vec![5], |a, b| add(a, b)), vec![5]);
+    }
+
+    #[test]
+    #[cfg(feature = "bfv")]
+    fn test_encrypted_fold() {
+        use crate::bfv_pke::Bfv;
+
+        const N: usize = 16;
+        const Q: u64 = 1 << 57;
+        // 97 ≡ 1 mod 32
+        const T: u64 = 97;
+
+        let (bfv, sk) = Bfv::<N, Q, T>::keygen().unwrap();
+        let no_keys = Evaluator::<N, Q, T>::new();
+        assert!(matches!(
+            BfvSlots::new(&no_keys),
+            Err(FoldError::Eval(EvalError::MissingGaloisKey { .. }))
+        ));
+        let evaluator = Evaluator::new()
+            .with_relin_key(bfv.gen_relin_key(&sk, 8))
+            .with_galois_keys(
+                bfv.gen_galois_keys(&sk, &BfvSlots::<N, Q, T>::galois_elements(), 8)
+                    .unwrap(),
+            );
+        let rot = BfvSlots::new(&evaluator).unwrap();
+        let encoder = BatchEncoder::<N, T>::new().unwrap();
+
+        let rows = [vec![3, 1, 4, 1, 5, 9, 2, 6], vec![2, 7, 1, 8, 2, 8, 1, 8]];
+        let ct = bfv.encrypt(encoder.encode(&rows.concat()).unwrap());
+        let decrypt = |ct: &BfvCiphertext<N, Q, T>| encoder.decode(&ct.decrypt(&sk));
+        let plain = PlainSlots { slots: N / 2 };
+        let add = |a: &Vec<u64>, b: &Vec<u64>| a.iter().zip(b).map(|(x, y)| (x + y) % T).collect();
+        let mul = |a: &Vec<u64>, b: &Vec<u64>| a.iter().zip(b).map(|(x, y)| x * y % T).collect();
+        let per_row =
+            |f: &dyn Fn(&Vec<u64>) -> Vec<u64>| rows.iter().flat_map(f).collect::<Vec<_>>();
+
+        let sum = fold_slots(&rot, &ct, |a, b| a.clone() + b.clone());
+        assert_eq!(decrypt(&sum), per_row(&|row| fold_slots(&plain, row, add)));
+        let prod = fold_slots(&rot, &ct, |a, b| evaluator.mul(a, b).unwrap());
+        assert_eq!(decrypt(&prod), per_row(&|row| fold_slots(&plain, row, mul)));
+        let prefix = prefix_slots(&rot, &ct, |a, b| a.clone() + b.clone());
+        assert_eq!(
+            decrypt(&prefix),
+            per_row(&|row| prefix_slots(&plain, row, add))
+        );
+    }
+}
@@ -0,0 +1,244 @@
+//! Plain LWE public key encryption, FrodoPKE style (https://frodokem.org/files/FrodoKEM-specification-20210604.pdf,
+//! Algorithms 9 to 11), with Regev's scheme as the n̄ = 1, B = 1 case. No ring structure: the security
+//! rests on plain LWE, at the price of n x n public matrices.
+//!
+//! Matrices are [`PolyMatrix`] over Z_q[x]/(x + 1) = Z_q, so every entry is a degree 0 polynomial.
+//! With A uniform and S, E drawn from the error distribution the public key is B = A S + E (n x n̄). A
+//! message of n̄ x n̄ entries of `bits` bits each is encrypted as
+//!
+//!   C_1 = S' A + E',  C_2 = S' B + E'' + encode(M)
+//!
+//! and C_2 - C_1 S = encode(M) + S' E + E'' - E' S decodes back to M while that error stays below
+//! q / 2^(bits + 1). A is stored, not expanded from a seed, and errors are rounded Gaussians rather than
+//! Frodo's tables, so this is for experiments, not interoperability.
+
+use std::fmt;
+
+use zeroize::{Zeroize, ZeroizeOnDrop, Zeroizing};
+
+use crate::matrix::PolyMatrix;
+use crate::polynomial::{Element, ErrorDist, Polynomial, SigmaError};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FrodoParams {
+    pub n: usize,
+    /// Columns of S and rows of S', so messages have n̄^2 entries.
+    pub n_bar: usize,
+    /// Bits per message entry, B in the specification.
+    pub bits: u32,
+    pub error: ErrorDist,
+}
+
+impl FrodoParams {
+    /// Dimensions of FrodoKEM-640, meant for q = 2^15.
+    pub const FRODO_640: Self = Self {
+        n: 640,
+        n_bar: 8,
+        bits: 2,
+        error: ErrorDist::Gaussian { sigma: 2.8 },
+    };
+
+    /// Regev: one bit per ciphertext.
+    pub fn regev(n: usize) -> Self {
+        Self {
+            n,
+            n_bar: 1,
+            bits: 1,
+            error: ErrorDist::STANDARD,
+        }
+    }
+
+    /// Entries per message, n̄^2.
+    pub fn message_len(&self) -> usize {
+        self.n_bar * self.n_bar
+    }
+
+    fn error_matrix<const Q: u64>(&self, rows: usize, cols: usize) -> PolyMatrix<1, Q> {
+        PolyMatrix::new(
+            rows,
+            cols,
+            (0..rows * cols)
+                .map(|_| Polynomial::error_valid(self.error))
+                .collect(),
+        )
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum FrodoError {
+    /// 2^bits has to stay below q.
+    Bits {
+        bits: u32,
+        q: u64,
+    },
+    Sigma(SigmaError),
+}
+
+impl fmt::Display for FrodoError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FrodoError::Bits { bits, q } => {
+                write!(f, "{bits} bits per entry do not fit q = {q}")
+            }
+            FrodoError::Sigma(e) => write!(f, "{e}"),
+        }
+    }
+}
+
+impl std::error::Error for FrodoError {}
+
+impl From<SigmaError> for FrodoError {
+    fn from(e: SigmaError) -> Self {
+        FrodoError::Sigma(e)
+    }
+}
+
+/// The public key (A, B), encrypts.
+#[derive(Debug, Clone)]
+pub struct Frodo<const Q: u64> {
+    params: FrodoParams,
+    a: PolyMatrix<1, Q>,
+    b: PolyMatrix<1, Q>,
+}
+
+/// S, n x n̄. Never printed.
+#[derive(Clone)]
+pub struct FrodoSecretKey<const Q: u64> {
+    params: FrodoParams,
+    s: PolyMatrix<1, Q>,
+}
+
+impl<const Q: u64> Drop for FrodoSecretKey<Q> {
+    fn drop(&mut self) {
+        self.s.zeroize();
+    }
+}
+
+impl<const Q: u64> ZeroizeOnDrop for FrodoSecretKey<Q> {}
+
+impl<const Q: u64> fmt::Debug for FrodoSecretKey<Q> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "FrodoSecretKey<{Q}>(..)")
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FrodoCiphertext<const Q: u64> {
+    /// n̄ x n
+    pub c_1: PolyMatrix<1, Q>,
+    /// n̄ x n̄
+    pub c_2: PolyMatrix<1, Q>,
+}
+
+impl<const Q: u64> Frodo<Q> {
+    pub fn keygen(params: FrodoParams) -> Result<(Self, FrodoSecretKey<Q>), FrodoError> {
+        if params.bits == 0 || params.bits >= 64 || (1u64 << params.bits) >= Q {
+            return Err(FrodoError::Bits {
+                bits: params.bits,
+                q: Q,
+            });
+        }
+        params.error.validate()?;
+        let a = PolyMatrix::rand(params.n, params.n);
+        let s = params.error_matrix(params.n, params.n_bar);
+        let b = &a * &s + params.error_matrix(params.n, params.n_bar);
+        Ok((Self { params, a, b }, FrodoSecretKey { params, s }))
+    }
+
+    pub fn params(&self) -> &FrodoParams {
+        &self.params
+    }
+
+    /// `message` holds n̄^2 entries below 2^bits, row by row.
+    pub fn encrypt(&self, message: &[u64]) -> FrodoCiphertext<Q> {
+        let p = &self.params;
+        assert_eq!(
+            message.len(),
+            p.message_len(),
+            "message has {} entries, expected {}",
+            message.len(),
+            p.message_len()
+        );
+        let s_1 = Zeroizing::new(p.error_matrix(p.n_bar, p.n));
+        let c_1 = &*s_1 * &self.a + p.error_matrix(p.n_bar, p.n);
+        let c_2 = &*s_1 * &self.b + p.error_matrix(p.n_bar, p.n_bar) + encode(p, message);
+        FrodoCiphertext { c_1, c_2 }
+    }
+}
+
+impl<const Q: u64> FrodoSecretKey<Q> {
+    pub fn decrypt(&self, ct: &FrodoCiphertext<Q>) -> Vec<u64> {
+        decode(
+            &self.params,
+            &Zeroizing::new(ct.c_2.clone() - &ct.c_1 * &self.s),
+        )
+    }
+}
+
+/// v -> round(v q / 2^bits)
+fn encode<const Q: u64>(p: &FrodoParams, message: &[u64]) -> PolyMatrix<1, Q> {
+    let entries = message
+        .iter()
+        .map(|&v| {
+            assert!(v >> p.bits == 0, "entry {v} does not fit {} bits", p.bits);
+            let x = (v as u128 * Q as u128 + (1 << (p.bits - 1))) >> p.bits;
+            Polynomial::new([Element::new(x as i64)])
+        })
+        .collect();
+    PolyMatrix::new(p.n_bar, p.n_bar, entries)
+}
+
+/// x -> round(x 2^bits / q) mod 2^bits
+fn decode<const Q: u64>(p: &FrodoParams, m: &PolyMatrix<1, Q>) -> Vec<u64> {
+    (0..p.n_bar)
+        .flat_map(|i| (0..p.n_bar).map(move |j| (i, j)))
+        .map(|(i, j)| {
+            let x = m.entry(i, j).inner[0].value() as u128;
+            (((x << p.bits) + Q as u128 / 2) / Q as u128) as u64 % (1 << p.bits)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::Rng;
+
+    #[test]
+    fn test_regev() {
+        let (pk, sk) = Frodo::<{ 1 << 16 }>::keygen(FrodoParams::regev(128)).unwrap();
+        for bit in [0, 1, 1, 0] {
+            assert_eq!(sk.decrypt(&pk.encrypt(&[bit])), vec![bit]);
+        }
+    }
+
+    #[test]
+    fn test_frodo_640() {
+        let (pk, sk) = Frodo::<{ 1 << 15 }>::keygen(FrodoParams::FRODO_640).unwrap();
+        let mut rng = rand::rng();
+        let message: Vec<u64> = (0..64).map(|_| rng.random_range(0..4)).collect();
+        let ct = pk.encrypt(&message);
+        assert_eq!((ct.c_1.rows(), ct.c_1.cols()), (8, 640));
+        assert_eq!(sk.decrypt(&ct), message);
+        assert_eq!(format!("{sk:?}"), "FrodoSecretKey<32768>(..)");
+    }
+
+    #[test]
+    fn test_message_shape() {
+        let (pk, _) = Frodo::<{ 1 << 16 }>::keygen(FrodoParams::regev(16)).unwrap();
+        assert!(std::panic::catch_unwind(|| pk.encrypt(&[0, 1])).is_err());
+        assert!(std::panic::catch_unwind(|| pk.encrypt(&[2])).is_err());
+
+        let wide = FrodoParams {
+            bits: 16,
+            ..FrodoParams::regev(16)
+        };
+        assert_eq!(
+            Frodo::<{ 1 << 16 }>::keygen(wide).unwrap_err(),
+            FrodoError::Bits {
+                bits: 16,
+                q: 1 << 16
+            }
+        );
+    }
+}
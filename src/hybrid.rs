@@ -0,0 +1,669 @@
+//! Hybrid HE between two parties: a [`Client`] that Pasta encrypts its data and a [`Server`] that
+//! transciphers it into BFV (see [`crate::pasta_bfv`]) and computes on it. The two only exchange byte
+//! messages:
+//!
+//! - setup, once: BFV public key, relinearization key, the Pasta key encrypted under BFV
+//! - upload: nonce, word count, the Pasta ciphertext, one word per data word, a tag per block
+//! - download: word count, the result ciphertexts, the tags of the blocks they hold
+//!
+//! Integers are little endian u64 (u32 for the `KeySwitchKey` base), keys and ciphertexts are their
+//! `to_bytes`. Data of `words` words is transciphered in chunks of n blocks of W words, each chunk giving W
+//! ciphertexts with block b of the chunk in slot b, see [`EncryptedData`].
+//!
+//! The client tags every uploaded block with a MAC under a key only it holds, over the nonce, index and
+//! contents of the block. The server can't make tags of its own, it carries the uploaded ones along with
+//! the data and returns them with the result, and [`Client::download`] checks them against the client's
+//! upload: a download with blocks dropped, duplicated or reordered, or made from another upload, is
+//! rejected. The tags vouch for the blocks the server claims to have processed, in order, not for the BFV
+//! ciphertexts themselves.
+
+use std::fmt;
+
+use sha3::{
+    Shake128,
+    digest::{ExtendableOutput, Update, XofReader},
+};
+
+use crate::batch::BatchEncoder;
+use rand::RngCore;
+use zeroize::Zeroizing;
+
+use crate::bfv_pke::{Bfv, BfvCiphertext, BfvPublicKey, Decryptor, Evaluator, KeyGenError};
+use crate::cancel::CancellationToken;
+use crate::encoding::Encoder;
+use crate::keyswitch::KeySwitchKey;
+use crate::pasta_bfv::{EncryptedPastaKey, Transcipher, TranscipherError};
+use crate::pasta_plain::{PASTA_R, PASTA_T, Pasta, PastaKey};
+use crate::polynomial::DecodeError;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum HybridError {
+    /// A malformed message.
+    Decode(DecodeError),
+    KeyGen(KeyGenError),
+    Transcipher(TranscipherError),
+    /// `EncryptedData` with a ciphertext count that doesn't fit its word count.
+    CiphertextCount {
+        words: usize,
+        ciphertexts: usize,
+    },
+    /// A result ciphertext, or a setup, under a different key than the server's or client's.
+    ContextMismatch,
+    /// The tag of this block doesn't match the uploaded block at this index, or is missing. Reported by
+    /// the client on download.
+    BlockMismatch {
+        block: usize,
+    },
+    /// An upload word not below t.
+    WordOutOfRange {
+        index: usize,
+        value: u64,
+        modulus: u64,
+    },
+}
+
+impl fmt::Display for HybridError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HybridError::Decode(e) => write!(f, "malformed message: {e}"),
+            HybridError::KeyGen(e) => write!(f, "{e}"),
+            HybridError::Transcipher(e) => write!(f, "{e}"),
+            HybridError::CiphertextCount { words, ciphertexts } => {
+                write!(f, "{ciphertexts} ciphertexts don't hold {words} words")
+            }
+            HybridError::ContextMismatch => write!(f, "result under a different key"),
+            HybridError::BlockMismatch { block } => {
+                write!(f, "output block {block} does not match input block {block}")
+            }
+            HybridError::WordOutOfRange {
+                index,
+                value,
+                modulus,
+            } => write!(f, "word {index} ({value}) not below t = {modulus}"),
+        }
+    }
+}
+
+impl std::error::Error for HybridError {}
+
+impl From<DecodeError> for HybridError {
+    fn from(e: DecodeError) -> Self {
+        HybridError::Decode(e)
+    }
+}
+
+impl From<KeyGenError> for HybridError {
+    fn from(e: KeyGenError) -> Self {
+        HybridError::KeyGen(e)
+    }
+}
+
+impl From<TranscipherError> for HybridError {
+    fn from(e: TranscipherError) -> Self {
+        HybridError::Transcipher(e)
+    }
+}
+
+/// Transciphered data on the server: W ciphertexts per chunk of n blocks, ciphertext `c * W + i` holding
+/// word i of block `c * n + b` in slot b. Computations that act slot by slot keep this layout.
+#[derive(Debug, Clone)]
+pub struct EncryptedData<const N: usize, const Q: u64, const T: u64> {
+    pub words: usize,
+    pub ciphertexts: Vec<BfvCiphertext<N, Q, T>>,
+    /// The client's tags of the uploaded blocks, in order, one per Pasta block. Slot by slot computations
+    /// keep them valid.
+    pub tags: Vec<BlockTag>,
+}
+
+/// SHAKE128(tag key || nonce || block index || Pasta block) of one uploaded block, the tag key being a
+/// client secret.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BlockTag(pub [u8; 32]);
+
+impl BlockTag {
+    pub const BYTES: usize = 32;
+}
+
+/// The tags of every block of `ciphertext`, Pasta words under `nonce`.
+fn block_tags<const W: usize>(tag_key: &[u8; 32], nonce: u64, ciphertext: &[u64]) -> Vec<BlockTag> {
+    let mut keyed = Shake128::default();
+    keyed.update(b"rlattice block tag");
+    keyed.update(tag_key);
+    keyed.update(&nonce.to_le_bytes());
+    ciphertext
+        .chunks(W)
+        .enumerate()
+        .map(|(index, block)| {
+            let mut hasher = keyed.clone();
+            hasher.update(&(index as u64).to_le_bytes());
+            hasher.update(&(block.len() as u64).to_le_bytes());
+            for w in block {
+                hasher.update(&w.to_le_bytes());
+            }
+            let mut tag = [0u8; BlockTag::BYTES];
+            hasher.finalize_xof().read(&mut tag);
+            BlockTag(tag)
+        })
+        .collect()
+}
+
+/// The first block where `tags` differs from `expected`, a missing or extra tag counting as a difference.
+fn tag_mismatch(expected: &[BlockTag], tags: &[BlockTag]) -> Result<(), HybridError> {
+    let blocks = expected.len().max(tags.len());
+    match (0..blocks).find(|&b| expected.get(b) != tags.get(b)) {
+        Some(block) => Err(HybridError::BlockMismatch { block }),
+        None => Ok(()),
+    }
+}
+
+/// Tags filling `bytes`, which has to be a whole number of them.
+fn read_tags(bytes: &[u8]) -> Result<Vec<BlockTag>, DecodeError> {
+    if !bytes.len().is_multiple_of(BlockTag::BYTES) {
+        return Err(DecodeError::Length {
+            expected: bytes.len() / BlockTag::BYTES * BlockTag::BYTES,
+            got: bytes.len(),
+        });
+    }
+    Ok(bytes
+        .chunks(BlockTag::BYTES)
+        .map(|tag| BlockTag(tag.try_into().expect("chunks of BYTES")))
+        .collect())
+}
+
+/// Ciphertexts in `words` words of data.
+fn ciphertext_count<const N: usize, const W: usize>(words: usize) -> usize {
+    words.div_ceil(N * W) * W
+}
+
+/// Splits a little endian u64 off the front of `bytes`.
+fn read_u64(bytes: &mut &[u8]) -> Result<u64, DecodeError> {
+    let Some((value, rest)) = bytes.split_first_chunk::<8>() else {
+        return Err(DecodeError::Length {
+            expected: 8,
+            got: bytes.len(),
+        });
+    };
+    *bytes = rest;
+    Ok(u64::from_le_bytes(*value))
+}
+
+/// `count` items of `len` bytes each, a count read from a message can overflow that.
+fn byte_len(count: usize, len: usize) -> Result<usize, DecodeError> {
+    count.checked_mul(len).ok_or(DecodeError::InvalidField {
+        field: "count",
+        value: count as u64,
+    })
+}
+
+/// Splits `count` words off the front of `bytes`.
+fn read_words(bytes: &mut &[u8], count: usize) -> Result<Vec<u64>, DecodeError> {
+    let expected = byte_len(count, 8)?;
+    if bytes.len() < expected {
+        return Err(DecodeError::Length {
+            expected,
+            got: bytes.len(),
+        });
+    }
+    (0..count).map(|_| read_u64(bytes)).collect()
+}
+
+/// An upload message: nonce, Pasta ciphertext with every word < t, and the block tags.
+struct Upload {
+    nonce: u64,
+    ciphertext: Vec<u64>,
+    tags: Vec<BlockTag>,
+}
+
+fn read_upload<const T: u64, const W: usize>(message: &[u8]) -> Result<Upload, DecodeError> {
+    let mut bytes = message;
+    let nonce = read_u64(&mut bytes)?;
+    let words = read_u64(&mut bytes)? as usize;
+    let ciphertext = read_words(&mut bytes, words)?;
+    if let Some(&w) = ciphertext.iter().find(|&&w| w >= T) {
+        return Err(DecodeError::InvalidField {
+            field: "word",
+            value: w,
+        });
+    }
+    let expected = byte_len(words.div_ceil(W), BlockTag::BYTES)?;
+    if bytes.len() != expected {
+        return Err(DecodeError::Length {
+            expected,
+            got: bytes.len(),
+        });
+    }
+    Ok(Upload {
+        nonce,
+        ciphertext,
+        tags: read_tags(bytes)?,
+    })
+}
+
+fn read_ciphertexts<const N: usize, const Q: u64, const T: u64>(
+    bfv: &Bfv<N, Q, T>,
+    bytes: &[u8],
+    count: usize,
+) -> Result<Vec<BfvCiphertext<N, Q, T>>, DecodeError> {
+    let len = BfvCiphertext::<N, Q, T>::BYTES;
+    let expected = byte_len(count, len)?;
+    if bytes.len() != expected {
+        return Err(DecodeError::Length {
+            expected,
+            got: bytes.len(),
+        });
+    }
+    bytes
+        .chunks(len)
+        .map(|ct| bfv.ciphertext_from_bytes(ct))
+        .collect()
+}
+
+/// Data owner: holds the BFV secret key and the Pasta key, t is the Pasta modulus and has to allow
+/// batching.
+pub struct Client<
+    const N: usize,
+    const Q: u64,
+    const T: u64,
+    const W: usize = PASTA_T,
+    const R: usize = PASTA_R,
+> {
+    bfv: Bfv<N, Q, T>,
+    decryptor: Decryptor<N>,
+    pasta_key: PastaKey<W>,
+    /// Keys the block tags, never leaves the client.
+    tag_key: Zeroizing<[u8; 32]>,
+    encoder: BatchEncoder<N, T>,
+}
+
+impl<const N: usize, const Q: u64, const T: u64, const W: usize, const R: usize>
+    Client<N, Q, T, W, R>
+{
+    pub fn generate() -> Result<Self, HybridError> {
+        let encoder = BatchEncoder::new().map_err(TranscipherError::from)?;
+        let (bfv, sk) = Bfv::keygen()?;
+        let mut tag_key = Zeroizing::new([0u8; 32]);
+        rand::rng().fill_bytes(tag_key.as_mut());
+        Ok(Self {
+            bfv,
+            decryptor: Decryptor::new(sk),
+            pasta_key: PastaKey::generate(&mut rand::rng(), T),
+            tag_key,
+            encoder,
+        })
+    }
+
+    pub fn public_key(&self) -> &BfvPublicKey<N, Q> {
+        self.bfv.public_key()
+    }
+
+    /// The setup message, with a relinearization key of base 2^`relin_base_log`.
+    pub fn setup(&self, relin_base_log: u32) -> Vec<u8> {
+        let key = EncryptedPastaKey::<N, Q, T, W>::encrypt(&self.bfv, &self.pasta_key)
+            .expect("checked in generate");
+        let mut out = self.bfv.public_key().to_bytes();
+        out.extend(
+            self.bfv
+                .gen_relin_key(self.decryptor.secret_key(), relin_base_log)
+                .to_bytes(),
+        );
+        for ct in key.words() {
+            out.extend(ct.to_bytes());
+        }
+        out
+    }
+
+    /// Upload message of `data` under a fresh random nonce, fails with `HybridError::WordOutOfRange`
+    /// unless every word is < t. The client keeps it as the record `download` checks the result against.
+    pub fn upload(&self, data: &[u64]) -> Result<Vec<u8>, HybridError> {
+        if let Some((index, &value)) = data.iter().enumerate().find(|(_, w)| **w >= T) {
+            return Err(HybridError::WordOutOfRange {
+                index,
+                value,
+                modulus: T,
+            });
+        }
+        let nonce = rand::random();
+        let mut pasta = Pasta::<W, R>::with_key(self.pasta_key.clone(), T);
+        let ciphertext = pasta.encrypt_with_nonce(nonce, data);
+        let mut out = nonce.to_le_bytes().to_vec();
+        out.extend((data.len() as u64).to_le_bytes());
+        for w in &ciphertext {
+            out.extend(w.to_le_bytes());
+        }
+        for tag in block_tags::<W>(&self.tag_key, nonce, &ciphertext) {
+            out.extend(tag.0);
+        }
+        Ok(out)
+    }
+
+    /// The data words of a download message computed from `upload`, the client's own upload message.
+    /// Fails with `HybridError::BlockMismatch` unless the download carries the tags of every uploaded
+    /// block, in order.
+    pub fn download(&self, upload: &[u8], message: &[u8]) -> Result<Vec<u64>, HybridError> {
+        let upload = read_upload::<T, W>(upload)?;
+        let mut bytes = message;
+        let words = read_u64(&mut bytes)? as usize;
+        let count = ciphertext_count::<N, W>(words);
+        let len = byte_len(count, BfvCiphertext::<N, Q, T>::BYTES)?;
+        if bytes.len() < len {
+            return Err(DecodeError::Length {
+                expected: len,
+                got: bytes.len(),
+            }
+            .into());
+        }
+        let (ciphertexts, tags) = bytes.split_at(len);
+        let ciphertexts = read_ciphertexts(&self.bfv, ciphertexts, count)?;
+        let expected = block_tags::<W>(&self.tag_key, upload.nonce, &upload.ciphertext);
+        tag_mismatch(&expected, &read_tags(tags)?)?;
+        // every tag matched, the word count can still cut or pad the last block
+        if words != upload.ciphertext.len() {
+            return Err(HybridError::BlockMismatch {
+                block: words.min(upload.ciphertext.len()) / W,
+            });
+        }
+        let slots = ciphertexts
+            .iter()
+            .map(|ct| self.encoder.decode(&self.decryptor.decrypt(ct)))
+            .collect::<Vec<_>>();
+        Ok((0..words)
+            .map(|k| {
+                let (chunk, k) = (k / (N * W), k % (N * W));
+                slots[chunk * W + k % W][k / W]
+            })
+            .collect())
+    }
+}
+
+/// Computing party, holds no secrets.
+pub struct Server<
+    const N: usize,
+    const Q: u64,
+    const T: u64,
+    const W: usize = PASTA_T,
+    const R: usize = PASTA_R,
+> {
+    bfv: Bfv<N, Q, T>,
+    transcipher: Transcipher<N, Q, T, Pasta<W, R>>,
+}
+
+impl<const N: usize, const Q: u64, const T: u64, const W: usize, const R: usize>
+    Server<N, Q, T, W, R>
+{
+    pub fn from_setup(message: &[u8]) -> Result<Self, HybridError> {
+        let pk_len = BfvPublicKey::<N, Q>::BYTES;
+        if message.len() < pk_len + 4 {
+            return Err(DecodeError::Length {
+                expected: pk_len + 4,
+                got: message.len(),
+            }
+            .into());
+        }
+        let (pk, rest) = message.split_at(pk_len);
+        let bfv = Bfv::from_public_key(BfvPublicKey::from_bytes(pk)?);
+
+        let base_log = u32::from_le_bytes(rest[..4].try_into().unwrap());
+        let rlk_len = KeySwitchKey::<N, Q, T>::bytes_len(base_log.clamp(1, 32));
+        if rest.len() < rlk_len {
+            return Err(DecodeError::Length {
+                expected: pk_len + rlk_len,
+                got: message.len(),
+            }
+            .into());
+        }
+        let (rlk, key) = rest.split_at(rlk_len);
+        let rlk = KeySwitchKey::from_bytes(bfv.context().clone(), rlk)?;
+        let key = read_ciphertexts(&bfv, key, 2 * W)?;
+
+        let transcipher = Transcipher::new(key, Evaluator::new().with_relin_key(rlk))?;
+        Ok(Self { bfv, transcipher })
+    }
+
+    /// The client's public key, from the setup message.
+    pub fn public_key(&self) -> &BfvPublicKey<N, Q> {
+        self.bfv.public_key()
+    }
+
+    /// Evaluation keys for computing on transciphered data.
+    pub fn evaluator(&self) -> &Evaluator<N, Q, T> {
+        self.transcipher.evaluator()
+    }
+
+    /// BFV encryptions of the data in an upload message.
+    pub fn transcipher(&self, message: &[u8]) -> Result<EncryptedData<N, Q, T>, HybridError> {
+        self.transcipher_cancellable(message, &CancellationToken::new())
+    }
+
+    /// `transcipher` that stops with `TranscipherError::Cancelled` once `token` is cancelled, see
+    /// [`Transcipher::decrypt_cancellable`].
+    pub fn transcipher_cancellable(
+        &self,
+        message: &[u8],
+        token: &CancellationToken,
+    ) -> Result<EncryptedData<N, Q, T>, HybridError> {
+        let Upload {
+            nonce,
+            ciphertext,
+            tags,
+        } = read_upload::<T, W>(message)?;
+        let words = ciphertext.len();
+        let mut ciphertexts = Vec::with_capacity(ciphertext_count::<N, W>(words));
+        for (chunk, words) in ciphertext.chunks(N * W).enumerate() {
+            let first_block = (chunk * N) as u64;
+            ciphertexts.extend(self.transcipher.decrypt_cancellable(
+                nonce,
+                first_block,
+                words,
+                token,
+            )?);
+        }
+        Ok(EncryptedData {
+            words,
+            ciphertexts,
+            tags,
+        })
+    }
+
+    /// The download message of `data`, which has to be under the server's key. Its tags go along as they
+    /// are, the client checks them.
+    pub fn download(&self, data: &EncryptedData<N, Q, T>) -> Result<Vec<u8>, HybridError> {
+        if data.ciphertexts.len() != ciphertext_count::<N, W>(data.words) {
+            return Err(HybridError::CiphertextCount {
+                words: data.words,
+                ciphertexts: data.ciphertexts.len(),
+            });
+        }
+        let mut out = (data.words as u64).to_le_bytes().to_vec();
+        for ct in &data.ciphertexts {
+            if ct.context() != self.bfv.context() {
+                return Err(HybridError::ContextMismatch);
+            }
+            out.extend(ct.to_bytes());
+        }
+        for tag in &data.tags {
+            out.extend(tag.0);
+        }
+        Ok(out)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const N: usize = 8;
+    const Q: u64 = 1 << 59;
+    // 17 ≡ 1 mod 16
+    const T: u64 = 17;
+
+    #[test]
+    fn test_roundtrip() {
+        let client = Client::<N, Q, T, 2, 2>::generate().unwrap();
+        let server = Server::<N, Q, T, 2, 2>::from_setup(&client.setup(8)).unwrap();
+
+        // two chunks, the second one short
+        let data = (0..21).map(|i| i * 5 % T).collect::<Vec<_>>();
+        let upload = client.upload(&data).unwrap();
+        // 11 blocks of 2 words
+        assert_eq!(upload.len(), 16 + 8 * data.len() + 32 * 11);
+        let mut encrypted = server.transcipher(&upload).unwrap();
+        assert_eq!(encrypted.ciphertexts.len(), 4);
+        assert_eq!(
+            client
+                .download(&upload, &server.download(&encrypted).unwrap())
+                .unwrap(),
+            data
+        );
+
+        // squares slot by slot
+        let evaluator = server.evaluator();
+        encrypted.ciphertexts = encrypted
+            .ciphertexts
+            .iter()
+            .map(|ct| evaluator.mul(ct, ct).unwrap())
+            .collect();
+        let squares = data.iter().map(|w| w * w % T).collect::<Vec<_>>();
+        assert_eq!(
+            client
+                .download(&upload, &server.download(&encrypted).unwrap())
+                .unwrap(),
+            squares
+        );
+    }
+
+    #[test]
+    fn test_block_tags() {
+        let client = Client::<N, Q, T, 2, 1>::generate().unwrap();
+        let server = Server::<N, Q, T, 2, 1>::from_setup(&client.setup(8)).unwrap();
+        // two chunks, 11 blocks
+        let words = (0..21).map(|i| i % T).collect::<Vec<_>>();
+        let upload = client.upload(&words).unwrap();
+        let data = server.transcipher(&upload).unwrap();
+        assert_eq!(data.tags.len(), 11);
+        let download = |data: &EncryptedData<N, Q, T>| {
+            client.download(&upload, &server.download(data).unwrap())
+        };
+        assert_eq!(download(&data), Ok(words.clone()));
+
+        // the second chunk dropped, tags included
+        let mut dropped = data.clone();
+        dropped.words = 2 * N;
+        dropped.ciphertexts.truncate(2);
+        dropped.tags.truncate(N);
+        assert_eq!(
+            download(&dropped),
+            Err(HybridError::BlockMismatch { block: N })
+        );
+        // the first two blocks of the chunk swapped, tags included
+        let mut swapped = data.clone();
+        swapped.tags.swap(0, 1);
+        assert_eq!(
+            download(&swapped),
+            Err(HybridError::BlockMismatch { block: 0 })
+        );
+        // a tag forged by the server, which doesn't hold the tag key
+        let mut forged = data.clone();
+        forged.tags[3].0[0] ^= 1;
+        assert_eq!(
+            download(&forged),
+            Err(HybridError::BlockMismatch { block: 3 })
+        );
+        // the last word cut off
+        let mut cut = data.clone();
+        cut.words = 20;
+        assert_eq!(
+            download(&cut),
+            Err(HybridError::BlockMismatch { block: 10 })
+        );
+        // the result of another upload of the same data
+        let other = server.transcipher(&client.upload(&words).unwrap()).unwrap();
+        assert_eq!(
+            download(&other),
+            Err(HybridError::BlockMismatch { block: 0 })
+        );
+
+        // slot by slot computations keep the tags
+        let mut doubled = data.clone();
+        doubled.ciphertexts = doubled
+            .ciphertexts
+            .iter()
+            .map(|c| c.clone() + c.clone())
+            .collect();
+        assert_eq!(
+            download(&doubled),
+            Ok(words.iter().map(|w| 2 * w % T).collect())
+        );
+    }
+
+    #[test]
+    fn test_malformed_messages() {
+        let client = Client::<N, Q, T, 2, 1>::generate().unwrap();
+        let setup = client.setup(8);
+        assert!(matches!(
+            Server::<N, Q, T, 2, 1>::from_setup(&setup[..setup.len() - 1]),
+            Err(HybridError::Decode(DecodeError::Length { .. }))
+        ));
+        let server = Server::<N, Q, T, 2, 1>::from_setup(&setup).unwrap();
+
+        assert_eq!(
+            client.upload(&[1, T, 3]),
+            Err(HybridError::WordOutOfRange {
+                index: 1,
+                value: T,
+                modulus: T
+            })
+        );
+        let upload = client.upload(&[1, 2, 3]).unwrap();
+        assert!(matches!(
+            server.transcipher(&upload[..upload.len() - 8]),
+            Err(HybridError::Decode(DecodeError::Length { .. }))
+        ));
+        let mut bad = upload.clone();
+        bad[16..24].copy_from_slice(&T.to_le_bytes());
+        assert_eq!(
+            server.transcipher(&bad).unwrap_err(),
+            HybridError::Decode(DecodeError::InvalidField {
+                field: "word",
+                value: T
+            })
+        );
+        assert!(client.download(&upload, &[0; 7]).is_err());
+        // an upload with a tag missing
+        assert!(matches!(
+            server.transcipher(&upload[..upload.len() - 32]),
+            Err(HybridError::Decode(DecodeError::Length { .. }))
+        ));
+
+        // a word count whose byte length overflows
+        let mut huge = upload.clone();
+        huge[8..16].copy_from_slice(&u64::MAX.to_le_bytes());
+        assert_eq!(
+            server.transcipher(&huge).unwrap_err(),
+            HybridError::Decode(DecodeError::InvalidField {
+                field: "count",
+                value: u64::MAX
+            })
+        );
+        let mut download = u64::MAX.to_le_bytes().to_vec();
+        download.extend([0; 16]);
+        assert!(client.download(&upload, &download).is_err());
+
+        let mut encrypted = server.transcipher(&upload).unwrap();
+        encrypted.words = N * 2 + 1;
+        assert_eq!(
+            server.download(&encrypted).unwrap_err(),
+            HybridError::CiphertextCount {
+                words: N * 2 + 1,
+                ciphertexts: 2
+            }
+        );
+        let other = Client::<N, Q, T, 2, 1>::generate().unwrap();
+        let other = Server::<N, Q, T, 2, 1>::from_setup(&other.setup(8)).unwrap();
+        encrypted.words = 3;
+        assert_eq!(
+            other.download(&encrypted).unwrap_err(),
+            HybridError::ContextMismatch
+        );
+        assert!(Client::<N, Q, 13>::generate().is_err());
+    }
+}
@@ -0,0 +1,375 @@
+//! Key switching for RLWE ciphertexts, the one place relinearization, Galois rotations and secret key
+//! rotation get their switching keys from.
+//!
+//! A key from s_from to s_to holds, for every gadget power g_j = 2^(j * base_log),
+//!
+//! (b_j, a_j) with b_j + a_j s_to = g_j s_from + e_j
+//!
+//! Switching a polynomial d decomposes it into digits d = sum_j d_j g_j and returns
+//! (sum_j d_j b_j, sum_j d_j a_j), whose phase under s_to is d s_from + sum_j d_j e_j. A larger base
+//! means fewer digits (smaller keys, faster switching) and more noise, about n * digits * 2^base_log * |e| / 2.
+//!
+//! The phase convention is the one of `BfvCiphertext`: c_1 + c_2 s.
+
+use std::sync::Arc;
+
+use zeroize::Zeroizing;
+
+use crate::backend::{NativeBackend, RingBackend};
+use crate::bfv_pke::{BfvContext, BfvPublicKey};
+use crate::ntt::NttPolynomial;
+use crate::polynomial::{DecodeError, Element, ErrorDist, Polynomial, SigmaError};
+
+/// Base 2^`base_log` decomposition of values mod q, digits least significant first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Gadget {
+    base_log: u32,
+    digits: usize,
+}
+
+impl Gadget {
+    pub fn new(q: u64, base_log: u32) -> Self {
+        assert!(
+            (1..=32).contains(&base_log),
+            "base_log {base_log} not in 1..=32"
+        );
+        let q_bits = 64 - (q - 1).leading_zeros();
+        Self {
+            base_log,
+            digits: q_bits.div_ceil(base_log) as usize,
+        }
+    }
+
+    pub fn base_log(&self) -> u32 {
+        self.base_log
+    }
+
+    /// Number of digits, ceil(log q / base_log).
+    pub fn digits(&self) -> usize {
+        self.digits
+    }
+
+    /// The digits of `value` < q, sum_j digit_j 2^(j * base_log) = value.
+    pub fn decompose_scalar(&self, value: u64) -> impl Iterator<Item = u64> + '_ {
+        let mask = (1u64 << self.base_log) - 1;
+        (0..self.digits).map(move |j| {
+            // base_log * j can reach 64 on the last digit of a 64 bit modulus
+            value.checked_shr(j as u32 * self.base_log).unwrap_or(0) & mask
+        })
+    }
+
+    /// Coefficient-wise decomposition, `digits` polynomials with coefficients below 2^base_log.
+    pub fn decompose<const N: usize, const Q: u64>(
+        &self,
+        p: &Polynomial<N, Q>,
+    ) -> Vec<Polynomial<N, Q>> {
+        let mut out = vec![Polynomial::new([Element::new(0); N]); self.digits];
+        for (i, c) in p.inner.iter().enumerate() {
+            for (j, digit) in self.decompose_scalar(c.value()).enumerate() {
+                out[j].inner[i] = Element::new(digit as i64);
+            }
+        }
+        out
+    }
+
+    /// g_j = 2^(j * base_log) mod q.
+    pub fn powers<const Q: u64>(&self) -> Vec<Element<Q>> {
+        let base = Element::<Q>::new(((1u128 << self.base_log) % Q as u128) as i64);
+        let mut power = Element::new(1);
+        (0..self.digits)
+            .map(|_| {
+                let p = power;
+                power = power * base;
+                p
+            })
+            .collect()
+    }
+}
+
+/// Switching key from s_from to s_to. Rows are kept in the evaluation domain of the context, so a switch
+/// costs one forward NTT per digit and two inverse NTTs.
+#[derive(Debug, Clone)]
+pub struct KeySwitchKey<const N: usize, const Q: u64, const T: u64, B: RingBackend = NativeBackend>
+{
+    gadget: Gadget,
+    rows: Vec<(NttPolynomial<N, Q>, NttPolynomial<N, Q>)>,
+    /// Context of the target key, switched ciphertexts belong to it.
+    ctx: Arc<BfvContext<N, Q, T, B>>,
+}
+
+impl<const N: usize, const Q: u64, const T: u64, B: RingBackend> KeySwitchKey<N, Q, T, B> {
+    /// `from` is any ring element (s^2 for relinearization, s(x^k) for rotations), `to` the ternary secret
+    /// of `ctx`, `error` the distribution of the e_j.
+    pub fn new(
+        ctx: Arc<BfvContext<N, Q, T, B>>,
+        from: &Polynomial<N, Q>,
+        to: &Polynomial<N, 3>,
+        base_log: u32,
+        error: ErrorDist,
+    ) -> Result<Self, SigmaError> {
+        error.validate()?;
+        Ok(Self::new_valid(ctx, from, to, base_log, error))
+    }
+
+    /// `new` with an `error` that passed `ErrorDist::validate`.
+    pub(crate) fn new_valid(
+        ctx: Arc<BfvContext<N, Q, T, B>>,
+        from: &Polynomial<N, Q>,
+        to: &Polynomial<N, 3>,
+        base_log: u32,
+        error: ErrorDist,
+    ) -> Self {
+        let gadget = Gadget::new(Q, base_log);
+        let s_to = Zeroizing::new(ctx.cache(&Zeroizing::new(to.lift_centered::<Q>())));
+        let rows = gadget
+            .powers::<Q>()
+            .into_iter()
+            .map(|g| {
+                let a = Polynomial::<N, Q>::rand();
+                let e = Polynomial::<N, Q>::error_valid(error);
+                let a_s = ctx.mul_cached(&ctx.cache(&a), &s_to);
+                let b = B::add(&B::sub(&(*from * g), &a_s), &e);
+                (ctx.cache(&b), ctx.cache(&a))
+            })
+            .collect();
+        Self { gadget, rows, ctx }
+    }
+
+    /// Like `new`, but with only the public key of the target: every row is a public key encryption
+    /// (p_0 u_j + e_j + g_j s_from, p_1 u_j + e'_j) of g_j s_from, so the owner of s_from can hand a key
+    /// to someone else's key pair (proxy re-encryption). The row noise e_j + e'_j s_to + e u_j is about n
+    /// times that of `new`.
+    pub fn from_public_key(
+        ctx: Arc<BfvContext<N, Q, T, B>>,
+        from: &Polynomial<N, Q>,
+        pk: &BfvPublicKey<N, Q>,
+        base_log: u32,
+        error: ErrorDist,
+    ) -> Result<Self, SigmaError> {
+        error.validate()?;
+        Ok(Self::from_public_key_valid(ctx, from, pk, base_log, error))
+    }
+
+    /// `from_public_key` with an `error` that passed `ErrorDist::validate`.
+    pub(crate) fn from_public_key_valid(
+        ctx: Arc<BfvContext<N, Q, T, B>>,
+        from: &Polynomial<N, Q>,
+        pk: &BfvPublicKey<N, Q>,
+        base_log: u32,
+        error: ErrorDist,
+    ) -> Self {
+        let gadget = Gadget::new(Q, base_log);
+        let (p_0, p_1) = (ctx.cache(pk.p_0()), ctx.cache(pk.p_1()));
+        let rows = gadget
+            .powers::<Q>()
+            .into_iter()
+            .map(|g| {
+                let u = Zeroizing::new(ctx.cache(&Zeroizing::new(
+                    Polynomial::<N, 3>::ternary_error().lift_centered::<Q>(),
+                )));
+                let e_1 = Polynomial::<N, Q>::error_valid(error);
+                let e_2 = Polynomial::<N, Q>::error_valid(error);
+                let b = B::add(&B::add(&ctx.mul_cached(&p_0, &u), &e_1), &(*from * g));
+                let a = B::add(&ctx.mul_cached(&p_1, &u), &e_2);
+                (ctx.cache(&b), ctx.cache(&a))
+            })
+            .collect();
+        Self { gadget, rows, ctx }
+    }
+
+    pub fn gadget(&self) -> &Gadget {
+        &self.gadget
+    }
+
+    pub fn context(&self) -> &Arc<BfvContext<N, Q, T, B>> {
+        &self.ctx
+    }
+
+    /// Length of `to_bytes` for `base_log`.
+    pub fn bytes_len(base_log: u32) -> usize {
+        4 + Gadget::new(Q, base_log).digits() * 2 * Polynomial::<N, Q>::BYTES
+    }
+
+    /// `base_log` as 4 little endian bytes, then (b_j, a_j) for every digit as `Polynomial::to_bytes` in
+    /// the coefficient domain.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = self.gadget.base_log.to_le_bytes().to_vec();
+        for (b, a) in self.rows() {
+            out.extend(b.to_bytes());
+            out.extend(a.to_bytes());
+        }
+        out
+    }
+
+    /// Inverse of `to_bytes` for a key into the key pair of `ctx`.
+    pub fn from_bytes(ctx: Arc<BfvContext<N, Q, T, B>>, bytes: &[u8]) -> Result<Self, DecodeError> {
+        let Some((base_log, rows)) = bytes.split_first_chunk::<4>() else {
+            return Err(DecodeError::Length {
+                expected: 4,
+                got: bytes.len(),
+            });
+        };
+        let base_log = check_base_log(u32::from_le_bytes(*base_log))?;
+        let expected = Self::bytes_len(base_log);
+        if bytes.len() != expected {
+            return Err(DecodeError::Length {
+                expected,
+                got: bytes.len(),
+            });
+        }
+        let rows = rows
+            .chunks(2 * Polynomial::<N, Q>::BYTES)
+            .map(|row| {
+                let (b, a) = row.split_at(Polynomial::<N, Q>::BYTES);
+                Ok((Polynomial::from_bytes(b)?, Polynomial::from_bytes(a)?))
+            })
+            .collect::<Result<Vec<_>, DecodeError>>()?;
+        Self::from_rows(ctx, base_log, &rows)
+    }
+
+    /// The rows (b_j, a_j) in the coefficient domain, least significant digit first.
+    pub(crate) fn rows(&self) -> Vec<(Polynomial<N, Q>, Polynomial<N, Q>)> {
+        let coefficients = |p: &NttPolynomial<N, Q>| match &self.ctx.ntt {
+            Some(table) => p.to_coefficients(table),
+            None => *p.as_polynomial(),
+        };
+        self.rows
+            .iter()
+            .map(|(b, a)| (coefficients(b), coefficients(a)))
+            .collect()
+    }
+
+    /// Inverse of `rows`, one row per digit of base 2^`base_log`.
+    pub(crate) fn from_rows(
+        ctx: Arc<BfvContext<N, Q, T, B>>,
+        base_log: u32,
+        rows: &[(Polynomial<N, Q>, Polynomial<N, Q>)],
+    ) -> Result<Self, DecodeError> {
+        let gadget = Gadget::new(Q, check_base_log(base_log)?);
+        if rows.len() != gadget.digits() {
+            return Err(DecodeError::InvalidField {
+                field: "rows",
+                value: rows.len() as u64,
+            });
+        }
+        let rows = rows
+            .iter()
+            .map(|(b, a)| (ctx.cache(b), ctx.cache(a)))
+            .collect();
+        Ok(Self { gadget, rows, ctx })
+    }
+
+    /// (k_1, k_2) with k_1 + k_2 s_to = d s_from + small noise.
+    pub fn switch(&self, d: &Polynomial<N, Q>) -> (Polynomial<N, Q>, Polynomial<N, Q>) {
+        let zero = Polynomial::new([Element::new(0); N]);
+        let ctx = &self.ctx;
+        self.gadget.decompose(d).iter().zip(self.rows.iter()).fold(
+            (zero, zero),
+            |(k_1, k_2), (digit, (b, a))| {
+                let digit = ctx.cache(digit);
+                (
+                    B::add(&k_1, &ctx.mul_cached(&digit, b)),
+                    B::add(&k_2, &ctx.mul_cached(&digit, a)),
+                )
+            },
+        )
+    }
+}
+
+fn check_base_log(base_log: u32) -> Result<u32, DecodeError> {
+    if (1..=32).contains(&base_log) {
+        Ok(base_log)
+    } else {
+        Err(DecodeError::InvalidField {
+            field: "base_log",
+            value: base_log as u64,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bfv_pke::Bfv;
+
+    #[test]
+    fn test_gadget_roundtrip() {
+        const Q: u64 = (1 << 31) - 1;
+        for base_log in [1, 4, 7, 16, 31, 32] {
+            let gadget = Gadget::new(Q, base_log);
+            assert_eq!(gadget.digits(), 31usize.div_ceil(base_log as usize));
+            let p = Polynomial::<8, Q>::rand();
+            let back = gadget
+                .decompose(&p)
+                .iter()
+                .zip(gadget.powers::<Q>())
+                .fold(Polynomial::new([Element::new(0); 8]), |acc, (d, g)| {
+                    acc + *d * g
+                });
+            assert_eq!(back, p, "base_log {base_log}");
+        }
+        // 64 bit values with a digit boundary at bit 64
+        let gadget = Gadget::new(u64::MAX, 16);
+        let digits = gadget.decompose_scalar(u64::MAX - 1).collect::<Vec<_>>();
+        assert_eq!(digits, vec![0xfffe, 0xffff, 0xffff, 0xffff]);
+    }
+
+    #[test]
+    fn test_bytes() {
+        const N: usize = 16;
+        const Q: u64 = 12_289;
+        let (bfv, sk) = Bfv::<N, Q, 257>::keygen().unwrap();
+        let rlk = bfv.gen_relin_key(&sk, 5);
+        let bytes = rlk.to_bytes();
+        assert_eq!(bytes.len(), KeySwitchKey::<N, Q, 257>::bytes_len(5));
+        let back = KeySwitchKey::<N, Q, 257>::from_bytes(bfv.context().clone(), &bytes).unwrap();
+        assert_eq!(back.to_bytes(), bytes);
+        let d = Polynomial::<N, Q>::rand();
+        assert_eq!(back.switch(&d), rlk.switch(&d));
+
+        assert_eq!(
+            KeySwitchKey::<N, Q, 257>::from_bytes(bfv.context().clone(), &bytes[..10]).unwrap_err(),
+            DecodeError::Length {
+                expected: bytes.len(),
+                got: 10
+            }
+        );
+        let mut bad = bytes.clone();
+        bad[..4].copy_from_slice(&40u32.to_le_bytes());
+        assert_eq!(
+            KeySwitchKey::<N, Q, 257>::from_bytes(bfv.context().clone(), &bad).unwrap_err(),
+            DecodeError::InvalidField {
+                field: "base_log",
+                value: 40
+            }
+        );
+    }
+
+    #[test]
+    fn test_switch_phase() {
+        const N: usize = 16;
+        const Q: u64 = 1 << 40;
+        let (bfv, s_to) = Bfv::<N, Q, 257>::keygen().unwrap();
+        let s_from = Polynomial::<N, Q>::rand();
+        for base_log in [4, 10, 20] {
+            let ksk = KeySwitchKey::new(
+                bfv.context().clone(),
+                &s_from,
+                s_to.poly(),
+                base_log,
+                ErrorDist::STANDARD,
+            )
+            .unwrap();
+            let d = Polynomial::<N, Q>::rand();
+            let (k_1, k_2) = ksk.switch(&d);
+            let phase = k_1 + k_2 * *s_to.lift_centered::<Q>();
+            let noise = (phase - d * s_from).linf_norm();
+            // |e_j| <= 6 sigma < 20
+            let bound = ((20 * N * ksk.gadget().digits()) as u64) << base_log;
+            assert!(
+                noise <= bound,
+                "base_log {base_log}: noise {noise} > {bound}"
+            );
+        }
+    }
+}
@@ -0,0 +1,136 @@
+//! Kreyvium (https://eprint.iacr.org/2015/113.pdf), Trivium with a 128 bit key and IV for FHE transciphering.
+//! Besides Trivium's 288 bit state it keeps the key and the IV in two rotating 128 bit registers K* and
+//! IV*, whose bit 0 enters the feedback (the key bit also the output) in every clock. Only the AND gates
+//! of the feedback raise the multiplicative depth, which is what makes it cheap to evaluate
+//! homomorphically over a boolean FHE scheme.
+//!
+//! Indices follow the paper shifted to 0: `s[0]` is s_1, `kstar[0]` is K*_0.
+
+use zeroize::{Zeroize, ZeroizeOnDrop};
+
+/// Clocks without output after loading key and IV (4 * 288).
+pub const KREYVIUM_WARMUP: usize = 1152;
+
+pub struct Kreyvium {
+    s: [bool; 288],
+    kstar: [bool; 128],
+    ivstar: [bool; 128],
+}
+
+impl Drop for Kreyvium {
+    fn drop(&mut self) {
+        self.s.zeroize();
+        self.kstar.zeroize();
+        self.ivstar.zeroize();
+    }
+}
+
+impl ZeroizeOnDrop for Kreyvium {}
+
+impl Kreyvium {
+    /// Loads key and IV and runs the warm up, the next bit out of `next_bit` is z_1.
+    pub fn new(key: &[bool; 128], iv: &[bool; 128]) -> Self {
+        let mut s = [false; 288];
+        s[..93].copy_from_slice(&key[..93]);
+        s[93..177].copy_from_slice(&iv[..84]);
+        s[177..221].copy_from_slice(&iv[84..]);
+        s[221..287].fill(true);
+        let mut cipher = Self {
+            s,
+            // (K*_127, ..., K*_0) = (K_0, ..., K_127)
+            kstar: core::array::from_fn(|j| key[127 - j]),
+            ivstar: core::array::from_fn(|j| iv[127 - j]),
+        };
+        for _ in 0..KREYVIUM_WARMUP {
+            cipher.clock();
+        }
+        cipher
+    }
+
+    /// One clock of the state, returns the output bit of this clock.
+    fn clock(&mut self) -> bool {
+        let s = &self.s;
+        let t1 = s[65] ^ s[92];
+        let t2 = s[161] ^ s[176];
+        let t3 = s[242] ^ s[287] ^ self.kstar[0];
+        let z = t1 ^ t2 ^ t3;
+        let t1 = t1 ^ (s[90] & s[91]) ^ s[170] ^ self.ivstar[0];
+        let t2 = t2 ^ (s[174] & s[175]) ^ s[263];
+        let t3 = t3 ^ (s[285] & s[286]) ^ s[68];
+
+        // shift all three registers by one, the heads are overwritten right after
+        self.s.copy_within(0..287, 1);
+        self.s[0] = t3;
+        self.s[93] = t1;
+        self.s[177] = t2;
+        self.kstar.rotate_left(1);
+        self.ivstar.rotate_left(1);
+        z
+    }
+
+    pub fn next_bit(&mut self) -> bool {
+        self.clock()
+    }
+
+    /// The next `len` keystream bits.
+    pub fn keystream(&mut self, len: usize) -> Vec<bool> {
+        (0..len).map(|_| self.clock()).collect()
+    }
+
+    /// XORs the following keystream bits onto `bits`, decryption is the same call on a fresh instance.
+    pub fn apply(&mut self, bits: &mut [bool]) {
+        for b in bits.iter_mut() {
+            *b ^= self.clock();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{Rng, rng};
+
+    use super::*;
+
+    fn rand_128() -> [bool; 128] {
+        let mut rng = rng();
+        core::array::from_fn(|_| rng.random())
+    }
+
+    #[test]
+    fn test_roundtrip() {
+        let (key, iv) = (rand_128(), rand_128());
+        let msg = rand_128().to_vec();
+        let mut ct = msg.clone();
+        Kreyvium::new(&key, &iv).apply(&mut ct);
+        assert_ne!(ct, msg);
+        Kreyvium::new(&key, &iv).apply(&mut ct);
+        assert_eq!(ct, msg);
+    }
+
+    #[test]
+    fn test_keystream_depends_on_key_and_iv() {
+        let (key, iv) = (rand_128(), rand_128());
+        let ks = Kreyvium::new(&key, &iv).keystream(256);
+
+        // the stream is the same however it is read
+        let mut bitwise = Kreyvium::new(&key, &iv);
+        assert_eq!((0..256).map(|_| bitwise.next_bit()).collect::<Vec<_>>(), ks);
+
+        // a single flipped key or IV bit changes the stream
+        for i in [0, 92, 127] {
+            let mut other_key = key;
+            other_key[i] ^= true;
+            assert_ne!(Kreyvium::new(&other_key, &iv).keystream(256), ks);
+            let mut other_iv = iv;
+            other_iv[i] ^= true;
+            assert_ne!(Kreyvium::new(&key, &other_iv).keystream(256), ks);
+        }
+        // roughly balanced
+        let ones = Kreyvium::new(&key, &iv)
+            .keystream(4096)
+            .iter()
+            .filter(|b| **b)
+            .count();
+        assert!((1_700..2_400).contains(&ones), "{ones} ones");
+    }
+}
@@ -1,5 +1,99 @@
+//! Each scheme sits behind a feature: `pasta` (the symmetric ciphers), `bfv`, `tfhe` and `bgg`, with
+//! `pasta` and `bfv` on by default. The transciphering and hybrid modules need both `pasta` and `bfv`.
+//! Without the default `std` feature only `field`, `polynomial` and `pasta_plain` are built, on `alloc`,
+//! so client-side Pasta encryption runs on targets without an operating system.
+
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
+
+#[cfg(feature = "bfv")]
+pub mod backend;
+#[cfg(feature = "bfv")]
+pub mod batch;
+#[cfg(feature = "bfv")]
+pub mod bfv_params;
+#[cfg(feature = "bfv")]
 pub mod bfv_pke;
+#[cfg(feature = "bfv")]
+pub mod bfv_rns;
+#[cfg(feature = "bfv")]
 pub mod bfv_ske;
+#[cfg(feature = "tfhe")]
+pub mod blind_rotation;
+#[cfg(feature = "std")]
+pub mod cancel;
+#[cfg(feature = "cbor")]
+pub mod cbor;
+#[cfg(all(feature = "pasta", feature = "std"))]
+pub mod cipher;
+#[cfg(feature = "bfv")]
+pub mod circuit;
+#[cfg(feature = "constant-time")]
+pub mod ct;
+#[cfg(feature = "bfv")]
+pub mod encoding;
+#[cfg(feature = "std")]
+pub mod error;
+#[cfg(feature = "std")]
+pub mod estimator;
+pub mod field;
+#[cfg(all(feature = "pasta", feature = "std"))]
+pub mod filip;
+#[cfg(feature = "std")]
+pub mod fold;
+#[cfg(feature = "std")]
+pub mod frodo;
+#[cfg(all(feature = "pasta", feature = "bfv"))]
+pub mod hybrid;
+#[cfg(feature = "bfv")]
+pub mod keyswitch;
+#[cfg(all(feature = "pasta", feature = "std"))]
+pub mod kreyvium;
+#[cfg(feature = "bfv")]
+pub mod lwe;
+#[cfg(all(feature = "pasta", feature = "std"))]
+pub mod masta;
+#[cfg(feature = "std")]
+pub mod matrix;
+#[cfg(feature = "bfv")]
+pub mod noise;
+#[cfg(feature = "std")]
+pub mod ntt;
+#[cfg(feature = "bfv")]
+pub mod packing;
+#[cfg(feature = "std")]
+pub mod parallel;
+#[cfg(all(feature = "pasta", feature = "bfv"))]
+pub mod pasta_bfv;
+#[cfg(feature = "bgg")]
 pub mod pasta_bgg;
+#[cfg(all(feature = "pasta", feature = "std"))]
+pub mod pasta_kat;
+#[cfg(feature = "pasta")]
 pub mod pasta_plain;
 pub mod polynomial;
+#[cfg(all(feature = "pasta", feature = "std"))]
+pub mod rasta;
+#[cfg(feature = "tfhe")]
+pub mod rgsw;
+#[cfg(feature = "std")]
+pub mod rns;
+#[cfg(feature = "bfv")]
+pub mod seal;
+#[cfg(feature = "bfv")]
+pub mod security;
+#[cfg(all(feature = "pasta", feature = "bfv"))]
+pub mod session;
+#[cfg(feature = "std")]
+pub mod shrink;
+#[cfg(feature = "simd")]
+pub mod simd;
+#[cfg(feature = "std")]
+pub mod sparse;
+#[cfg(feature = "tfhe")]
+pub mod tfhe;
+#[cfg(feature = "std")]
+pub mod tournament;
+#[cfg(feature = "wasm")]
+pub mod wasm;
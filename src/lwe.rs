@@ -0,0 +1,449 @@
+//! LWE ciphertexts of a single Z_t value, for sending a few scalar results back instead of whole RLWE
+//! ciphertexts.
+//!
+//! `BfvCiphertext::extract_lwe` pulls one plaintext coefficient out of a BFV ciphertext, encrypted under the
+//! coefficient vector of the same secret. `mod_switch` rescales it to a smaller modulus and
+//! [`LweKeySwitchKey`] moves it to a shorter secret, so each result travels as n' + 1 words of log q' bits.
+//!
+//! [`LweSecretKey`] also encrypts directly, Regev style, for schemes that start from plain LWE. Any modulus
+//! up to 2^63 works, u32 sized ones included.
+//!
+//! The phase is b + <a, s> = Δm + e, the same convention as `BfvCiphertext`.
+
+use std::fmt;
+use std::ops::{Add, Sub};
+
+use rand::Rng;
+use zeroize::{Zeroize, ZeroizeOnDrop, Zeroizing};
+
+use crate::bfv_pke::decode;
+use crate::keyswitch::Gadget;
+use crate::polynomial::{DecodeError, Element, ErrorDist, Polynomial, SigmaError};
+
+/// A vector secret of length n with entries in {-1, 0, 1}. Zeroized on drop and never printed.
+#[derive(Clone, PartialEq)]
+pub struct LweSecretKey<const N: usize> {
+    s: Polynomial<N, 3>,
+}
+
+impl<const N: usize> LweSecretKey<N> {
+    pub fn new(s: Polynomial<N, 3>) -> Self {
+        Self { s }
+    }
+
+    /// Uniform ternary entries.
+    pub fn generate() -> Self {
+        Self::new(Polynomial::ternary_error())
+    }
+
+    /// Uniform entries in {0, 1}, the secrets blind rotation needs.
+    pub fn binary() -> Self {
+        let mut rng = rand::rng();
+        Self::new(Polynomial::new(core::array::from_fn(|_| {
+            Element::new(rng.random_range(0..=1))
+        })))
+    }
+
+    /// Entries in {0, 1, 2}, 2 standing for -1.
+    pub fn poly(&self) -> &Polynomial<N, 3> {
+        &self.s
+    }
+
+    pub fn encrypt<const Q: u64, const T: u64>(&self, m: u64) -> LweCipher<N, Q, T> {
+        LweCipher::encrypt(&self.s, m)
+    }
+
+    pub fn decrypt<const Q: u64, const T: u64>(&self, ct: &LweCipher<N, Q, T>) -> u64 {
+        ct.decrypt(&self.s)
+    }
+}
+
+impl<const N: usize> Drop for LweSecretKey<N> {
+    fn drop(&mut self) {
+        self.s.zeroize();
+    }
+}
+
+impl<const N: usize> ZeroizeOnDrop for LweSecretKey<N> {}
+
+impl<const N: usize> fmt::Debug for LweSecretKey<N> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "LweSecretKey<{N}>(..)")
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct LweCipher<const N: usize, const Q: u64, const T: u64> {
+    /// Plain vector of length n, not a ring element.
+    pub a: Polynomial<N, Q>,
+    pub b: Element<Q>,
+}
+
+/// Centered lift of the secret coefficients, wiped when dropped.
+fn secret_vector<const N: usize, const Q: u64>(
+    sk: &Polynomial<N, 3>,
+) -> Zeroizing<Polynomial<N, Q>> {
+    Zeroizing::new(sk.lift_centered::<Q>())
+}
+
+fn inner_product<const N: usize, const Q: u64>(
+    a: &Polynomial<N, Q>,
+    s: &Polynomial<N, Q>,
+) -> Element<Q> {
+    a.inner
+        .iter()
+        .zip(s.inner.iter())
+        .fold(Element::new(0), |acc, (x, y)| acc + *x * *y)
+}
+
+/// Sample extraction: the LWE ciphertext whose phase is coefficient `index` of the phase c_1 + c_2 s of an
+/// RLWE ciphertext, under the coefficient vector of s. Coefficient k of c_2 s is
+/// sum_{j<=k} c_2[k-j] s_j - sum_{j>k} c_2[n+k-j] s_j.
+pub fn extract_lwe<const N: usize, const Q: u64, const T: u64>(
+    c_1: &Polynomial<N, Q>,
+    c_2: &Polynomial<N, Q>,
+    index: usize,
+) -> LweCipher<N, Q, T> {
+    assert!(index < N, "coefficient {index} out of range for n = {N}");
+    let a = Polynomial::new(core::array::from_fn(|j| {
+        if j <= index {
+            c_2.inner[index - j]
+        } else {
+            -c_2.inner[N + index - j]
+        }
+    }));
+    LweCipher::new(a, c_1.inner[index])
+}
+
+/// round(x * q' / q) mod q'
+fn rescale<const Q: u64, const Q2: u64>(x: Element<Q>) -> Element<Q2> {
+    let v = (x.value() as u128 * Q2 as u128 + Q as u128 / 2) / Q as u128;
+    Element::new((v % Q2 as u128) as i64)
+}
+
+impl<const N: usize, const Q: u64, const T: u64> LweCipher<N, Q, T> {
+    /// Length of `to_bytes`: the packed `a` then b in whole bytes.
+    pub const BYTES: usize = Polynomial::<N, Q>::BYTES + Polynomial::<N, Q>::COEFF_BITS.div_ceil(8);
+
+    pub fn new(a: Polynomial<N, Q>, b: Element<Q>) -> Self {
+        Self { a, b }
+    }
+
+    /// Fresh encryption of m < t: b = Δm + e - <a, s> with uniform `a` and Gaussian e.
+    pub fn encrypt(sk: &Polynomial<N, 3>, m: u64) -> Self {
+        assert!(m < T, "message {m} out of range for t = {T}");
+        let a = Polynomial::<N, Q>::rand();
+        let e = Element::new(ErrorDist::STANDARD.sample_valid(&mut rand::rng()));
+        let b = Element::new((Q / T * m) as i64) + e - inner_product(&a, &secret_vector(sk));
+        Self::new(a, b)
+    }
+
+    /// b + <a, s>
+    fn phase(&self, sk: &Polynomial<N, 3>) -> Element<Q> {
+        self.b + inner_product(&self.a, &secret_vector(sk))
+    }
+
+    pub fn decrypt(&self, sk: &Polynomial<N, 3>) -> u64 {
+        decode::<Q, T>(self.phase(sk).value())
+    }
+
+    /// Same plaintext under modulus q'. Adds rounding noise of about (1 + |s|_1) / 2 (in units of q'),
+    /// so q' / t has to stay well above the weight of the secret.
+    pub fn mod_switch<const Q2: u64>(&self) -> LweCipher<N, Q2, T> {
+        LweCipher::new(
+            Polynomial::new(core::array::from_fn(|i| rescale(self.a.inner[i]))),
+            rescale(self.b),
+        )
+    }
+
+    /// `a` as `Polynomial::to_bytes`, then b little endian in `COEFF_BITS.div_ceil(8)` bytes.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = self.a.to_bytes();
+        let b_bytes = Self::BYTES - Polynomial::<N, Q>::BYTES;
+        out.extend_from_slice(&self.b.value().to_le_bytes()[..b_bytes]);
+        out
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, DecodeError> {
+        if bytes.len() != Self::BYTES {
+            return Err(DecodeError::Length {
+                expected: Self::BYTES,
+                got: bytes.len(),
+            });
+        }
+        let (a_bytes, b_bytes) = bytes.split_at(Polynomial::<N, Q>::BYTES);
+        let a = Polynomial::from_bytes(a_bytes)?;
+        let mut buf = [0u8; 8];
+        buf[..b_bytes.len()].copy_from_slice(b_bytes);
+        let b = u64::from_le_bytes(buf);
+        if b >= Q {
+            return Err(DecodeError::OutOfRange { index: N, value: b });
+        }
+        Ok(Self::new(a, Element::new(b as i64)))
+    }
+}
+
+impl<const N: usize, const Q: u64, const T: u64> Add for LweCipher<N, Q, T> {
+    type Output = Self;
+
+    /// Encryption of m + m' mod t, the errors add up.
+    fn add(self, rhs: Self) -> Self {
+        Self::new(self.a + rhs.a, self.b + rhs.b)
+    }
+}
+
+impl<const N: usize, const Q: u64, const T: u64> Sub for LweCipher<N, Q, T> {
+    type Output = Self;
+
+    fn sub(self, rhs: Self) -> Self {
+        Self::new(self.a - rhs.a, self.b - rhs.b)
+    }
+}
+
+/// Key switching from an LWE secret of length n to one of length m (usually m < n), with a base 2^`base_log`
+/// decomposition of the `a` entries ([`Gadget`]). Row (i, j) encrypts s_i * 2^(j * base_log) under the new secret.
+#[derive(Debug, Clone)]
+pub struct LweKeySwitchKey<const N: usize, const M: usize, const Q: u64> {
+    gadget: Gadget,
+    rows: Vec<(Polynomial<M, Q>, Element<Q>)>,
+}
+
+impl<const N: usize, const M: usize, const Q: u64> LweKeySwitchKey<N, M, Q> {
+    /// Rows with `ErrorDist::STANDARD` noise. `to` can be any key of length m, e.g. the coefficient vector
+    /// of a BFV secret so switched ciphertexts can be packed back with `Evaluator::pack_lwes`.
+    pub fn new(from: &Polynomial<N, 3>, to: &Polynomial<M, 3>, base_log: u32) -> Self {
+        Self::generate(from, to, base_log, ErrorDist::STANDARD)
+    }
+
+    pub fn with_error(
+        from: &Polynomial<N, 3>,
+        to: &Polynomial<M, 3>,
+        base_log: u32,
+        error: ErrorDist,
+    ) -> Result<Self, SigmaError> {
+        error.validate()?;
+        Ok(Self::generate(from, to, base_log, error))
+    }
+
+    fn generate(
+        from: &Polynomial<N, 3>,
+        to: &Polynomial<M, 3>,
+        base_log: u32,
+        error: ErrorDist,
+    ) -> Self {
+        let gadget = Gadget::new(Q, base_log);
+        let powers = gadget.powers::<Q>();
+        let s = secret_vector::<N, Q>(from);
+        let s_to = secret_vector::<M, Q>(to);
+
+        let mut rng = rand::rng();
+        let mut rows = Vec::with_capacity(N * gadget.digits());
+        for s_i in s.inner.iter() {
+            for power in powers.iter() {
+                let a = Polynomial::<M, Q>::rand();
+                let e = Element::new(error.sample_valid(&mut rng));
+                let b = *s_i * *power + e - inner_product(&a, &s_to);
+                rows.push((a, b));
+            }
+        }
+        Self { gadget, rows }
+    }
+
+    pub fn gadget(&self) -> &Gadget {
+        &self.gadget
+    }
+
+    /// Length of `to_bytes` for `base_log`.
+    pub fn bytes_len(base_log: u32) -> usize {
+        4 + N * Gadget::new(Q, base_log).digits() * LweCipher::<M, Q, 2>::BYTES
+    }
+
+    /// `base_log` as 4 little endian bytes, then row (i, j) as `LweCipher::to_bytes`, i major.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = self.gadget.base_log().to_le_bytes().to_vec();
+        for (a, b) in &self.rows {
+            out.extend(LweCipher::<M, Q, 2>::new(*a, *b).to_bytes());
+        }
+        out
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, DecodeError> {
+        let Some((base_log, rows)) = bytes.split_first_chunk::<4>() else {
+            return Err(DecodeError::Length {
+                expected: 4,
+                got: bytes.len(),
+            });
+        };
+        let base_log = u32::from_le_bytes(*base_log);
+        if !(1..=32).contains(&base_log) {
+            return Err(DecodeError::InvalidField {
+                field: "base_log",
+                value: base_log as u64,
+            });
+        }
+        let expected = Self::bytes_len(base_log);
+        if bytes.len() != expected {
+            return Err(DecodeError::Length {
+                expected,
+                got: bytes.len(),
+            });
+        }
+        let rows = rows
+            .chunks(LweCipher::<M, Q, 2>::BYTES)
+            .map(|row| LweCipher::<M, Q, 2>::from_bytes(row).map(|ct| (ct.a, ct.b)))
+            .collect::<Result<_, DecodeError>>()?;
+        Ok(Self {
+            gadget: Gadget::new(Q, base_log),
+            rows,
+        })
+    }
+
+    /// Same plaintext under the new secret, the noise grows by about n * digits * 2^base_log.
+    pub fn switch<const T: u64>(&self, ct: &LweCipher<N, Q, T>) -> LweCipher<M, Q, T> {
+        let digits = self.gadget.digits();
+        let mut a = Polynomial::<M, Q>::new([Element::new(0); M]);
+        let mut b = ct.b;
+        for (i, a_i) in ct.a.inner.iter().enumerate() {
+            for (j, digit) in self.gadget.decompose_scalar(a_i.value()).enumerate() {
+                let digit = Element::new(digit as i64);
+                let (row_a, row_b) = &self.rows[i * digits + j];
+                a = a + *row_a * digit;
+                b = b + *row_b * digit;
+            }
+        }
+        LweCipher::new(a, b)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bfv_pke::Bfv;
+
+    const N: usize = 16;
+    const Q: u64 = 1 << 30;
+    const T: u64 = 257;
+
+    #[test]
+    fn test_extract_every_coefficient() {
+        let (bfv, sk) = Bfv::<N, Q, T>::keygen().unwrap();
+        let m = Polynomial::<N, T>::rand();
+        let ct = bfv.encrypt(m);
+        for k in 0..N {
+            assert_eq!(ct.extract_lwe(k).decrypt(sk.poly()), m.inner[k].value());
+        }
+    }
+
+    #[test]
+    fn test_mod_and_key_switch() {
+        let (bfv, sk) = Bfv::<N, Q, T>::keygen().unwrap();
+        let m = Polynomial::<N, T>::rand();
+        let lwe = bfv.encrypt(m).extract_lwe(3);
+
+        let small = lwe.mod_switch::<{ 1 << 16 }>();
+        assert_eq!(small.decrypt(sk.poly()), m.inner[3].value());
+
+        let short_sk = Polynomial::<8, 3>::ternary_error();
+        let ksk = LweKeySwitchKey::<N, 8, Q>::new(sk.poly(), &short_sk, 6);
+        let short = ksk.switch(&lwe).mod_switch::<{ 1 << 20 }>();
+        assert_eq!(short.decrypt(&short_sk), m.inner[3].value());
+    }
+
+    #[test]
+    fn test_regev_u32_modulus() {
+        const Q32: u64 = 1 << 32;
+        let sk = LweSecretKey::<256>::generate();
+        let (x, y) = (5, 9);
+        let ct_x = sk.encrypt::<Q32, 16>(x);
+        let ct_y = sk.encrypt::<Q32, 16>(y);
+        assert_eq!(sk.decrypt(&ct_x), x);
+        assert_eq!(sk.decrypt(&(ct_x.clone() + ct_y.clone())), (x + y) % 16);
+        assert_eq!(sk.decrypt(&(ct_x - ct_y)), (x + 16 - y) % 16);
+
+        let binary = LweSecretKey::<256>::binary();
+        assert!(binary.poly().inner.iter().all(|c| c.value() < 2));
+        assert_eq!(binary.decrypt(&binary.encrypt::<Q32, 2>(1)), 1);
+        assert_eq!(format!("{binary:?}"), "LweSecretKey<256>(..)");
+    }
+
+    #[test]
+    fn test_key_switch_bytes() {
+        let (from, to) = (LweSecretKey::<N>::generate(), LweSecretKey::<8>::generate());
+        let ksk = LweKeySwitchKey::<N, 8, Q>::with_error(
+            from.poly(),
+            to.poly(),
+            10,
+            ErrorDist::CenteredBinomial { eta: 2 },
+        )
+        .unwrap();
+        let bytes = ksk.to_bytes();
+        assert_eq!(bytes.len(), LweKeySwitchKey::<N, 8, Q>::bytes_len(10));
+        let back = LweKeySwitchKey::<N, 8, Q>::from_bytes(&bytes).unwrap();
+        assert_eq!(back.gadget().base_log(), 10);
+        let ct = from.encrypt::<Q, T>(200);
+        assert_eq!(back.switch(&ct), ksk.switch(&ct));
+        assert_eq!(to.decrypt(&back.switch(&ct)), 200);
+
+        assert_eq!(
+            LweKeySwitchKey::<N, 8, Q>::from_bytes(&bytes[..9]).unwrap_err(),
+            DecodeError::Length {
+                expected: bytes.len(),
+                got: 9
+            }
+        );
+        let mut bad = bytes.clone();
+        bad[..4].copy_from_slice(&0u32.to_le_bytes());
+        assert_eq!(
+            LweKeySwitchKey::<N, 8, Q>::from_bytes(&bad).unwrap_err(),
+            DecodeError::InvalidField {
+                field: "base_log",
+                value: 0
+            }
+        );
+    }
+
+    #[test]
+    fn test_bytes_roundtrip() {
+        let (bfv, sk) = Bfv::<N, Q, T>::keygen().unwrap();
+        let lwe = bfv
+            .encrypt(Polynomial::<N, T>::rand())
+            .extract_lwe(0)
+            .mod_switch::<{ 1 << 16 }>();
+        let bytes = lwe.to_bytes();
+        assert_eq!(bytes.len(), LweCipher::<N, { 1 << 16 }, T>::BYTES);
+        assert_eq!(bytes.len(), 17 * 2);
+        let back = LweCipher::<N, { 1 << 16 }, T>::from_bytes(&bytes).unwrap();
+        assert_eq!(back.decrypt(sk.poly()), lwe.decrypt(sk.poly()));
+        assert_eq!(back, lwe);
+
+        assert_eq!(
+            LweCipher::<N, 12_289, T>::from_bytes(&[0xff; LweCipher::<N, 12_289, T>::BYTES]),
+            Err(DecodeError::OutOfRange {
+                index: 0,
+                value: (1 << 14) - 1
+            })
+        );
+    }
+
+    #[test]
+    fn test_secrets_zeroize_on_drop() {
+        fn assert_zeroize_on_drop<S: ZeroizeOnDrop>() {}
+        assert_zeroize_on_drop::<LweSecretKey<N>>();
+        assert_zeroize_on_drop::<crate::bfv_pke::BfvSecretKey<N>>();
+        #[cfg(feature = "tfhe")]
+        assert_zeroize_on_drop::<crate::tfhe::TfheSecretKey<N, N>>();
+        assert_zeroize_on_drop::<crate::frodo::FrodoSecretKey<Q>>();
+        #[cfg(feature = "pasta")]
+        {
+            assert_zeroize_on_drop::<crate::pasta_plain::PastaKey<2>>();
+            assert_zeroize_on_drop::<crate::rasta::Agrasta>();
+            assert_zeroize_on_drop::<crate::masta::Masta<5, 2>>();
+            assert_zeroize_on_drop::<crate::filip::Filip>();
+            assert_zeroize_on_drop::<crate::kreyvium::Kreyvium>();
+        }
+
+        let mut s = secret_vector::<N, Q>(&Polynomial::ternary_error());
+        s.zeroize();
+        assert!(s.inner.iter().all(|c| c.value() == 0));
+    }
+}
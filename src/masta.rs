@@ -0,0 +1,266 @@
+//! MASTA (Ha et al., "Masta: An HE-friendly cipher using modular arithmetic"), the ring based relative of
+//! Pasta. The state is a single vector of `N` words mod p, read as an element of
+//! Z_p[x]/(x^N - alpha). Every affine layer multiplies it by a fresh ring element and adds a constant, both
+//! squeezed from the same SHAKE128(nonce || block counter) stream as Pasta ([`XofSampler`]), so the layer
+//! costs one ring product instead of a t x t matrix. The nonlinear layer is chi over Z_p,
+//! y_i = x_i + (x_{i+1} + 1) * x_{i+2}, and the key is added back at the end:
+//!
+//! keystream = A_R . chi . A_{R-1} . ... . chi . A_0 (k) + k
+//!
+//! Parameter sets (N, R, p) are left to the caller.
+
+use zeroize::{Zeroize, ZeroizeOnDrop, Zeroizing};
+
+use std::fmt;
+
+use crate::field::{LazyAcc, Modulus, Ring};
+use crate::pasta_plain::XofSampler;
+
+/// Default `alpha` of the ring x^N - alpha.
+pub const MASTA_ALPHA: u64 = 7;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MastaError {
+    KeyLength {
+        expected: usize,
+        got: usize,
+    },
+    KeyOutOfRange {
+        index: usize,
+        value: u64,
+        modulus: u64,
+    },
+    /// Below 2.
+    Modulus {
+        modulus: u64,
+    },
+}
+
+impl fmt::Display for MastaError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MastaError::KeyLength { expected, got } => {
+                write!(f, "key must be {expected} words, got {got}")
+            }
+            MastaError::KeyOutOfRange {
+                index,
+                value,
+                modulus,
+            } => write!(
+                f,
+                "key word {index} ({value}) not below the modulus {modulus}"
+            ),
+            MastaError::Modulus { modulus } => write!(f, "modulus {modulus} below 2"),
+        }
+    }
+}
+
+impl std::error::Error for MastaError {}
+
+pub struct Masta<const N: usize, const R: usize> {
+    key: [u64; N],
+    field: Modulus,
+    alpha: u64,
+}
+
+impl<const N: usize, const R: usize> Drop for Masta<N, R> {
+    fn drop(&mut self) {
+        self.key.zeroize();
+    }
+}
+
+impl<const N: usize, const R: usize> ZeroizeOnDrop for Masta<N, R> {}
+
+impl<const N: usize, const R: usize> Masta<N, R> {
+    /// `try_new` that panics on invalid input.
+    pub fn new(key: Vec<u64>, modulus: u64) -> Self {
+        Self::try_new(key, modulus).unwrap_or_else(|e| panic!("{e}"))
+    }
+
+    /// `key` holds N words below `modulus`. The key is wiped on error too.
+    pub fn try_new(mut key: Vec<u64>, modulus: u64) -> Result<Self, MastaError> {
+        assert!(R >= 1, "masta needs at least one round");
+        assert!(N >= 3, "chi needs a state of at least 3 words");
+        let checked = if modulus < 2 {
+            Err(MastaError::Modulus { modulus })
+        } else if key.len() != N {
+            Err(MastaError::KeyLength {
+                expected: N,
+                got: key.len(),
+            })
+        } else if let Some((index, &value)) = key.iter().enumerate().find(|(_, w)| **w >= modulus) {
+            Err(MastaError::KeyOutOfRange {
+                index,
+                value,
+                modulus,
+            })
+        } else {
+            Ok(())
+        };
+        let out = checked.map(|()| Self {
+            key: core::array::from_fn(|i| key[i]),
+            field: Modulus::new(modulus),
+            alpha: MASTA_ALPHA % modulus,
+        });
+        key.zeroize();
+        out
+    }
+
+    /// Uses the ring x^N - `alpha` instead of `MASTA_ALPHA`. `alpha` = p - 1 gives the negacyclic ring.
+    pub fn with_alpha(mut self, alpha: u64) -> Self {
+        self.alpha = alpha % self.field.modulus();
+        self
+    }
+
+    pub fn modulus(&self) -> u64 {
+        self.field.modulus()
+    }
+
+    pub fn keystream(&self, nonce: u64, block_counter: u64) -> [u64; N] {
+        let mut sampler = XofSampler::seeded(self.field.modulus(), nonce, block_counter);
+        let mut state = self.key;
+        for _ in 0..R {
+            self.affine(&mut sampler, &mut state);
+            self.chi(&mut state);
+        }
+        self.affine(&mut sampler, &mut state);
+        for (s, k) in state.iter_mut().zip(self.key) {
+            *s = self.field.add(*s, k);
+        }
+        state
+    }
+
+    pub fn encrypt_with_nonce(&self, nonce: u64, plaintext: &[u64]) -> Vec<u64> {
+        let mut out = plaintext.to_vec();
+        for (b, block) in out.chunks_mut(N).enumerate() {
+            let ks = Zeroizing::new(self.keystream(nonce, b as u64));
+            for (w, &k) in block.iter_mut().zip(ks.iter()) {
+                *w = self.field.add(*w, k);
+            }
+        }
+        out
+    }
+
+    pub fn decrypt_with_nonce(&self, nonce: u64, ciphertext: &[u64]) -> Vec<u64> {
+        let mut out = ciphertext.to_vec();
+        for (b, block) in out.chunks_mut(N).enumerate() {
+            let ks = Zeroizing::new(self.keystream(nonce, b as u64));
+            for (w, &k) in block.iter_mut().zip(ks.iter()) {
+                *w = self.field.sub(*w, k);
+            }
+        }
+        out
+    }
+
+    /// state = a * state + c in Z_p[x]/(x^N - alpha), a with nonzero coefficients then c, as squeezed.
+    fn affine(&self, sampler: &mut XofSampler, state: &mut [u64; N]) {
+        let a = sampler.vec(N, false);
+        let c = sampler.vec(N, true);
+        let prod = self.ring_mul(&a, state);
+        for i in 0..N {
+            state[i] = self.field.add(prod[i], c[i]);
+        }
+    }
+
+    fn ring_mul(&self, a: &[u64], b: &[u64; N]) -> [u64; N] {
+        core::array::from_fn(|k| {
+            // x^N = alpha: terms with i + j = k and with i + j = k + N
+            let mut low = LazyAcc::new(self.field);
+            let mut high = LazyAcc::new(self.field);
+            for i in 0..N {
+                if i <= k {
+                    low.add_mul(a[i], b[k - i]);
+                } else {
+                    high.add_mul(a[i], b[k + N - i]);
+                }
+            }
+            self.field
+                .add(low.finish(), self.field.mul(self.alpha, high.finish()))
+        })
+    }
+
+    fn chi(&self, state: &mut [u64; N]) {
+        let x = *state;
+        for i in 0..N {
+            let t = self.field.add(x[(i + 1) % N], 1);
+            state[i] = self.field.add(x[i], self.field.mul(t, x[(i + 2) % N]));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{Rng, rng};
+
+    use super::*;
+    use crate::polynomial::Polynomial;
+
+    const P: u64 = 65_537;
+
+    fn rand_key<const N: usize>() -> Vec<u64> {
+        let mut rng = rng();
+        (0..N).map(|_| rng.random_range(0..P)).collect()
+    }
+
+    #[test]
+    fn test_roundtrip() {
+        let masta = Masta::<16, 4>::new(rand_key::<16>(), P);
+        let plain = (0..40).map(|i| i * 1_637 % P).collect::<Vec<_>>();
+        let ct = masta.encrypt_with_nonce(5, &plain);
+        assert_ne!(ct, plain);
+        assert_eq!(masta.decrypt_with_nonce(5, &ct), plain);
+
+        assert_ne!(masta.keystream(5, 0), masta.keystream(5, 1));
+        assert_ne!(masta.keystream(5, 0), masta.keystream(6, 0));
+    }
+
+    #[test]
+    fn test_try_new() {
+        assert!(Masta::<4, 1>::try_new(rand_key::<4>(), P).is_ok());
+        assert!(matches!(
+            Masta::<4, 1>::try_new(rand_key::<3>(), P),
+            Err(MastaError::KeyLength {
+                expected: 4,
+                got: 3
+            })
+        ));
+        assert!(matches!(
+            Masta::<4, 1>::try_new(vec![1, 2, P, 3], P),
+            Err(MastaError::KeyOutOfRange { index: 2, .. })
+        ));
+        assert!(matches!(
+            Masta::<4, 1>::try_new(vec![0; 4], 1),
+            Err(MastaError::Modulus { modulus: 1 })
+        ));
+    }
+
+    #[test]
+    fn test_ring_mul_matches_polynomial() {
+        const N: usize = 8;
+        let masta = Masta::<N, 1>::new(rand_key::<N>(), P).with_alpha(P - 1);
+        let a = Polynomial::<N, P>::rand();
+        let b = Polynomial::<N, P>::rand();
+        let words = |p: &Polynomial<N, P>| p.inner.map(|e| e.value());
+        let expected = words(&(a * b));
+        assert_eq!(masta.ring_mul(&words(&a), &words(&b)), expected);
+
+        // alpha = 1 is the cyclic ring, x^(N-1) * x = 1
+        let cyclic = Masta::<N, 1>::new(rand_key::<N>(), P).with_alpha(1);
+        let mut x_top = [0; N];
+        x_top[N - 1] = 1;
+        let mut x = [0; N];
+        x[1] = 1;
+        let mut one = [0; N];
+        one[0] = 1;
+        assert_eq!(cyclic.ring_mul(&x, &x_top), one);
+    }
+
+    #[test]
+    fn test_chi() {
+        let masta = Masta::<3, 1>::new(vec![0, 0, 0], 17);
+        let mut s = [1, 2, 3];
+        masta.chi(&mut s);
+        // y_i = x_i + (x_{i+1} + 1) x_{i+2}: 1 + 3 * 3, 2 + 4 * 1, 3 + 2 * 2
+        assert_eq!(s, [10, 6, 7]);
+    }
+}
@@ -0,0 +1,308 @@
+//! Vectors and matrices over R_a = Z_a[x]/(x^n+1), for module-lattice constructions (module-LWE, trapdoors,
+//! the BGG experiments) without going through diamond_io's matrix type.
+
+use std::ops::{Add, Mul, Sub};
+
+use zeroize::Zeroize;
+
+use crate::polynomial::{Element, Polynomial};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PolyVector<const N: usize, const A: u64> {
+    pub entries: Vec<Polynomial<N, A>>,
+}
+
+/// Row-major `rows x cols` matrix.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PolyMatrix<const N: usize, const A: u64> {
+    rows: usize,
+    cols: usize,
+    entries: Vec<Polynomial<N, A>>,
+}
+
+fn zero<const N: usize, const A: u64>() -> Polynomial<N, A> {
+    Polynomial::new([Element::new(0); N])
+}
+
+impl<const N: usize, const A: u64> PolyVector<N, A> {
+    pub fn new(entries: Vec<Polynomial<N, A>>) -> Self {
+        Self { entries }
+    }
+
+    pub fn zero(len: usize) -> Self {
+        Self::new(vec![zero(); len])
+    }
+
+    pub fn rand(len: usize) -> Self {
+        Self::new((0..len).map(|_| Polynomial::rand()).collect())
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// sum_i self_i * rhs_i
+    pub fn dot(&self, rhs: &Self) -> Polynomial<N, A> {
+        assert_eq!(self.len(), rhs.len(), "vector lengths differ");
+        self.entries
+            .iter()
+            .zip(rhs.entries.iter())
+            .fold(zero(), |acc, (a, b)| acc + *a * *b)
+    }
+
+    /// Every entry times the ring element `c`.
+    pub fn scale(&self, c: &Polynomial<N, A>) -> Self {
+        Self::new(self.entries.iter().map(|e| *e * *c).collect())
+    }
+}
+
+impl<const N: usize, const A: u64> Add for PolyVector<N, A> {
+    type Output = Self;
+
+    fn add(self, rhs: Self) -> Self::Output {
+        assert_eq!(self.len(), rhs.len(), "vector lengths differ");
+        Self::new(
+            self.entries
+                .into_iter()
+                .zip(rhs.entries)
+                .map(|(a, b)| a + b)
+                .collect(),
+        )
+    }
+}
+
+impl<const N: usize, const A: u64> Sub for PolyVector<N, A> {
+    type Output = Self;
+
+    fn sub(self, rhs: Self) -> Self::Output {
+        assert_eq!(self.len(), rhs.len(), "vector lengths differ");
+        Self::new(
+            self.entries
+                .into_iter()
+                .zip(rhs.entries)
+                .map(|(a, b)| a - b)
+                .collect(),
+        )
+    }
+}
+
+impl<const N: usize, const A: u64> PolyMatrix<N, A> {
+    /// `entries` in row-major order.
+    pub fn new(rows: usize, cols: usize, entries: Vec<Polynomial<N, A>>) -> Self {
+        assert_eq!(
+            entries.len(),
+            rows * cols,
+            "expected {rows} x {cols} entries"
+        );
+        Self {
+            rows,
+            cols,
+            entries,
+        }
+    }
+
+    pub fn zero(rows: usize, cols: usize) -> Self {
+        Self::new(rows, cols, vec![zero(); rows * cols])
+    }
+
+    pub fn identity(size: usize) -> Self {
+        let mut m = Self::zero(size, size);
+        let mut one = zero();
+        one.inner[0] = Element::new(1);
+        for i in 0..size {
+            m.entries[i * size + i] = one;
+        }
+        m
+    }
+
+    pub fn rand(rows: usize, cols: usize) -> Self {
+        Self::new(
+            rows,
+            cols,
+            (0..rows * cols).map(|_| Polynomial::rand()).collect(),
+        )
+    }
+
+    /// Matrix with the given rows, all of the same length.
+    pub fn from_rows(rows: Vec<PolyVector<N, A>>) -> Self {
+        let cols = rows.first().map_or(0, |r| r.len());
+        assert!(
+            rows.iter().all(|r| r.len() == cols),
+            "rows of different lengths"
+        );
+        let n_rows = rows.len();
+        Self::new(
+            n_rows,
+            cols,
+            rows.into_iter().flat_map(|r| r.entries).collect(),
+        )
+    }
+
+    pub fn rows(&self) -> usize {
+        self.rows
+    }
+
+    pub fn cols(&self) -> usize {
+        self.cols
+    }
+
+    pub fn entry(&self, i: usize, j: usize) -> &Polynomial<N, A> {
+        &self.entries[i * self.cols + j]
+    }
+
+    pub fn row(&self, i: usize) -> PolyVector<N, A> {
+        PolyVector::new(self.entries[i * self.cols..(i + 1) * self.cols].to_vec())
+    }
+
+    pub fn transpose(&self) -> Self {
+        let mut entries = Vec::with_capacity(self.entries.len());
+        for j in 0..self.cols {
+            for i in 0..self.rows {
+                entries.push(*self.entry(i, j));
+            }
+        }
+        Self::new(self.cols, self.rows, entries)
+    }
+
+    pub fn mul_vec(&self, v: &PolyVector<N, A>) -> PolyVector<N, A> {
+        assert_eq!(
+            self.cols,
+            v.len(),
+            "matrix has {} columns, vector length {}",
+            self.cols,
+            v.len()
+        );
+        PolyVector::new((0..self.rows).map(|i| self.row(i).dot(v)).collect())
+    }
+
+    pub fn mul_mat(&self, rhs: &Self) -> Self {
+        assert_eq!(
+            self.cols, rhs.rows,
+            "{}x{} times {}x{}",
+            self.rows, self.cols, rhs.rows, rhs.cols
+        );
+        let mut entries = Vec::with_capacity(self.rows * rhs.cols);
+        for i in 0..self.rows {
+            for j in 0..rhs.cols {
+                let mut acc = zero();
+                for k in 0..self.cols {
+                    acc = acc + *self.entry(i, k) * *rhs.entry(k, j);
+                }
+                entries.push(acc);
+            }
+        }
+        Self::new(self.rows, rhs.cols, entries)
+    }
+}
+
+impl<const N: usize, const A: u64> Zeroize for PolyMatrix<N, A> {
+    fn zeroize(&mut self) {
+        self.entries.iter_mut().for_each(Zeroize::zeroize);
+    }
+}
+
+impl<const N: usize, const A: u64> Add for PolyMatrix<N, A> {
+    type Output = Self;
+
+    fn add(self, rhs: Self) -> Self::Output {
+        assert_eq!(
+            (self.rows, self.cols),
+            (rhs.rows, rhs.cols),
+            "matrix shapes differ"
+        );
+        let entries = self
+            .entries
+            .into_iter()
+            .zip(rhs.entries)
+            .map(|(a, b)| a + b)
+            .collect();
+        Self::new(self.rows, self.cols, entries)
+    }
+}
+
+impl<const N: usize, const A: u64> Sub for PolyMatrix<N, A> {
+    type Output = Self;
+
+    fn sub(self, rhs: Self) -> Self::Output {
+        assert_eq!(
+            (self.rows, self.cols),
+            (rhs.rows, rhs.cols),
+            "matrix shapes differ"
+        );
+        let entries = self
+            .entries
+            .into_iter()
+            .zip(rhs.entries)
+            .map(|(a, b)| a - b)
+            .collect();
+        Self::new(self.rows, self.cols, entries)
+    }
+}
+
+impl<const N: usize, const A: u64> Mul<&PolyVector<N, A>> for &PolyMatrix<N, A> {
+    type Output = PolyVector<N, A>;
+
+    fn mul(self, v: &PolyVector<N, A>) -> Self::Output {
+        self.mul_vec(v)
+    }
+}
+
+impl<const N: usize, const A: u64> Mul for &PolyMatrix<N, A> {
+    type Output = PolyMatrix<N, A>;
+
+    fn mul(self, rhs: Self) -> Self::Output {
+        self.mul_mat(rhs)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const N: usize = 8;
+    const Q: u64 = 97;
+
+    #[test]
+    fn test_identity_and_shapes() {
+        let m = PolyMatrix::<N, Q>::rand(2, 3);
+        let v = PolyVector::<N, Q>::rand(3);
+        assert_eq!(&PolyMatrix::identity(2) * &m, m);
+        assert_eq!(&m * &PolyMatrix::identity(3), m);
+        assert_eq!(&PolyMatrix::identity(3) * &v, v);
+
+        let mv = &m * &v;
+        assert_eq!(mv.len(), 2);
+        assert_eq!(mv.entries[1], m.row(1).dot(&v));
+
+        let t = m.transpose();
+        assert_eq!((t.rows(), t.cols()), (3, 2));
+        assert_eq!(t.entry(2, 1), m.entry(1, 2));
+    }
+
+    #[test]
+    fn test_mul_associative() {
+        let a = PolyMatrix::<N, Q>::rand(2, 3);
+        let b = PolyMatrix::<N, Q>::rand(3, 4);
+        let v = PolyVector::<N, Q>::rand(4);
+        assert_eq!(&(&a * &b) * &v, &a * &(&b * &v));
+
+        // module-LWE shaped: b = A s + e, <b, r> = s^T A^T r + <e, r>
+        let s = PolyVector::<N, Q>::rand(3);
+        let e = PolyVector::<N, Q>::rand(2);
+        let r = PolyVector::<N, Q>::rand(2);
+        let lhs = (&a * &s + e.clone()).dot(&r);
+        let rhs = s.dot(&(&a.transpose() * &r)) + e.dot(&r);
+        assert_eq!(lhs, rhs);
+    }
+
+    #[test]
+    #[should_panic(expected = "matrix has 3 columns")]
+    fn test_shape_mismatch_panics() {
+        let m = PolyMatrix::<N, Q>::rand(2, 3);
+        let _ = &m * &PolyVector::rand(2);
+    }
+}
@@ -0,0 +1,340 @@
+//! Noise-aware evaluation helpers for exploratory workloads.
+//!
+//! Instead of running a whole computation and getting garbage back once the noise overflows Δ/2,
+//! evaluate step by step, watch the noise budget (with the secret key, so this is for experiments only)
+//! and stop right before the output would be corrupted.
+//!
+//! [`bump_and_retry`] goes the other way: it reruns a whole [`Experiment`] at larger presets until it
+//! decrypts with budget to spare, and reports which parameters were needed.
+
+use std::fmt;
+
+use crate::bfv_pke::{BfvCiphertext, BfvSecretKey};
+use crate::cancel::CancellationToken;
+
+/// Outcome of [`evaluate_within_budget`].
+#[derive(Debug)]
+pub struct PartialEvaluation<const N: usize, const Q: u64, const T: u64> {
+    /// Outputs of the steps that finished with enough budget left, in order.
+    pub completed: Vec<BfvCiphertext<N, Q, T>>,
+    /// Noise budget (bits) measured after each completed step.
+    pub budgets: Vec<u32>,
+    /// Index of the step whose output fell below the budget, if any.
+    pub exhausted_at: Option<usize>,
+    /// The token was cancelled before all steps ran.
+    pub cancelled: bool,
+    /// Number of steps that were not completed (including the exhausted one).
+    pub remaining: usize,
+}
+
+impl<const N: usize, const Q: u64, const T: u64> PartialEvaluation<N, Q, T> {
+    pub fn is_complete(&self) -> bool {
+        self.exhausted_at.is_none() && !self.cancelled
+    }
+}
+
+/// Runs `steps` one after another starting from `input`, each step taking the previous output.
+/// Stops as soon as an output has less than `min_budget` bits of noise budget left; that output is dropped
+/// and every completed output before it is returned.
+pub fn evaluate_within_budget<const N: usize, const Q: u64, const T: u64, I, F>(
+    input: BfvCiphertext<N, Q, T>,
+    steps: I,
+    sk: &BfvSecretKey<N>,
+    min_budget: u32,
+) -> PartialEvaluation<N, Q, T>
+where
+    I: IntoIterator<Item = F>,
+    F: FnOnce(&BfvCiphertext<N, Q, T>) -> BfvCiphertext<N, Q, T>,
+{
+    evaluate_cancellable(input, steps, sk, min_budget, &CancellationToken::new())
+}
+
+/// `evaluate_within_budget` that also checks `token` before every step and stops (with `cancelled` set)
+/// once it is cancelled.
+pub fn evaluate_cancellable<const N: usize, const Q: u64, const T: u64, I, F>(
+    input: BfvCiphertext<N, Q, T>,
+    steps: I,
+    sk: &BfvSecretKey<N>,
+    min_budget: u32,
+    token: &CancellationToken,
+) -> PartialEvaluation<N, Q, T>
+where
+    I: IntoIterator<Item = F>,
+    F: FnOnce(&BfvCiphertext<N, Q, T>) -> BfvCiphertext<N, Q, T>,
+{
+    let mut steps = steps.into_iter().peekable();
+    let mut completed = Vec::new();
+    let mut budgets = Vec::new();
+    let mut current = input;
+
+    while steps.peek().is_some() {
+        if token.is_cancelled() {
+            return PartialEvaluation {
+                exhausted_at: None,
+                cancelled: true,
+                remaining: steps.count(),
+                completed,
+                budgets,
+            };
+        }
+        let step = steps.next().unwrap();
+        let next = step(&current);
+        let budget = next.noise_budget(sk);
+        if budget < min_budget {
+            return PartialEvaluation {
+                exhausted_at: Some(completed.len()),
+                cancelled: false,
+                remaining: 1 + steps.count(),
+                completed,
+                budgets,
+            };
+        }
+        budgets.push(budget);
+        completed.push(next.clone());
+        current = next;
+    }
+
+    PartialEvaluation {
+        completed,
+        budgets,
+        exhausted_at: None,
+        cancelled: false,
+        remaining: 0,
+    }
+}
+
+/// One parameter set tried by [`bump_and_retry`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ParamSet {
+    pub n: usize,
+    pub q: u64,
+    pub t: u64,
+}
+
+impl fmt::Display for ParamSet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.q.is_power_of_two() {
+            write!(f, "n={} q=2^{} t={}", self.n, self.q.ilog2(), self.t)
+        } else {
+            write!(f, "n={} q={} t={}", self.n, self.q, self.t)
+        }
+    }
+}
+
+/// What an [`Experiment`] reports after one run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Trial {
+    /// Every output decrypted to the plaintext reference.
+    pub correct: bool,
+    /// Smallest noise budget (bits) over the outputs.
+    pub budget: u32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TrialOutcome {
+    Ok { budget: u32 },
+    BudgetExhausted { budget: u32 },
+    DecryptionFailure,
+}
+
+/// A computation that runs at any (n, q) for a fixed plaintext modulus: it generates keys, encrypts,
+/// evaluates and checks the outputs against its plaintext reference itself.
+pub trait Experiment<const T: u64> {
+    fn run<const N: usize, const Q: u64>(&self) -> Trial;
+}
+
+/// Every attempt of [`bump_and_retry`] in order, and the first parameter set that passed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BumpReport {
+    pub attempts: Vec<(ParamSet, TrialOutcome)>,
+    pub chosen: Option<ParamSet>,
+}
+
+impl fmt::Display for BumpReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (params, outcome) in self.attempts.iter() {
+            match outcome {
+                TrialOutcome::Ok { budget } => writeln!(f, "{params}: ok, {budget} bits left")?,
+                TrialOutcome::BudgetExhausted { budget } => {
+                    writeln!(f, "{params}: only {budget} bits of budget left")?
+                }
+                TrialOutcome::DecryptionFailure => writeln!(f, "{params}: decryption failure")?,
+            }
+        }
+        match self.chosen {
+            Some(params) => write!(f, "chosen {params}"),
+            None => write!(f, "no preset was large enough"),
+        }
+    }
+}
+
+fn attempt<const T: u64, E: Experiment<T>, const N: usize, const Q: u64>(
+    experiment: &E,
+    min_budget: u32,
+    report: &mut BumpReport,
+) -> bool {
+    let params = ParamSet { n: N, q: Q, t: T };
+    let trial = experiment.run::<N, Q>();
+    let outcome = if !trial.correct {
+        TrialOutcome::DecryptionFailure
+    } else if trial.budget < min_budget {
+        TrialOutcome::BudgetExhausted {
+            budget: trial.budget,
+        }
+    } else {
+        TrialOutcome::Ok {
+            budget: trial.budget,
+        }
+    };
+    report.attempts.push((params, outcome));
+    let ok = matches!(outcome, TrialOutcome::Ok { .. });
+    if ok {
+        report.chosen = Some(params);
+    }
+    ok
+}
+
+/// Runs `experiment` at the smallest preset and, while it fails to decrypt or ends with less than
+/// `min_budget` bits of budget, again at the next larger one. Presets grow n and q together, from
+/// (16, 2^20) to (512, 2^50); not a security estimate, only enough room for the noise.
+pub fn bump_and_retry<const T: u64, E: Experiment<T>>(
+    experiment: &E,
+    min_budget: u32,
+) -> BumpReport {
+    let mut report = BumpReport {
+        attempts: Vec::new(),
+        chosen: None,
+    };
+    let _ = attempt::<T, E, 16, { 1 << 20 }>(experiment, min_budget, &mut report)
+        || attempt::<T, E, 32, { 1 << 24 }>(experiment, min_budget, &mut report)
+        || attempt::<T, E, 64, { 1 << 28 }>(experiment, min_budget, &mut report)
+        || attempt::<T, E, 128, { 1 << 32 }>(experiment, min_budget, &mut report)
+        || attempt::<T, E, 256, { 1 << 40 }>(experiment, min_budget, &mut report)
+        || attempt::<T, E, 512, { 1 << 50 }>(experiment, min_budget, &mut report);
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bfv_pke::Bfv;
+    use crate::polynomial::{Element, Polynomial};
+
+    #[test]
+    fn test_stops_before_corruption() {
+        const T: u64 = 3;
+        const N: usize = 4;
+        const Q: u64 = 1 << 20;
+
+        let (bfv, sk) = Bfv::<N, Q, T>::keygen().unwrap();
+        let m = Polynomial::<N, T>::new([
+            Element::new(1),
+            Element::new(2),
+            Element::new(0),
+            Element::new(1),
+        ]);
+        let ct = bfv.encrypt(m);
+
+        // every doubling doubles the noise too, so this can't finish 40 steps
+        let steps = (0..40).map(|_| |c: &BfvCiphertext<N, Q, T>| c.clone() + c.clone());
+        let res = evaluate_within_budget(ct, steps, &sk, 1);
+
+        assert!(!res.is_complete());
+        assert_eq!(res.exhausted_at, Some(res.completed.len()));
+        assert_eq!(res.completed.len() + res.remaining, 40);
+
+        let mut expected = m;
+        for out in res.completed {
+            expected = expected + expected;
+            assert_eq!(out.decrypt(&sk), expected);
+        }
+    }
+
+    #[test]
+    fn test_completes_when_budget_allows() {
+        const T: u64 = 2;
+        const N: usize = 4;
+        const Q: u64 = 1 << 20;
+
+        let (bfv, sk) = Bfv::<N, Q, T>::keygen().unwrap();
+        let ct = bfv.encrypt(Polynomial::<N, T>::rand());
+
+        let steps = (0..3).map(|_| |c: &BfvCiphertext<N, Q, T>| c.clone() + c.clone());
+        let res = evaluate_within_budget(ct, steps, &sk, 1);
+
+        assert!(res.is_complete());
+        assert_eq!(res.completed.len(), 3);
+        assert_eq!(res.remaining, 0);
+    }
+
+    #[test]
+    fn test_cancelled_between_steps() {
+        const T: u64 = 2;
+        const N: usize = 4;
+        const Q: u64 = 1 << 20;
+
+        let (bfv, sk) = Bfv::<N, Q, T>::keygen().unwrap();
+        let ct = bfv.encrypt(Polynomial::<N, T>::rand());
+
+        // the third step cancels, so the fourth never runs
+        let token = CancellationToken::new();
+        let steps = (0..5).map(|i| {
+            let token = token.clone();
+            move |c: &BfvCiphertext<N, Q, T>| {
+                if i == 2 {
+                    token.cancel();
+                }
+                c.clone() + c.clone()
+            }
+        });
+        let res = evaluate_cancellable(ct, steps, &sk, 0, &token);
+
+        assert!(res.cancelled);
+        assert!(!res.is_complete());
+        assert_eq!(res.exhausted_at, None);
+        assert_eq!(res.completed.len(), 3);
+        assert_eq!(res.remaining, 2);
+    }
+
+    /// `doublings` times c + c, checked against the plaintext.
+    struct Doublings(usize);
+
+    impl Experiment<3> for Doublings {
+        fn run<const N: usize, const Q: u64>(&self) -> Trial {
+            let (bfv, sk) = Bfv::<N, Q, 3>::keygen().unwrap();
+            let m = Polynomial::<N, 3>::rand();
+            let mut ct = bfv.encrypt(m);
+            let mut expected = m;
+            for _ in 0..self.0 {
+                ct = ct.clone() + ct;
+                expected = expected + expected;
+            }
+            Trial {
+                budget: ct.noise_budget(&sk),
+                correct: ct.decrypt(&sk) == expected,
+            }
+        }
+    }
+
+    #[test]
+    fn test_bump_and_retry() {
+        let report = bump_and_retry(&Doublings(24), 4);
+        let chosen = report.chosen.unwrap();
+        assert!(chosen.q > 1 << 24);
+        assert_eq!(report.attempts.last().unwrap().0, chosen);
+        assert!(matches!(
+            report.attempts.last().unwrap().1,
+            TrialOutcome::Ok { budget } if budget >= 4
+        ));
+        assert!(
+            report.attempts[..report.attempts.len() - 1]
+                .iter()
+                .all(|(_, o)| !matches!(o, TrialOutcome::Ok { .. }))
+        );
+
+        // nothing on the ladder holds 60 doublings
+        let report = bump_and_retry(&Doublings(60), 0);
+        assert_eq!(report.chosen, None);
+        assert_eq!(report.attempts.len(), 6);
+    }
+}
@@ -0,0 +1,365 @@
+//! Negacyclic NTT over Z_q[x]/(x^n+1).
+//! Needs n a power of two and q prime with q ≡ 1 mod 2n.
+//! https://eprint.iacr.org/2016/504.pdf (Algorithms 1 and 2)
+
+use crate::field::{ConstModulus, Modulus, PrimeField, PrimeModulus, Ring, is_prime};
+use crate::polynomial::{Element, Polynomial};
+use zeroize::Zeroize;
+
+pub fn pow_mod(base: u64, exp: u64, q: u64) -> u64 {
+    Modulus::new(q).pow(base, exp)
+}
+
+/// Whether x^n+1 mod q splits completely, i.e. the negacyclic NTT exists.
+pub fn is_ntt_friendly(n: usize, q: u64) -> bool {
+    n.is_power_of_two() && is_prime(q) && (q - 1).is_multiple_of(2 * n as u64)
+}
+
+/// Some primitive 2n-th root of unity mod q.
+pub fn primitive_root_2n(n: usize, q: u64) -> Option<u64> {
+    if !is_ntt_friendly(n, q) {
+        return None;
+    }
+    let exp = (q - 1) / (2 * n as u64);
+    // psi^n = -1 means the order is exactly 2n since 2n is a power of two
+    (2..q)
+        .map(|g| pow_mod(g, exp, q))
+        .find(|&psi| pow_mod(psi, n as u64, q) == q - 1)
+}
+
+/// The largest q < 2^bits that is NTT friendly for n, `None` if there is none (also for bits = 0).
+pub fn largest_ntt_prime(n: usize, bits: u32) -> Option<u64> {
+    let step = 2 * n as u64;
+    let top = 1u64.checked_shl(bits).map_or(u64::MAX, |b| b - 1);
+    (1..=top.checked_sub(1)? / step)
+        .rev()
+        .map(|k| k * step + 1)
+        .find(|&q| is_prime(q))
+}
+
+pub(crate) fn bit_reverse(x: usize, bits: u32) -> usize {
+    if bits == 0 {
+        0
+    } else {
+        x.reverse_bits() >> (usize::BITS - bits)
+    }
+}
+
+/// psi^bitrev(i), psi^-bitrev(i) for i < n and n^-1, for a primitive 2n-th root psi mod q.
+fn twiddles<F: PrimeField>(field: F, n: usize, psi: u64) -> Option<(Vec<u64>, Vec<u64>, u64)> {
+    let psi_inv = field.inv(psi)?;
+    let bits = n.ilog2();
+    let psi_rev = (0..n)
+        .map(|i| field.pow(psi, bit_reverse(i, bits) as u64))
+        .collect();
+    let psi_inv_rev = (0..n)
+        .map(|i| field.pow(psi_inv, bit_reverse(i, bits) as u64))
+        .collect();
+    Some((psi_rev, psi_inv_rev, field.inv(n as u64)?))
+}
+
+/// Coefficients -> evaluations (bit reversed order), in place.
+fn forward_in_place<R: Ring>(ring: R, psi_rev: &[u64], a: &mut [u64]) {
+    let n = a.len();
+    let mut t = n;
+    let mut m = 1;
+    while m < n {
+        t /= 2;
+        for i in 0..m {
+            let j_1 = 2 * i * t;
+            let s = psi_rev[m + i];
+            for j in j_1..j_1 + t {
+                let u = a[j];
+                let v = ring.mul(a[j + t], s);
+                a[j] = ring.add(u, v);
+                a[j + t] = ring.sub(u, v);
+            }
+        }
+        m *= 2;
+    }
+}
+
+/// Evaluations (bit reversed order) -> coefficients, in place.
+fn inverse_in_place<R: Ring>(ring: R, psi_inv_rev: &[u64], n_inv: u64, a: &mut [u64]) {
+    let mut t = 1;
+    let mut m = a.len();
+    while m > 1 {
+        let h = m / 2;
+        let mut j_1 = 0;
+        for i in 0..h {
+            let s = psi_inv_rev[h + i];
+            for j in j_1..j_1 + t {
+                let u = a[j];
+                let v = a[j + t];
+                a[j] = ring.add(u, v);
+                a[j + t] = ring.mul(ring.sub(u, v), s);
+            }
+            j_1 += 2 * t;
+        }
+        t *= 2;
+        m = h;
+    }
+    for x in a.iter_mut() {
+        *x = ring.mul(*x, n_inv);
+    }
+}
+
+/// Twiddle factors for one (N, Q) pair.
+#[derive(Debug, Clone)]
+pub struct NttTable<const N: usize, const Q: u64> {
+    /// psi^bitrev(i)
+    psi_rev: Vec<u64>,
+    /// psi^-bitrev(i)
+    psi_inv_rev: Vec<u64>,
+    n_inv: u64,
+}
+
+impl<const N: usize, const Q: u64> NttTable<N, Q> {
+    /// `None` if (N, Q) is not NTT friendly.
+    pub fn new() -> Option<Self> {
+        let psi = primitive_root_2n(N, Q)?;
+        let (psi_rev, psi_inv_rev, n_inv) = twiddles(PrimeModulus::new(Q)?, N, psi)?;
+        Some(Self {
+            psi_rev,
+            psi_inv_rev,
+            n_inv,
+        })
+    }
+
+    /// Coefficients -> evaluations (bit reversed order).
+    pub fn forward(&self, p: &Polynomial<N, Q>) -> Polynomial<N, Q> {
+        let mut a = p.inner.map(|e| e.value());
+        forward_in_place(ConstModulus::<Q>, &self.psi_rev, &mut a);
+        Self::from_values(&a)
+    }
+
+    /// Evaluations (bit reversed order) -> coefficients.
+    pub fn inverse(&self, p: &Polynomial<N, Q>) -> Polynomial<N, Q> {
+        let mut a = p.inner.map(|e| e.value());
+        inverse_in_place(ConstModulus::<Q>, &self.psi_inv_rev, self.n_inv, &mut a);
+        Self::from_values(&a)
+    }
+
+    /// Negacyclic product through the evaluation domain.
+    pub fn mul(&self, a: &Polynomial<N, Q>, b: &Polynomial<N, Q>) -> Polynomial<N, Q> {
+        let a_hat = self.forward(a);
+        let b_hat = self.forward(b);
+        self.inverse(&pointwise(&a_hat, &b_hat))
+    }
+
+    fn from_values(a: &[u64]) -> Polynomial<N, Q> {
+        Polynomial::new(core::array::from_fn(|i| Element::new(a[i] as i64)))
+    }
+}
+
+/// Twiddle factors for degree n and a prime only known at runtime, e.g. one prime of an RNS basis.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PrimeNttTable {
+    q: PrimeModulus,
+    psi_rev: Vec<u64>,
+    psi_inv_rev: Vec<u64>,
+    n_inv: u64,
+}
+
+impl PrimeNttTable {
+    /// `None` if (n, q) is not NTT friendly.
+    pub fn new(n: usize, q: PrimeModulus) -> Option<Self> {
+        let psi = primitive_root_2n(n, q.modulus())?;
+        let (psi_rev, psi_inv_rev, n_inv) = twiddles(q, n, psi)?;
+        Some(Self {
+            q,
+            psi_rev,
+            psi_inv_rev,
+            n_inv,
+        })
+    }
+
+    pub fn degree(&self) -> usize {
+        self.psi_rev.len()
+    }
+
+    /// Negacyclic product of reduced coefficients mod q through the evaluation domain. N has to be the
+    /// degree of the table.
+    pub fn mul<const N: usize>(&self, a: &[u64; N], b: &[u64; N]) -> [u64; N] {
+        assert_eq!(N, self.degree(), "table is for another degree");
+        let (mut a, mut b) = (*a, *b);
+        forward_in_place(self.q, &self.psi_rev, &mut a);
+        forward_in_place(self.q, &self.psi_rev, &mut b);
+        let mut c = core::array::from_fn(|i| self.q.mul(a[i], b[i]));
+        inverse_in_place(self.q, &self.psi_inv_rev, self.n_inv, &mut c);
+        c
+    }
+}
+
+/// Coefficient-wise product, the ring product for polynomials in the evaluation domain.
+pub fn pointwise<const N: usize, const Q: u64>(
+    a: &Polynomial<N, Q>,
+    b: &Polynomial<N, Q>,
+) -> Polynomial<N, Q> {
+    Polynomial::new(core::array::from_fn(|i| a.inner[i] * b.inner[i]))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Domain {
+    Coefficient,
+    Evaluation,
+}
+
+/// A polynomial tagged with the domain it is currently in. Conversions are lazy: asking for the domain it
+/// is already in is free, so long lived operands (public keys, relinearization keys) can be moved to the
+/// evaluation domain once and reused by every product.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct NttPolynomial<const N: usize, const Q: u64> {
+    poly: Polynomial<N, Q>,
+    domain: Domain,
+}
+
+impl<const N: usize, const Q: u64> Zeroize for NttPolynomial<N, Q> {
+    fn zeroize(&mut self) {
+        self.poly.zeroize();
+    }
+}
+
+impl<const N: usize, const Q: u64> NttPolynomial<N, Q> {
+    pub fn coefficient(poly: Polynomial<N, Q>) -> Self {
+        Self {
+            poly,
+            domain: Domain::Coefficient,
+        }
+    }
+
+    /// Wraps values that are already NTT evaluations.
+    pub fn evaluation(poly_hat: Polynomial<N, Q>) -> Self {
+        Self {
+            poly: poly_hat,
+            domain: Domain::Evaluation,
+        }
+    }
+
+    pub fn domain(&self) -> Domain {
+        self.domain
+    }
+
+    /// The raw values, in whatever domain `domain` says.
+    pub fn as_polynomial(&self) -> &Polynomial<N, Q> {
+        &self.poly
+    }
+
+    pub fn into_evaluation(self, table: &NttTable<N, Q>) -> Self {
+        match self.domain {
+            Domain::Evaluation => self,
+            Domain::Coefficient => Self::evaluation(table.forward(&self.poly)),
+        }
+    }
+
+    pub fn into_coefficient(self, table: &NttTable<N, Q>) -> Self {
+        match self.domain {
+            Domain::Coefficient => self,
+            Domain::Evaluation => Self::coefficient(table.inverse(&self.poly)),
+        }
+    }
+
+    /// Coefficients, running the inverse NTT only if needed.
+    pub fn to_coefficients(&self, table: &NttTable<N, Q>) -> Polynomial<N, Q> {
+        self.into_coefficient(table).poly
+    }
+
+    /// Ring product, left in the evaluation domain.
+    pub fn mul(&self, rhs: &Self, table: &NttTable<N, Q>) -> Self {
+        let a = self.into_evaluation(table);
+        let b = rhs.into_evaluation(table);
+        Self::evaluation(pointwise(&a.poly, &b.poly))
+    }
+
+    /// Sum, in the domain of `self` (the NTT is linear so either domain works).
+    pub fn add(&self, rhs: &Self, table: &NttTable<N, Q>) -> Self {
+        let rhs = match self.domain {
+            Domain::Coefficient => rhs.into_coefficient(table),
+            Domain::Evaluation => rhs.into_evaluation(table),
+        };
+        Self {
+            poly: self.poly + rhs.poly,
+            domain: self.domain,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ntt_friendly() {
+        assert!(is_ntt_friendly(4, 17));
+        assert!(is_ntt_friendly(256, 7681));
+        assert!(is_ntt_friendly(1024, 12_289));
+        // 32 is not prime, 97 - 1 is not divisible by 128
+        assert!(!is_ntt_friendly(4, 32));
+        assert!(!is_ntt_friendly(64, 97));
+        assert!(NttTable::<4, 32>::new().is_none());
+        assert_eq!(largest_ntt_prime(1024, 15), Some(18_433));
+        assert_eq!(largest_ntt_prime(8, 14), Some(16_369));
+        assert_eq!(largest_ntt_prime(1024, 11), None);
+        assert_eq!(largest_ntt_prime(8, 0), None);
+        assert_eq!(largest_ntt_prime(8, 1), None);
+    }
+
+    #[test]
+    fn test_ntt_roundtrip() {
+        let table = NttTable::<256, 7681>::new().unwrap();
+        let p = Polynomial::<256, 7681>::rand();
+        assert_eq!(table.inverse(&table.forward(&p)), p);
+    }
+
+    #[test]
+    fn test_ntt_mul_matches_schoolbook() {
+        fn check<const N: usize, const Q: u64>() {
+            let table = NttTable::<N, Q>::new().unwrap();
+            let a = Polynomial::<N, Q>::rand();
+            let b = Polynomial::<N, Q>::rand();
+            assert_eq!(table.mul(&a, &b), a.mul_schoolbook(&b));
+        }
+        check::<4, 17>();
+        check::<8, 97>();
+        check::<64, 12_289>();
+        check::<256, 7681>();
+    }
+
+    #[test]
+    fn test_prime_table_mul() {
+        let q = PrimeModulus::new(12_289).unwrap();
+        let table = PrimeNttTable::new(64, q).unwrap();
+        let a = Polynomial::<64, 12_289>::rand();
+        let b = Polynomial::<64, 12_289>::rand();
+        let values = |p: &Polynomial<64, 12_289>| p.inner.map(|e| e.value());
+        assert_eq!(
+            table.mul(&values(&a), &values(&b)),
+            values(&a.mul_schoolbook(&b))
+        );
+        assert!(PrimeNttTable::new(128, PrimeModulus::new(97).unwrap()).is_none());
+    }
+
+    #[test]
+    fn test_ntt_polynomial_domains() {
+        let table = NttTable::<64, 12_289>::new().unwrap();
+        let a = Polynomial::<64, 12_289>::rand();
+        let b = Polynomial::<64, 12_289>::rand();
+
+        let a_ntt = NttPolynomial::coefficient(a);
+        let a_hat = a_ntt.into_evaluation(&table);
+        assert_eq!(a_hat.domain(), Domain::Evaluation);
+        // already there, nothing to do
+        assert_eq!(a_hat.into_evaluation(&table), a_hat);
+        assert_eq!(a_hat.to_coefficients(&table), a);
+
+        let b_ntt = NttPolynomial::coefficient(b);
+        let prod = a_hat.mul(&b_ntt, &table);
+        assert_eq!(prod.domain(), Domain::Evaluation);
+        assert_eq!(prod.to_coefficients(&table), a * b);
+
+        // mixed domains add up in the domain of the left operand
+        let sum = a_ntt.add(&b_ntt.into_evaluation(&table), &table);
+        assert_eq!(sum.domain(), Domain::Coefficient);
+        assert_eq!(*sum.as_polynomial(), a + b);
+    }
+}
@@ -0,0 +1,177 @@
+//! LWE to RLWE packing (Chen, Dai, Kim, Song, https://eprint.iacr.org/2020/015, Algorithm 2): many LWE
+//! ciphertexts under the coefficient vector of a BFV secret become one BFV ciphertext, the inverse of
+//! `extract_lwe`.
+//!
+//! Each LWE ciphertext turns into an RLWE ciphertext whose constant coefficient is its phase, the other
+//! coefficients are garbage. Pairs are merged with x^(n/l) and the automorphism x -> x^(l+1), which cancels
+//! the garbage in between and doubles what is kept, then the trace over the remaining automorphisms clears
+//! the coefficients the packed values do not occupy. Everything ends up multiplied by n, undone by
+//! multiplying by n^-1 beforehand.
+
+use crate::backend::RingBackend;
+use crate::bfv_pke::{BfvCiphertext, EvalError, Evaluator};
+use crate::lwe::LweCipher;
+use crate::ntt::pow_mod;
+use crate::polynomial::{Element, Polynomial};
+
+/// Galois elements `pack_lwes` needs keys for: 2^j + 1 for j = 1..=log2(n).
+pub fn packing_elements<const N: usize>() -> Vec<usize> {
+    (1..=N.ilog2()).map(|j| (1 << j) + 1).collect()
+}
+
+/// n^-1, exact mod q when q is odd. For even q it is n^-1 mod t (t odd) in (-t/2, t/2], which is exact on
+/// the plaintext but multiplies the noise by up to t / 2.
+fn inverse_n<const N: usize, const Q: u64, const T: u64>() -> Element<Q> {
+    // (m + 1) / 2 is the inverse of 2 mod an odd m
+    let inv = |m: u64| pow_mod(m.div_ceil(2), N.ilog2() as u64, m);
+    if Q % 2 == 1 {
+        return Element::new(inv(Q) as i64);
+    }
+    assert!(T % 2 == 1, "packing needs an odd q or an odd t");
+    let u = inv(T);
+    Element::new(if u > T / 2 {
+        u as i64 - T as i64
+    } else {
+        u as i64
+    })
+}
+
+impl<const N: usize, const Q: u64, const T: u64, B: RingBackend> Evaluator<N, Q, T, B> {
+    /// BFV ciphertext with the plaintext of `lwes[j]` at coefficient j n / l, l the number of ciphertexts
+    /// rounded up to a power of two, and zero everywhere else. The LWE ciphertexts have to be under the
+    /// coefficient vector of the BFV secret, as `extract_lwe` returns them. Needs the Galois keys of
+    /// [`packing_elements`]; every level adds key switching noise, the first ones doubled log2(n) times.
+    pub fn pack_lwes(
+        &self,
+        lwes: &[LweCipher<N, Q, T>],
+    ) -> Result<BfvCiphertext<N, Q, T, B>, EvalError> {
+        if lwes.is_empty() || lwes.len() > N {
+            return Err(EvalError::PackCount {
+                count: lwes.len(),
+                n: N,
+            });
+        }
+        let ctx = self.galois_key(3)?.context();
+        let scale = inverse_n::<N, Q, T>();
+        let zero = Polynomial::new([Element::new(0); N]);
+        let mut cts: Vec<_> = lwes
+            .iter()
+            .map(|lwe| {
+                // constant coefficient of c_2 s is <a, s>
+                let c_2 = Polynomial::new(core::array::from_fn(|i| {
+                    if i == 0 {
+                        lwe.a.inner[0]
+                    } else {
+                        -lwe.a.inner[N - i]
+                    }
+                }));
+                let mut c_1 = zero;
+                c_1.inner[0] = lwe.b;
+                BfvCiphertext::new(c_1 * scale, c_2 * scale, ctx.clone())
+            })
+            .collect();
+        cts.resize(
+            lwes.len().next_power_of_two(),
+            BfvCiphertext::new(zero, zero, ctx.clone()),
+        );
+
+        let mut ct = self.pack_tree(&cts)?;
+        let mut l = 2 * cts.len();
+        while l <= N {
+            ct = ct.clone() + self.rotate(&ct, l + 1)?;
+            l *= 2;
+        }
+        Ok(ct)
+    }
+
+    /// l ciphertexts (a power of two) to one with ciphertext j at coefficient j n / l, times l.
+    fn pack_tree(
+        &self,
+        cts: &[BfvCiphertext<N, Q, T, B>],
+    ) -> Result<BfvCiphertext<N, Q, T, B>, EvalError> {
+        if let [ct] = cts {
+            return Ok(ct.clone());
+        }
+        let l = cts.len();
+        let half = |parity| {
+            let part: Vec<_> = cts.iter().skip(parity).step_by(2).cloned().collect();
+            self.pack_tree(&part)
+        };
+        let (even, odd) = (half(0)?, half(1)?);
+        let mut x = Polynomial::<N, Q>::new([Element::new(0); N]);
+        x.inner[N / l] = Element::new(1);
+        // (even + x odd) + tau_{l+1}(even - x odd)
+        let diff = even.clone() + &odd * -x;
+        Ok(even + &odd * x + self.rotate(&diff, l + 1)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bfv_pke::Bfv;
+
+    const N: usize = 16;
+    const T: u64 = 17;
+
+    #[test]
+    fn test_roundtrip() {
+        const Q: u64 = 1 << 50;
+        let (bfv, sk) = Bfv::<N, Q, T>::keygen().unwrap();
+        let evaluator = Evaluator::new().with_galois_keys(
+            bfv.gen_galois_keys(&sk, &packing_elements::<N>(), 10)
+                .unwrap(),
+        );
+        let m = Polynomial::<N, T>::rand();
+        let ct = bfv.encrypt(m);
+        let lwes: Vec<_> = (0..N).map(|k| ct.extract_lwe(k)).collect();
+        assert_eq!(evaluator.pack_lwes(&lwes).unwrap().decrypt(&sk), m);
+
+        // 3 ciphertexts pad to 4, at stride 4
+        let packed = evaluator.pack_lwes(&lwes[..3]).unwrap().decrypt(&sk);
+        for (i, c) in packed.inner.iter().enumerate() {
+            let expected = if i % 4 == 0 && i < 12 {
+                m.inner[i / 4].value()
+            } else {
+                0
+            };
+            assert_eq!(c.value(), expected, "coefficient {i}");
+        }
+    }
+
+    #[test]
+    fn test_odd_modulus() {
+        /// Prime, so n^-1 mod q exists.
+        const Q: u64 = 4_294_957_057;
+        let (bfv, sk) = Bfv::<N, Q, T>::keygen().unwrap();
+        let evaluator = Evaluator::new().with_galois_keys(
+            bfv.gen_galois_keys(&sk, &packing_elements::<N>(), 4)
+                .unwrap(),
+        );
+        let m = Polynomial::<N, T>::rand();
+        let ct = bfv.encrypt(m);
+        let lwes: Vec<_> = (0..N).rev().map(|k| ct.extract_lwe(k)).collect();
+        let packed = evaluator.pack_lwes(&lwes).unwrap().decrypt(&sk);
+        for i in 0..N {
+            assert_eq!(packed.inner[i], m.inner[N - 1 - i]);
+        }
+    }
+
+    #[test]
+    fn test_missing_keys() {
+        let (bfv, sk) = Bfv::<N, { 1 << 50 }, T>::keygen().unwrap();
+        let lwe = bfv.encrypt(Polynomial::rand()).extract_lwe(0);
+        assert_eq!(
+            Evaluator::<N, { 1 << 50 }, T>::new()
+                .pack_lwes(std::slice::from_ref(&lwe))
+                .unwrap_err(),
+            EvalError::MissingGaloisKey { element: 3 }
+        );
+        let evaluator =
+            Evaluator::new().with_galois_keys(bfv.gen_galois_keys(&sk, &[3, 5, 9], 10).unwrap());
+        assert_eq!(
+            evaluator.pack_lwes(&[lwe]).unwrap_err(),
+            EvalError::MissingGaloisKey { element: 17 }
+        );
+    }
+}
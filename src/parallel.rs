@@ -0,0 +1,237 @@
+//! Thread pool behind the `parallel` feature. The crate keeps its own rayon pool instead of using the global
+//! one, so setting the thread count here doesn't affect the rest of the application.
+//! Without the feature everything in here runs sequentially on the calling thread.
+//!
+//! [`ThreadConfig`] sets the thread count, can hand the work to the caller's rayon pool instead, and
+//! switches parallelism off per [`Op`], for servers that already spread requests over their own threads.
+
+#[cfg(feature = "parallel")]
+use std::sync::OnceLock;
+#[cfg(feature = "parallel")]
+use std::sync::atomic::{AtomicBool, AtomicU8, Ordering};
+
+#[cfg(feature = "parallel")]
+static POOL: OnceLock<rayon::ThreadPool> = OnceLock::new();
+#[cfg(feature = "parallel")]
+static OPS: AtomicU8 = AtomicU8::new(Op::ALL);
+#[cfg(feature = "parallel")]
+static CALLER_POOL: AtomicBool = AtomicBool::new(false);
+
+/// The parallel paths, each can be switched off on its own.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Op {
+    /// `Polynomial` products from `PARALLEL_THRESHOLD` coefficients on, and `mul_parallel`.
+    PolyMul = 1,
+    /// The two ciphertext halves of BFV encryption and plaintext multiplication.
+    Bfv = 2,
+    /// The blocks of `Pasta::encrypt_parallel` and `decrypt_parallel`.
+    Keystream = 4,
+}
+
+impl Op {
+    #[cfg(feature = "parallel")]
+    const ALL: u8 = Op::PolyMul as u8 | Op::Bfv as u8 | Op::Keystream as u8;
+}
+
+/// Thread count, pool and per op switches, set process wide with [`ThreadConfig::apply`].
+#[cfg(feature = "parallel")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ThreadConfig {
+    num_threads: Option<usize>,
+    caller_pool: bool,
+    ops: u8,
+}
+
+#[cfg(feature = "parallel")]
+impl Default for ThreadConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(feature = "parallel")]
+impl ThreadConfig {
+    /// Every op parallel on the crate pool, one thread per cpu.
+    pub fn new() -> Self {
+        Self {
+            num_threads: None,
+            caller_pool: false,
+            ops: Op::ALL,
+        }
+    }
+
+    /// The settings in effect.
+    pub fn current() -> Self {
+        Self {
+            num_threads: POOL.get().map(|p| p.current_num_threads()),
+            caller_pool: CALLER_POOL.load(Ordering::Relaxed),
+            ops: OPS.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Size of the crate pool, 0 means one per cpu. Only takes effect before the first parallel op.
+    pub fn num_threads(mut self, num_threads: usize) -> Self {
+        self.num_threads = Some(num_threads);
+        self
+    }
+
+    /// Runs parallel ops on the rayon pool of the calling thread (the global one outside any pool)
+    /// instead of the crate pool.
+    pub fn caller_pool(mut self, caller_pool: bool) -> Self {
+        self.caller_pool = caller_pool;
+        self
+    }
+
+    pub fn parallel(mut self, op: Op, parallel: bool) -> Self {
+        if parallel {
+            self.ops |= op as u8;
+        } else {
+            self.ops &= !(op as u8);
+        }
+        self
+    }
+
+    /// Every op on the calling thread.
+    pub fn sequential(mut self) -> Self {
+        self.ops = 0;
+        self
+    }
+
+    pub fn is_parallel(&self, op: Op) -> bool {
+        self.ops & op as u8 != 0
+    }
+
+    /// Makes this the process wide configuration. The switches can change at any time, the thread
+    /// count fails with `AlreadyInitialized` once the crate pool exists.
+    pub fn apply(self) -> Result<(), ThreadPoolError> {
+        if let Some(n) = self.num_threads {
+            set_num_threads(n)?;
+        }
+        CALLER_POOL.store(self.caller_pool, Ordering::Relaxed);
+        OPS.store(self.ops, Ordering::Relaxed);
+        Ok(())
+    }
+}
+
+/// Whether `op` runs in parallel, always false without the `parallel` feature.
+pub fn is_parallel(op: Op) -> bool {
+    #[cfg(feature = "parallel")]
+    return OPS.load(Ordering::Relaxed) & op as u8 != 0;
+    #[cfg(not(feature = "parallel"))]
+    {
+        let _ = op;
+        false
+    }
+}
+
+#[cfg(feature = "parallel")]
+#[derive(Debug)]
+pub enum ThreadPoolError {
+    /// The pool was already created, either by a previous call or by the first parallel op.
+    AlreadyInitialized,
+    Build(rayon::ThreadPoolBuildError),
+}
+
+#[cfg(feature = "parallel")]
+impl std::fmt::Display for ThreadPoolError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ThreadPoolError::AlreadyInitialized => write!(f, "thread pool already initialized"),
+            ThreadPoolError::Build(e) => write!(f, "failed to build thread pool: {e}"),
+        }
+    }
+}
+
+#[cfg(feature = "parallel")]
+impl std::error::Error for ThreadPoolError {}
+
+/// Sets the number of worker threads, 0 means one per cpu. Has to run before the first parallel op.
+#[cfg(feature = "parallel")]
+pub fn set_num_threads(num_threads: usize) -> Result<(), ThreadPoolError> {
+    let pool = rayon::ThreadPoolBuilder::new()
+        .num_threads(num_threads)
+        .build()
+        .map_err(ThreadPoolError::Build)?;
+    POOL.set(pool)
+        .map_err(|_| ThreadPoolError::AlreadyInitialized)
+}
+
+#[cfg(feature = "parallel")]
+fn pool() -> &'static rayon::ThreadPool {
+    POOL.get_or_init(|| {
+        rayon::ThreadPoolBuilder::new()
+            .build()
+            .expect("failed to build rlattice thread pool")
+    })
+}
+
+pub fn num_threads() -> usize {
+    #[cfg(feature = "parallel")]
+    if CALLER_POOL.load(Ordering::Relaxed) {
+        return rayon::current_num_threads();
+    }
+    #[cfg(feature = "parallel")]
+    return pool().current_num_threads();
+    #[cfg(not(feature = "parallel"))]
+    1
+}
+
+/// Runs `f` inside the crate pool so its rayon iterators use our workers, or directly with
+/// `ThreadConfig::caller_pool`.
+#[cfg(feature = "parallel")]
+pub(crate) fn install<R: Send>(f: impl FnOnce() -> R + Send) -> R {
+    if CALLER_POOL.load(Ordering::Relaxed) {
+        f()
+    } else {
+        pool().install(f)
+    }
+}
+
+/// Runs both closures, concurrently when `op` is parallel.
+#[cfg(feature = "bfv")]
+pub(crate) fn join<RA: Send, RB: Send>(
+    op: Op,
+    a: impl FnOnce() -> RA + Send,
+    b: impl FnOnce() -> RB + Send,
+) -> (RA, RB) {
+    #[cfg(feature = "parallel")]
+    if is_parallel(op) {
+        return install(|| rayon::join(a, b));
+    }
+    #[cfg(not(feature = "parallel"))]
+    let _ = op;
+    (a(), b())
+}
+
+#[cfg(all(test, feature = "bfv"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_join() {
+        let (a, b) = join(Op::Bfv, || 1 + 1, || "two");
+        assert_eq!((a, b), (2, "two"));
+        assert!(num_threads() >= 1);
+    }
+
+    #[cfg(feature = "parallel")]
+    #[test]
+    fn test_thread_config() {
+        let config = ThreadConfig::new()
+            .sequential()
+            .parallel(Op::Keystream, true);
+        assert!(config.is_parallel(Op::Keystream) && !config.is_parallel(Op::Bfv));
+        assert_eq!(
+            ThreadConfig::new().sequential().parallel(Op::Bfv, true).ops,
+            Op::Bfv as u8
+        );
+
+        // results don't depend on the switches, so flipping them under concurrent tests is harmless
+        config.caller_pool(true).apply().unwrap();
+        assert!(!is_parallel(Op::PolyMul));
+        assert_eq!(join(Op::Bfv, || 1, || 2), (1, 2));
+        assert_eq!(num_threads(), rayon::current_num_threads());
+        ThreadConfig::new().apply().unwrap();
+        assert!(is_parallel(Op::PolyMul));
+    }
+}
@@ -0,0 +1,672 @@
+//! Transciphering: the decryption of an HE-friendly cipher evaluated under BFV. The client uploads its
+//! cipher key encrypted under BFV once and afterwards only cipher ciphertexts, one field element per word;
+//! the server computes the keystream homomorphically and subtracts it, which leaves BFV encryptions of the
+//! data. [`Transcipher`] is written once against [`BfvKeystream`], the ciphers with a keystream circuit:
+//! Pasta and MASTA. Rasta works over GF(2), where BFV can't batch.
+//!
+//! The cipher modulus is the BFV plaintext modulus t, which has to allow batching (t ≡ 1 mod 2n). Key
+//! ciphertext i holds key word i in every slot and slot b runs block `first_block + b` with the affine
+//! layers of that block, so one evaluation decrypts up to n blocks. The result is one ciphertext per block
+//! word, slot b of ciphertext i holding word i of block b.
+//!
+//! The Pasta circuit has depth R + 1 (the Feistel sboxes square once, the final cube twice) and 2(R + 1)
+//! affine layers of W^2 plaintext products each, MASTA has depth R. Pasta-3 is out of reach of a single 64
+//! bit q, the tests run reduced instances.
+
+use std::fmt;
+use std::marker::PhantomData;
+
+use crate::backend::{NativeBackend, RingBackend};
+use crate::batch::BatchEncoder;
+use crate::bfv_pke::{Bfv, BfvCiphertext, Evaluator, ParamError, Plaintext};
+use crate::cancel::{CancellationToken, Cancelled};
+use crate::cipher::HeFriendlyCipher;
+use crate::circuit::{Circuit, CircuitError, Wire};
+use crate::encoding::Encoder;
+use crate::masta::{MASTA_ALPHA, Masta};
+use crate::pasta_plain::{Pasta, PastaError, PastaKey, RoundMaterials, XofSampler};
+use crate::polynomial::{Element, Polynomial};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TranscipherError {
+    /// t doesn't allow batching.
+    Batching(ParamError),
+    /// The key is not a Pasta key mod t.
+    Key(PastaError),
+    /// A key word not below t.
+    KeyWord {
+        index: usize,
+        value: u64,
+        modulus: u64,
+    },
+    TooManyBlocks {
+        blocks: usize,
+        slots: usize,
+    },
+    /// An encrypted key needs one ciphertext per key word, `BfvKeystream::KEY_WORDS`.
+    KeyWords {
+        expected: usize,
+        got: usize,
+    },
+    Circuit(CircuitError),
+    /// The token was cancelled between two blocks or gates.
+    Cancelled,
+}
+
+impl fmt::Display for TranscipherError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TranscipherError::Batching(e) => write!(f, "{e}"),
+            TranscipherError::Key(e) => write!(f, "{e}"),
+            TranscipherError::KeyWord {
+                index,
+                value,
+                modulus,
+            } => write!(
+                f,
+                "key word {index} ({value}) not below the modulus {modulus}"
+            ),
+            TranscipherError::TooManyBlocks { blocks, slots } => {
+                write!(f, "{blocks} blocks don't fit in {slots} slots")
+            }
+            TranscipherError::KeyWords { expected, got } => {
+                write!(f, "encrypted key must be {expected} ciphertexts, got {got}")
+            }
+            TranscipherError::Circuit(e) => write!(f, "{e}"),
+            TranscipherError::Cancelled => write!(f, "{Cancelled}"),
+        }
+    }
+}
+
+impl std::error::Error for TranscipherError {}
+
+impl From<ParamError> for TranscipherError {
+    fn from(e: ParamError) -> Self {
+        TranscipherError::Batching(e)
+    }
+}
+
+impl From<PastaError> for TranscipherError {
+    fn from(e: PastaError) -> Self {
+        TranscipherError::Key(e)
+    }
+}
+
+impl From<CircuitError> for TranscipherError {
+    fn from(e: CircuitError) -> Self {
+        match e {
+            CircuitError::Cancelled => TranscipherError::Cancelled,
+            e => TranscipherError::Circuit(e),
+        }
+    }
+}
+
+impl From<Cancelled> for TranscipherError {
+    fn from(_: Cancelled) -> Self {
+        TranscipherError::Cancelled
+    }
+}
+
+/// The 2W words of a `PastaKey<W>`, each encrypted in every slot.
+#[derive(Debug, Clone)]
+pub struct EncryptedPastaKey<
+    const N: usize,
+    const Q: u64,
+    const T: u64,
+    const W: usize,
+    B: RingBackend = NativeBackend,
+> {
+    words: Vec<BfvCiphertext<N, Q, T, B>>,
+}
+
+impl<const N: usize, const Q: u64, const T: u64, const W: usize, B: RingBackend>
+    EncryptedPastaKey<N, Q, T, W, B>
+{
+    /// Client side, `key` must be a key for the Pasta modulus t.
+    pub fn encrypt(bfv: &Bfv<N, Q, T, B>, key: &PastaKey<W>) -> Result<Self, TranscipherError> {
+        PastaKey::<W>::try_new(key.words().to_vec(), T)?;
+        Ok(Self {
+            words: encrypt_key(bfv, key.words())?,
+        })
+    }
+
+    /// Key words encrypted elsewhere, e.g. received from the client. There have to be 2W.
+    pub fn from_ciphertexts(
+        words: Vec<BfvCiphertext<N, Q, T, B>>,
+    ) -> Result<Self, TranscipherError> {
+        if words.len() != 2 * W {
+            return Err(TranscipherError::KeyWords {
+                expected: 2 * W,
+                got: words.len(),
+            });
+        }
+        Ok(Self { words })
+    }
+
+    pub fn words(&self) -> &[BfvCiphertext<N, Q, T, B>] {
+        &self.words
+    }
+
+    /// The key ciphertexts, as [`Transcipher::new`] takes them.
+    pub fn into_words(self) -> Vec<BfvCiphertext<N, Q, T, B>> {
+        self.words
+    }
+}
+
+/// Client side, the key words of any cipher, each encrypted in every slot. The words must be below t.
+pub fn encrypt_key<const N: usize, const Q: u64, const T: u64, B: RingBackend>(
+    bfv: &Bfv<N, Q, T, B>,
+    words: &[u64],
+) -> Result<Vec<BfvCiphertext<N, Q, T, B>>, TranscipherError> {
+    if let Some((index, &value)) = words.iter().enumerate().find(|(_, w)| **w >= T) {
+        return Err(TranscipherError::KeyWord {
+            index,
+            value,
+            modulus: T,
+        });
+    }
+    let encoder = BatchEncoder::<N, T>::new()?;
+    Ok(words
+        .iter()
+        .map(|&w| bfv.encrypt(encoder.encode(&[w; N]).expect("n values")))
+        .collect())
+}
+
+/// A cipher whose keystream the server can evaluate under BFV, block b of the keystream in slot b.
+pub trait BfvKeystream: HeFriendlyCipher {
+    /// Key words, one ciphertext each.
+    const KEY_WORDS: usize;
+    /// Keystream words per block, the `block_size` of every instance.
+    const BLOCK_WORDS: usize;
+
+    /// Adds the keystream of (`nonce`, blocks from `first_block`) to `c` and returns its `BLOCK_WORDS`
+    /// wires, `key` holding the `KEY_WORDS` key inputs. `token` is checked before the materials of
+    /// every block are derived.
+    fn keystream_circuit<const N: usize, const T: u64>(
+        c: &mut Circuit<N, T>,
+        key: &[Wire],
+        encode: &PerBlock<'_, N, T>,
+        nonce: u64,
+        first_block: u64,
+        token: &CancellationToken,
+    ) -> Result<Vec<Wire>, Cancelled>;
+}
+
+impl<const W: usize, const R: usize> BfvKeystream for Pasta<W, R> {
+    const KEY_WORDS: usize = 2 * W;
+    const BLOCK_WORDS: usize = W;
+
+    fn keystream_circuit<const N: usize, const T: u64>(
+        c: &mut Circuit<N, T>,
+        key: &[Wire],
+        encode: &PerBlock<'_, N, T>,
+        nonce: u64,
+        first_block: u64,
+        token: &CancellationToken,
+    ) -> Result<Vec<Wire>, Cancelled> {
+        let materials = (0..encode.blocks())
+            .map(|b| {
+                token.check()?;
+                Ok(RoundMaterials::derive(
+                    T,
+                    nonce,
+                    first_block + b as u64,
+                    W,
+                    R,
+                ))
+            })
+            .collect::<Result<Vec<_>, Cancelled>>()?;
+
+        let (mut l, mut r) = (key[..W].to_vec(), key[W..].to_vec());
+        for layer in 0..=R {
+            let dense = materials
+                .iter()
+                .map(|m| {
+                    let layer = &m.layers[layer];
+                    (layer.mat_l.to_rows(), layer.mat_r.to_rows())
+                })
+                .collect::<Vec<_>>();
+            l = affine(
+                c,
+                &l,
+                encode,
+                |b, i, j| dense[b].0[i][j],
+                |b, i| materials[b].layers[layer].rc_l[i],
+            );
+            r = affine(
+                c,
+                &r,
+                encode,
+                |b, i, j| dense[b].1[i][j],
+                |b, i| materials[b].layers[layer].rc_r[i],
+            );
+            mix(c, &mut l, &mut r);
+            if layer + 1 == R {
+                l = cube(c, &l);
+                r = cube(c, &r);
+            } else if layer < R {
+                l = feistel(c, &l);
+                r = feistel(c, &r);
+            }
+        }
+        Ok(l)
+    }
+}
+
+/// The circuit runs the ring x^N - `MASTA_ALPHA`, not one set with `Masta::with_alpha`.
+impl<const M: usize, const R: usize> BfvKeystream for Masta<M, R> {
+    const KEY_WORDS: usize = M;
+    const BLOCK_WORDS: usize = M;
+
+    fn keystream_circuit<const N: usize, const T: u64>(
+        c: &mut Circuit<N, T>,
+        key: &[Wire],
+        encode: &PerBlock<'_, N, T>,
+        nonce: u64,
+        first_block: u64,
+        token: &CancellationToken,
+    ) -> Result<Vec<Wire>, Cancelled> {
+        // per block, the (a, c) of every affine layer in squeezing order
+        let mut samplers = (0..encode.blocks())
+            .map(|b| {
+                token.check()?;
+                Ok(XofSampler::seeded(T, nonce, first_block + b as u64))
+            })
+            .collect::<Result<Vec<_>, Cancelled>>()?;
+        let alpha = MASTA_ALPHA % T;
+
+        let mut state = key.to_vec();
+        for round in 0..=R {
+            // each block's sampler yields its layers in order
+            let layer = samplers
+                .iter_mut()
+                .map(|sampler| (sampler.vec(M, false), sampler.vec(M, true)))
+                .collect::<Vec<_>>();
+            // a * state in Z_t[x]/(x^M - alpha) is the matrix with row k, column j a_(k-j), or
+            // alpha a_(k+M-j) above the diagonal
+            state = affine(
+                c,
+                &state,
+                encode,
+                |b, k, j| {
+                    let a = &layer[b].0;
+                    if j <= k {
+                        a[k - j]
+                    } else {
+                        (alpha as u128 * a[k + M - j] as u128 % T as u128) as u64
+                    }
+                },
+                |b, k| layer[b].1[k],
+            );
+            if round < R {
+                state = chi(c, &state, encode);
+            }
+        }
+        Ok(state.iter().zip(key).map(|(&s, &k)| c.add(s, k)).collect())
+    }
+}
+
+/// Server side decryption of `C` ciphertexts, e.g. `Pasta<W, R>`, into BFV ciphertexts. `evaluator` needs
+/// a relinearization key.
+pub struct Transcipher<
+    const N: usize,
+    const Q: u64,
+    const T: u64,
+    C: BfvKeystream,
+    B: RingBackend = NativeBackend,
+> {
+    key: Vec<BfvCiphertext<N, Q, T, B>>,
+    evaluator: Evaluator<N, Q, T, B>,
+    encoder: BatchEncoder<N, T>,
+    cipher: PhantomData<fn() -> C>,
+}
+
+impl<const N: usize, const Q: u64, const T: u64, C: BfvKeystream, B: RingBackend> Clone
+    for Transcipher<N, Q, T, C, B>
+{
+    fn clone(&self) -> Self {
+        Self {
+            key: self.key.clone(),
+            evaluator: self.evaluator.clone(),
+            encoder: self.encoder.clone(),
+            cipher: PhantomData,
+        }
+    }
+}
+
+impl<const N: usize, const Q: u64, const T: u64, C: BfvKeystream, B: RingBackend> fmt::Debug
+    for Transcipher<N, Q, T, C, B>
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Transcipher")
+            .field("key", &self.key)
+            .field("evaluator", &self.evaluator)
+            .field("encoder", &self.encoder)
+            .finish()
+    }
+}
+
+impl<const N: usize, const Q: u64, const T: u64, C: BfvKeystream, B: RingBackend>
+    Transcipher<N, Q, T, C, B>
+{
+    /// `key` holds the `C::KEY_WORDS` encrypted key words, e.g. `EncryptedPastaKey::into_words` or
+    /// received from the client.
+    pub fn new(
+        key: Vec<BfvCiphertext<N, Q, T, B>>,
+        evaluator: Evaluator<N, Q, T, B>,
+    ) -> Result<Self, TranscipherError> {
+        if key.len() != C::KEY_WORDS {
+            return Err(TranscipherError::KeyWords {
+                expected: C::KEY_WORDS,
+                got: key.len(),
+            });
+        }
+        Ok(Self {
+            key,
+            evaluator,
+            encoder: BatchEncoder::new()?,
+            cipher: PhantomData,
+        })
+    }
+
+    pub fn evaluator(&self) -> &Evaluator<N, Q, T, B> {
+        &self.evaluator
+    }
+
+    /// The circuit computing `ciphertext` minus the keystream of (`nonce`, blocks from `first_block`),
+    /// taking the `C::KEY_WORDS` key words as inputs. Slots past the last block, and words past the end of a short
+    /// last block, hold the negated keystream.
+    pub fn circuit(
+        &self,
+        nonce: u64,
+        first_block: u64,
+        ciphertext: &[u64],
+    ) -> Result<Circuit<N, T>, TranscipherError> {
+        self.circuit_cancellable(nonce, first_block, ciphertext, &CancellationToken::new())
+    }
+
+    /// `circuit`, checking `token` before deriving the round materials of every block.
+    fn circuit_cancellable(
+        &self,
+        nonce: u64,
+        first_block: u64,
+        ciphertext: &[u64],
+        token: &CancellationToken,
+    ) -> Result<Circuit<N, T>, TranscipherError> {
+        let blocks = ciphertext.len().div_ceil(C::BLOCK_WORDS);
+        if blocks > N {
+            return Err(TranscipherError::TooManyBlocks { blocks, slots: N });
+        }
+        let encode = PerBlock {
+            encoder: &self.encoder,
+            blocks,
+        };
+
+        let mut c = Circuit::new();
+        let key = (0..C::KEY_WORDS).map(|_| c.input()).collect::<Vec<_>>();
+        let ks = C::keystream_circuit(&mut c, &key, &encode, nonce, first_block, token)?;
+
+        let mut minus_one = Polynomial::new([Element::new(0); N]);
+        minus_one.inner[0] = Element::new(-1);
+        for (i, &ks) in ks.iter().enumerate() {
+            let neg = c.mul_plain(ks, minus_one);
+            let words =
+                encode.encode(|b| ciphertext.get(b * C::BLOCK_WORDS + i).copied().unwrap_or(0));
+            let data = c.add_plain(neg, words);
+            c.output(data);
+        }
+        Ok(c)
+    }
+
+    /// BFV encryptions of the plaintext of `ciphertext`, which starts at block `first_block` of the message
+    /// under `nonce`: `C::BLOCK_WORDS` ciphertexts, word i of block b in slot b of the i-th.
+    pub fn decrypt(
+        &self,
+        nonce: u64,
+        first_block: u64,
+        ciphertext: &[u64],
+    ) -> Result<Vec<BfvCiphertext<N, Q, T, B>>, TranscipherError> {
+        self.decrypt_cancellable(nonce, first_block, ciphertext, &CancellationToken::new())
+    }
+
+    /// `decrypt` that checks `token` before every block and gate and returns
+    /// `TranscipherError::Cancelled` once it is cancelled.
+    pub fn decrypt_cancellable(
+        &self,
+        nonce: u64,
+        first_block: u64,
+        ciphertext: &[u64],
+        token: &CancellationToken,
+    ) -> Result<Vec<BfvCiphertext<N, Q, T, B>>, TranscipherError> {
+        let circuit = self.circuit_cancellable(nonce, first_block, ciphertext, token)?;
+        Ok(circuit.evaluate_cancellable(&self.evaluator, &self.key, token)?)
+    }
+}
+
+/// Plaintexts with slot b taken from block b, for the blocks of one circuit.
+pub struct PerBlock<'a, const N: usize, const T: u64> {
+    encoder: &'a BatchEncoder<N, T>,
+    blocks: usize,
+}
+
+impl<const N: usize, const T: u64> PerBlock<'_, N, T> {
+    pub fn blocks(&self) -> usize {
+        self.blocks
+    }
+
+    pub fn encode(&self, f: impl Fn(usize) -> u64) -> Plaintext<N, T> {
+        let values = (0..self.blocks).map(f).collect::<Vec<_>>();
+        self.encoder.encode(&values).expect("at most n blocks")
+    }
+}
+
+/// x -> M x + rc, M and rc given per block b by `mat(b, i, j)` and `rc(b, i)`.
+fn affine<const N: usize, const T: u64>(
+    c: &mut Circuit<N, T>,
+    x: &[Wire],
+    encode: &PerBlock<N, T>,
+    mat: impl Fn(usize, usize, usize) -> u64,
+    rc: impl Fn(usize, usize) -> u64,
+) -> Vec<Wire> {
+    (0..x.len())
+        .map(|i| {
+            let mut sum = c.mul_plain(x[0], encode.encode(|b| mat(b, i, 0)));
+            for (j, &x_j) in x.iter().enumerate().skip(1) {
+                let term = c.mul_plain(x_j, encode.encode(|b| mat(b, i, j)));
+                sum = c.add(sum, term);
+            }
+            c.add_plain(sum, encode.encode(|b| rc(b, i)))
+        })
+        .collect()
+}
+
+/// l, r -> 2l + r, l + 2r
+fn mix<const N: usize, const T: u64>(c: &mut Circuit<N, T>, l: &mut [Wire], r: &mut [Wire]) {
+    for (l, r) in l.iter_mut().zip(r.iter_mut()) {
+        let sum = c.add(*l, *r);
+        *l = c.add(*l, sum);
+        *r = c.add(*r, sum);
+    }
+}
+
+fn cube<const N: usize, const T: u64>(c: &mut Circuit<N, T>, x: &[Wire]) -> Vec<Wire> {
+    x.iter()
+        .map(|&x| {
+            let sq = c.mul(x, x);
+            c.mul(sq, x)
+        })
+        .collect()
+}
+
+/// x_i -> x_i + (x_(i+1) + 1) x_(i+2), indices mod the length.
+fn chi<const N: usize, const T: u64>(
+    c: &mut Circuit<N, T>,
+    x: &[Wire],
+    encode: &PerBlock<N, T>,
+) -> Vec<Wire> {
+    let one = encode.encode(|_| 1);
+    (0..x.len())
+        .map(|i| {
+            let next = c.add_plain(x[(i + 1) % x.len()], one);
+            let prod = c.mul(next, x[(i + 2) % x.len()]);
+            c.add(x[i], prod)
+        })
+        .collect()
+}
+
+/// x_i -> x_i + x_(i-1)^2, x_0 unchanged.
+fn feistel<const N: usize, const T: u64>(c: &mut Circuit<N, T>, x: &[Wire]) -> Vec<Wire> {
+    let mut out = x.to_vec();
+    for i in 1..x.len() {
+        let sq = c.mul(x[i - 1], x[i - 1]);
+        out[i] = c.add(x[i], sq);
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{Rng, rng};
+
+    use super::*;
+    use crate::pasta_plain::Pasta;
+
+    const N: usize = 8;
+    // 17 ≡ 1 mod 16, n q^2 t stays below 2^127
+    const Q: u64 = 1 << 59;
+    const T: u64 = 17;
+
+    fn transcipher<const W: usize, const R: usize>(blocks: usize, words: usize) {
+        let mut rng = rng();
+        let key = PastaKey::<W>::generate(&mut rng, T);
+        let mut pasta = Pasta::<W, R>::with_key(key.clone(), T);
+        let plain = (0..words)
+            .map(|_| rng.random_range(0..T))
+            .collect::<Vec<_>>();
+        // the tail of a message from block 3 on
+        let mut message = vec![0; 3 * W];
+        message.extend(&plain);
+        let ct = pasta.encrypt_with_nonce(5, &message)[3 * W..].to_vec();
+        assert_eq!(ct.len().div_ceil(W), blocks);
+
+        let (bfv, sk) = Bfv::<N, Q, T>::keygen().unwrap();
+        let evaluator = Evaluator::new().with_relin_key(bfv.gen_relin_key(&sk, 8));
+        let server = Transcipher::<N, Q, T, Pasta<W, R>>::new(
+            EncryptedPastaKey::encrypt(&bfv, &key).unwrap().into_words(),
+            evaluator,
+        )
+        .unwrap();
+        assert_eq!(server.circuit(5, 3, &ct).unwrap().depth(), R as u32 + 1);
+
+        let data = server.decrypt(5, 3, &ct).unwrap();
+        assert_eq!(data.len(), W);
+        let encoder = BatchEncoder::<N, T>::new().unwrap();
+        let slots = data
+            .iter()
+            .map(|d| encoder.decode(&d.decrypt(&sk)))
+            .collect::<Vec<_>>();
+        for (k, &p) in plain.iter().enumerate() {
+            assert_eq!(slots[k % W][k / W], p, "word {k}");
+        }
+        assert!(data.iter().all(|d| d.noise_budget(&sk) > 0));
+    }
+
+    #[test]
+    fn test_transcipher() {
+        // one round is the cube sbox alone
+        transcipher::<4, 1>(3, 10);
+        // a Feistel round before the cube, up to n blocks
+        transcipher::<2, 2>(N, 2 * N);
+    }
+
+    #[test]
+    fn test_transcipher_masta() {
+        let mut rng = rng();
+        let key = (0..4).map(|_| rng.random_range(0..T)).collect::<Vec<_>>();
+        let mut masta = Masta::<4, 2>::new(key.clone(), T);
+        let plain = (0..10).map(|_| rng.random_range(0..T)).collect::<Vec<_>>();
+        let ct = HeFriendlyCipher::encrypt(&mut masta, 7, &plain);
+
+        let (bfv, sk) = Bfv::<N, Q, T>::keygen().unwrap();
+        let evaluator = Evaluator::new().with_relin_key(bfv.gen_relin_key(&sk, 8));
+        let server =
+            Transcipher::<N, Q, T, Masta<4, 2>>::new(encrypt_key(&bfv, &key).unwrap(), evaluator)
+                .unwrap();
+        assert_eq!(server.circuit(7, 0, &ct).unwrap().depth(), 2);
+
+        let data = server.decrypt(7, 0, &ct).unwrap();
+        assert_eq!(data.len(), 4);
+        let encoder = BatchEncoder::<N, T>::new().unwrap();
+        let slots = data
+            .iter()
+            .map(|d| encoder.decode(&d.decrypt(&sk)))
+            .collect::<Vec<_>>();
+        for (k, &p) in plain.iter().enumerate() {
+            assert_eq!(slots[k % 4][k / 4], p, "word {k}");
+        }
+    }
+
+    #[test]
+    fn test_errors() {
+        let key = PastaKey::<2>::new(vec![1, 2, 3, 20], 257);
+        let (bfv, _) = Bfv::<N, Q, T>::keygen().unwrap();
+        assert_eq!(
+            EncryptedPastaKey::encrypt(&bfv, &key).unwrap_err(),
+            TranscipherError::Key(PastaError::KeyOutOfRange {
+                index: 3,
+                value: 20,
+                modulus: T
+            })
+        );
+        let key = PastaKey::<2>::new(vec![1, 2, 3, 4], T);
+        let words = EncryptedPastaKey::encrypt(&bfv, &key).unwrap().words;
+        assert_eq!(
+            EncryptedPastaKey::<N, Q, T, 2>::from_ciphertexts(words[1..].to_vec()).unwrap_err(),
+            TranscipherError::KeyWords {
+                expected: 4,
+                got: 3
+            }
+        );
+        assert_eq!(
+            Transcipher::<N, Q, T, Masta<5, 1>>::new(words.clone(), Evaluator::new()).unwrap_err(),
+            TranscipherError::KeyWords {
+                expected: 5,
+                got: 4
+            }
+        );
+        assert_eq!(
+            encrypt_key(&bfv, &[1, T]).unwrap_err(),
+            TranscipherError::KeyWord {
+                index: 1,
+                value: T,
+                modulus: T
+            }
+        );
+        let server = Transcipher::<N, Q, T, Pasta<2, 1>>::new(words, Evaluator::new()).unwrap();
+        assert_eq!(
+            server.circuit(0, 0, &[0; 2 * N + 1]).unwrap_err(),
+            TranscipherError::TooManyBlocks {
+                blocks: N + 1,
+                slots: N
+            }
+        );
+        assert!(matches!(
+            server.decrypt(0, 0, &[1, 2]).unwrap_err(),
+            TranscipherError::Circuit(CircuitError::Eval(_))
+        ));
+        let token = CancellationToken::new();
+        token.cancel();
+        assert_eq!(
+            server
+                .decrypt_cancellable(0, 0, &[1, 2], &token)
+                .unwrap_err(),
+            TranscipherError::Cancelled
+        );
+        // no batching mod 13 at n = 8
+        let (bfv, _) = Bfv::<N, Q, 13>::keygen().unwrap();
+        assert!(matches!(
+            EncryptedPastaKey::encrypt(&bfv, &PastaKey::<2>::new(vec![1, 2, 3, 4], 13)),
+            Err(TranscipherError::Batching(_))
+        ));
+    }
+}
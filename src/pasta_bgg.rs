@@ -1,186 +1,299 @@
 //! Attempt to implement Pasta homomorphically over bgg.
-//! But while realized I need to hide the Pasta key by fhe ciphertext which is PRF key in our context.
-//! Will back with this after try simple bgg/bfv experiments.
+//! The Pasta key is the PRF key in our context, so it has to stay hidden: [`keystream_hidden_key`] keeps it
+//! under BFV and only evaluates the BFV decryption over BGG, with the BFV secret as a hidden encoding.
 //!
-//! Also unlike plain this logic is incorrect.
+//! The keystream is described once as a diamond-io circuit ([`pasta_circuit`]) with the round structure of
+//! `Pasta::keystream_with`, and evaluated over BGG encodings or plain polynomials. The keystream plaintexts
+//! match the plain one when p is the BGG modulus (see `test_keystream_matches_plain`).
 
-use byteorder::{BigEndian, ByteOrder};
+use std::ops::Index;
+
+use crate::backend::RingBackend;
+use crate::bfv_pke::BfvSecretKey;
+use crate::pasta_bfv::{BfvKeystream, Transcipher, TranscipherError};
+use crate::pasta_plain::{RoundMaterials, SequentialMatrix};
+use crate::polynomial::Polynomial;
 use diamond_io::poly::PolyElem;
 use diamond_io::{
-    bgg::{BggEncoding, circuit::Evaluable},
+    bgg::{BggEncoding, circuit::PolyCircuit},
     poly::{Poly, PolyMatrix, PolyParams},
 };
-use sha3::{
-    Shake128,
-    digest::{ExtendableOutput, Update, XofReader},
-};
 
 pub const PASTA_T: usize = 128;
 /// Number of rounds (Pasta-3)
 pub const PASTA_R: usize = 3;
 
-pub fn keystream_bgg<M: PolyMatrix>(
+/// One half (l or r) of a `T` word Pasta state over BGG: word i is the encoding at index i, of the constant
+/// polynomial with that word. A dense matrix over the coefficients of one packed polynomial is not a ring
+/// operation, so every word gets its own encoding and the sbox and mix layers address them individually.
+#[derive(Debug, Clone)]
+pub struct BggState<M: PolyMatrix, const T: usize> {
+    words: Vec<BggEncoding<M>>,
+}
+
+impl<M: PolyMatrix, const T: usize> BggState<M, T> {
+    pub fn new(words: Vec<BggEncoding<M>>) -> Self {
+        assert_eq!(words.len(), T, "state half needs {T} encodings");
+        Self { words }
+    }
+
+    pub fn words(&self) -> &[BggEncoding<M>] {
+        &self.words
+    }
+
+    pub fn into_words(self) -> Vec<BggEncoding<M>> {
+        self.words
+    }
+}
+
+impl<M: PolyMatrix, const T: usize> Index<usize> for BggState<M, T> {
+    type Output = BggEncoding<M>;
+
+    fn index(&self, i: usize) -> &BggEncoding<M> {
+        &self.words[i]
+    }
+}
+
+/// Keystream over a `T` word state, use `PASTA_T` for the standard Pasta-3 instance.
+/// The affine layers come from the same [`RoundMaterials`] derivation as the plain Pasta over `pasta_modulus`.
+/// Words are encodings of their own, so `T` is not bounded by the ring dimension.
+pub fn keystream_bgg<M: PolyMatrix, const T: usize>(
     params: &<M::P as Poly>::Params,
-    enc_left: &BggEncoding<M>,
-    enc_right: &BggEncoding<M>,
+    left: &BggState<M, T>,
+    right: &BggState<M, T>,
     enc_one: &BggEncoding<M>,
+    pasta_modulus: u64,
     nonce: u64,
     ctr: u64,
-) -> BggEncoding<M>
+) -> BggState<M, T>
 where
     BggEncoding<M>: Clone,
 {
-    /* init shake */
-    let mut seed = [0u8; 16];
-    BigEndian::write_u64(&mut seed[..8], nonce);
-    BigEndian::write_u64(&mut seed[8..], ctr);
-    let mut hasher = Shake128::default();
-    hasher.update(&seed);
-    let mut xof = hasher.finalize_xof();
-
-    let mut mats_l = Vec::<M>::new();
-    let mut mats_r = Vec::<M>::new();
-    let mut rcs_l = Vec::<M::P>::new();
-    let mut rcs_r = Vec::<M::P>::new();
-
-    for _ in 0..=PASTA_R {
-        mats_l.push(random_sequential_matrix::<M>(&mut xof, params));
-        mats_r.push(random_sequential_matrix::<M>(&mut xof, params));
-        rcs_l.push(random_constant::<M>(&mut xof, params));
-        rcs_r.push(random_constant::<M>(&mut xof, params));
-    }
-
-    let mut l = enc_left.clone();
-    let mut r = enc_right.clone();
-
-    for round_idx in 0..=PASTA_R {
-        pasta_round::<M>(
-            params,
-            &mut l,
-            &mut r,
-            &mats_l[round_idx],
-            &mats_r[round_idx],
-            &rcs_l[round_idx],
-            &rcs_r[round_idx],
-            round_idx == PASTA_R - 1,
-            enc_one,
-        );
+    let materials = RoundMaterials::derive(pasta_modulus, nonce, ctr, T, PASTA_R);
+    keystream_bgg_with::<M, T>(params, left, right, enc_one, &materials)
+}
+
+/// Keystream from already derived materials, e.g. kept in a `MaterialsCache` so evaluating many
+/// counters of a session samples the matrices and constants of every (nonce, block counter) once.
+pub fn keystream_bgg_with<M: PolyMatrix, const T: usize>(
+    params: &<M::P as Poly>::Params,
+    left: &BggState<M, T>,
+    right: &BggState<M, T>,
+    enc_one: &BggEncoding<M>,
+    materials: &RoundMaterials,
+) -> BggState<M, T>
+where
+    BggEncoding<M>: Clone,
+{
+    let inputs = [left.words(), right.words()].concat();
+    BggState::new(pasta_circuit::<T>(materials).eval(params, enc_one, &inputs))
+}
+
+/// The keystream of one (nonce, block counter) as a diamond-io circuit over any `Evaluable`: inputs
+/// l_0 .. l_{T-1} then r_0 .. r_{T-1}, one per word, outputs the T keystream words. The matrices and
+/// round constants of `materials` are baked in as constant gates, so words have to fit u32 digits.
+pub fn pasta_circuit<const T: usize>(materials: &RoundMaterials) -> PolyCircuit {
+    assert_eq!(
+        materials.layers.len(),
+        PASTA_R + 1,
+        "materials derived for another round count"
+    );
+    let mut circuit = PolyCircuit::new();
+    let inputs = circuit.input(2 * T);
+    let (mut l, mut r) = (inputs[..T].to_vec(), inputs[T..].to_vec());
+
+    for (round_idx, layer) in materials.layers[..PASTA_R].iter().enumerate() {
+        affine(&mut circuit, &mut l, &layer.mat_l, &layer.rc_l);
+        affine(&mut circuit, &mut r, &layer.mat_r, &layer.rc_r);
+        mix(&mut circuit, &mut l, &mut r);
+        if round_idx == PASTA_R - 1 {
+            cube(&mut circuit, &mut l);
+            cube(&mut circuit, &mut r);
+        } else {
+            feistel(&mut circuit, &mut l);
+            feistel(&mut circuit, &mut r);
+        }
     }
-    pasta_affine::<M>(&mut l, &mats_l[PASTA_R], &rcs_l[PASTA_R], enc_one);
-    pasta_affine::<M>(&mut r, &mats_r[PASTA_R], &rcs_r[PASTA_R], enc_one);
-    mix::<M>(&mut l, &mut r);
+    let last = &materials.layers[PASTA_R];
+    affine(&mut circuit, &mut l, &last.mat_l, &last.rc_l);
+    affine(&mut circuit, &mut r, &last.mat_r, &last.rc_r);
+    mix(&mut circuit, &mut l, &mut r);
 
-    l
+    circuit.output(l);
+    circuit
 }
 
-fn random_constant<M: PolyMatrix>(
-    xof: &mut dyn XofReader,
+/// Pasta keystream with the key hidden under BFV: `transcipher` holds the BFV encrypted key and computes
+/// BFV encryptions of the keystream, BGG only evaluates their decryption with the BFV secret as the hidden
+/// encoding `enc_sk` (see [`bfv_secret_poly`]). No key word is ever encoded. BGG can't multiply two hidden
+/// encodings, so the nonlinear layers have to stay on the BFV side.
+///
+/// One encoding per word i, of the phase Δ' ks_i + e of its keystream ciphertext switched to the BGG
+/// modulus q (Δ' = q / t), slot b holding word i of block `first_block + b`.
+pub fn keystream_hidden_key<
+    M: PolyMatrix,
+    const N: usize,
+    const Q: u64,
+    const T: u64,
+    C: BfvKeystream,
+    B: RingBackend,
+>(
     params: &<M::P as Poly>::Params,
-) -> M::P {
-    let coeffs = (0..PASTA_T)
-        .map(|_| {
-            let mut buf = [0u8; 8];
-            xof.read(&mut buf);
-            <M::P as Poly>::Elem::constant(&params.modulus(), u64::from_le_bytes(buf))
+    transcipher: &Transcipher<N, Q, T, C, B>,
+    enc_sk: &BggEncoding<M>,
+    enc_one: &BggEncoding<M>,
+    nonce: u64,
+    first_block: u64,
+    blocks: usize,
+) -> Result<Vec<BggEncoding<M>>, TranscipherError>
+where
+    BggEncoding<M>: Clone,
+{
+    assert_eq!(
+        params.ring_dimension() as usize,
+        N,
+        "bgg and bfv ring dimensions differ"
+    );
+    let q = bgg_modulus::<M>(params);
+    // the transcipher circuit subtracts the keystream from the (zero) data
+    let minus_ks = transcipher.decrypt(nonce, first_block, &vec![0; blocks * C::BLOCK_WORDS])?;
+    Ok(minus_ks
+        .iter()
+        .map(|ct| {
+            let circuit = bfv_phase_circuit(&-*ct.c_1(), &-*ct.c_2(), q);
+            let mut out = circuit.eval(params, enc_one, std::slice::from_ref(enc_sk));
+            out.pop().unwrap()
         })
-        .collect::<Vec<_>>();
-    M::P::from_coeffs(params, &coeffs)
+        .collect())
+}
+
+/// Circuit of one input s, the BFV secret, computing the phase c_1 + c_2 s with (c_1, c_2) switched from Q
+/// to the modulus q. Only the public c_2 multiplies the input, so s can be a hidden encoding.
+pub fn bfv_phase_circuit<const N: usize, const Q: u64>(
+    c_1: &Polynomial<N, Q>,
+    c_2: &Polynomial<N, Q>,
+    q: u64,
+) -> PolyCircuit {
+    assert!(q < 1 << 32, "q = {q} doesn't fit u32 digits");
+    // round(x q / Q)
+    let digits = |c: &Polynomial<N, Q>| {
+        c.inner
+            .iter()
+            .map(|x| {
+                ((x.value() as u128 * q as u128 + Q as u128 / 2) / Q as u128 % q as u128) as u32
+            })
+            .collect::<Vec<_>>()
+    };
+    let mut circuit = PolyCircuit::new();
+    let s = circuit.input(1)[0];
+    let c_1 = circuit.const_digits_poly(&digits(c_1));
+    let c_2 = circuit.const_digits_poly(&digits(c_2));
+    let c_2_s = circuit.mul_gate(c_2, s);
+    let phase = circuit.add_gate(c_1, c_2_s);
+    circuit.output(vec![phase]);
+    circuit
 }
 
-fn random_sequential_matrix<M: PolyMatrix>(
-    xof: &mut dyn XofReader,
+/// The ternary BFV secret as a BGG plaintext, -1 as q - 1.
+pub fn bfv_secret_poly<M: PolyMatrix, const N: usize>(
     params: &<M::P as Poly>::Params,
-) -> M {
-    let first = random_constant::<M>(xof, params)
-        .coeffs()
-        .into_iter()
+    sk: &BfvSecretKey<N>,
+) -> M::P {
+    let q = bgg_modulus::<M>(params);
+    let words = sk
+        .poly()
+        .to_centered()
+        .iter()
+        .map(|&c| c.rem_euclid(q as i64) as u64)
         .collect::<Vec<_>>();
-    let mut rows = Vec::<M::P>::with_capacity(PASTA_T);
-    rows.push(M::P::from_coeffs(params, &first));
-
-    for _ in 1..PASTA_T {
-        let prev = rows
-            .last()
-            .unwrap()
-            .coeffs()
-            .into_iter()
-            .collect::<Vec<_>>();
-        let mut nxt = vec![<M::P as Poly>::Elem::zero(&params.modulus()); PASTA_T];
-        for j in 0..PASTA_T {
-            let term = first[j].clone() * prev[PASTA_T - 1].clone();
-            nxt[j] = if j == 0 {
-                term
-            } else {
-                term + prev[j - 1].clone()
-            };
-        }
-        rows.push(M::P::from_coeffs(params, &nxt));
-    }
-    M::from_poly_vec_row(&params, rows)
+    poly_from_words::<M>(params, &words)
 }
 
-fn pasta_round<M: PolyMatrix>(
-    params: &<M::P as Poly>::Params,
-    l: &mut BggEncoding<M>,
-    r: &mut BggEncoding<M>,
-    mat_l: &M,
-    mat_r: &M,
-    rc_l: &M::P,
-    rc_r: &M::P,
-    last_round: bool,
-    enc_one: &BggEncoding<M>,
-) {
-    pasta_affine::<M>(l, mat_l, rc_l, enc_one);
-    pasta_affine::<M>(r, mat_r, rc_r, enc_one);
-    mix::<M>(l, r);
+/// The BGG modulus, a single u64 sized prime in the parameters this module works with.
+fn bgg_modulus<M: PolyMatrix>(params: &<M::P as Poly>::Params) -> u64 {
+    params
+        .modulus()
+        .to_string()
+        .parse()
+        .expect("bgg modulus must fit u64")
+}
 
-    if last_round {
-        cube::<M>(l);
-        cube::<M>(r);
-    } else {
-        feistel::<M>(params, l);
-        feistel::<M>(params, r);
-    }
+/// Pasta words (already reduced mod p) as the coefficients of one ring element.
+fn poly_from_words<M: PolyMatrix>(params: &<M::P as Poly>::Params, words: &[u64]) -> M::P {
+    let coeffs = words
+        .iter()
+        .map(|&w| <M::P as Poly>::Elem::constant(&params.modulus(), w))
+        .collect::<Vec<_>>();
+    M::P::from_coeffs(params, &coeffs)
 }
 
-fn pasta_affine<M: PolyMatrix>(
-    state: &mut BggEncoding<M>,
-    mat: &M,
-    rc: &M::P,
-    enc_one: &BggEncoding<M>,
-) {
-    // todo cannot multiply `BggEncoding<M>` by `<M as PolyMatrix>::P`
-    // todo condition failed: self.ncol (136) must equal rhs.nrow (1)
-    let mut state_m = state.clone().vector * mat.clone();
-    state_m = state_m.clone() + enc_one.clone().vector * rc.clone();
-    *state = BggEncoding::<M>::new(state_m, state.pubkey.clone(), None);
+/// Gate for the constant polynomial `word`.
+fn constant(circuit: &mut PolyCircuit, word: u64) -> usize {
+    let digit = u32::try_from(word).expect("pasta words must fit u32 digits");
+    circuit.const_digits_poly(&[digit])
+}
+
+/// state <- mat * state + rc * one, one row of the sequential matrix at a time.
+fn affine(circuit: &mut PolyCircuit, state: &mut Vec<usize>, mat: &SequentialMatrix, rc: &[u64]) {
+    *state = mat
+        .rows()
+        .zip(rc)
+        .map(|(row, &c)| {
+            row.iter()
+                .zip(state.iter())
+                .fold(constant(circuit, c), |acc, (&w, &x)| {
+                    let w = constant(circuit, w);
+                    let term = circuit.mul_gate(w, x);
+                    circuit.add_gate(acc, term)
+                })
+        })
+        .collect();
 }
 
-fn mix<M: PolyMatrix>(l: &mut BggEncoding<M>, r: &mut BggEncoding<M>) {
-    let sum = l.clone() + r.clone();
-    *l = l.clone() + sum.clone();
-    *r = r.clone() + sum;
+fn mix(circuit: &mut PolyCircuit, l: &mut [usize], r: &mut [usize]) {
+    for (l_i, r_i) in l.iter_mut().zip(r.iter_mut()) {
+        let sum = circuit.add_gate(*l_i, *r_i);
+        *l_i = circuit.add_gate(*l_i, sum);
+        *r_i = circuit.add_gate(*r_i, sum);
+    }
+}
+
+/// [0, x_0, .., x_{T-2}]: the words shifted up by one with a zero constant coming in. Unlike rotating a
+/// packed polynomial by x, whose top coefficient wraps around negated in x^n + 1, nothing wraps.
+fn shift_words(circuit: &mut PolyCircuit, state: &[usize]) -> Vec<usize> {
+    let zero = constant(circuit, 0);
+    std::iter::once(zero)
+        .chain(state[..state.len() - 1].iter().copied())
+        .collect()
 }
 
-fn feistel<M: PolyMatrix>(params: &<M::P as Poly>::Params, state: &mut BggEncoding<M>) {
-    let rot1 = state.rotate(params, 1);
-    *state = state.clone() + rot1.clone() * rot1;
+/// x_i <- x_i + x_{i-1}^2 from the old state, x_0 + 0^2 = x_0.
+fn feistel(circuit: &mut PolyCircuit, state: &mut [usize]) {
+    let shifted = shift_words(circuit, state);
+    for (x, prev) in state.iter_mut().zip(shifted) {
+        let sq = circuit.mul_gate(prev, prev);
+        *x = circuit.add_gate(*x, sq);
+    }
 }
 
-fn cube<M: PolyMatrix>(state: &mut BggEncoding<M>) {
-    *state = state.clone() * state.clone();
-    *state = state.clone() * state.clone();
+fn cube(circuit: &mut PolyCircuit, state: &mut [usize]) {
+    for x in state.iter_mut() {
+        let sq = circuit.mul_gate(*x, *x);
+        *x = circuit.mul_gate(sq, *x);
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::field::Modulus;
+    use crate::pasta_plain::{MaterialsCache, Pasta, PastaKey, XofSampler};
+    use crate::polynomial::Element;
     use diamond_io::{
         bgg::sampler::{BGGEncodingSampler, BGGPublicKeySampler},
-        io::utils::build_poly_vec,
         poly::{
             dcrt::{
-                DCRTPolyHashSampler, DCRTPolyParams, DCRTPolyUniformSampler,
+                DCRTPoly, DCRTPolyHashSampler, DCRTPolyParams, DCRTPolyUniformSampler, FinRingElem,
                 matrix::base::BaseMatrix,
             },
             sampler::PolyUniformSampler,
@@ -189,11 +302,22 @@ mod tests {
     };
     use sha3::Keccak256;
 
-    #[test]
-    fn test_encoding_add() {
-        // Create parameters for testing
-        // todo: if ring dimension is less than PASTA_T it return error.
-        let params = DCRTPolyParams::new(256, 2, 17, 1);
+    /// Pasta field prime
+    const P: u64 = 65_537;
+
+    /// Reduced state size, every word is its own encoding.
+    const T: usize = 4;
+
+    /// BGG.enc(1, plaintexts[0], ..) under fresh keys, plaintext i revealed if `reveal[i]`. The error is
+    /// zero, so the returned sampler re-encodes anything under the same secret.
+    fn encode_polys(
+        params: &DCRTPolyParams,
+        plaintexts: &[DCRTPoly],
+        reveal: &[bool],
+    ) -> (
+        Vec<BggEncoding<BaseMatrix<DCRTPoly>>>,
+        BGGEncodingSampler<DCRTPolyUniformSampler>,
+    ) {
         // Create samplers
         let key: [u8; 32] = rand::random();
         let d = 3;
@@ -205,31 +329,296 @@ mod tests {
         let tag: u64 = rand::random();
         let tag_bytes = tag.to_le_bytes();
 
-        // Create random public keys
-        let reveal_plaintexts = [true; 3];
-        let pubkeys = bgg_pubkey_sampler.sample(&params, &tag_bytes, &reveal_plaintexts);
-
-        // Create secret and plaintexts
-        let secrets = vec![create_bit_random_poly(&params); d];
-        let plaintexts =
-            build_poly_vec::<BaseMatrix<_>>(&params, &[true, true, true, true], 1, 4, 4, None);
-        println!("{:?}", plaintexts.len());
-
-        // Create encoding sampler and encodings
-        let bgg_encoding_sampler = BGGEncodingSampler::new(&params, &secrets, uniform_sampler, 0.0);
-        let encs = bgg_encoding_sampler.sample(&params, &pubkeys, &plaintexts);
-        // BGG.enc(1, poly1, poly2)
-        assert_eq!(encs.len(), 3);
-        let enc_one = encs[0].clone();
-        let enc_left = encs[1].clone();
-        let enc_right = encs[2].clone();
-        println!("sampled bgg");
-
-        let _ = keystream_bgg(&params, &enc_left, &enc_right, &enc_one, 0, 0);
-        // let ks1 = keystream_bgg(&params, &enc_left, &enc_right, &enc_one, 0, 1);
-        println!("sampled ks0");
-        // assert_ne!(ks0.vector, ks1.vector);
-
-        // later turn into
+        // Create random public keys, one for the constant 1 and one per plaintext
+        let reveal_plaintexts = [&[true], reveal].concat();
+        let pubkeys = bgg_pubkey_sampler.sample(params, &tag_bytes, &reveal_plaintexts);
+
+        let secrets = vec![create_bit_random_poly(params); d];
+        let bgg_encoding_sampler = BGGEncodingSampler::new(params, &secrets, uniform_sampler, 0.0);
+        let encs = bgg_encoding_sampler.sample(params, &pubkeys, plaintexts);
+        assert_eq!(encs.len(), plaintexts.len() + 1);
+        (encs, bgg_encoding_sampler)
+    }
+
+    /// BGG.enc(1, words[0], .., words[len - 1]), every word as a revealed constant polynomial.
+    fn encode_words(
+        params: &DCRTPolyParams,
+        words: &[u64],
+    ) -> Vec<BggEncoding<BaseMatrix<DCRTPoly>>> {
+        let plaintexts = words
+            .iter()
+            .map(|&w| poly_from_words::<BaseMatrix<DCRTPoly>>(params, &[w]))
+            .collect::<Vec<_>>();
+        encode_polys(params, &plaintexts, &vec![true; words.len()]).0
+    }
+
+    /// The words an evaluated state encodes, the constant coefficients of its plaintexts.
+    fn decode_words(encs: &[BggEncoding<BaseMatrix<DCRTPoly>>]) -> Vec<FinRingElem> {
+        encs.iter()
+            .map(|enc| enc.plaintext.as_ref().unwrap().coeffs()[0].clone())
+            .collect()
+    }
+
+    /// The l and r halves of `encode_words` over 2 * W words.
+    fn halves<const W: usize>(
+        encs: &[BggEncoding<BaseMatrix<DCRTPoly>>],
+    ) -> (
+        BggState<BaseMatrix<DCRTPoly>, W>,
+        BggState<BaseMatrix<DCRTPoly>, W>,
+    ) {
+        (
+            BggState::new(encs[1..=W].to_vec()),
+            BggState::new(encs[W + 1..].to_vec()),
+        )
+    }
+
+    #[test]
+    fn test_encoding_add() {
+        let params = DCRTPolyParams::new(256, 2, 17, 1);
+        let encs = encode_words(&params, &(0..2 * T as u64).collect::<Vec<_>>());
+        let (left, right) = halves::<T>(&encs);
+
+        let ks0 = keystream_bgg(&params, &left, &right, &encs[0], P, 0, 0);
+        let ks1 = keystream_bgg(&params, &left, &right, &encs[0], P, 0, 1);
+        assert_eq!(ks0.words().len(), T);
+        assert_ne!(ks0[0].vector, ks1[0].vector);
+    }
+
+    #[test]
+    fn test_state_larger_than_ring() {
+        // 32 words over a ring of dimension 16, which a single packed polynomial could not hold
+        const T: usize = 32;
+        let params = DCRTPolyParams::new(16, 1, 17, 1);
+        let p = bgg_modulus::<BaseMatrix<DCRTPoly>>(&params);
+
+        let key = PastaKey::<T>::generate(&mut rand::rng(), p);
+        let mut pasta = Pasta::<T, PASTA_R>::with_key(key.clone(), p);
+        let encs = encode_words(&params, key.words());
+        let (left, right) = halves::<T>(&encs);
+        let ks = keystream_bgg(&params, &left, &right, &encs[0], p, 1, 0);
+        let expected = pasta
+            .keystream(1, 0)
+            .iter()
+            .map(|&w| FinRingElem::constant(&params.modulus(), w))
+            .collect::<Vec<_>>();
+        assert_eq!(decode_words(ks.words()), expected);
+    }
+
+    #[test]
+    fn test_keystream_cached() {
+        let params = DCRTPolyParams::new(256, 2, 17, 1);
+        let encs = encode_words(&params, &(0..2 * T as u64).collect::<Vec<_>>());
+        let (left, right) = halves::<T>(&encs);
+        let mut cache = MaterialsCache::new(2);
+
+        for ctr in [0, 1, 0, 2, 0] {
+            let materials = cache.get_or_derive(P, 3, ctr, T, PASTA_R);
+            let ks = keystream_bgg_with(&params, &left, &right, &encs[0], materials);
+            let expected = keystream_bgg(&params, &left, &right, &encs[0], P, 3, ctr);
+            for (x, y) in ks.words().iter().zip(expected.words()) {
+                assert_eq!(x.vector, y.vector);
+            }
+        }
+        // ctr 0 was dropped when ctr 2 came in
+        assert_eq!(cache.stats(), (1, 4));
+        assert_eq!(cache.len(), 2);
+    }
+
+    #[test]
+    fn test_keystream_matches_plain() {
+        // a single crt prime, so the bgg plaintexts live in the same Z_p as the plain keystream
+        let params = DCRTPolyParams::new(256, 1, 17, 1);
+        let p = bgg_modulus::<BaseMatrix<DCRTPoly>>(&params);
+        let key = PastaKey::<T>::generate(&mut rand::rng(), p);
+        let mut pasta = Pasta::<T, PASTA_R>::with_key(key.clone(), p);
+
+        let encs = encode_words(&params, key.words());
+        let (left, right) = halves::<T>(&encs);
+        for (nonce, ctr) in [(0, 0), (0, 1), (9, 4)] {
+            let ks = keystream_bgg(&params, &left, &right, &encs[0], p, nonce, ctr);
+            let expected = pasta
+                .keystream(nonce, ctr)
+                .iter()
+                .map(|&w| FinRingElem::constant(&params.modulus(), w))
+                .collect::<Vec<_>>();
+            assert_eq!(
+                decode_words(ks.words()),
+                expected,
+                "nonce {nonce}, block {ctr}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_circuit_over_plaintexts() {
+        // the same circuit on bare polynomials, no encodings needed
+        let params = DCRTPolyParams::new(256, 1, 17, 1);
+        let p = bgg_modulus::<BaseMatrix<DCRTPoly>>(&params);
+        let key = PastaKey::<T>::generate(&mut rand::rng(), p);
+        let mut pasta = Pasta::<T, PASTA_R>::with_key(key.clone(), p);
+
+        let inputs = key
+            .words()
+            .iter()
+            .map(|&w| poly_from_words::<BaseMatrix<DCRTPoly>>(&params, &[w]))
+            .collect::<Vec<_>>();
+        let materials = RoundMaterials::derive(p, 3, 5, T, PASTA_R);
+        let out =
+            pasta_circuit::<T>(&materials).eval(&params, &DCRTPoly::const_one(&params), &inputs);
+        assert_eq!(
+            out.iter()
+                .map(|x| x.coeffs()[0].clone())
+                .collect::<Vec<_>>(),
+            pasta
+                .keystream(3, 5)
+                .iter()
+                .map(|&w| FinRingElem::constant(&params.modulus(), w))
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_keystream_hidden_key() {
+        use crate::batch::BatchEncoder;
+        use crate::bfv_pke::{Bfv, Evaluator};
+        use crate::encoding::Encoder;
+        use crate::pasta_bfv::EncryptedPastaKey;
+
+        // the bfv ring is the bgg one, t = 97 allows batching over it and n q^2 t stays below 2^127
+        const N: usize = 16;
+        const Q: u64 = 1 << 57;
+        const T: u64 = 97;
+        let params = DCRTPolyParams::new(N as u32, 1, 30, 1);
+        let q = bgg_modulus::<BaseMatrix<DCRTPoly>>(&params);
+
+        let key = PastaKey::<2>::generate(&mut rand::rng(), T);
+        let mut pasta = Pasta::<2, 1>::with_key(key.clone(), T);
+        let (bfv, sk) = Bfv::<N, Q, T>::keygen().unwrap();
+        let transcipher = Transcipher::<N, Q, T, Pasta<2, 1>>::new(
+            EncryptedPastaKey::encrypt(&bfv, &key).unwrap().into_words(),
+            Evaluator::new().with_relin_key(bfv.gen_relin_key(&sk, 8)),
+        )
+        .unwrap();
+
+        // the bfv secret is the only thing encoded, hidden
+        let s = bfv_secret_poly::<BaseMatrix<DCRTPoly>, N>(&params, &sk);
+        let (encs, sampler) = encode_polys(&params, std::slice::from_ref(&s), &[false]);
+        let ks = keystream_hidden_key(&params, &transcipher, &encs[1], &encs[0], 4, 0, N).unwrap();
+        assert_eq!(ks.len(), 2);
+
+        let encoder = BatchEncoder::<N, T>::new().unwrap();
+        let minus_ks = transcipher.decrypt(4, 0, &[0; 2 * N]).unwrap();
+        for (i, (enc, ct)) in ks.iter().zip(&minus_ks).enumerate() {
+            assert!(enc.plaintext.is_none());
+            // the phase the encoding has to hold, from the same circuit over bare polynomials
+            let phase = bfv_phase_circuit(&-*ct.c_1(), &-*ct.c_2(), q)
+                .eval(
+                    &params,
+                    &DCRTPoly::const_one(&params),
+                    std::slice::from_ref(&s),
+                )
+                .pop()
+                .unwrap();
+            let expected = sampler.sample(
+                &params,
+                &[encs[0].pubkey.clone(), enc.pubkey.clone()],
+                std::slice::from_ref(&phase),
+            );
+            assert_eq!(enc.vector, expected[1].vector);
+
+            // round(x t / q) of every coefficient is the bfv plaintext, its slots the keystream
+            let m = Polynomial::<N, T>::new(core::array::from_fn(|j| {
+                let x: u64 = phase.coeffs()[j].value().to_string().parse().unwrap();
+                Element::new(((x as u128 * T as u128 + q as u128 / 2) / q as u128) as i64)
+            }));
+            let slots = encoder.decode(&m);
+            for (b, slot) in slots.iter().enumerate() {
+                assert_eq!(
+                    *slot,
+                    pasta.keystream(4, b as u64)[i],
+                    "word {i}, block {b}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_feistel_matches_plain() {
+        let params = DCRTPolyParams::new(256, 1, 17, 1);
+        let p = bgg_modulus::<BaseMatrix<DCRTPoly>>(&params);
+        let mut words: [u64; PASTA_T] = core::array::from_fn(|_| rand::random_range(0..p));
+
+        let mut circuit = PolyCircuit::new();
+        let mut state = circuit.input(PASTA_T);
+        feistel(&mut circuit, &mut state);
+        circuit.output(state);
+        let inputs = words
+            .iter()
+            .map(|&w| poly_from_words::<BaseMatrix<DCRTPoly>>(&params, &[w]))
+            .collect::<Vec<_>>();
+        let out = circuit.eval(&params, &DCRTPoly::const_one(&params), &inputs);
+
+        Pasta::<PASTA_T, PASTA_R>::sbox_feistel(&mut words, Modulus::new(p));
+        for (x, w) in out.iter().zip(words) {
+            assert_eq!(x.coeffs()[0], FinRingElem::constant(&params.modulus(), w));
+            // a packed rotation would have pulled -x_{T-1} into coefficient 0
+            assert!(
+                x.coeffs()[1..]
+                    .iter()
+                    .all(|c| *c == FinRingElem::constant(&params.modulus(), 0))
+            );
+        }
+    }
+
+    #[test]
+    fn test_full_state_words() {
+        let params = DCRTPolyParams::new(256, 2, 17, 1);
+        let words = (0..2 * PASTA_T as u64)
+            .map(|w| w * 3 % P)
+            .collect::<Vec<_>>();
+        let encs = encode_words(&params, &words);
+        let (left, right) = halves::<PASTA_T>(&encs);
+        for i in 0..PASTA_T {
+            assert_eq!(
+                decode_words(std::slice::from_ref(&left[i])),
+                vec![FinRingElem::constant(&params.modulus(), words[i])]
+            );
+        }
+        assert_eq!(
+            decode_words(right.words()),
+            words[PASTA_T..]
+                .iter()
+                .map(|&w| FinRingElem::constant(&params.modulus(), w))
+                .collect::<Vec<_>>()
+        );
+        let short = encs[..4].to_vec();
+        assert!(std::panic::catch_unwind(move || BggState::<_, PASTA_T>::new(short)).is_err());
+    }
+
+    #[test]
+    fn test_affine_layers_match_plain_sampler() {
+        let params = DCRTPolyParams::new(256, 2, 17, 1);
+        let materials = RoundMaterials::derive(P, 7, 1, PASTA_T, PASTA_R);
+        let mut sampler = XofSampler::seeded(P, 7, 1);
+
+        for layer in materials.layers.iter() {
+            let mat_l = sampler.sequential_matrix(PASTA_T);
+            let rc_l = sampler.vec(PASTA_T, true);
+            assert_eq!(layer.mat_l.to_rows(), mat_l);
+            assert_eq!(layer.rc_l, rc_l);
+            assert_eq!(layer.mat_r.to_rows(), sampler.sequential_matrix(PASTA_T));
+            assert_eq!(layer.rc_r, sampler.vec(PASTA_T, true));
+
+            // the bgg side scales by exactly the same words, as constant polynomials
+            for &w in mat_l[0].iter().chain(&rc_l) {
+                let c = poly_from_words::<BaseMatrix<DCRTPoly>>(&params, &[w]);
+                assert_eq!(c.coeffs()[0], FinRingElem::constant(&params.modulus(), w));
+                assert!(
+                    c.coeffs()[1..]
+                        .iter()
+                        .all(|x| *x == FinRingElem::constant(&params.modulus(), 0))
+                );
+            }
+        }
     }
 }
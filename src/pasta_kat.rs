@@ -0,0 +1,230 @@
+//! Known-answer vectors for plain Pasta, so changes to the linear layer or the SHAKE sampling can't silently
+//! change the cipher.
+//!
+//! Vectors are plain text, one `field = value` per line, records separated by blank lines, `#` starts a
+//! comment. Word lists are comma separated decimals:
+//!
+//! ```text
+//! t = 128
+//! rounds = 3
+//! modulus = 65537
+//! nonce = 123456789
+//! key = 1, 2, ...
+//! plaintext = ...
+//! ciphertext = ...
+//! ```
+//!
+//! `testdata/pasta.kat` holds the vectors checked by the tests.
+//! todo: add the vectors of the isec-tugraz hybrid-HE-framework PASTA_3 next to our own ones, the format
+//! only needs their key, nonce and block words.
+
+use std::fmt;
+
+use crate::pasta_plain::{PASTA_R, PASTA_T, Pasta, PastaError, PastaKey};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KatVector {
+    pub t: usize,
+    pub rounds: usize,
+    pub modulus: u64,
+    pub nonce: u64,
+    pub key: Vec<u64>,
+    pub plaintext: Vec<u64>,
+    pub ciphertext: Vec<u64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum KatError {
+    /// Not `field = value`, or the value doesn't parse.
+    Syntax {
+        line: usize,
+    },
+    UnknownField {
+        line: usize,
+        field: String,
+    },
+    /// The record ending at `line` lacks `field`.
+    MissingField {
+        line: usize,
+        field: &'static str,
+    },
+    /// No `Pasta` instance for this state size and round count.
+    UnsupportedInstance {
+        t: usize,
+        rounds: usize,
+    },
+    Key(PastaError),
+}
+
+impl fmt::Display for KatError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            KatError::Syntax { line } => write!(f, "line {line}: expected `field = value`"),
+            KatError::UnknownField { line, field } => {
+                write!(f, "line {line}: unknown field `{field}`")
+            }
+            KatError::MissingField { line, field } => {
+                write!(f, "record ending at line {line} has no `{field}`")
+            }
+            KatError::UnsupportedInstance { t, rounds } => {
+                write!(f, "no pasta instance with t = {t} and {rounds} rounds")
+            }
+            KatError::Key(e) => write!(f, "{e}"),
+        }
+    }
+}
+
+impl std::error::Error for KatError {}
+
+#[derive(Default)]
+struct Record {
+    t: Option<usize>,
+    rounds: Option<usize>,
+    modulus: Option<u64>,
+    nonce: Option<u64>,
+    key: Option<Vec<u64>>,
+    plaintext: Option<Vec<u64>>,
+    ciphertext: Option<Vec<u64>>,
+}
+
+impl Record {
+    fn is_empty(&self) -> bool {
+        self.t.is_none()
+            && self.rounds.is_none()
+            && self.modulus.is_none()
+            && self.nonce.is_none()
+            && self.key.is_none()
+            && self.plaintext.is_none()
+            && self.ciphertext.is_none()
+    }
+
+    fn finish(self, line: usize) -> Result<KatVector, KatError> {
+        fn get<V>(v: Option<V>, line: usize, field: &'static str) -> Result<V, KatError> {
+            v.ok_or(KatError::MissingField { line, field })
+        }
+        Ok(KatVector {
+            t: get(self.t, line, "t")?,
+            rounds: get(self.rounds, line, "rounds")?,
+            modulus: get(self.modulus, line, "modulus")?,
+            nonce: get(self.nonce, line, "nonce")?,
+            key: get(self.key, line, "key")?,
+            plaintext: get(self.plaintext, line, "plaintext")?,
+            ciphertext: get(self.ciphertext, line, "ciphertext")?,
+        })
+    }
+}
+
+fn parse_words(value: &str) -> Option<Vec<u64>> {
+    if value.is_empty() {
+        return Some(vec![]);
+    }
+    value.split(',').map(|w| w.trim().parse().ok()).collect()
+}
+
+/// All records of a vector file, in order.
+pub fn parse(text: &str) -> Result<Vec<KatVector>, KatError> {
+    let mut out = vec![];
+    let mut record = Record::default();
+    let mut last = 0;
+    for (i, raw) in text.lines().enumerate() {
+        let line = i + 1;
+        let content = raw.split('#').next().unwrap().trim();
+        if content.is_empty() {
+            if raw.trim().is_empty() && !record.is_empty() {
+                out.push(std::mem::take(&mut record).finish(last)?);
+            }
+            continue;
+        }
+        last = line;
+        let (field, value) = content.split_once('=').ok_or(KatError::Syntax { line })?;
+        let value = value.trim();
+        let syntax = KatError::Syntax { line };
+        match field.trim() {
+            "t" => record.t = Some(value.parse().map_err(|_| syntax)?),
+            "rounds" => record.rounds = Some(value.parse().map_err(|_| syntax)?),
+            "modulus" => record.modulus = Some(value.parse().map_err(|_| syntax)?),
+            "nonce" => record.nonce = Some(value.parse().map_err(|_| syntax)?),
+            "key" => record.key = Some(parse_words(value).ok_or(syntax)?),
+            "plaintext" => record.plaintext = Some(parse_words(value).ok_or(syntax)?),
+            "ciphertext" => record.ciphertext = Some(parse_words(value).ok_or(syntax)?),
+            other => {
+                return Err(KatError::UnknownField {
+                    line,
+                    field: other.to_string(),
+                });
+            }
+        }
+    }
+    if !record.is_empty() {
+        out.push(record.finish(last)?);
+    }
+    Ok(out)
+}
+
+impl KatVector {
+    /// Encrypts `plaintext` with the instance the vector names. Only the instances the crate defines are
+    /// supported: the default one, `Pasta3` (t = 128, 3 rounds) and `Pasta4` (t = 32, 4 rounds).
+    pub fn encrypt(&self) -> Result<Vec<u64>, KatError> {
+        fn run<const T: usize, const R: usize>(v: &KatVector) -> Result<Vec<u64>, KatError> {
+            let key = PastaKey::<T>::try_new(v.key.clone(), v.modulus).map_err(KatError::Key)?;
+            let mut pasta = Pasta::<T, R>::with_key(key, v.modulus);
+            Ok(pasta.encrypt_with_nonce(v.nonce, &v.plaintext))
+        }
+        match (self.t, self.rounds) {
+            (PASTA_T, PASTA_R) => run::<PASTA_T, PASTA_R>(self),
+            (128, 3) => run::<128, 3>(self),
+            (32, 4) => run::<32, 4>(self),
+            (t, rounds) => Err(KatError::UnsupportedInstance { t, rounds }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const VECTORS: &str = include_str!("../testdata/pasta.kat");
+
+    #[test]
+    fn test_vectors() {
+        let vectors = parse(VECTORS).unwrap();
+        assert!(vectors.len() >= 3);
+        for (i, v) in vectors.iter().enumerate() {
+            assert_eq!(v.encrypt().unwrap(), v.ciphertext, "vector {i}");
+        }
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let record = "t = 1\nrounds = 3\nmodulus = 17\nnonce = 0\nkey = 1, 2\nplaintext =\n";
+        assert_eq!(
+            parse(record),
+            Err(KatError::MissingField {
+                line: 6,
+                field: "ciphertext"
+            })
+        );
+        let v = parse(&format!("# comment\n{record}ciphertext = \n\n")).unwrap();
+        assert_eq!(v.len(), 1);
+        assert_eq!((v[0].key.clone(), v[0].plaintext.len()), (vec![1, 2], 0));
+
+        assert_eq!(parse("key 1, 2"), Err(KatError::Syntax { line: 1 }));
+        assert_eq!(parse("key = 1, x"), Err(KatError::Syntax { line: 1 }));
+        assert_eq!(
+            parse("tag = 1"),
+            Err(KatError::UnknownField {
+                line: 1,
+                field: "tag".to_string()
+            })
+        );
+        let unsupported = KatVector {
+            t: 3,
+            rounds: 2,
+            ..v[0].clone()
+        };
+        assert_eq!(
+            unsupported.encrypt(),
+            Err(KatError::UnsupportedInstance { t: 3, rounds: 2 })
+        );
+    }
+}
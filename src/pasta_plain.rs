@@ -1,232 +1,809 @@
 //! Implementation of PASTA: https://eprint.iacr.org/2021/731.pdf
 //! Referred PASTA_3 from: https://github.com/isec-tugraz/hybrid-HE-framework/blob/master/ciphers/pasta_3/plain/pasta_3_plain.cpp
+//! PASTA_4 (4 rounds, t = 32) only differs in the sizes, see `Pasta4`.
 
-use std::io::Read;
+use alloc::collections::{BTreeMap, VecDeque};
+use alloc::{vec, vec::Vec};
+use core::fmt;
 
+use crate::field::{LazyAcc, Modulus, Ring};
 use byteorder::{BigEndian, ByteOrder};
+use rand::Rng;
 use sha3::{
     Shake128, Shake128Reader,
     digest::{ExtendableOutput, Update},
 };
+use zeroize::{Zeroize, ZeroizeOnDrop, Zeroizing};
 
 /// Plaintext size
 pub const PASTA_T: usize = 1;
 /// Round count
 pub const PASTA_R: usize = 3;
+/// Largest modulus `try_new` accepts, in bits. Keeps at least 256 products in a lazy linear layer sum
+/// between reductions.
+pub const MAX_MODULUS_BITS: u32 = 60;
 
-const NONCE: u64 = 123_456_789;
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PastaError {
+    KeyLength {
+        expected: usize,
+        got: usize,
+    },
+    KeyOutOfRange {
+        index: usize,
+        value: u64,
+        modulus: u64,
+    },
+    /// Below 2 or wider than `MAX_MODULUS_BITS`.
+    Modulus {
+        modulus: u64,
+    },
+    /// `R = 0`.
+    Rounds,
+    /// A matrix with an empty first row.
+    EmptyMatrix,
+    /// A `MaterialsCache` of capacity 0.
+    CacheCapacity,
+    /// Round materials derived for another round count, `expected` is R + 1 layers.
+    Materials {
+        expected: usize,
+        got: usize,
+    },
+}
+
+impl fmt::Display for PastaError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PastaError::KeyLength { expected, got } => {
+                write!(f, "key must be 2T = {expected} words, got {got}")
+            }
+            PastaError::KeyOutOfRange {
+                index,
+                value,
+                modulus,
+            } => write!(
+                f,
+                "key word {index} ({value}) not below the modulus {modulus}"
+            ),
+            PastaError::Modulus { modulus } => {
+                write!(f, "modulus {modulus} not in 2..2^{MAX_MODULUS_BITS}")
+            }
+            PastaError::Rounds => write!(f, "pasta needs at least one round"),
+            PastaError::EmptyMatrix => write!(f, "empty matrix"),
+            PastaError::CacheCapacity => write!(f, "cache capacity must be positive"),
+            PastaError::Materials { expected, got } => write!(
+                f,
+                "materials derived for another round count: {got} layers, expected {expected}"
+            ),
+        }
+    }
+}
 
-type Block = [u64; PASTA_T];
+impl core::error::Error for PastaError {}
 
-#[inline(always)]
-fn mul_mod(a: u64, b: u64, p: u64) -> u64 {
-    ((a as u128 * b as u128) % p as u128) as u64
+fn check_modulus(modulus: u64) -> Result<(), PastaError> {
+    if modulus < 2 || 64 - modulus.leading_zeros() > MAX_MODULUS_BITS {
+        return Err(PastaError::Modulus { modulus });
+    }
+    Ok(())
 }
-#[inline(always)]
-fn add_mod(a: u64, b: u64, p: u64) -> u64 {
-    let s = a.wrapping_add(b);
-    if s >= p { s - p } else { s }
+
+/// PASTA over 2 x `T` words of state with `R` rounds. The defaults are the reduced `PASTA_T` / `PASTA_R`
+/// instance, the paper's parameter sets are [`Pasta3`] and [`Pasta4`].
+pub struct Pasta<const T: usize = PASTA_T, const R: usize = PASTA_R> {
+    key: PastaKey<T>,
+    field: Modulus,
+    cache: Option<MaterialsCache>,
 }
 
-pub struct Pasta {
-    key: Vec<u64>,
-    p: u64,
-    mask: u64,
+/// 3 rounds over 2 x 128 words.
+pub type Pasta3 = Pasta<128, 3>;
+/// 4 rounds over 2 x 32 words.
+pub type Pasta4 = Pasta<32, 4>;
+
+/// Secret key of a `Pasta<T, _>`: 2T field elements, the left then the right half of the initial state.
+/// Cleared from memory when dropped, `Debug` doesn't print the words.
+#[derive(Clone, PartialEq, Eq)]
+pub struct PastaKey<const T: usize = PASTA_T> {
+    words: Vec<u64>,
+}
+
+impl<const T: usize> PastaKey<T> {
+    /// `try_new` that panics on invalid input.
+    pub fn new(words: Vec<u64>, modulus: u64) -> Self {
+        Self::try_new(words, modulus).unwrap_or_else(|e| panic!("{e}"))
+    }
+
+    /// 2T words, all below `modulus`, under a modulus `Pasta` accepts. The words are wiped on error too.
+    pub fn try_new(words: Vec<u64>, modulus: u64) -> Result<Self, PastaError> {
+        let mut words = Zeroizing::new(words);
+        check_modulus(modulus)?;
+        if words.len() != 2 * T {
+            return Err(PastaError::KeyLength {
+                expected: 2 * T,
+                got: words.len(),
+            });
+        }
+        if let Some((index, &value)) = words.iter().enumerate().find(|(_, w)| **w >= modulus) {
+            return Err(PastaError::KeyOutOfRange {
+                index,
+                value,
+                modulus,
+            });
+        }
+        Ok(Self {
+            words: core::mem::take(&mut *words),
+        })
+    }
+
+    /// # Panics
+    /// If `Pasta` can't use `modulus`, see [`PastaKey::try_generate`].
+    pub fn generate(rng: &mut impl Rng, modulus: u64) -> Self {
+        Self::try_generate(rng, modulus).unwrap_or_else(|e| panic!("{e}"))
+    }
+
+    /// Uniform key words below `modulus`.
+    pub fn try_generate(rng: &mut impl Rng, modulus: u64) -> Result<Self, PastaError> {
+        check_modulus(modulus)?;
+        Self::try_new(
+            (0..2 * T).map(|_| rng.random_range(0..modulus)).collect(),
+            modulus,
+        )
+    }
+
+    pub fn words(&self) -> &[u64] {
+        &self.words
+    }
+}
+
+impl<const T: usize> Zeroize for PastaKey<T> {
+    fn zeroize(&mut self) {
+        self.words.zeroize();
+    }
+}
+
+impl<const T: usize> Drop for PastaKey<T> {
+    fn drop(&mut self) {
+        self.zeroize();
+    }
+}
+
+impl<const T: usize> ZeroizeOnDrop for PastaKey<T> {}
+
+impl<const T: usize> fmt::Debug for PastaKey<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "PastaKey<{T}>(..)")
+    }
+}
+
+/// Field elements mod p squeezed from SHAKE128(nonce || block counter) with [`Ring::sample`].
+pub struct XofSampler {
     shake: Shake128Reader,
+    field: Modulus,
+}
+
+impl XofSampler {
+    /// Sampler over the unseeded SHAKE stream, call `reseed` before use.
+    pub fn new(modulus: u64) -> Self {
+        Self {
+            shake: Shake128::default().finalize_xof(),
+            field: Modulus::new(modulus),
+        }
+    }
+
+    pub fn seeded(modulus: u64, nonce: u64, block_counter: u64) -> Self {
+        let mut sampler = Self::new(modulus);
+        sampler.reseed(nonce, block_counter);
+        sampler
+    }
+
+    pub fn reseed(&mut self, nonce: u64, block_counter: u64) {
+        let mut shake = Shake128::default();
+        let mut seed = [0u8; 16];
+        BigEndian::write_u64(&mut seed[0..8], nonce);
+        BigEndian::write_u64(&mut seed[8..16], block_counter);
+        shake.update(&seed);
+        self.shake = shake.finalize_xof();
+    }
+
+    pub fn field_element(&mut self, allow_zero: bool) -> u64 {
+        self.field.sample(&mut self.shake, allow_zero)
+    }
+
+    pub fn vec(&mut self, len: usize, allow_zero: bool) -> Vec<u64> {
+        (0..len).map(|_| self.field_element(allow_zero)).collect()
+    }
+
+    /// t x t sequential matrix, only the first row is sampled (nonzero entries).
+    pub fn sequential(&mut self, t: usize) -> SequentialMatrix {
+        SequentialMatrix::new(self.vec(t, false), self.field)
+    }
+
+    /// `sequential` expanded into all t rows.
+    pub fn sequential_matrix(&mut self, t: usize) -> Vec<Vec<u64>> {
+        self.sequential(t).to_rows()
+    }
+}
+
+fn calculate_row(prev_row: &[u64], first_row: &[u64], field: Modulus) -> Vec<u64> {
+    debug_assert_eq!(prev_row.len(), first_row.len());
+    let t = first_row.len();
+
+    (0..t)
+        .map(|j| {
+            let tmp = field.mul(first_row[j], prev_row[t - 1]);
+            if j != 0 {
+                field.add(tmp, prev_row[j - 1])
+            } else {
+                tmp
+            }
+        })
+        .collect()
+}
+
+/// Pasta's random matrix, kept as its first row. Row i follows from row i - 1 by the recurrence of
+/// `calculate_row` (multiplication by x modulo x^t - first_row(x)), so a product only needs the current
+/// row: O(t) work per output and O(t) memory instead of t^2 stored words.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SequentialMatrix {
+    pub first_row: Vec<u64>,
+    field: Modulus,
+}
+
+impl SequentialMatrix {
+    /// # Panics
+    /// If `first_row` is empty, see [`SequentialMatrix::try_new`].
+    pub fn new(first_row: Vec<u64>, field: Modulus) -> Self {
+        Self::try_new(first_row, field).unwrap_or_else(|e| panic!("{e}"))
+    }
+
+    pub fn try_new(first_row: Vec<u64>, field: Modulus) -> Result<Self, PastaError> {
+        if first_row.is_empty() {
+            return Err(PastaError::EmptyMatrix);
+        }
+        Ok(Self { first_row, field })
+    }
+
+    pub fn dim(&self) -> usize {
+        self.first_row.len()
+    }
+
+    /// The rows in order, each computed from the previous one.
+    pub fn rows(&self) -> impl Iterator<Item = Vec<u64>> + '_ {
+        let mut row = Some(self.first_row.clone());
+        (0..self.dim()).map(move |i| {
+            let cur = row.take().unwrap();
+            if i + 1 < self.dim() {
+                row = Some(calculate_row(&cur, &self.first_row, self.field));
+            }
+            cur
+        })
+    }
+
+    /// The dense t x t matrix, for consumers that need every row at once (the BGG keystream).
+    pub fn to_rows(&self) -> Vec<Vec<u64>> {
+        self.rows().collect()
+    }
+
+    /// self * v without materializing the matrix.
+    pub fn mul_vec(&self, v: &[u64]) -> Vec<u64> {
+        assert_eq!(v.len(), self.dim(), "vector length differs from the matrix");
+        self.rows()
+            .map(|row| {
+                // one reduction per row instead of one per term
+                let mut acc = LazyAcc::new(self.field);
+                for (a, b) in row.iter().zip(v) {
+                    acc.add_mul(*a, *b);
+                }
+                acc.finish()
+            })
+            .collect()
+    }
+}
+
+/// One affine layer of the keystream, for the left and right halves.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AffineLayer {
+    pub mat_l: SequentialMatrix,
+    pub rc_l: Vec<u64>,
+    pub mat_r: SequentialMatrix,
+    pub rc_r: Vec<u64>,
+}
+
+/// Everything the keystream for one (nonce, block counter) samples from SHAKE: `rounds + 1` affine
+/// layers (one per round plus the final one). This is the single place the derivation lives, the BGG
+/// keystream converts these instead of sampling on its own.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RoundMaterials {
+    pub layers: Vec<AffineLayer>,
+}
+
+impl RoundMaterials {
+    pub fn derive(modulus: u64, nonce: u64, block_counter: u64, t: usize, rounds: usize) -> Self {
+        let mut sampler = XofSampler::seeded(modulus, nonce, block_counter);
+        // sampling order follows the reference: matrix then constants, left then right
+        let layers = (0..=rounds)
+            .map(|_| {
+                let mat_l = sampler.sequential(t);
+                let rc_l = sampler.vec(t, true);
+                let mat_r = sampler.sequential(t);
+                let rc_r = sampler.vec(t, true);
+                AffineLayer {
+                    mat_l,
+                    rc_l,
+                    mat_r,
+                    rc_r,
+                }
+            })
+            .collect();
+        Self { layers }
+    }
+}
+
+/// Round materials memoized by (modulus, t, rounds, nonce, block counter), for callers evaluating the same
+/// keystream block over and over (benchmarks, the BGG conformance tests). One cache can serve several
+/// Pasta instances. Holds at most `capacity` entries and drops the oldest one first.
+#[derive(Debug, Clone)]
+pub struct MaterialsCache {
+    capacity: usize,
+    entries: BTreeMap<MaterialsKey, RoundMaterials>,
+    order: VecDeque<MaterialsKey>,
+    hits: u64,
+    misses: u64,
+}
+
+/// (modulus, t, rounds, nonce, block counter)
+type MaterialsKey = (u64, usize, usize, u64, u64);
+
+impl MaterialsCache {
+    /// # Panics
+    /// If `capacity` is 0, see [`MaterialsCache::try_new`].
+    pub fn new(capacity: usize) -> Self {
+        Self::try_new(capacity).unwrap_or_else(|e| panic!("{e}"))
+    }
+
+    pub fn try_new(capacity: usize) -> Result<Self, PastaError> {
+        if capacity == 0 {
+            return Err(PastaError::CacheCapacity);
+        }
+        Ok(Self {
+            capacity,
+            entries: BTreeMap::new(),
+            order: VecDeque::with_capacity(capacity),
+            hits: 0,
+            misses: 0,
+        })
+    }
+
+    /// The materials of (`nonce`, `block_counter`) for the given instance, derived on a miss.
+    pub fn get_or_derive(
+        &mut self,
+        modulus: u64,
+        nonce: u64,
+        block_counter: u64,
+        t: usize,
+        rounds: usize,
+    ) -> &RoundMaterials {
+        let key = (modulus, t, rounds, nonce, block_counter);
+        if self.entries.contains_key(&key) {
+            self.hits += 1;
+        } else {
+            self.misses += 1;
+            if self.entries.len() == self.capacity {
+                let oldest = self.order.pop_front().unwrap();
+                self.entries.remove(&oldest);
+            }
+            let materials = RoundMaterials::derive(modulus, nonce, block_counter, t, rounds);
+            self.entries.insert(key, materials);
+            self.order.push_back(key);
+        }
+        &self.entries[&key]
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// (hits, misses) since the cache was created.
+    pub fn stats(&self) -> (u64, u64) {
+        (self.hits, self.misses)
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.order.clear();
+    }
 }
 
 impl Pasta {
+    /// # Panics
+    /// Same as [`Pasta::with_key`].
+    pub fn new(key: PastaKey, modulus: u64) -> Self {
+        Self::with_key(key, modulus)
+    }
+
+    pub fn try_new(key: Vec<u64>, modulus: u64) -> Result<Self, PastaError> {
+        Self::try_from_words(key, modulus)
+    }
+}
+
+impl<const T: usize, const R: usize> Pasta<T, R> {
+    /// # Panics
+    /// If `R` is 0, `modulus` is out of range or `key` was made for a larger modulus, see
+    /// [`Pasta::try_with_key`].
+    pub fn with_key(key: PastaKey<T>, modulus: u64) -> Self {
+        Self::try_with_key(key, modulus).unwrap_or_else(|e| panic!("{e}"))
+    }
+
+    pub fn try_with_key(key: PastaKey<T>, modulus: u64) -> Result<Self, PastaError> {
+        if R == 0 {
+            return Err(PastaError::Rounds);
+        }
+        check_modulus(modulus)?;
+        if let Some((index, &value)) = key.words.iter().enumerate().find(|(_, w)| **w >= modulus) {
+            return Err(PastaError::KeyOutOfRange {
+                index,
+                value,
+                modulus,
+            });
+        }
+        Ok(Self {
+            key,
+            field: Modulus::new(modulus),
+            cache: None,
+        })
+    }
+
+    /// Validating constructor from bare key words, see [`PastaError`].
+    pub fn try_from_words(key: Vec<u64>, modulus: u64) -> Result<Self, PastaError> {
+        Self::try_with_key(PastaKey::try_new(key, modulus)?, modulus)
+    }
+
+    /// Memoizes the round materials of up to `capacity` (nonce, block counter) pairs for `keystream` and
+    /// everything built on it. Only worth it when the same blocks are evaluated repeatedly.
+    ///
+    /// # Panics
+    /// If `capacity` is 0, see [`Pasta::try_with_cache`].
+    pub fn with_cache(self, capacity: usize) -> Self {
+        self.try_with_cache(capacity)
+            .unwrap_or_else(|e| panic!("{e}"))
+    }
+
+    pub fn try_with_cache(mut self, capacity: usize) -> Result<Self, PastaError> {
+        self.cache = Some(MaterialsCache::try_new(capacity)?);
+        Ok(self)
+    }
+
+    pub fn cache(&self) -> Option<&MaterialsCache> {
+        self.cache.as_ref()
+    }
+
+    pub fn modulus(&self) -> u64 {
+        self.field.modulus()
+    }
+
     /// im not sure if this consider secure PRF, but here is what we could output n length of integers(mod p)
     /// by leveraging keystream function of Pasta
     pub fn prf(&mut self, nonce: u64, n: usize) -> Vec<u64> {
-        let mut ctr = 0u64;
-        let mut out = Vec::with_capacity(n);
-        while out.len() < n {
-            let ks = self.keystream(nonce, ctr);
-            out.extend_from_slice(&ks);
-            ctr += 1;
-        }
-        out.truncate(n);
+        let mut out = vec![0; n];
+        self.keystream_into(nonce, 0, &mut out);
         out
     }
 
-    pub fn new(key: Vec<u64>, modulus: u64) -> Self {
-        let reader = Shake128::default().finalize_xof();
-        let bits = 64 - modulus.leading_zeros();
-        let mask = (1u64 << bits) - 1;
-        Self {
-            key,
-            p: modulus,
-            mask,
-            shake: reader,
-        }
+    /// Adds the keystream of (`nonce`, block index) to every block. A nonce must never be used twice
+    /// under the same key, the two messages would share a keystream.
+    pub fn encrypt_with_nonce(&mut self, nonce: u64, plaintext: &[u64]) -> Vec<u64> {
+        let mut out = plaintext.to_vec();
+        self.encrypt_in_place(nonce, &mut out);
+        out
     }
 
-    pub fn encrypt(&mut self, plaintext: &[u64]) -> Vec<u64> {
-        let n_blocks = (plaintext.len() + PASTA_T - 1) / PASTA_T;
-        let mut out = plaintext.to_vec();
+    pub fn decrypt_with_nonce(&mut self, nonce: u64, ciphertext: &[u64]) -> Vec<u64> {
+        let mut out = ciphertext.to_vec();
+        self.decrypt_in_place(nonce, &mut out);
+        out
+    }
+
+    /// `encrypt_with_nonce` overwriting `words`, nothing is allocated per message.
+    pub fn encrypt_in_place(&mut self, nonce: u64, words: &mut [u64]) {
+        self.apply_from(nonce, 0, words, Direction::Encrypt);
+    }
 
-        for b in 0..n_blocks {
-            let ks = self.keystream(NONCE, b as u64);
-            for (i, w) in out[b * PASTA_T..].iter_mut().take(PASTA_T).enumerate() {
-                *w = add_mod(*w, ks[i], self.p);
+    pub fn decrypt_in_place(&mut self, nonce: u64, words: &mut [u64]) {
+        self.apply_from(nonce, 0, words, Direction::Decrypt);
+    }
+
+    /// Decrypts a piece of a longer ciphertext that starts at block `first_block` (word `first_block * T`),
+    /// without touching the blocks before it.
+    pub fn decrypt_blocks(&mut self, nonce: u64, first_block: u64, ciphertext: &[u64]) -> Vec<u64> {
+        let mut out = ciphertext.to_vec();
+        self.apply_from(nonce, first_block, &mut out, Direction::Decrypt);
+        out
+    }
+
+    fn apply_from(
+        &mut self,
+        nonce: u64,
+        first_block: u64,
+        words: &mut [u64],
+        direction: Direction,
+    ) {
+        for (b, block) in words.chunks_mut(T).enumerate() {
+            let ks = Zeroizing::new(self.keystream(nonce, first_block + b as u64));
+            for (w, &k) in block.iter_mut().zip(ks.iter()) {
+                *w = match direction {
+                    Direction::Encrypt => self.field.add(*w, k),
+                    Direction::Decrypt => self.field.sub(*w, k),
+                };
             }
         }
-        out
     }
-    
-    pub fn decrypt(&mut self, ciphertext: &[u64]) -> Vec<u64> {
-        let n_blocks = (ciphertext.len() + PASTA_T - 1) / PASTA_T;
-        let mut out = ciphertext.to_vec();
 
-        for b in 0..n_blocks {
-            let ks = self.keystream(NONCE, b as u64);
-            for (i, w) in out[b * PASTA_T..].iter_mut().take(PASTA_T).enumerate() {
-                let mut v = *w;
-                if v < ks[i] {
-                    v = v.wrapping_add(self.p);
-                }
-                *w = v - ks[i];
+    /// Fills `out` with the keystream of consecutive blocks starting at `first_block`, the last block is
+    /// cut to what fits.
+    pub fn keystream_into(&mut self, nonce: u64, first_block: u64, out: &mut [u64]) {
+        for (b, chunk) in out.chunks_mut(T).enumerate() {
+            let ks = Zeroizing::new(self.keystream(nonce, first_block + b as u64));
+            chunk.copy_from_slice(&ks[..chunk.len()]);
+        }
+    }
+
+    /// Keystream of block `block_counter`, computed on its own without the blocks before it.
+    pub fn keystream(&mut self, nonce: u64, block_counter: u64) -> [u64; T] {
+        let modulus = self.field.modulus();
+        match self.cache.take() {
+            Some(mut cache) => {
+                let ks =
+                    self.keystream_with(cache.get_or_derive(modulus, nonce, block_counter, T, R));
+                self.cache = Some(cache);
+                ks
+            }
+            None => {
+                self.keystream_with(&RoundMaterials::derive(modulus, nonce, block_counter, T, R))
             }
         }
-        out
     }
 
-    pub fn keystream(&mut self, nonce: u64, block_counter: u64) -> Block {
-        self.init_shake(nonce, block_counter);
+    fn key_halves(&self) -> ([u64; T], [u64; T]) {
+        (
+            core::array::from_fn(|i| self.key.words[i]),
+            core::array::from_fn(|i| self.key.words[T + i]),
+        )
+    }
 
-        let mut l: Block = [0; PASTA_T];
-        let mut r: Block = [0; PASTA_T];
-        l.copy_from_slice(&self.key[..PASTA_T]);
-        r.copy_from_slice(&self.key[PASTA_T..]);
+    /// Keystream from already derived materials, so callers running the same (nonce, block counter)
+    /// more than once (e.g. encrypt then decrypt) can keep them around instead of squeezing SHAKE again.
+    ///
+    /// # Panics
+    /// If `materials` were derived for another round count, see [`Pasta::try_keystream_with`].
+    pub fn keystream_with(&self, materials: &RoundMaterials) -> [u64; T] {
+        self.try_keystream_with(materials)
+            .unwrap_or_else(|e| panic!("{e}"))
+    }
+
+    pub fn try_keystream_with(&self, materials: &RoundMaterials) -> Result<[u64; T], PastaError> {
+        if materials.layers.len() != R + 1 {
+            return Err(PastaError::Materials {
+                expected: R + 1,
+                got: materials.layers.len(),
+            });
+        }
+        let (mut l, mut r) = self.key_halves();
 
-        for r_idx in 0..PASTA_R {
-            self.round(&mut l, &mut r, r_idx);
+        for r_idx in 0..R {
+            self.round(&mut l, &mut r, r_idx, &materials.layers[r_idx]);
         }
-        self.linear_layer(&mut l);
-        self.linear_layer(&mut r);
+        let last = &materials.layers[R];
+        self.linear_layer(&mut l, &last.mat_l, &last.rc_l);
+        self.linear_layer(&mut r, &last.mat_r, &last.rc_r);
         self.mix(&mut l, &mut r);
+        // the discarded half is as secret as the output
+        r.zeroize();
+
+        Ok(l)
+    }
+
+    /// Same keystream as `keystream`, but every affine layer is squeezed right before it is applied, so
+    /// only the current layer is alive instead of the (R + 1) * 2 of `RoundMaterials`.
+    pub fn keystream_fused(&self, nonce: u64, block_counter: u64) -> [u64; T] {
+        let mut sampler = XofSampler::seeded(self.field.modulus(), nonce, block_counter);
+        let (mut l, mut r) = self.key_halves();
+
+        for r_idx in 0..R {
+            self.fused_affine(&mut sampler, &mut l);
+            self.fused_affine(&mut sampler, &mut r);
+            self.mix(&mut l, &mut r);
+            self.sbox(&mut l, &mut r, r_idx);
+        }
+        self.fused_affine(&mut sampler, &mut l);
+        self.fused_affine(&mut sampler, &mut r);
+        self.mix(&mut l, &mut r);
+        // the discarded half is as secret as the output
+        r.zeroize();
 
         l
     }
 
-    fn round(&mut self, l: &mut Block, r: &mut Block, r_idx: usize) {
-        self.linear_layer(l);
-        self.linear_layer(r);
+    /// `linear_layer` with the matrix and constants squeezed in the `RoundMaterials` order.
+    fn fused_affine(&self, sampler: &mut XofSampler, state: &mut [u64; T]) {
+        let mat = sampler.sequential(T);
+        let rc = sampler.vec(T, true);
+        self.linear_layer(state, &mat, &rc);
+    }
+
+    fn round(&self, l: &mut [u64; T], r: &mut [u64; T], r_idx: usize, layer: &AffineLayer) {
+        self.linear_layer(l, &layer.mat_l, &layer.rc_l);
+        self.linear_layer(r, &layer.mat_r, &layer.rc_r);
         self.mix(l, r);
+        self.sbox(l, r, r_idx);
+    }
 
-        if r_idx == PASTA_R - 1 {
-            Self::sbox_cube(l, self.p);
-            Self::sbox_cube(r, self.p);
+    /// Feistel sbox in every round but the last, which uses the cube.
+    fn sbox(&self, l: &mut [u64; T], r: &mut [u64; T], r_idx: usize) {
+        if r_idx == R - 1 {
+            Self::sbox_cube(l, self.field);
+            Self::sbox_cube(r, self.field);
         } else {
-            Self::sbox_feistel(l, self.p);
-            Self::sbox_feistel(r, self.p);
+            Self::sbox_feistel(l, self.field);
+            Self::sbox_feistel(r, self.field);
         }
     }
 
-    fn sbox_cube(state: &mut Block, p: u64) {
+    fn sbox_cube(state: &mut [u64; T], field: Modulus) {
         for x in state.iter_mut() {
-            let sq = mul_mod(*x, *x, p);
-            *x = mul_mod(sq, *x, p);
+            let sq = field.mul(*x, *x);
+            *x = field.mul(sq, *x);
         }
     }
-    fn sbox_feistel(state: &mut Block, p: u64) {
+
+    /// x_i <- x_i + x_{i-1}^2, x_0 unchanged. Crate visible as the reference for the BGG Feistel layer.
+    pub(crate) fn sbox_feistel(state: &mut [u64; T], field: Modulus) {
         let mut out = *state;
-        for i in 1..PASTA_T {
-            let sq = mul_mod(state[i - 1], state[i - 1], p);
-            out[i] = add_mod(state[i], sq, p);
+        for i in 1..T {
+            let sq = field.mul(state[i - 1], state[i - 1]);
+            out[i] = field.add(state[i], sq);
         }
         *state = out;
     }
 
-    fn mix(&self, l: &mut Block, r: &mut Block) {
-        for i in 0..PASTA_T {
-            let s = add_mod(l[i], r[i], self.p);
-            l[i] = add_mod(l[i], s, self.p);
-            r[i] = add_mod(r[i], s, self.p);
+    fn mix(&self, l: &mut [u64; T], r: &mut [u64; T]) {
+        for i in 0..T {
+            let s = self.field.add(l[i], r[i]);
+            l[i] = self.field.add(l[i], s);
+            r[i] = self.field.add(r[i], s);
         }
     }
 
-    fn linear_layer(&mut self, state: &mut Block) {
-        let mat = self.rand_matrix();
-        let mut new = [0u64; PASTA_T];
-        for i in 0..PASTA_T {
-            for j in 0..PASTA_T {
-                new[i] = add_mod(new[i], mul_mod(mat[i][j], state[j], self.p), self.p);
-            }
-        }
-        *state = new;
-        let rc = self.rand_vec(true);
-        for i in 0..PASTA_T {
-            state[i] = add_mod(state[i], rc[i], self.p);
+    fn linear_layer(&self, state: &mut [u64; T], mat: &SequentialMatrix, rc: &[u64]) {
+        let new = mat.mul_vec(state);
+        for i in 0..T {
+            state[i] = self.field.add(new[i], rc[i]);
         }
     }
+}
 
-    fn init_shake(&mut self, nonce: u64, block_counter: u64) {
-        let mut shake = Shake128::default();
-        let mut seed = [0u8; 16];
-        BigEndian::write_u64(&mut seed[0..8], nonce);
-        BigEndian::write_u64(&mut seed[8..16], block_counter);
-        shake.update(&seed);
-        self.shake = shake.finalize_xof();
-    }
+/// Which way a [`PastaStream`] applies the keystream.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    Encrypt,
+    Decrypt,
+}
 
-    fn rand_field_element(&mut self, allow_zero: bool) -> u64 {
-        loop {
-            let mut buf = [0u8; 8];
-            self.shake.read(&mut buf).unwrap();
-            let cand = u64::from_be_bytes(buf) & self.mask;
-            if (!allow_zero && cand == 0) || cand >= self.p {
-                continue;
-            }
-            return cand;
+/// Incremental `encrypt_with_nonce` / `decrypt_with_nonce`: feed the message in pieces of any length to
+/// `update`, only the current keystream block is kept around. The output is the same as the one-shot call
+/// on the concatenated pieces.
+pub struct PastaStream<'a, const T: usize, const R: usize> {
+    pasta: &'a Pasta<T, R>,
+    direction: Direction,
+    nonce: u64,
+    block: u64,
+    ks: [u64; T],
+    /// Keystream words of `ks` already used, T when the next block has to be generated.
+    pos: usize,
+}
+
+impl<const T: usize, const R: usize> Pasta<T, R> {
+    pub fn stream(&self, nonce: u64, direction: Direction) -> PastaStream<'_, T, R> {
+        PastaStream {
+            pasta: self,
+            direction,
+            nonce,
+            block: 0,
+            ks: [0; T],
+            pos: T,
         }
     }
+}
 
-    fn rand_vec(&mut self, allow_zero: bool) -> Vec<u64> {
-        (0..PASTA_T)
-            .map(|_| self.rand_field_element(allow_zero))
-            .collect()
+impl<const T: usize, const R: usize> Drop for PastaStream<'_, T, R> {
+    fn drop(&mut self) {
+        self.ks.zeroize();
     }
+}
 
-    fn rand_matrix(&mut self) -> Vec<Vec<u64>> {
-        let first_row = self.rand_vec(false);
-        let mut mat: Vec<Vec<u64>> = Vec::with_capacity(PASTA_T);
-        mat.push(first_row);
+impl<const T: usize, const R: usize> ZeroizeOnDrop for PastaStream<'_, T, R> {}
 
-        for i in 1..PASTA_T {
-            let next = self.calculate_row(&mat[i - 1], &mat[0]);
-            mat.push(next);
+impl<const T: usize, const R: usize> PastaStream<'_, T, R> {
+    /// Encrypts or decrypts `words` in place, continuing where the previous call stopped.
+    pub fn update(&mut self, words: &mut [u64]) {
+        let field = self.pasta.field;
+        for w in words.iter_mut() {
+            if self.pos == T {
+                let materials =
+                    RoundMaterials::derive(field.modulus(), self.nonce, self.block, T, R);
+                self.ks = self.pasta.keystream_with(&materials);
+                self.block += 1;
+                self.pos = 0;
+            }
+            let k = self.ks[self.pos];
+            *w = match self.direction {
+                Direction::Encrypt => field.add(*w, k),
+                Direction::Decrypt => field.sub(*w, k),
+            };
+            self.pos += 1;
         }
+    }
 
-        mat
+    /// Jumps to the start of block `block_index`, the next `update` continues from word `block_index * T`.
+    pub fn seek(&mut self, block_index: u64) {
+        self.block = block_index;
+        self.pos = T;
     }
 
-    fn calculate_row(&self, prev_row: &Vec<u64>, first_row: &Vec<u64>) -> Vec<u64> {
-        debug_assert_eq!(prev_row.len(), PASTA_T);
-        debug_assert_eq!(first_row.len(), PASTA_T);
+    /// Words processed so far.
+    pub fn position(&self) -> u64 {
+        (self.block * T as u64).saturating_sub((T - self.pos) as u64)
+    }
+}
 
-        let m = self.p as u128;
+#[cfg(feature = "parallel")]
+impl<const T: usize, const R: usize> Pasta<T, R> {
+    /// `encrypt_with_nonce` with the keystream blocks computed concurrently, each block has its own
+    /// SHAKE seed so they are independent. Sequential when `Op::Keystream` is switched off.
+    pub fn encrypt_parallel(&self, nonce: u64, plaintext: &[u64]) -> Vec<u64> {
+        self.apply_parallel(nonce, plaintext, Direction::Encrypt)
+    }
 
-        (0..PASTA_T)
-            .map(|j| {
-                let mut tmp = (first_row[j] as u128 * prev_row[PASTA_T - 1] as u128) % m;
-                if j != 0 {
-                    tmp = (tmp + prev_row[j - 1] as u128) % m;
-                }
+    pub fn decrypt_parallel(&self, nonce: u64, ciphertext: &[u64]) -> Vec<u64> {
+        self.apply_parallel(nonce, ciphertext, Direction::Decrypt)
+    }
 
-                tmp as u64
-            })
-            .collect()
+    fn apply_parallel(&self, nonce: u64, words: &[u64], direction: Direction) -> Vec<u64> {
+        use rayon::prelude::*;
+
+        let apply_block = |(b, block): (usize, &mut [u64])| {
+            let materials = RoundMaterials::derive(self.field.modulus(), nonce, b as u64, T, R);
+            let ks = Zeroizing::new(self.keystream_with(&materials));
+            for (w, &k) in block.iter_mut().zip(ks.iter()) {
+                *w = match direction {
+                    Direction::Encrypt => self.field.add(*w, k),
+                    Direction::Decrypt => self.field.sub(*w, k),
+                };
+            }
+        };
+        let mut out = words.to_vec();
+        if crate::parallel::is_parallel(crate::parallel::Op::Keystream) {
+            crate::parallel::install(|| out.par_chunks_mut(T).enumerate().for_each(apply_block));
+        } else {
+            out.chunks_mut(T).enumerate().for_each(apply_block);
+        }
+        out
     }
 }
 
 #[cfg(test)]
 mod tests {
+    #[cfg(feature = "std")]
     use rand::{Rng, rng};
-    use std::io::Read;
+    #[cfg(feature = "std")]
+    use sha3::digest::XofReader;
 
     use super::*;
+    use crate::polynomial::Polynomial;
 
     const P: u64 = 65_537;
 
@@ -234,99 +811,439 @@ mod tests {
         (0..2 * PASTA_T).map(|i| (i * 17) as u64 % P).collect()
     }
 
+    #[cfg(feature = "std")]
     fn rand_demo_key() -> Vec<u64> {
         let mut rng = rng();
         (0..2 * PASTA_T).map(|_| rng.random_range(0..P)).collect()
     }
 
+    #[cfg(feature = "std")]
     #[test]
     fn roundtrip() {
         let mut rng = rng();
         let key = rand_demo_key();
-        let mut pasta = Pasta::new(key, P);
+        let mut pasta = Pasta::new(PastaKey::new(key, P), P);
 
         let plain: Vec<u64> = (0..500).map(|_| rng.random_range(0..P)).collect();
         println!("{:?}", plain);
-        let ct = pasta.encrypt(&plain);
+        let ct = pasta.encrypt_with_nonce(7, &plain);
         println!("{:?}", ct);
-        let dec = pasta.decrypt(&ct);
+        let dec = pasta.decrypt_with_nonce(7, &ct);
         println!("{:?}", dec);
 
         assert_eq!(plain, dec);
     }
 
+    #[test]
+    fn test_nonces_give_different_ciphertexts() {
+        let mut pasta = Pasta::new(PastaKey::new(demo_key(), P), P);
+        let plain = vec![5u64; 16];
+        let ct_1 = pasta.encrypt_with_nonce(1, &plain);
+        let ct_2 = pasta.encrypt_with_nonce(2, &plain);
+        assert_ne!(ct_1, ct_2);
+        assert_eq!(pasta.encrypt_with_nonce(1, &plain), ct_1);
+
+        assert_eq!(pasta.decrypt_with_nonce(2, &ct_2), plain);
+        assert_ne!(pasta.decrypt_with_nonce(1, &ct_2), plain);
+    }
+
+    #[cfg(feature = "std")]
     #[test]
     fn test_init_shake() {
-        let mut pasta = Pasta::new(vec![1, 2, 3, 4], 100);
-        pasta.init_shake(123456789, 0);
-        let mut f = pasta.shake.clone();
+        let mut sampler = XofSampler::new(100);
+        sampler.reseed(123456789, 0);
+        let mut f = sampler.shake.clone();
         let mut buf = [0u8; 8];
-        f.read(&mut buf).unwrap();
+        f.read(&mut buf);
         println!("{:?}", buf);
 
-        pasta.init_shake(123456789, 1);
-        let mut f = pasta.shake.clone();
+        sampler.reseed(123456789, 1);
+        let mut f = sampler.shake.clone();
         let mut buf = [0u8; 8];
-        f.read(&mut buf).unwrap();
+        f.read(&mut buf);
         println!("{:?}", buf);
 
-        pasta.init_shake(123456789, 2);
-        let mut f = pasta.shake;
+        sampler.reseed(123456789, 2);
+        let mut f = sampler.shake;
         let mut buf = [0u8; 8];
-        f.read(&mut buf).unwrap();
+        f.read(&mut buf);
         println!("{:?}", buf);
     }
 
+    #[cfg(feature = "std")]
     #[test]
     fn test_keystream() {
-        let mut pasta = Pasta::new(demo_key(), P);
+        let mut pasta = Pasta::new(PastaKey::new(demo_key(), P), P);
         let ks = pasta.keystream(123456789, 0);
         println!("{:?}", ks);
+        assert_eq!(ks, [23734]);
 
         let ks = pasta.keystream(123456789, 1);
         println!("{:?}", ks);
+        assert_eq!(ks, [16031]);
 
         let ks = pasta.keystream(123456789, 2);
         println!("{:?}", ks);
+        assert_eq!(ks, [4481]);
+
+        let mut pasta = Pasta::new(PastaKey::new(vec![3, 1_000], P), P);
+        assert_eq!(
+            pasta.encrypt_with_nonce(123456789, &[1, 2, 3, 65_536]),
+            vec![10515, 48073, 17840, 60099]
+        );
     }
 
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_keystream_modes_agree() {
+        let mut pasta = Pasta::new(PastaKey::new(rand_demo_key(), P), P);
+        for ctr in 0..20 {
+            let materials = RoundMaterials::derive(P, 42, ctr, PASTA_T, PASTA_R);
+            let ks = pasta.keystream(42, ctr);
+            assert_eq!(pasta.keystream_with(&materials), ks);
+            assert_eq!(pasta.keystream_fused(42, ctr), ks);
+        }
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_pasta_4() {
+        let mut rng = rng();
+        let key = (0..2 * 32)
+            .map(|_| rng.random_range(0..P))
+            .collect::<Vec<_>>();
+        let mut pasta = Pasta4::with_key(PastaKey::new(key.clone(), P), P);
+
+        let ks = pasta.keystream(9, 0);
+        let materials = RoundMaterials::derive(P, 9, 0, 32, 4);
+        assert_eq!(materials.layers.len(), 5);
+        assert_eq!(pasta.keystream_with(&materials), ks);
+        assert_eq!(pasta.keystream_fused(9, 0), ks);
+
+        // one round more than the same state size under 3 rounds
+        let mut three = Pasta::<32, 3>::with_key(PastaKey::new(key, P), P);
+        assert_ne!(three.keystream(9, 0), ks);
+
+        let plain = (0..70).map(|_| rng.random_range(0..P)).collect::<Vec<_>>();
+        let ct = pasta.encrypt_with_nonce(9, &plain);
+        assert_eq!(pasta.decrypt_with_nonce(9, &ct), plain);
+    }
+
+    #[test]
+    fn test_reduced_round_instances() {
+        // a single round is only the cube sbox between two affine layers
+        let mut one = Pasta::<4, 1>::with_key(PastaKey::new(vec![1, 2, 3, 4, 5, 6, 7, 8], P), P);
+        let ks = one.keystream(3, 0);
+        assert_eq!(one.keystream_fused(3, 0), ks);
+        let ct = one.encrypt_with_nonce(3, &[10, 20, 30, 40, 50]);
+        assert_eq!(one.decrypt_with_nonce(3, &ct), vec![10, 20, 30, 40, 50]);
+
+        // the default instance is the reduced PASTA_T / PASTA_R one
+        let mut default = Pasta::new(PastaKey::new(demo_key(), P), P);
+        let mut explicit = Pasta::<PASTA_T, PASTA_R>::with_key(PastaKey::new(demo_key(), P), P);
+        assert_eq!(default.keystream(1, 1), explicit.keystream(1, 1));
+    }
+
+    #[test]
+    fn test_in_place() {
+        let mut pasta =
+            Pasta::<4, 2>::with_key(PastaKey::new(vec![5, 6, 7, 8, 9, 10, 11, 12], P), P);
+        let plain = (0..10).map(|i| i * 4_099 % P).collect::<Vec<_>>();
+        let mut buf = plain.clone();
+        pasta.encrypt_in_place(3, &mut buf);
+        assert_eq!(buf, pasta.encrypt_with_nonce(3, &plain));
+        pasta.decrypt_in_place(3, &mut buf);
+        assert_eq!(buf, plain);
+
+        let mut ks = [0; 6];
+        pasta.keystream_into(3, 1, &mut ks);
+        assert_eq!(ks[..4], pasta.keystream(3, 1));
+        assert_eq!(ks[4..], pasta.keystream(3, 2)[..2]);
+        assert_eq!(pasta.prf(3, 6)[4..], pasta.keystream(3, 1)[..2]);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_key() {
+        let mut rng = rng();
+        let key = PastaKey::<4>::generate(&mut rng, 17);
+        assert_eq!(key.words().len(), 8);
+        assert!(key.words().iter().all(|&w| w < 17));
+        assert_eq!(format!("{key:?}"), "PastaKey<4>(..)");
+
+        let mut zeroed = key.clone();
+        zeroed.zeroize();
+        assert!(zeroed.words().iter().all(|&w| w == 0));
+
+        for modulus in [0, 1, 1 << 60] {
+            assert_eq!(
+                PastaKey::<4>::try_generate(&mut rng, modulus).err(),
+                Some(PastaError::Modulus { modulus })
+            );
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "not below the modulus")]
+    fn test_key_out_of_range() {
+        PastaKey::<1>::new(vec![3, 17], 17);
+    }
+
+    #[test]
+    fn test_try_new() {
+        assert!(Pasta::try_new(demo_key(), P).is_ok());
+        assert_eq!(
+            Pasta::try_new(vec![1, 2, 3], P).err(),
+            Some(PastaError::KeyLength {
+                expected: 2,
+                got: 3
+            })
+        );
+        assert_eq!(
+            Pasta::try_new(vec![1, P], P).err(),
+            Some(PastaError::KeyOutOfRange {
+                index: 1,
+                value: P,
+                modulus: P
+            })
+        );
+        for modulus in [0, 1, 1 << 60, u64::MAX] {
+            assert_eq!(
+                Pasta::<4, 2>::try_from_words(vec![0; 8], modulus).err(),
+                Some(PastaError::Modulus { modulus })
+            );
+        }
+        assert!(Pasta::<4, 2>::try_from_words(vec![0; 8], (1 << 60) - 1).is_ok());
+    }
+
+    #[test]
+    fn test_try_errors() {
+        let key = || PastaKey::<4>::new(vec![5, 6, 7, 8, 9, 10, 11, P - 1], P);
+        assert_eq!(
+            Pasta::<4, 0>::try_with_key(key(), P).err(),
+            Some(PastaError::Rounds)
+        );
+        assert_eq!(
+            Pasta::<4, 2>::try_with_key(key(), 17).err(),
+            Some(PastaError::KeyOutOfRange {
+                index: 7,
+                value: P - 1,
+                modulus: 17
+            })
+        );
+        assert_eq!(
+            SequentialMatrix::try_new(vec![], Modulus::new(P)).err(),
+            Some(PastaError::EmptyMatrix)
+        );
+        assert_eq!(
+            MaterialsCache::try_new(0).err(),
+            Some(PastaError::CacheCapacity)
+        );
+        assert_eq!(
+            Pasta::<4, 2>::with_key(key(), P).try_with_cache(0).err(),
+            Some(PastaError::CacheCapacity)
+        );
+        let materials = RoundMaterials::derive(P, 1, 0, 4, 3);
+        assert_eq!(
+            Pasta::<4, 2>::with_key(key(), P)
+                .try_keystream_with(&materials)
+                .err(),
+            Some(PastaError::Materials {
+                expected: 3,
+                got: 4
+            })
+        );
+    }
+
+    #[test]
+    fn test_materials_cache() {
+        let key = vec![5, 6, 7, 8, 9, 10, 11, 12];
+        let mut plain = Pasta::<4, 2>::with_key(PastaKey::new(key.clone(), P), P);
+        let mut cached = Pasta::<4, 2>::with_key(PastaKey::new(key, P), P).with_cache(2);
+
+        for (nonce, ctr) in [(1, 0), (1, 1), (1, 0), (2, 0), (1, 1)] {
+            assert_eq!(cached.keystream(nonce, ctr), plain.keystream(nonce, ctr));
+        }
+        let cache = cached.cache().unwrap();
+        // (1, 0) hit once, (2, 0) evicted (1, 0) and (1, 1) was still there
+        assert_eq!(cache.stats(), (2, 3));
+        assert_eq!(cache.len(), 2);
+        assert!(plain.cache().is_none());
+
+        let msg = [1, 2, 3, 4, 5, 6];
+        let ct = cached.encrypt_with_nonce(9, &msg);
+        assert_eq!(cached.decrypt_with_nonce(9, &ct), msg);
+        assert_eq!(cached.cache().unwrap().stats().0, 4);
+
+        // one cache shared by instances that differ only in modulus or rounds
+        let mut shared = MaterialsCache::new(4);
+        let a = shared.get_or_derive(P, 1, 0, 4, 2).clone();
+        assert_eq!(a, RoundMaterials::derive(P, 1, 0, 4, 2));
+        let b = shared.get_or_derive(17, 1, 0, 4, 2).clone();
+        assert_eq!(b, RoundMaterials::derive(17, 1, 0, 4, 2));
+        let c = shared.get_or_derive(P, 1, 0, 4, 3).clone();
+        assert_eq!(c.layers.len(), 4);
+        assert_eq!(shared.stats(), (0, 3));
+    }
+
+    #[test]
+    fn test_stream_matches_one_shot() {
+        let mut pasta =
+            Pasta::<4, 2>::with_key(PastaKey::new(vec![5, 6, 7, 8, 9, 10, 11, 12], P), P);
+        let plain = (0..23).map(|i| i * 1_000 % P).collect::<Vec<_>>();
+        let ct = pasta.encrypt_with_nonce(77, &plain);
+
+        let mut buf = plain.clone();
+        let mut enc = pasta.stream(77, Direction::Encrypt);
+        // pieces cutting across block boundaries, and an empty one
+        let (a, rest) = buf.split_at_mut(3);
+        let (b, rest) = rest.split_at_mut(0);
+        let (c, d) = rest.split_at_mut(9);
+        for piece in [a, b, c, d] {
+            enc.update(piece);
+        }
+        assert_eq!(enc.position(), 23);
+        assert_eq!(buf, ct);
+
+        let mut dec = pasta.stream(77, Direction::Decrypt);
+        for chunk in buf.chunks_mut(5) {
+            dec.update(chunk);
+        }
+        assert_eq!(buf, plain);
+
+        fn assert_zeroize_on_drop<S: ZeroizeOnDrop>() {}
+        assert_zeroize_on_drop::<PastaStream<'_, 4, 2>>();
+    }
+
+    #[test]
+    fn test_random_access() {
+        let mut pasta =
+            Pasta::<4, 2>::with_key(PastaKey::new(vec![5, 6, 7, 8, 9, 10, 11, 12], P), P);
+        let plain = (0..30).map(|i| i * 31 % P).collect::<Vec<_>>();
+        let ct = pasta.encrypt_with_nonce(8, &plain);
+
+        // blocks 3 and 4, the last one cut short
+        assert_eq!(pasta.decrypt_blocks(8, 3, &ct[12..18]), plain[12..18]);
+
+        let mut dec = pasta.stream(8, Direction::Decrypt);
+        dec.seek(5);
+        assert_eq!(dec.position(), 20);
+        let mut tail = ct[20..].to_vec();
+        dec.update(&mut tail);
+        assert_eq!(tail, plain[20..]);
+        assert_eq!(dec.position(), 30);
+    }
+
+    #[cfg(feature = "parallel")]
+    #[test]
+    fn test_parallel_matches_sequential() {
+        let mut pasta =
+            Pasta::<4, 2>::with_key(PastaKey::new(vec![5, 6, 7, 8, 9, 10, 11, 12], P), P);
+        let plain = (0..37).map(|i| i * 977 % P).collect::<Vec<_>>();
+        let ct = pasta.encrypt_parallel(5, &plain);
+        assert_eq!(ct, pasta.encrypt_with_nonce(5, &plain));
+        assert_eq!(pasta.decrypt_parallel(5, &ct), plain);
+    }
+
+    #[test]
+    fn test_polynomial_from_xof_matches_sampler() {
+        let mut sampler = XofSampler::seeded(P, 123456789, 4);
+        let poly = Polynomial::<8, P>::from_xof(&mut sampler.shake.clone());
+        let words = poly.inner.iter().map(|e| e.value()).collect::<Vec<_>>();
+        assert_eq!(words, sampler.vec(8, true));
+    }
+
+    #[cfg(feature = "std")]
     #[test]
     fn test_rand_field_element() {
-        let mut pasta = Pasta::new(vec![1, 2, 3, 4], 100);
-        pasta.init_shake(123456789, 0);
-        let fp = pasta.rand_field_element(true);
+        let mut sampler = XofSampler::seeded(100, 123456789, 0);
+        let fp = sampler.field_element(true);
         println!("{:?}", fp);
-        let fp = pasta.rand_field_element(true);
+        let fp = sampler.field_element(true);
         println!("{:?}", fp);
-        let fp = pasta.rand_field_element(true);
+        let fp = sampler.field_element(true);
         println!("{:?}", fp);
-        let fp = pasta.rand_field_element(true);
+        let fp = sampler.field_element(true);
         println!("{:?}", fp);
     }
 
+    #[cfg(feature = "std")]
     #[test]
     fn test_rand_vec() {
-        let mut pasta = Pasta::new(vec![1, 2, 3, 4], 100);
-        pasta.init_shake(123456789, 0);
-        let fp = pasta.rand_vec(true);
+        let mut sampler = XofSampler::seeded(100, 123456789, 0);
+        let fp = sampler.vec(PASTA_T, true);
         println!("{:?}", fp);
-        let fp = pasta.rand_vec(true);
+        let fp = sampler.vec(PASTA_T, true);
         println!("{:?}", fp);
-        let fp = pasta.rand_vec(true);
+        let fp = sampler.vec(PASTA_T, true);
         println!("{:?}", fp);
-        let fp = pasta.rand_vec(true);
+        let fp = sampler.vec(PASTA_T, true);
         println!("{:?}", fp);
     }
 
+    #[cfg(feature = "std")]
     #[test]
     fn test_rand_matrix() {
-        let mut pasta = Pasta::new(vec![1, 2, 3, 4], 100);
-        pasta.init_shake(123456789, 0);
-        let m = pasta.rand_matrix();
+        let mut sampler = XofSampler::seeded(100, 123456789, 0);
+        let m = sampler.sequential_matrix(PASTA_T);
         println!("{:?}", m);
-        let m = pasta.rand_matrix();
+        let m = sampler.sequential_matrix(PASTA_T);
         println!("{:?}", m);
-        let m = pasta.rand_matrix();
+        let m = sampler.sequential_matrix(PASTA_T);
         println!("{:?}", m);
     }
+
+    #[test]
+    fn test_sequential_matrix_recurrence() {
+        let t = 8;
+        let mut sampler = XofSampler::seeded(P, 7, 3);
+        let m = sampler.sequential_matrix(t);
+        assert!(m[0].iter().all(|&x| x != 0 && x < P));
+        for i in 1..t {
+            for j in 0..t {
+                let prev = if j == 0 { 0 } else { m[i - 1][j - 1] };
+                let expected = (m[0][j] * m[i - 1][t - 1] + prev) % P;
+                assert_eq!(m[i][j], expected);
+            }
+        }
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_sequential_mul_matches_dense() {
+        let field = Modulus::new(P);
+        let mut rng = rng();
+        for t in [1, 2, 5, 32] {
+            let mut sampler = XofSampler::seeded(P, t as u64, 0);
+            let mat = sampler.sequential(t);
+            let v = (0..t).map(|_| rng.random_range(0..P)).collect::<Vec<_>>();
+            let dense = mat
+                .to_rows()
+                .iter()
+                .map(|row| {
+                    row.iter()
+                        .zip(&v)
+                        .fold(0, |acc, (a, b)| field.add(acc, field.mul(*a, *b)))
+                })
+                .collect::<Vec<_>>();
+            assert_eq!(mat.mul_vec(&v), dense);
+            assert_eq!(mat.rows().count(), t);
+        }
+    }
+
+    #[test]
+    fn test_round_materials_sampling_order() {
+        let t = 4;
+        let materials = RoundMaterials::derive(P, 11, 2, t, PASTA_R);
+        assert_eq!(materials.layers.len(), PASTA_R + 1);
+
+        let mut sampler = XofSampler::seeded(P, 11, 2);
+        for layer in materials.layers.iter() {
+            assert_eq!(layer.mat_l.to_rows(), sampler.sequential_matrix(t));
+            assert_eq!(layer.rc_l, sampler.vec(t, true));
+            assert_eq!(layer.mat_r.to_rows(), sampler.sequential_matrix(t));
+            assert_eq!(layer.rc_r, sampler.vec(t, true));
+        }
+        assert_ne!(materials, RoundMaterials::derive(P, 11, 3, t, PASTA_R));
+    }
 }
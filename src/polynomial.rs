@@ -1,23 +1,29 @@
-use rand::{distr::Uniform, prelude::*};
-use std::{
+use crate::field::{ConstModulus, LazyAcc, Ring};
+use alloc::{vec, vec::Vec};
+use core::{
     fmt,
+    marker::PhantomData,
     ops::{Add, Mul, Neg, Sub},
-    usize,
 };
+#[cfg(feature = "std")]
+use rand::{distr::Uniform, prelude::*};
+use sha3::{
+    Shake128,
+    digest::{ExtendableOutput, Update, XofReader},
+};
+use zeroize::Zeroize;
 
 #[derive(Debug, Clone, Copy, PartialEq)]
+#[repr(transparent)]
 pub struct Element<const A: u64> {
     value: u64,
 }
 
 impl<const A: u64> Element<A> {
     pub fn new(value: i64) -> Self {
-        let value = Self::balanced(value);
-        Self { value }
-    }
-
-    fn balanced(x: i64) -> u64 {
-        ((x % A as i64 + A as i64) % A as i64) as u64
+        Self {
+            value: ConstModulus::<A>.reduce_i64(value),
+        }
     }
 
     pub fn value(&self) -> u64 {
@@ -29,8 +35,9 @@ impl<const A: u64> Add for Element<A> {
     type Output = Self;
 
     fn add(self, rhs: Self) -> Self::Output {
-        let value = Self::balanced(self.value as i64 + rhs.value as i64);
-        Self { value }
+        Self {
+            value: ConstModulus::<A>.add(self.value, rhs.value),
+        }
     }
 }
 
@@ -38,8 +45,9 @@ impl<const A: u64> Sub for Element<A> {
     type Output = Self;
 
     fn sub(self, rhs: Self) -> Self::Output {
-        let value = Self::balanced(self.value as i64 - rhs.value as i64);
-        Self { value }
+        Self {
+            value: ConstModulus::<A>.sub(self.value, rhs.value),
+        }
     }
 }
 
@@ -47,29 +55,203 @@ impl<const A: u64> Neg for Element<A> {
     type Output = Self;
 
     fn neg(self) -> Self::Output {
-        Self::new(-(self.value as i64))
+        Self {
+            value: ConstModulus::<A>.neg(self.value),
+        }
     }
 }
 
 impl<const A: u64> Mul for Element<A> {
     type Output = Self;
     fn mul(self, rhs: Self) -> Self::Output {
-        let value = Self::balanced(self.value as i64 * rhs.value as i64);
-        Self { value }
+        Self {
+            value: ConstModulus::<A>.mul(self.value, rhs.value),
+        }
     }
 }
 
-/// R_{a} = Z_{a}[x]/(x^n+1)
+/// Distribution over {-1, 0, 1} for secrets and small errors.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Ternary {
+    /// Each coefficient is nonzero with probability p, then ±1 with equal chance.
+    Probability(f64),
+    /// Exactly h nonzero coefficients at random positions (sparse secrets).
+    HammingWeight(usize),
+}
+
+impl Ternary {
+    /// Uniform over {-1, 0, 1}.
+    pub const UNIFORM: Self = Ternary::Probability(2.0 / 3.0);
+
+    /// A probability in [0, 1], or a Hamming weight of at most `n` (the ring dimension).
+    pub fn validate(&self, n: usize) -> Result<(), TernaryError> {
+        match *self {
+            Ternary::Probability(p) if !(0.0..=1.0).contains(&p) => {
+                Err(TernaryError::Probability { p })
+            }
+            Ternary::HammingWeight(h) if h > n => Err(TernaryError::HammingWeight { h, n }),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TernaryError {
+    /// Not in [0, 1].
+    Probability { p: f64 },
+    /// More nonzero coefficients than the ring dimension.
+    HammingWeight { h: usize, n: usize },
+}
+
+impl fmt::Display for TernaryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TernaryError::Probability { p } => write!(f, "probability {p} not in [0, 1]"),
+            TernaryError::HammingWeight { h, n } => {
+                write!(f, "hamming weight {h} exceeds ring dimension {n}")
+            }
+        }
+    }
+}
+
+impl core::error::Error for TernaryError {}
+
+/// Distribution of RLWE errors.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ErrorDist {
+    /// Discrete Gaussian with parameter sigma, tails cut at 6 sigma.
+    Gaussian { sigma: f64 },
+    /// Centered binomial, the difference of two sums of `eta` coin flips, variance eta / 2.
+    CenteredBinomial { eta: u32 },
+    /// No error at all, so phases are exact. Only for debugging, never secure.
+    Zero,
+}
+
+impl ErrorDist {
+    /// sigma = 3.2, the value the security tables assume.
+    pub const STANDARD: Self = ErrorDist::Gaussian { sigma: 3.2 };
+
+    /// Largest |x| `sample` returns.
+    #[cfg(feature = "std")]
+    pub fn bound(&self) -> u64 {
+        match *self {
+            ErrorDist::Gaussian { sigma } => (6.0 * sigma).ceil() as u64,
+            ErrorDist::CenteredBinomial { eta } => eta as u64,
+            ErrorDist::Zero => 0,
+        }
+    }
+
+    /// Standard deviation of `sample`, ignoring the Gaussian tail cut.
+    #[cfg(feature = "std")]
+    pub fn std_dev(&self) -> f64 {
+        match *self {
+            ErrorDist::Gaussian { sigma } => sigma,
+            ErrorDist::CenteredBinomial { eta } => (eta as f64 / 2.0).sqrt(),
+            ErrorDist::Zero => 0.0,
+        }
+    }
+
+    /// Fails for a Gaussian whose sigma is not positive, the one invalid distribution.
+    pub fn validate(&self) -> Result<(), SigmaError> {
+        match *self {
+            ErrorDist::Gaussian { sigma } if sigma.is_nan() || sigma <= 0.0 => {
+                Err(SigmaError { sigma })
+            }
+            _ => Ok(()),
+        }
+    }
+
+    #[cfg(feature = "std")]
+    pub fn sample(&self, rng: &mut impl Rng) -> Result<i64, SigmaError> {
+        self.validate()?;
+        Ok(self.sample_valid(rng))
+    }
+
+    /// `sample` for a distribution that passed `validate`.
+    #[cfg(feature = "std")]
+    pub(crate) fn sample_valid(&self, rng: &mut impl Rng) -> i64 {
+        match *self {
+            #[cfg(feature = "constant-time")]
+            ErrorDist::Gaussian { sigma } => crate::ct::gaussian(rng, sigma),
+            #[cfg(not(feature = "constant-time"))]
+            ErrorDist::Gaussian { sigma } => {
+                // uniform candidates on the cut support, accepted with the Gaussian weight
+                let bound = (6.0 * sigma).ceil() as i64;
+                loop {
+                    let x = rng.random_range(-bound..=bound);
+                    let weight = (-((x * x) as f64) / (2.0 * sigma * sigma)).exp();
+                    if rng.random_bool(weight) {
+                        return x;
+                    }
+                }
+            }
+            ErrorDist::CenteredBinomial { eta } => (0..eta)
+                .map(|_| rng.random::<bool>() as i64 - rng.random::<bool>() as i64)
+                .sum(),
+            ErrorDist::Zero => 0,
+        }
+    }
+}
+
+/// A Gaussian `ErrorDist` with sigma not positive.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SigmaError {
+    pub sigma: f64,
+}
+
+impl fmt::Display for SigmaError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "sigma {} must be positive", self.sigma)
+    }
+}
+
+impl core::error::Error for SigmaError {}
+
+/// Which polynomial the ring is reduced by, x^n+1 or x^n-1.
+pub trait RingKind:
+    fmt::Debug + Clone + Copy + Default + PartialEq + Send + Sync + 'static
+{
+    /// x^n = -1 when true, x^n = 1 otherwise.
+    const NEGACYCLIC: bool;
+}
+
+/// Z_a[x]/(x^n+1), what every scheme in the crate uses.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Negacyclic;
+
+/// Z_a[x]/(x^n-1), plain cyclic convolution.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Cyclic;
+
+impl RingKind for Negacyclic {
+    const NEGACYCLIC: bool = true;
+}
+
+impl RingKind for Cyclic {
+    const NEGACYCLIC: bool = false;
+}
+
+/// R_{a} = Z_{a}[x]/(x^n+1), or Z_{a}[x]/(x^n-1) with `R = Cyclic`.
 #[derive(PartialEq, Clone, Copy)]
-pub struct Polynomial<const N: usize, const A: u64> {
+pub struct Polynomial<const N: usize, const A: u64, R: RingKind = Negacyclic> {
     pub inner: [Element<A>; N],
+    ring: PhantomData<R>,
 }
 
-impl<const N: usize, const A: u64> Polynomial<N, A> {
+impl<const N: usize, const A: u64, R: RingKind> Polynomial<N, A, R> {
     pub fn new(inner: [Element<A>; N]) -> Self {
-        Self { inner }
+        Self {
+            inner,
+            ring: PhantomData,
+        }
+    }
+
+    /// Same coefficients read in the other ring.
+    pub fn into_ring<S: RingKind>(self) -> Polynomial<N, A, S> {
+        Polynomial::new(self.inner)
     }
 
+    #[cfg(feature = "std")]
     pub fn rand() -> Self {
         let mut rng = rand::rng();
         let side = Uniform::new(0, A as i64).unwrap();
@@ -78,27 +260,128 @@ impl<const N: usize, const A: u64> Polynomial<N, A> {
             .collect::<Vec<_>>()
             .try_into()
             .unwrap();
-        Self { inner }
+        Self::new(inner)
     }
 
-    pub fn lift<const B: u64>(&self) -> Polynomial<N, B> {
-        Polynomial::<N, B>::new(core::array::from_fn(|i| {
+    pub fn lift<const B: u64>(&self) -> Polynomial<N, B, R> {
+        Polynomial::<N, B, R>::new(core::array::from_fn(|i| {
             Element::<B>::new(self.inner[i].value as i64)
         }))
     }
 
-    /// Uniform error in {0,1}.  Good enough for tests.
+    /// Like `lift` but reads coefficients above a/2 as negative, so -1 in Z_a stays -1 in Z_b.
+    pub fn lift_centered<const B: u64>(&self) -> Polynomial<N, B, R> {
+        Polynomial::<N, B, R>::new(core::array::from_fn(|i| {
+            let v = self.inner[i].value;
+            if v > A / 2 {
+                Element::<B>::new(v as i64 - A as i64)
+            } else {
+                Element::<B>::new(v as i64)
+            }
+        }))
+    }
+
+    /// Coefficients as integers in [-a/2, a/2).
+    pub fn to_centered(&self) -> [i64; N] {
+        core::array::from_fn(|i| {
+            let v = self.inner[i].value;
+            if v >= A.div_ceil(2) {
+                v as i64 - A as i64
+            } else {
+                v as i64
+            }
+        })
+    }
+
+    /// max |c_i| over the centered coefficients.
+    pub fn linf_norm(&self) -> u64 {
+        self.to_centered()
+            .iter()
+            .map(|c| c.unsigned_abs())
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// sqrt(sum c_i^2) over the centered coefficients.
+    #[cfg(feature = "std")]
+    pub fn l2_norm(&self) -> f64 {
+        self.to_centered()
+            .iter()
+            .map(|&c| (c as f64) * (c as f64))
+            .sum::<f64>()
+            .sqrt()
+    }
+
+    /// Uniform coefficients squeezed from `xof` with `sample_mod`, in order of degree.
+    pub fn from_xof(xof: &mut impl XofReader) -> Self {
+        Self::new(core::array::from_fn(|_| {
+            Element::new(sample_mod(xof, A, true) as i64)
+        }))
+    }
+
+    /// Uniform polynomial expanded from a 32 byte seed with SHAKE128, the same seed always gives the same
+    /// polynomial. Lets a uniform public component (the `a` of a key) be stored as its seed.
+    pub fn rand_from_seed(seed: [u8; 32]) -> Self {
+        let mut shake = Shake128::default();
+        shake.update(b"rlattice polynomial");
+        shake.update(&seed);
+        Self::from_xof(&mut shake.finalize_xof())
+    }
+
+    /// Uniform in {-1,0,1}, for masks like the u of encryption. Errors come from `error`.
+    #[cfg(feature = "std")]
     pub fn ternary_error() -> Self {
+        Self::ternary_valid(Ternary::UNIFORM)
+    }
+
+    /// Coefficients drawn from `dist`, negative ones stored as a - |e|.
+    #[cfg(feature = "std")]
+    pub fn error(dist: ErrorDist) -> Result<Self, SigmaError> {
+        dist.validate()?;
+        Ok(Self::error_valid(dist))
+    }
+
+    /// `error` for a distribution that passed `ErrorDist::validate`.
+    #[cfg(feature = "std")]
+    pub(crate) fn error_valid(dist: ErrorDist) -> Self {
         let mut rng = rand::rng();
         Self::new(core::array::from_fn(|_| {
-            let r: i8 = rng.random_range(0..=1);
-            Element::new(r as i64)
+            Element::new(dist.sample_valid(&mut rng))
         }))
     }
 
-    pub fn msb<const T: u64>(self) -> Polynomial<N, T> {
+    /// Coefficients in {-1,0,1} (-1 stored as a-1) drawn from `dist`.
+    #[cfg(feature = "std")]
+    pub fn ternary(dist: Ternary) -> Result<Self, TernaryError> {
+        dist.validate(N)?;
+        Ok(Self::ternary_valid(dist))
+    }
+
+    /// `ternary` for a distribution that passed `Ternary::validate`.
+    #[cfg(feature = "std")]
+    pub(crate) fn ternary_valid(dist: Ternary) -> Self {
+        let mut rng = rand::rng();
+        let mut inner = [Element::new(0); N];
+        match dist {
+            Ternary::Probability(p) => {
+                for c in inner.iter_mut() {
+                    if rng.random_bool(p) {
+                        *c = Element::new(if rng.random() { 1 } else { -1 });
+                    }
+                }
+            }
+            Ternary::HammingWeight(h) => {
+                for i in rand::seq::index::sample(&mut rng, N, h) {
+                    inner[i] = Element::new(if rng.random() { 1 } else { -1 });
+                }
+            }
+        }
+        Self::new(inner)
+    }
+
+    pub fn msb<const T: u64>(self) -> Polynomial<N, T, R> {
         let log_t = T.ilog2() as usize;
-        Polynomial::<N, T>::new(core::array::from_fn(|i| {
+        Polynomial::<N, T, R>::new(core::array::from_fn(|i| {
             let v = self.inner[i].value;
             let bits = u64_msb_bits(v, A.ilog2() as usize, log_t);
             Element::<T>::new(bits as i64)
@@ -106,6 +389,22 @@ impl<const N: usize, const A: u64> Polynomial<N, A> {
     }
 }
 
+/// Uniform value mod `modulus` from an XOF: 8 big endian bytes masked to the bit length of the modulus,
+/// rejected until it lands below the modulus (and is nonzero unless `allow_zero`).
+/// All XOF -> Z_p sampling in the crate goes through this.
+pub fn sample_mod(xof: &mut impl XofReader, modulus: u64, allow_zero: bool) -> u64 {
+    let mask = u64::MAX >> modulus.leading_zeros();
+    loop {
+        let mut buf = [0u8; 8];
+        xof.read(&mut buf);
+        let cand = u64::from_be_bytes(buf) & mask;
+        if (!allow_zero && cand == 0) || cand >= modulus {
+            continue;
+        }
+        return cand;
+    }
+}
+
 pub fn u64_msb(value: u64, len: usize) -> u64 {
     (value >> (len - 1)) & 1
 }
@@ -115,69 +414,371 @@ pub fn u64_msb_bits(value: u64, len: usize, log_t: usize) -> u64 {
     (value >> shift) & ((1 << log_t) - 1)
 }
 
-impl<const N: usize, const A: u64> fmt::Debug for Polynomial<N, A> {
+impl<const N: usize, const A: u64, R: RingKind> Zeroize for Polynomial<N, A, R> {
+    fn zeroize(&mut self) {
+        for e in self.inner.iter_mut() {
+            e.value.zeroize();
+        }
+    }
+}
+
+impl<const N: usize, const A: u64, R: RingKind> fmt::Debug for Polynomial<N, A, R> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         let coeffs: Vec<u64> = self.inner.iter().map(|e| e.value).collect();
         write!(f, "{:?}", coeffs)
     }
 }
 
-impl<const N: usize, const A: u64> Add for Polynomial<N, A> {
+#[cfg(feature = "simd")]
+impl<const N: usize, const A: u64, R: RingKind> Polynomial<N, A, R> {
+    fn as_u64s(&self) -> &[u64] {
+        // SAFETY: Element is repr(transparent) over u64
+        unsafe { core::slice::from_raw_parts(self.inner.as_ptr() as *const u64, N) }
+    }
+
+    fn as_u64s_mut(&mut self) -> &mut [u64] {
+        // SAFETY: Element is repr(transparent) over u64 and every kernel keeps values reduced mod A
+        unsafe { core::slice::from_raw_parts_mut(self.inner.as_mut_ptr() as *mut u64, N) }
+    }
+}
+
+impl<const N: usize, const A: u64, R: RingKind> Add for Polynomial<N, A, R> {
     type Output = Self;
 
+    #[cfg(feature = "simd")]
+    fn add(mut self, rhs: Self) -> Self::Output {
+        crate::simd::add_assign_mod(self.as_u64s_mut(), rhs.as_u64s(), A);
+        self
+    }
+
+    #[cfg(not(feature = "simd"))]
     fn add(self, rhs: Self) -> Self::Output {
         let inner = core::array::from_fn(|i| self.inner[i] + rhs.inner[i]);
-        Self { inner }
+        Self::new(inner)
     }
 }
 
-// todo: NTT/iNTT
-impl<const N: usize, const A: u64> Mul<Polynomial<N, A>> for Polynomial<N, A> {
-    type Output = Self;
+/// Ring dimension from which `Mul` switches from schoolbook to Karatsuba.
+pub const KARATSUBA_THRESHOLD: usize = 64;
+/// Ring dimension from which `Mul` spreads the product over the thread pool (`parallel` feature).
+#[cfg(feature = "parallel")]
+pub const PARALLEL_THRESHOLD: usize = 1024;
+/// Below this length the Karatsuba recursion falls back to schoolbook.
+const KARATSUBA_BASE: usize = 16;
 
-    fn mul(self, rhs: Polynomial<N, A>) -> Self::Output {
-        let mut out = [Element::<A>::new(0); N];
+impl<const N: usize, const A: u64, R: RingKind> Polynomial<N, A, R> {
+    /// O(n^2) convolution.
+    pub fn mul_schoolbook(&self, rhs: &Self) -> Self {
+        Self::new(core::array::from_fn(|k| self.convolution_coeff(rhs, k)))
+    }
 
-        for i in 0..N {
-            for j in 0..N {
-                let prod = self.inner[i] * rhs.inner[j];
-                let k = i + j;
-                if k < N {
-                    out[k] = out[k] + prod;
-                } else {
-                    out[k - N] = out[k - N] - prod;
-                }
+    /// c_k = sum_{i<=k} a_i b_{k-i} -/+ sum_{i>k} a_i b_{n+k-i} (minus when negacyclic), both sums
+    /// reduced once.
+    #[inline]
+    fn convolution_coeff(&self, rhs: &Self, k: usize) -> Element<A> {
+        let ring = ConstModulus::<A>;
+        let mut low = LazyAcc::new(ring);
+        let mut wrapped = LazyAcc::new(ring);
+        for i in 0..=k {
+            low.add_mul(self.inner[i].value, rhs.inner[k - i].value);
+        }
+        for i in k + 1..N {
+            wrapped.add_mul(self.inner[i].value, rhs.inner[N + k - i].value);
+        }
+        let value = if R::NEGACYCLIC {
+            ring.sub(low.finish(), wrapped.finish())
+        } else {
+            ring.add(low.finish(), wrapped.finish())
+        };
+        Element { value }
+    }
+
+    /// Product via Karatsuba, works for any modulus (no NTT friendly prime needed).
+    pub fn mul_karatsuba(&self, rhs: &Self) -> Self {
+        let full = karatsuba(&self.inner, &rhs.inner);
+        let mut out = [Element::<A>::new(0); N];
+        for (k, c) in full.into_iter().enumerate() {
+            if k < N {
+                out[k] = out[k] + c;
+            } else if R::NEGACYCLIC {
+                out[k - N] = out[k - N] - c;
+            } else {
+                out[k - N] = out[k - N] + c;
             }
         }
         Self::new(out)
     }
 }
 
+#[cfg(feature = "parallel")]
+impl<const N: usize, const A: u64, R: RingKind> Polynomial<N, A, R> {
+    /// Product with every output coefficient computed on its own rayon task, sequentially when
+    /// `Op::PolyMul` is switched off.
+    pub fn mul_parallel(&self, rhs: &Self) -> Self {
+        use rayon::prelude::*;
+
+        if !crate::parallel::is_parallel(crate::parallel::Op::PolyMul) {
+            return Self::new(core::array::from_fn(|k| self.convolution_coeff(rhs, k)));
+        }
+        let out: Vec<Element<A>> = crate::parallel::install(|| {
+            (0..N)
+                .into_par_iter()
+                .map(|k| self.convolution_coeff(rhs, k))
+                .collect()
+        });
+        Self::new(out.try_into().unwrap())
+    }
+}
+
+/// Full product of two equal length coefficient slices, the output has `2 * len - 1` terms.
+fn karatsuba<const A: u64>(a: &[Element<A>], b: &[Element<A>]) -> Vec<Element<A>> {
+    debug_assert_eq!(a.len(), b.len());
+    let n = a.len();
+    if n == 0 {
+        return vec![];
+    }
+    if n <= KARATSUBA_BASE {
+        return (0..2 * n - 1)
+            .map(|k| {
+                let mut acc = LazyAcc::new(ConstModulus::<A>);
+                for i in k.saturating_sub(n - 1)..=k.min(n - 1) {
+                    acc.add_mul(a[i].value, b[k - i].value);
+                }
+                Element {
+                    value: acc.finish(),
+                }
+            })
+            .collect();
+    }
+    let mut out = vec![Element::<A>::new(0); 2 * n - 1];
+
+    /*
+        a = a_0 + a_1 x^h,  b = b_0 + b_1 x^h
+        ab = z_0 + (z_1 - z_0 - z_2) x^h + z_2 x^2h
+        z_1 = (a_0 + a_1)(b_0 + b_1)
+    */
+    let half = n / 2;
+    let (a_0, a_1) = a.split_at(half);
+    let (b_0, b_1) = b.split_at(half);
+    let a_01: Vec<_> = (0..n - half)
+        .map(|i| a_0.get(i).map_or(a_1[i], |x| *x + a_1[i]))
+        .collect();
+    let b_01: Vec<_> = (0..n - half)
+        .map(|i| b_0.get(i).map_or(b_1[i], |x| *x + b_1[i]))
+        .collect();
+
+    let z_0 = karatsuba(a_0, b_0);
+    let z_1 = karatsuba(&a_01, &b_01);
+    let z_2 = karatsuba(a_1, b_1);
+
+    for (i, c) in z_0.into_iter().enumerate() {
+        out[i] = out[i] + c;
+        out[i + half] = out[i + half] - c;
+    }
+    for (i, c) in z_2.into_iter().enumerate() {
+        out[i + 2 * half] = out[i + 2 * half] + c;
+        out[i + half] = out[i + half] - c;
+    }
+    for (i, c) in z_1.into_iter().enumerate() {
+        out[i + half] = out[i + half] + c;
+    }
+    out
+}
+
+// todo: NTT/iNTT
+impl<const N: usize, const A: u64, R: RingKind> Mul for Polynomial<N, A, R> {
+    type Output = Self;
+
+    fn mul(self, rhs: Self) -> Self::Output {
+        <Self as Mul<&Self>>::mul(self, &rhs)
+    }
+}
+
+/// By reference, so a secret operand behind `Zeroizing` is not copied out of it.
+impl<const N: usize, const A: u64, R: RingKind> Mul<&Self> for Polynomial<N, A, R> {
+    type Output = Self;
+
+    fn mul(self, rhs: &Self) -> Self::Output {
+        #[cfg(feature = "parallel")]
+        if N >= PARALLEL_THRESHOLD && crate::parallel::is_parallel(crate::parallel::Op::PolyMul) {
+            return self.mul_parallel(rhs);
+        }
+        if N >= KARATSUBA_THRESHOLD {
+            self.mul_karatsuba(rhs)
+        } else {
+            self.mul_schoolbook(rhs)
+        }
+    }
+}
+
 // Polynomial * Element
-impl<const N: usize, const A: u64> Mul<Element<A>> for Polynomial<N, A> {
+impl<const N: usize, const A: u64, R: RingKind> Mul<Element<A>> for Polynomial<N, A, R> {
     type Output = Self;
 
+    #[cfg(feature = "simd")]
+    fn mul(mut self, rhs: Element<A>) -> Self::Output {
+        crate::simd::mul_scalar_assign_mod(self.as_u64s_mut(), rhs.value, A);
+        self
+    }
+
+    #[cfg(not(feature = "simd"))]
     fn mul(self, rhs: Element<A>) -> Self::Output {
-        let mut out = [Element::<A>::new(0); N];
+        Self::new(core::array::from_fn(|i| self.inner[i] * rhs))
+    }
+}
+
+impl<const N: usize, const A: u64, R: RingKind> Sub for Polynomial<N, A, R> {
+    type Output = Self;
+
+    fn sub(self, rhs: Self) -> Self::Output {
+        Self::new(core::array::from_fn(|i| self.inner[i] - rhs.inner[i]))
+    }
+}
 
-        for i in 0..N {
-            out[i] = self.inner[i] * rhs;
+impl<const N: usize, const A: u64, R: RingKind> Polynomial<N, A, R> {
+    /// a(x) -> a(x^k), k must be odd for this to be a ring automorphism.
+    pub fn automorphism(&self, k: usize) -> Self {
+        debug_assert!(k % 2 == 1, "automorphism index must be odd");
+        let mut out = [Element::<A>::new(0); N];
+        for (i, c) in self.inner.iter().enumerate() {
+            // x^(ik) with x^n = -1 (or 1 in the cyclic ring)
+            let e = (i * k) % (2 * N);
+            if e < N {
+                out[e] = out[e] + *c;
+            } else if R::NEGACYCLIC {
+                out[e - N] = out[e - N] - *c;
+            } else {
+                out[e - N] = out[e - N] + *c;
+            }
         }
         Self::new(out)
     }
 }
 
-impl<const N: usize, const A: u64> Neg for Polynomial<N, A> {
+impl<const N: usize, const A: u64, R: RingKind> Neg for Polynomial<N, A, R> {
     type Output = Self;
     fn neg(self) -> Self::Output {
         Self::new(core::array::from_fn(|i| -self.inner[i]))
     }
 }
 
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DecodeError {
+    Length {
+        expected: usize,
+        got: usize,
+    },
+    /// A coefficient is >= the modulus.
+    OutOfRange {
+        index: usize,
+        value: u64,
+    },
+    /// A coefficient of a ternary secret is not in {-1, 0, 1}.
+    NotTernary {
+        index: usize,
+        value: i64,
+    },
+    /// Padding bits after the last coefficient are not zero.
+    NonZeroPadding,
+    /// A length or parameter field of a larger message has an unusable value.
+    InvalidField {
+        field: &'static str,
+        value: u64,
+    },
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DecodeError::Length { expected, got } => {
+                write!(f, "expected {expected} bytes, got {got}")
+            }
+            DecodeError::OutOfRange { index, value } => {
+                write!(f, "coefficient {index} = {value} is not reduced")
+            }
+            DecodeError::NotTernary { index, value } => {
+                write!(f, "coefficient {index} = {value} is not in {{-1, 0, 1}}")
+            }
+            DecodeError::NonZeroPadding => write!(f, "padding bits are not zero"),
+            DecodeError::InvalidField { field, value } => write!(f, "invalid {field} {value}"),
+        }
+    }
+}
+
+impl core::error::Error for DecodeError {}
+
+impl<const N: usize, const A: u64, R: RingKind> Polynomial<N, A, R> {
+    /// ceil(log2 a) bits per coefficient.
+    pub const COEFF_BITS: usize = (64 - (A - 1).leading_zeros()) as usize;
+    /// Length of `to_bytes`.
+    pub const BYTES: usize = (N * Self::COEFF_BITS).div_ceil(8);
+
+    /// Coefficients bit packed at `COEFF_BITS` each, little endian, lowest degree first.
+    /// Padding bits in the last byte are zero, so the encoding is canonical.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let bits = Self::COEFF_BITS;
+        let mut out = vec![0u8; Self::BYTES];
+        for (i, e) in self.inner.iter().enumerate() {
+            let v = e.value;
+            for b in 0..bits {
+                if (v >> b) & 1 == 1 {
+                    let pos = i * bits + b;
+                    out[pos / 8] |= 1 << (pos % 8);
+                }
+            }
+        }
+        out
+    }
+
+    /// Inverse of `to_bytes`, rejects anything `to_bytes` can't produce.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, DecodeError> {
+        if bytes.len() != Self::BYTES {
+            return Err(DecodeError::Length {
+                expected: Self::BYTES,
+                got: bytes.len(),
+            });
+        }
+        let bits = Self::COEFF_BITS;
+        let mut inner = [Element::new(0); N];
+        for (i, c) in inner.iter_mut().enumerate() {
+            let mut v = 0u64;
+            for b in 0..bits {
+                let pos = i * bits + b;
+                v |= (((bytes[pos / 8] >> (pos % 8)) & 1) as u64) << b;
+            }
+            if v >= A {
+                return Err(DecodeError::OutOfRange { index: i, value: v });
+            }
+            c.value = v;
+        }
+        let used = N * bits;
+        if !used.is_multiple_of(8) && bytes[Self::BYTES - 1] >> (used % 8) != 0 {
+            return Err(DecodeError::NonZeroPadding);
+        }
+        Ok(Self::new(inner))
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_from_xof() {
+        let xof = || {
+            let mut shake = Shake128::default();
+            shake.update(b"rlattice");
+            shake.finalize_xof()
+        };
+
+        let a = Polynomial::<16, 97>::from_xof(&mut xof());
+        assert_eq!(a, Polynomial::<16, 97>::from_xof(&mut xof()));
+        assert!(a.inner.iter().all(|e| e.value() < 97));
+
+        // a full 64 bit modulus doesn't overflow the mask
+        assert!(sample_mod(&mut xof(), u64::MAX, false) != 0);
+    }
+
     #[test]
     fn test_element_add_and_mul_mod_32() {
         type E = Element<32>;
@@ -232,8 +833,7 @@ mod tests {
         let p_x = Polynomial::<4, 2>::new([x_1, x_2, x_3, x_4]);
         let p_y = Polynomial::<4, 2>::new([y_1, y_2, y_3, y_4]);
 
-        let z_add =
-            Polynomial::<4, 2>::new([E::new(1 + 1), E::new(0 + 1), E::new(-1 + -1), E::new(2 + 3)]);
+        let z_add = Polynomial::<4, 2>::new([E::new(2), E::new(1), E::new(-2), E::new(5)]);
 
         assert_eq!(p_x + p_y, z_add);
 
@@ -241,11 +841,174 @@ mod tests {
             Polynomial::<4, 2>::new([x_1 * y_1, x_2 * y_2, x_3 * y_3, x_4 * y_4]);
 
         let coeffwise_product =
-            Polynomial::<4, 2>::new([E::new(1 * 1), E::new(0 * 1), E::new(-1 * -1), E::new(2 * 3)]);
+            Polynomial::<4, 2>::new([E::new(1), E::new(0), E::new(1), E::new(6)]);
 
         assert_eq!(z_mul_elementwise, coeffwise_product);
     }
 
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_karatsuba_matches_schoolbook() {
+        fn check<const N: usize, const A: u64>() {
+            let a = Polynomial::<N, A>::rand();
+            let b = Polynomial::<N, A>::rand();
+            assert_eq!(a.mul_karatsuba(&b), a.mul_schoolbook(&b));
+        }
+        check::<4, 32>();
+        check::<17, 97>();
+        check::<37, 65_537>();
+        check::<64, 12_289>();
+        check::<256, 3_329>();
+    }
+
+    #[cfg(feature = "parallel")]
+    #[test]
+    fn test_parallel_matches_schoolbook() {
+        let a = Polynomial::<1024, 12_289>::rand();
+        let b = Polynomial::<1024, 12_289>::rand();
+        assert_eq!(a.mul_parallel(&b), a.mul_schoolbook(&b));
+        assert_eq!(a * b, a.mul_karatsuba(&b));
+    }
+
+    #[test]
+    fn test_karatsuba_negacyclic_wrap() {
+        type P = Polynomial<128, 97>;
+        // x^127 * x = x^128 = -1
+        let mut x_127 = [Element::new(0); 128];
+        x_127[127] = Element::new(1);
+        let mut x = [Element::new(0); 128];
+        x[1] = Element::new(1);
+        let mut minus_one = [Element::new(0); 128];
+        minus_one[0] = Element::new(-1);
+        assert_eq!(P::new(x_127) * P::new(x), P::new(minus_one));
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_automorphism() {
+        type E = Element<97>;
+        type P = Polynomial<4, 97>;
+        // 1 + 2x + 3x^2 + 4x^3 -> 1 + 2x^3 + 3x^6 + 4x^9 = 1 + 2x^3 - 3x^2 + 4x
+        let p = P::new([E::new(1), E::new(2), E::new(3), E::new(4)]);
+        assert_eq!(
+            p.automorphism(3),
+            P::new([E::new(1), E::new(4), E::new(-3), E::new(2)])
+        );
+        assert_eq!(p.automorphism(1), p);
+
+        // automorphisms are ring homomorphisms
+        let a = Polynomial::<16, 97>::rand();
+        let b = Polynomial::<16, 97>::rand();
+        assert_eq!(
+            (a * b).automorphism(5),
+            a.automorphism(5) * b.automorphism(5)
+        );
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_ternary_probability() {
+        type P = Polynomial<4096, 97>;
+        let p = P::ternary(Ternary::Probability(0.5)).unwrap();
+        let (mut plus, mut minus) = (0, 0);
+        for e in p.inner.iter() {
+            match e.value() {
+                0 => {}
+                1 => plus += 1,
+                96 => minus += 1,
+                v => panic!("{v} is not ternary"),
+            }
+        }
+        // 2048 expected nonzero with std ~32, each sign ~1024 with std ~28
+        assert!((1800..2300).contains(&(plus + minus)));
+        assert!((800..1250).contains(&plus));
+        assert!((800..1250).contains(&minus));
+
+        let zero = P::ternary(Ternary::Probability(0.0)).unwrap();
+        assert!(zero.inner.iter().all(|e| e.value() == 0));
+        assert_eq!(
+            P::ternary(Ternary::Probability(1.5)).unwrap_err(),
+            TernaryError::Probability { p: 1.5 }
+        );
+        assert!(P::ternary(Ternary::Probability(f64::NAN)).is_err());
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_ternary_hamming_weight() {
+        type P = Polynomial<256, 97>;
+        for h in [0, 1, 64, 256] {
+            let p = P::ternary(Ternary::HammingWeight(h)).unwrap();
+            let nonzero = p.inner.iter().filter(|e| e.value() != 0).count();
+            assert_eq!(nonzero, h);
+            assert!(p.inner.iter().all(|e| [0, 1, 96].contains(&e.value())));
+        }
+        assert_eq!(
+            P::ternary(Ternary::HammingWeight(257)).unwrap_err(),
+            TernaryError::HammingWeight { h: 257, n: 256 }
+        );
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_error_dist() {
+        type P = Polynomial<4096, 97>;
+        let variance =
+            |p: &P| p.to_centered().iter().map(|&x| (x * x) as f64).sum::<f64>() / 4096.0;
+
+        let gaussian = P::error(ErrorDist::STANDARD).unwrap();
+        // sigma^2 = 10.24, the sample variance has std ~0.23
+        assert!((9.0..11.5).contains(&variance(&gaussian)));
+        assert!(gaussian.linf_norm() <= 20);
+
+        let cbd = P::error(ErrorDist::CenteredBinomial { eta: 2 }).unwrap();
+        assert!((0.9..1.1).contains(&variance(&cbd)));
+        assert!(cbd.linf_norm() <= 2);
+
+        assert!(
+            P::error(ErrorDist::Zero)
+                .unwrap()
+                .inner
+                .iter()
+                .all(|e| e.value() == 0)
+        );
+
+        for sigma in [0.0, -1.0, f64::NAN] {
+            let dist = ErrorDist::Gaussian { sigma };
+            assert!(P::error(dist).is_err());
+            assert!(dist.sample(&mut rand::rng()).is_err());
+        }
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_centered_and_norms() {
+        type E = Element<32>;
+        let p = Polynomial::<4, 32>::new([E::new(3), E::new(-4), E::new(16), E::new(15)]);
+        assert_eq!(p.to_centered(), [3, -4, -16, 15]);
+        assert_eq!(p.linf_norm(), 16);
+        assert_eq!(p.l2_norm(), ((9 + 16 + 256 + 225) as f64).sqrt());
+
+        type F = Element<97>;
+        let p = Polynomial::<3, 97>::new([F::new(48), F::new(49), F::new(-1)]);
+        assert_eq!(p.to_centered(), [48, -48, -1]);
+        assert_eq!(p.linf_norm(), 48);
+
+        let e = Polynomial::<64, 97>::ternary_error();
+        assert!(e.linf_norm() <= 1);
+        assert!(e.l2_norm() <= 8.0);
+    }
+
+    #[test]
+    fn test_lift_centered() {
+        let s = Polynomial::<3, 3>::new([Element::new(0), Element::new(1), Element::new(-1)]);
+        let lifted = s.lift_centered::<97>();
+        assert_eq!(lifted.inner.map(|e| e.value()), [0, 1, 96]);
+        // plain lift keeps the representative instead
+        assert_eq!(s.lift::<97>().inner.map(|e| e.value()), [0, 1, 2]);
+    }
+
+    #[cfg(feature = "std")]
     #[test]
     fn test_polynomial_rand_mod_32() {
         type P = Polynomial<4, 32>;
@@ -256,6 +1019,7 @@ mod tests {
         }
     }
 
+    #[cfg(feature = "std")]
     #[test]
     fn test_polynomial_rand_mod_2() {
         type P = Polynomial<4, 2>;
@@ -265,4 +1029,78 @@ mod tests {
             assert!(val < 2, "Value {} is not less than 2", val);
         }
     }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_bytes_roundtrip() {
+        assert_eq!(Polynomial::<4, 32>::COEFF_BITS, 5);
+        assert_eq!(Polynomial::<4, 33>::COEFF_BITS, 6);
+        assert_eq!(Polynomial::<256, 12_289>::BYTES, 448);
+
+        let a = Polynomial::<256, 12_289>::rand();
+        let bytes = a.to_bytes();
+        assert_eq!(bytes.len(), 448);
+        assert_eq!(Polynomial::<256, 12_289>::from_bytes(&bytes), Ok(a));
+
+        let b = Polynomial::<5, 3>::rand();
+        assert_eq!(Polynomial::<5, 3>::from_bytes(&b.to_bytes()), Ok(b));
+    }
+
+    #[test]
+    fn test_from_bytes_rejects_non_canonical() {
+        type P = Polynomial<3, 5>;
+        // 3 bits each, 9 bits -> 2 bytes
+        assert_eq!(
+            P::from_bytes(&[0]),
+            Err(DecodeError::Length {
+                expected: 2,
+                got: 1
+            })
+        );
+        // first coefficient 7 >= 5
+        assert_eq!(
+            P::from_bytes(&[0b0000_0111, 0]),
+            Err(DecodeError::OutOfRange { index: 0, value: 7 })
+        );
+        assert_eq!(P::from_bytes(&[0, 0b10]), Err(DecodeError::NonZeroPadding));
+    }
+
+    #[test]
+    fn test_rand_from_seed() {
+        let a = Polynomial::<64, 12_289>::rand_from_seed([7; 32]);
+        assert_eq!(a, Polynomial::<64, 12_289>::rand_from_seed([7; 32]));
+        assert_ne!(a, Polynomial::<64, 12_289>::rand_from_seed([8; 32]));
+        assert!(a.inner.iter().all(|e| e.value() < 12_289));
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_cyclic_ring() {
+        type C = Polynomial<4, 97, Cyclic>;
+        // x^3 * x = x^4 = 1
+        let x3 = C::new([0, 0, 0, 1].map(Element::new));
+        let x = C::new([0, 1, 0, 0].map(Element::new));
+        assert_eq!(x3 * x, C::new([1, 0, 0, 0].map(Element::new)));
+        assert_eq!(
+            (x3.into_ring::<Negacyclic>() * x.into_ring()).inner,
+            [96, 0, 0, 0].map(Element::new)
+        );
+
+        // every multiplication path agrees with the cyclic convolution
+        type Big = Polynomial<128, 12_289, Cyclic>;
+        let a = Big::rand();
+        let b = Big::rand();
+        let expected = Big::new(core::array::from_fn(|k| {
+            (0..128).fold(Element::new(0), |acc, i| {
+                acc + a.inner[i] * b.inner[(128 + k - i) % 128]
+            })
+        }));
+        assert_eq!(a.mul_schoolbook(&b), expected);
+        assert_eq!(a.mul_karatsuba(&b), expected);
+        assert_eq!(a * b, expected);
+        assert_eq!(
+            (a * b).automorphism(3),
+            a.automorphism(3) * b.automorphism(3)
+        );
+    }
 }
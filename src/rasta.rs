@@ -0,0 +1,273 @@
+//! Rasta (https://eprint.iacr.org/2018/181.pdf) and its aggressive variant Agrasta, the HE-friendly stream
+//! ciphers over GF(2) for boolean circuit FHE.
+//!
+//! The state is `N` bits (N odd, so chi is a permutation). Every affine layer is a random invertible N x N
+//! binary matrix plus a constant, squeezed from SHAKE128(nonce || block counter) like Pasta's layers, then
+//! chi, y_i = x_i ^ (!x_{i+1} & x_{i+2}), and the key is added back at the end:
+//!
+//! keystream = A_R . chi . A_{R-1} . ... . chi . A_0 (k) ^ k
+//!
+//! Bits are packed little endian into u64 words, bit i of the state is bit i % 64 of word i / 64.
+
+use byteorder::{BigEndian, ByteOrder};
+use sha3::{
+    Shake128, Shake128Reader,
+    digest::{ExtendableOutput, Update, XofReader},
+};
+use zeroize::{Zeroize, ZeroizeOnDrop, Zeroizing};
+
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RastaError {
+    /// The key has to be N bits.
+    KeyLength { expected: usize, got: usize },
+    /// A plaintext modulus other than 2, Rasta works over GF(2).
+    Modulus { modulus: u64 },
+}
+
+impl fmt::Display for RastaError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RastaError::KeyLength { expected, got } => {
+                write!(f, "key must be {expected} bits, got {got}")
+            }
+            RastaError::Modulus { modulus } => {
+                write!(f, "rasta works over GF(2), not mod {modulus}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for RastaError {}
+
+/// Agrasta, n = 129 with 4 rounds.
+pub type Agrasta = Rasta<129, 4>;
+
+pub struct Rasta<const N: usize, const R: usize> {
+    key: Vec<u64>,
+}
+
+impl<const N: usize, const R: usize> Drop for Rasta<N, R> {
+    fn drop(&mut self) {
+        self.key.zeroize();
+    }
+}
+
+impl<const N: usize, const R: usize> ZeroizeOnDrop for Rasta<N, R> {}
+
+const fn words(n: usize) -> usize {
+    n.div_ceil(64)
+}
+
+fn get_bit(v: &[u64], i: usize) -> bool {
+    v[i / 64] >> (i % 64) & 1 == 1
+}
+
+fn pack(bits: &[bool]) -> Vec<u64> {
+    let mut out = vec![0u64; words(bits.len())];
+    for (i, &b) in bits.iter().enumerate() {
+        out[i / 64] |= (b as u64) << (i % 64);
+    }
+    out
+}
+
+fn unpack(v: &[u64], n: usize) -> Vec<bool> {
+    (0..n).map(|i| get_bit(v, i)).collect()
+}
+
+/// Rank of a binary matrix given as packed rows of `n` bits.
+fn rank(rows: &[Vec<u64>], n: usize) -> usize {
+    let mut rows = rows.to_vec();
+    let mut rank = 0;
+    for col in 0..n {
+        let Some(pivot) = (rank..rows.len()).find(|&r| get_bit(&rows[r], col)) else {
+            continue;
+        };
+        rows.swap(rank, pivot);
+        let pivot_row = rows[rank].clone();
+        for (r, row) in rows.iter_mut().enumerate() {
+            if r != rank && get_bit(row, col) {
+                row.iter_mut().zip(&pivot_row).for_each(|(a, b)| *a ^= b);
+            }
+        }
+        rank += 1;
+    }
+    rank
+}
+
+/// Bits squeezed from SHAKE128(nonce || block counter), the same seed encoding as `XofSampler`.
+struct BitSampler {
+    shake: Shake128Reader,
+}
+
+impl BitSampler {
+    fn seeded(nonce: u64, block_counter: u64) -> Self {
+        let mut shake = Shake128::default();
+        let mut seed = [0u8; 16];
+        BigEndian::write_u64(&mut seed[0..8], nonce);
+        BigEndian::write_u64(&mut seed[8..16], block_counter);
+        shake.update(&seed);
+        Self {
+            shake: shake.finalize_xof(),
+        }
+    }
+
+    /// `n` uniform bits, packed.
+    fn vector(&mut self, n: usize) -> Vec<u64> {
+        let mut out = vec![0u64; words(n)];
+        for w in out.iter_mut() {
+            let mut buf = [0u8; 8];
+            self.shake.read(&mut buf);
+            *w = u64::from_be_bytes(buf);
+        }
+        if !n.is_multiple_of(64) {
+            out[words(n) - 1] &= (1 << (n % 64)) - 1;
+        }
+        out
+    }
+
+    /// Uniform invertible n x n matrix, singular ones are rejected and sampled again.
+    fn invertible_matrix(&mut self, n: usize) -> Vec<Vec<u64>> {
+        loop {
+            let rows = (0..n).map(|_| self.vector(n)).collect::<Vec<_>>();
+            if rank(&rows, n) == n {
+                return rows;
+            }
+        }
+    }
+}
+
+impl<const N: usize, const R: usize> Rasta<N, R> {
+    /// `try_new` that panics on a key of the wrong length.
+    pub fn new(key: &[bool]) -> Self {
+        Self::try_new(key).unwrap_or_else(|e| panic!("{e}"))
+    }
+
+    /// `key` holds N bits.
+    pub fn try_new(key: &[bool]) -> Result<Self, RastaError> {
+        assert!(N % 2 == 1, "chi is only a permutation for odd N");
+        assert!(R >= 1, "rasta needs at least one round");
+        if key.len() != N {
+            return Err(RastaError::KeyLength {
+                expected: N,
+                got: key.len(),
+            });
+        }
+        Ok(Self { key: pack(key) })
+    }
+
+    pub fn keystream(&self, nonce: u64, block_counter: u64) -> Vec<bool> {
+        unpack(
+            &Zeroizing::new(self.keystream_packed(nonce, block_counter)),
+            N,
+        )
+    }
+
+    fn keystream_packed(&self, nonce: u64, block_counter: u64) -> Vec<u64> {
+        let mut sampler = BitSampler::seeded(nonce, block_counter);
+        let mut state = self.key.clone();
+        for _ in 0..R {
+            Self::affine(&mut sampler, &mut state);
+            Self::chi(&mut state);
+        }
+        Self::affine(&mut sampler, &mut state);
+        state.iter_mut().zip(&self.key).for_each(|(s, k)| *s ^= k);
+        state
+    }
+
+    /// XORs the keystream of (`nonce`, block index) onto every N bit block, decryption is the same call.
+    pub fn encrypt_with_nonce(&self, nonce: u64, bits: &[bool]) -> Vec<bool> {
+        let mut out = bits.to_vec();
+        for (b, block) in out.chunks_mut(N).enumerate() {
+            let ks = Zeroizing::new(self.keystream(nonce, b as u64));
+            block.iter_mut().zip(ks.iter()).for_each(|(m, k)| *m ^= k);
+        }
+        out
+    }
+
+    pub fn decrypt_with_nonce(&self, nonce: u64, bits: &[bool]) -> Vec<bool> {
+        self.encrypt_with_nonce(nonce, bits)
+    }
+
+    /// state = M state ^ c, the matrix squeezed before the constant.
+    fn affine(sampler: &mut BitSampler, state: &mut Vec<u64>) {
+        let mat = sampler.invertible_matrix(N);
+        let c = sampler.vector(N);
+        let mut out = c;
+        for (i, row) in mat.iter().enumerate() {
+            let parity = row
+                .iter()
+                .zip(state.iter())
+                .fold(0, |acc, (a, b)| acc ^ (a & b).count_ones())
+                & 1;
+            out[i / 64] ^= (parity as u64) << (i % 64);
+        }
+        *state = out;
+    }
+
+    fn chi(state: &mut Vec<u64>) {
+        let x = unpack(state, N);
+        let y = (0..N)
+            .map(|i| x[i] ^ (!x[(i + 1) % N] & x[(i + 2) % N]))
+            .collect::<Vec<_>>();
+        *state = pack(&y);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{Rng, rng};
+
+    use super::*;
+
+    fn rand_bits(n: usize) -> Vec<bool> {
+        let mut rng = rng();
+        (0..n).map(|_| rng.random()).collect()
+    }
+
+    #[test]
+    fn test_roundtrip() {
+        let rasta = Rasta::<65, 3>::new(&rand_bits(65));
+        let msg = rand_bits(200);
+        let ct = rasta.encrypt_with_nonce(9, &msg);
+        assert_ne!(ct, msg);
+        assert_eq!(rasta.decrypt_with_nonce(9, &ct), msg);
+        assert_ne!(rasta.keystream(9, 0), rasta.keystream(9, 1));
+        assert_eq!(rasta.keystream(9, 0).len(), 65);
+    }
+
+    #[test]
+    fn test_agrasta() {
+        let agrasta = Agrasta::new(&rand_bits(129));
+        let msg = rand_bits(129);
+        assert_eq!(
+            agrasta.decrypt_with_nonce(1, &agrasta.encrypt_with_nonce(1, &msg)),
+            msg
+        );
+    }
+
+    #[test]
+    fn test_chi_is_a_permutation() {
+        let mut seen = std::collections::HashSet::new();
+        for v in 0..32u64 {
+            let mut state = vec![v];
+            Rasta::<5, 1>::chi(&mut state);
+            assert!(state[0] < 32);
+            seen.insert(state[0]);
+        }
+        assert_eq!(seen.len(), 32);
+    }
+
+    #[test]
+    fn test_sampled_matrices_invertible() {
+        let mut sampler = BitSampler::seeded(3, 4);
+        for n in [1, 7, 64, 65, 129] {
+            let mat = sampler.invertible_matrix(n);
+            assert_eq!(rank(&mat, n), n);
+            assert!(mat.iter().all(|row| row.len() == words(n)));
+        }
+        assert_eq!(rank(&[vec![0b101], vec![0b101], vec![0b011]], 3), 2);
+        assert_eq!(rank(&[vec![0b110], vec![0b011], vec![0b101]], 3), 2);
+    }
+}
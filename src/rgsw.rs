@@ -0,0 +1,310 @@
+//! RGSW ciphertexts and the external product with RLWE ciphertexts.
+//!
+//! An RGSW encryption of m under s is 2 * digits RLWE encryptions of zero with m g_j added to the first
+//! component (the `b` rows) or to the second one (the `a` rows), g_j the powers of a [`Gadget`]. The
+//! external product decomposes both components of an RLWE ciphertext in that base and sums the digits
+//! times the rows, which gives an RLWE encryption of m times its phase. Since only the small digits
+//! multiply the row noise, the noise grows additively: digits * n * 2^base_log * e per product, plus m
+//! times the input noise.
+//!
+//! Rows are kept in the NTT domain, so every product is 2 * digits forward transforms and 2 inverse ones.
+
+use std::ops::{Add, Sub};
+use std::sync::Arc;
+
+use zeroize::Zeroizing;
+
+use crate::backend::RingBackend;
+use crate::bfv_pke::BfvCiphertext;
+use crate::keyswitch::Gadget;
+use crate::lwe::{LweCipher, extract_lwe};
+use crate::ntt::{NttTable, pointwise};
+use crate::polynomial::{Element, ErrorDist, Polynomial};
+
+/// The NTT table of the ring and the decomposition base, shared by all ciphertexts that are multiplied
+/// together.
+#[derive(Debug, Clone)]
+pub struct RgswContext<const N: usize, const Q: u64> {
+    table: NttTable<N, Q>,
+    gadget: Gadget,
+}
+
+impl<const N: usize, const Q: u64> RgswContext<N, Q> {
+    /// `None` if (N, Q) is not NTT friendly.
+    pub fn new(base_log: u32) -> Option<Self> {
+        Some(Self {
+            table: NttTable::new()?,
+            gadget: Gadget::new(Q, base_log),
+        })
+    }
+
+    pub fn gadget(&self) -> &Gadget {
+        &self.gadget
+    }
+
+    pub fn table(&self) -> &NttTable<N, Q> {
+        &self.table
+    }
+}
+
+/// (c_1, c_2) with phase c_1 + c_2 s, the layout of `BfvCiphertext` without the scaling by Δ.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Rlwe<const N: usize, const Q: u64> {
+    pub c_1: Polynomial<N, Q>,
+    pub c_2: Polynomial<N, Q>,
+}
+
+impl<const N: usize, const Q: u64> Rlwe<N, Q> {
+    pub fn new(c_1: Polynomial<N, Q>, c_2: Polynomial<N, Q>) -> Self {
+        Self { c_1, c_2 }
+    }
+
+    /// (m, 0), phase m under every secret.
+    pub fn trivial(m: Polynomial<N, Q>) -> Self {
+        Self::new(m, Polynomial::new([Element::new(0); N]))
+    }
+
+    /// (m - a s + e, a)
+    pub fn encrypt(sk: &Polynomial<N, 3>, m: Polynomial<N, Q>) -> Self {
+        let a = Polynomial::<N, Q>::rand();
+        let e = Polynomial::error_valid(ErrorDist::STANDARD);
+        let s = Zeroizing::new(sk.lift_centered::<Q>());
+        Self::new(m + e - a * *s, a)
+    }
+
+    /// c_1 + c_2 s
+    pub fn phase(&self, sk: &Polynomial<N, 3>) -> Polynomial<N, Q> {
+        self.c_1 + self.c_2 * *Zeroizing::new(sk.lift_centered::<Q>())
+    }
+
+    /// LWE ciphertext of coefficient `index` of the phase, under the coefficient vector of s.
+    pub fn extract_lwe<const T: u64>(&self, index: usize) -> LweCipher<N, Q, T> {
+        extract_lwe(&self.c_1, &self.c_2, index)
+    }
+
+    /// Both components times x^k, k taken mod 2n. Exact, the noise only moves.
+    pub fn mul_monomial(&self, k: usize) -> Self {
+        Self::new(mul_monomial(&self.c_1, k), mul_monomial(&self.c_2, k))
+    }
+}
+
+impl<const N: usize, const Q: u64> Add for Rlwe<N, Q> {
+    type Output = Self;
+
+    fn add(self, rhs: Self) -> Self {
+        Self::new(self.c_1 + rhs.c_1, self.c_2 + rhs.c_2)
+    }
+}
+
+impl<const N: usize, const Q: u64> Sub for Rlwe<N, Q> {
+    type Output = Self;
+
+    fn sub(self, rhs: Self) -> Self {
+        Self::new(self.c_1 - rhs.c_1, self.c_2 - rhs.c_2)
+    }
+}
+
+/// x^k p in Z_q[x]/(x^n + 1), k taken mod 2n.
+fn mul_monomial<const N: usize, const Q: u64>(p: &Polynomial<N, Q>, k: usize) -> Polynomial<N, Q> {
+    let mut out = Polynomial::new([Element::new(0); N]);
+    for (i, c) in p.inner.iter().enumerate() {
+        let j = (i + k) % (2 * N);
+        if j < N {
+            out.inner[j] = *c;
+        } else {
+            out.inner[j - N] = -*c;
+        }
+    }
+    out
+}
+
+/// RGSW encryption of a ring element m, rows in the evaluation domain. Row j of `b_rows` is
+/// RLWE(0) + (m g_j, 0), row j of `a_rows` is RLWE(0) + (0, m g_j).
+#[derive(Debug, Clone)]
+pub struct RgswCiphertext<const N: usize, const Q: u64> {
+    b_rows: Vec<Rlwe<N, Q>>,
+    a_rows: Vec<Rlwe<N, Q>>,
+    ctx: Arc<RgswContext<N, Q>>,
+}
+
+impl<const N: usize, const Q: u64> RgswCiphertext<N, Q> {
+    /// m should be small (a bit, a monomial, a ternary polynomial): it multiplies the noise of every
+    /// ciphertext the result is multiplied with.
+    pub fn encrypt(
+        ctx: &Arc<RgswContext<N, Q>>,
+        sk: &Polynomial<N, 3>,
+        m: &Polynomial<N, Q>,
+    ) -> Self {
+        let table = &ctx.table;
+        let s_hat = Zeroizing::new(table.forward(&Zeroizing::new(sk.lift_centered::<Q>())));
+        let m_hat = table.forward(m);
+        // (-a s + e, a) with phase e
+        let rlwe_zero = || {
+            let a = table.forward(&Polynomial::<N, Q>::rand());
+            let e = table.forward(&Polynomial::error_valid(ErrorDist::STANDARD));
+            Rlwe::new(e - pointwise(&a, &s_hat), a)
+        };
+        let mut b_rows = Vec::with_capacity(ctx.gadget.digits());
+        let mut a_rows = Vec::with_capacity(ctx.gadget.digits());
+        for g in ctx.gadget.powers::<Q>() {
+            let m_g = m_hat * g;
+            b_rows.push(rlwe_zero() + Rlwe::trivial(m_g));
+            a_rows.push(rlwe_zero() + Rlwe::new(Polynomial::new([Element::new(0); N]), m_g));
+        }
+        Self {
+            b_rows,
+            a_rows,
+            ctx: ctx.clone(),
+        }
+    }
+
+    pub fn context(&self) -> &Arc<RgswContext<N, Q>> {
+        &self.ctx
+    }
+
+    /// RLWE encryption of m * phase(ct): the gadget digits of each component times the matching rows.
+    pub fn external_product(&self, ct: &Rlwe<N, Q>) -> Rlwe<N, Q> {
+        let (table, gadget) = (&self.ctx.table, &self.ctx.gadget);
+        let mut out = Rlwe::trivial(Polynomial::new([Element::new(0); N]));
+        for (part, rows) in [(&ct.c_1, &self.b_rows), (&ct.c_2, &self.a_rows)] {
+            for (digit, row) in gadget.decompose(part).iter().zip(rows) {
+                let digit = table.forward(digit);
+                out.c_1 = out.c_1 + pointwise(&digit, &row.c_1);
+                out.c_2 = out.c_2 + pointwise(&digit, &row.c_2);
+            }
+        }
+        Rlwe::new(table.inverse(&out.c_1), table.inverse(&out.c_2))
+    }
+
+    /// For m a bit: `if_one` when m = 1, `if_zero` when m = 0.
+    pub fn cmux(&self, if_zero: &Rlwe<N, Q>, if_one: &Rlwe<N, Q>) -> Rlwe<N, Q> {
+        *if_zero + self.external_product(&(*if_one - *if_zero))
+    }
+
+    /// BFV encryption of m * msg: the external product keeps the scaling by Δ, so the result decrypts under
+    /// the same key as long as m * e stays small.
+    pub fn mul_bfv<const T: u64, B: RingBackend>(
+        &self,
+        ct: &BfvCiphertext<N, Q, T, B>,
+    ) -> BfvCiphertext<N, Q, T, B> {
+        let out = self.external_product(&Rlwe::new(*ct.c_1(), *ct.c_2()));
+        BfvCiphertext::new(out.c_1, out.c_2, ct.context().clone())
+    }
+
+    /// RGSW encryption of m * m': the external product of `self` with every row of `rhs`.
+    pub fn mul(&self, rhs: &Self) -> Self {
+        let table = &self.ctx.table;
+        let product = |rows: &[Rlwe<N, Q>]| {
+            rows.iter()
+                .map(|row| {
+                    let row = Rlwe::new(table.inverse(&row.c_1), table.inverse(&row.c_2));
+                    let out = self.external_product(&row);
+                    Rlwe::new(table.forward(&out.c_1), table.forward(&out.c_2))
+                })
+                .collect()
+        };
+        Self {
+            b_rows: product(&rhs.b_rows),
+            a_rows: product(&rhs.a_rows),
+            ctx: self.ctx.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const N: usize = 64;
+    /// Prime, 1 mod 128.
+    const Q: u64 = 4_294_957_057;
+
+    fn setup() -> (Arc<RgswContext<N, Q>>, Polynomial<N, 3>) {
+        (
+            Arc::new(RgswContext::new(8).unwrap()),
+            Polynomial::ternary_error(),
+        )
+    }
+
+    fn x_pow<const A: u64>(k: usize) -> Polynomial<N, A> {
+        let mut p = Polynomial::new([Element::new(0); N]);
+        p.inner[k] = Element::new(1);
+        p
+    }
+
+    /// Largest centered coefficient of phase - expected.
+    fn noise(ct: &Rlwe<N, Q>, sk: &Polynomial<N, 3>, expected: Polynomial<N, Q>) -> u64 {
+        (ct.phase(sk) - expected)
+            .inner
+            .iter()
+            .map(|c| c.value().min(Q - c.value()))
+            .max()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_external_product() {
+        let (ctx, sk) = setup();
+        let msg = Polynomial::<N, Q>::rand();
+        let ct = Rlwe::encrypt(&sk, msg);
+        let m = Polynomial::<N, 3>::ternary_error().lift_centered::<Q>();
+        let out = RgswCiphertext::encrypt(&ctx, &sk, &m).external_product(&ct);
+        assert!(noise(&out, &sk, m * msg) < 1 << 20);
+    }
+
+    #[test]
+    fn test_cmux_and_monomial() {
+        let (ctx, sk) = setup();
+        let (m_0, m_1) = (Polynomial::<N, Q>::rand(), Polynomial::<N, Q>::rand());
+        let (ct_0, ct_1) = (Rlwe::encrypt(&sk, m_0), Rlwe::encrypt(&sk, m_1));
+        for (bit, expected) in [(0, m_0), (1, m_1)] {
+            let sel = RgswCiphertext::encrypt(&ctx, &sk, &(x_pow(0) * Element::new(bit)));
+            assert!(noise(&sel.cmux(&ct_0, &ct_1), &sk, expected) < 1 << 20);
+        }
+
+        assert_eq!(ct_0.mul_monomial(3).phase(&sk), ct_0.phase(&sk) * x_pow(3));
+        assert_eq!(ct_0.mul_monomial(2 * N).phase(&sk), ct_0.phase(&sk));
+    }
+
+    #[test]
+    fn test_extract_lwe() {
+        use crate::lwe::LweSecretKey;
+
+        const T: u64 = 256;
+        let sk = LweSecretKey::<N>::generate();
+        let m = Polynomial::<N, T>::rand();
+        let scaled = Polynomial::<N, Q>::new(core::array::from_fn(|i| {
+            Element::new((Q / T * m.inner[i].value()) as i64)
+        }));
+        let ct = Rlwe::encrypt(sk.poly(), scaled);
+        for k in 0..N {
+            assert_eq!(sk.decrypt(&ct.extract_lwe::<T>(k)), m.inner[k].value());
+        }
+    }
+
+    #[test]
+    fn test_mul() {
+        let (ctx, sk) = setup();
+        let m_1 = Polynomial::<N, 3>::ternary_error().lift_centered::<Q>();
+        let m_2 = Polynomial::<N, 3>::ternary_error().lift_centered::<Q>();
+        let product =
+            RgswCiphertext::encrypt(&ctx, &sk, &m_1).mul(&RgswCiphertext::encrypt(&ctx, &sk, &m_2));
+        // g_0 = 1, so the first b row has phase m_1 m_2 + e
+        let table = ctx.table();
+        let row = &product.b_rows[0];
+        let row = Rlwe::new(table.inverse(&row.c_1), table.inverse(&row.c_2));
+        assert!(noise(&row, &sk, m_1 * m_2) < 1 << 24);
+    }
+
+    #[test]
+    fn test_mul_bfv() {
+        use crate::bfv_pke::Bfv;
+
+        const T: u64 = 17;
+        let (bfv, sk) = Bfv::<N, Q, T>::keygen().unwrap();
+        let ctx = Arc::new(RgswContext::new(8).unwrap());
+        let m = Polynomial::<N, T>::rand();
+        let rgsw = RgswCiphertext::encrypt(&ctx, sk.poly(), &x_pow(5));
+        assert_eq!(rgsw.mul_bfv(&bfv.encrypt(m)).decrypt(&sk), m * x_pow(5));
+    }
+}
@@ -0,0 +1,451 @@
+//! Residue number system: a big modulus Q = q_0 * ... * q_{k-1} of word sized primes, with ring elements of
+//! Z_Q[x]/(x^n + 1) stored as one residue polynomial per prime. Everything but base conversion works
+//! residue by residue, so Q can be as large as the number of primes allows without any bignum arithmetic.
+//!
+//! [`BaseConverter`] moves a value from one basis to another (HPS, https://eprint.iacr.org/2018/117.pdf,
+//! Section 2.2): with y_i = [x_i (Q/q_i)^-1]_{q_i},
+//!
+//! x = sum_i y_i Q/q_i - v Q, v = round(sum_i y_i / q_i)
+//!
+//! which is the centered representative of x mod Q, evaluated mod every prime of the target basis. v is
+//! computed in floating point, exact unless x is within about 2^-50 Q of ±Q/2.
+//!
+//! Products go through a per prime NTT for the primes of a basis made [`RnsBasis::with_ntt`] that are
+//! NTT friendly for the degree (q_i ≡ 1 mod 2n), and are schoolbook for the others.
+
+use std::fmt;
+
+use rand::Rng;
+use zeroize::Zeroize;
+
+use crate::field::{LazyAcc, Modulus, PrimeField, PrimeModulus, Ring};
+use crate::ntt::PrimeNttTable;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RnsError {
+    NotPrime {
+        modulus: u64,
+    },
+    /// The same prime twice, the moduli have to be coprime.
+    Duplicate {
+        modulus: u64,
+    },
+    Empty,
+}
+
+impl fmt::Display for RnsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RnsError::NotPrime { modulus } => write!(f, "rns modulus {modulus} is not prime"),
+            RnsError::Duplicate { modulus } => write!(f, "rns modulus {modulus} appears twice"),
+            RnsError::Empty => write!(f, "rns basis needs at least one modulus"),
+        }
+    }
+}
+
+impl std::error::Error for RnsError {}
+
+/// The primes of one basis and the CRT constants of Q = prod q_i.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RnsBasis {
+    moduli: Vec<PrimeModulus>,
+    /// [(Q/q_i)^-1]_{q_i}
+    hat_inv: Vec<u64>,
+    /// NTT table per prime, empty unless `with_ntt` was called.
+    ntt: Vec<Option<PrimeNttTable>>,
+}
+
+impl RnsBasis {
+    pub fn new(moduli: &[u64]) -> Result<Self, RnsError> {
+        if moduli.is_empty() {
+            return Err(RnsError::Empty);
+        }
+        let mut primes = Vec::with_capacity(moduli.len());
+        for (i, &q) in moduli.iter().enumerate() {
+            if moduli[..i].contains(&q) {
+                return Err(RnsError::Duplicate { modulus: q });
+            }
+            primes.push(PrimeModulus::new(q).ok_or(RnsError::NotPrime { modulus: q })?);
+        }
+        let hat_inv = primes
+            .iter()
+            .enumerate()
+            .map(|(i, q_i)| {
+                let hat = moduli
+                    .iter()
+                    .enumerate()
+                    .filter(|(j, _)| *j != i)
+                    .fold(1, |acc, (_, &q_j)| q_i.mul(acc, q_j % q_i.modulus()));
+                q_i.inv(hat).expect("distinct primes are coprime")
+            })
+            .collect();
+        Ok(Self {
+            moduli: primes,
+            hat_inv,
+            ntt: Vec::new(),
+        })
+    }
+
+    /// Products of degree n polynomials through the NTT, for every prime that allows it.
+    pub fn with_ntt(mut self, n: usize) -> Self {
+        self.ntt = self
+            .moduli
+            .iter()
+            .map(|&q| PrimeNttTable::new(n, q))
+            .collect();
+        self
+    }
+
+    /// Number of primes with an NTT table.
+    pub fn ntt_primes(&self) -> usize {
+        self.ntt.iter().flatten().count()
+    }
+
+    /// This basis followed by `other`, e.g. Q then P for the extended basis QP.
+    pub fn concat(&self, other: &Self) -> Result<Self, RnsError> {
+        Self::new(&[self.moduli(), other.moduli()].concat())
+    }
+
+    pub fn len(&self) -> usize {
+        self.moduli.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.moduli.is_empty()
+    }
+
+    pub fn moduli(&self) -> Vec<u64> {
+        self.moduli.iter().map(|q| q.modulus()).collect()
+    }
+
+    pub fn modulus(&self, i: usize) -> PrimeModulus {
+        self.moduli[i]
+    }
+
+    /// [(Q/q_i)^-1]_{q_i} for every i.
+    pub fn hat_inv(&self) -> &[u64] {
+        &self.hat_inv
+    }
+
+    /// log2 Q
+    pub fn bits(&self) -> f64 {
+        self.moduli
+            .iter()
+            .map(|q| (q.modulus() as f64).log2())
+            .sum()
+    }
+
+    /// Q mod m
+    pub fn product_mod(&self, m: u64) -> u64 {
+        let ring = Modulus::new(m);
+        self.moduli
+            .iter()
+            .fold(1 % m, |acc, q| ring.mul(acc, q.modulus() % m))
+    }
+
+    /// y_i = [x_i (Q/q_i)^-1]_{q_i}, the CRT digits of coefficient `c`: x = sum_i y_i Q/q_i mod Q.
+    pub fn crt_digits<const N: usize>(&self, x: &RnsPolynomial<N>, c: usize) -> Vec<u64> {
+        self.moduli
+            .iter()
+            .zip(&self.hat_inv)
+            .zip(&x.residues)
+            .map(|((q, &inv), r)| q.mul(r[c], inv))
+            .collect()
+    }
+
+    pub fn zero<const N: usize>(&self) -> RnsPolynomial<N> {
+        RnsPolynomial {
+            residues: vec![[0; N]; self.len()],
+        }
+    }
+
+    /// Uniform mod Q, uniform residues are uniform mod Q by the CRT.
+    pub fn rand<const N: usize>(&self) -> RnsPolynomial<N> {
+        let mut rng = rand::rng();
+        RnsPolynomial {
+            residues: self
+                .moduli
+                .iter()
+                .map(|q| core::array::from_fn(|_| rng.random_range(0..q.modulus())))
+                .collect(),
+        }
+    }
+
+    /// Small signed coefficients (secrets, errors, messages) in every residue.
+    pub fn from_signed<const N: usize>(&self, x: &[i64; N]) -> RnsPolynomial<N> {
+        RnsPolynomial {
+            residues: self
+                .moduli
+                .iter()
+                .map(|q| x.map(|c| q.reduce_i64(c)))
+                .collect(),
+        }
+    }
+
+    pub fn add<const N: usize>(
+        &self,
+        a: &RnsPolynomial<N>,
+        b: &RnsPolynomial<N>,
+    ) -> RnsPolynomial<N> {
+        self.zip(a, b, |q, x, y| q.add(x, y))
+    }
+
+    pub fn sub<const N: usize>(
+        &self,
+        a: &RnsPolynomial<N>,
+        b: &RnsPolynomial<N>,
+    ) -> RnsPolynomial<N> {
+        self.zip(a, b, |q, x, y| q.sub(x, y))
+    }
+
+    pub fn neg<const N: usize>(&self, a: &RnsPolynomial<N>) -> RnsPolynomial<N> {
+        self.zip(a, a, |q, x, _| q.neg(x))
+    }
+
+    /// Multiplies residue i by `scalars[i]`.
+    pub fn mul_scalars<const N: usize>(
+        &self,
+        a: &RnsPolynomial<N>,
+        scalars: &[u64],
+    ) -> RnsPolynomial<N> {
+        RnsPolynomial {
+            residues: self
+                .moduli
+                .iter()
+                .zip(&a.residues)
+                .zip(scalars)
+                .map(|((q, r), &s)| r.map(|c| q.mul(c, s)))
+                .collect(),
+        }
+    }
+
+    /// Negacyclic product, residue by residue.
+    pub fn mul<const N: usize>(
+        &self,
+        a: &RnsPolynomial<N>,
+        b: &RnsPolynomial<N>,
+    ) -> RnsPolynomial<N> {
+        RnsPolynomial {
+            residues: self
+                .moduli
+                .iter()
+                .enumerate()
+                .zip(a.residues.iter().zip(&b.residues))
+                .map(|((i, &q), (x, y))| match self.ntt.get(i) {
+                    Some(Some(table)) if table.degree() == N => table.mul(x, y),
+                    _ => mul_schoolbook(q, x, y),
+                })
+                .collect(),
+        }
+    }
+
+    fn zip<const N: usize>(
+        &self,
+        a: &RnsPolynomial<N>,
+        b: &RnsPolynomial<N>,
+        op: impl Fn(&PrimeModulus, u64, u64) -> u64,
+    ) -> RnsPolynomial<N> {
+        RnsPolynomial {
+            residues: self
+                .moduli
+                .iter()
+                .zip(a.residues.iter().zip(&b.residues))
+                .map(|(q, (x, y))| core::array::from_fn(|c| op(q, x[c], y[c])))
+                .collect(),
+        }
+    }
+}
+
+/// Negacyclic product mod q in n^2 multiplications.
+fn mul_schoolbook<const N: usize>(q: PrimeModulus, x: &[u64; N], y: &[u64; N]) -> [u64; N] {
+    core::array::from_fn(|k| {
+        // x^n = -1: terms with i + j = k minus those with i + j = k + n
+        let mut pos = LazyAcc::new(q);
+        let mut neg = LazyAcc::new(q);
+        for i in 0..N {
+            if i <= k {
+                pos.add_mul(x[i], y[k - i]);
+            } else {
+                neg.add_mul(x[i], y[k + N - i]);
+            }
+        }
+        q.sub(pos.finish(), neg.finish())
+    })
+}
+
+/// An element of Z_Q[x]/(x^n + 1), residue i is the polynomial mod q_i of the basis it belongs to. The
+/// basis is not stored, operations go through [`RnsBasis`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RnsPolynomial<const N: usize> {
+    pub residues: Vec<[u64; N]>,
+}
+
+impl<const N: usize> Zeroize for RnsPolynomial<N> {
+    fn zeroize(&mut self) {
+        self.residues.iter_mut().for_each(|r| r.zeroize());
+    }
+}
+
+impl<const N: usize> RnsPolynomial<N> {
+    /// Residues of this basis followed by those of `other`, for a value known in both.
+    pub fn concat(&self, other: &Self) -> Self {
+        Self {
+            residues: [self.residues.clone(), other.residues.clone()].concat(),
+        }
+    }
+
+    /// The residues `range` as a polynomial of the matching sub basis.
+    pub fn split(&self, range: std::ops::Range<usize>) -> Self {
+        Self {
+            residues: self.residues[range].to_vec(),
+        }
+    }
+}
+
+/// Exact base conversion from basis Q to basis P, see the module docs.
+#[derive(Debug, Clone)]
+pub struct BaseConverter {
+    from: RnsBasis,
+    to: RnsBasis,
+    /// [Q/q_i]_{p_j}, indexed [j][i]
+    hat_mod: Vec<Vec<u64>>,
+    /// [Q]_{p_j}
+    q_mod: Vec<u64>,
+}
+
+impl BaseConverter {
+    pub fn new(from: &RnsBasis, to: &RnsBasis) -> Self {
+        let q = from.moduli();
+        let hat_mod = to
+            .moduli
+            .iter()
+            .map(|p| {
+                (0..q.len())
+                    .map(|i| {
+                        q.iter()
+                            .enumerate()
+                            .filter(|(l, _)| *l != i)
+                            .fold(1, |acc, (_, &q_l)| p.mul(acc, q_l % p.modulus()))
+                    })
+                    .collect()
+            })
+            .collect();
+        let q_mod = to
+            .moduli
+            .iter()
+            .map(|p| from.product_mod(p.modulus()))
+            .collect();
+        Self {
+            from: from.clone(),
+            to: to.clone(),
+            hat_mod,
+            q_mod,
+        }
+    }
+
+    /// The centered representative of `x` (mod Q) reduced mod every prime of the target basis.
+    pub fn convert<const N: usize>(&self, x: &RnsPolynomial<N>) -> RnsPolynomial<N> {
+        let mut out = self.to.zero::<N>();
+        for c in 0..N {
+            let y = self.from.crt_digits(x, c);
+            let v = y
+                .iter()
+                .zip(self.from.moduli.iter())
+                .map(|(&y_i, q_i)| y_i as f64 / q_i.modulus() as f64)
+                .sum::<f64>()
+                .round() as u64;
+            for (j, p) in self.to.moduli.iter().enumerate() {
+                let mut acc = LazyAcc::new(*p);
+                for (&y_i, &hat) in y.iter().zip(&self.hat_mod[j]) {
+                    acc.add_mul(y_i % p.modulus(), hat);
+                }
+                out.residues[j][c] = p.sub(acc.finish(), p.mul(v % p.modulus(), self.q_mod[j]));
+            }
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const Q: [u64; 2] = [1_073_741_789, 1_073_741_783];
+    const P: [u64; 3] = [1_073_741_741, 1_073_741_723, 1_073_741_719];
+
+    /// Centered value of coefficient `c` through i128, only for a basis of two 30 bit primes.
+    fn reconstruct<const N: usize>(basis: &RnsBasis, x: &RnsPolynomial<N>, c: usize) -> i128 {
+        let (q_0, q_1) = (basis.moduli()[0] as i128, basis.moduli()[1] as i128);
+        let y = basis.crt_digits(x, c);
+        let big = (y[0] as i128 * q_1 + y[1] as i128 * q_0).rem_euclid(q_0 * q_1);
+        if big > q_0 * q_1 / 2 {
+            big - q_0 * q_1
+        } else {
+            big
+        }
+    }
+
+    #[test]
+    fn test_basis_errors() {
+        assert_eq!(RnsBasis::new(&[]), Err(RnsError::Empty));
+        assert_eq!(
+            RnsBasis::new(&[Q[0], 15]),
+            Err(RnsError::NotPrime { modulus: 15 })
+        );
+        assert_eq!(
+            RnsBasis::new(&[Q[0], Q[1], Q[0]]),
+            Err(RnsError::Duplicate { modulus: Q[0] })
+        );
+    }
+
+    #[test]
+    fn test_signed_and_arithmetic() {
+        let basis = RnsBasis::new(&Q).unwrap();
+        let a = basis.from_signed::<4>(&[-3, 0, 7, -1]);
+        let b = basis.from_signed::<4>(&[2, 1, -5, 4]);
+        let values = |x: &RnsPolynomial<4>| {
+            (0..4)
+                .map(|c| reconstruct(&basis, x, c))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(values(&basis.add(&a, &b)), [-1, 1, 2, 3]);
+        assert_eq!(values(&basis.sub(&a, &b)), [-5, -1, 12, -5]);
+        assert_eq!(values(&basis.neg(&a)), [3, 0, -7, 1]);
+        // (-3 + 7x^2 - x^3) * x = -3x + 7x^3 - x^4 = 1 - 3x + 7x^3
+        let x = basis.from_signed::<4>(&[0, 1, 0, 0]);
+        assert_eq!(values(&basis.mul(&a, &x)), [1, -3, 0, 7]);
+    }
+
+    #[test]
+    fn test_ntt_mul() {
+        // 1 mod 32 primes and one that isn't, which stays schoolbook
+        let moduli = [1_073_741_441, 1_073_740_609, Q[0]];
+        let schoolbook = RnsBasis::new(&moduli).unwrap();
+        let ntt = schoolbook.clone().with_ntt(16);
+        assert_eq!(ntt.ntt_primes(), 2);
+        let (a, b) = (ntt.rand::<16>(), ntt.rand::<16>());
+        assert_eq!(ntt.mul(&a, &b), schoolbook.mul(&a, &b));
+        // other degrees fall back to schoolbook
+        let (a, b) = (ntt.rand::<8>(), ntt.rand::<8>());
+        assert_eq!(ntt.mul(&a, &b), schoolbook.mul(&a, &b));
+    }
+
+    #[test]
+    fn test_base_conversion_is_exact() {
+        let q = RnsBasis::new(&Q).unwrap();
+        let p = RnsBasis::new(&P).unwrap();
+        let to_p = BaseConverter::new(&q, &p);
+        let x = q.rand::<32>();
+        let converted = to_p.convert(&x);
+        for c in 0..32 {
+            let v = reconstruct(&q, &x, c);
+            for (j, &p_j) in P.iter().enumerate() {
+                assert_eq!(converted.residues[j][c], v.rem_euclid(p_j as i128) as u64);
+            }
+        }
+        // and back
+        let small = p.from_signed::<2>(&[-123_456_789_012, 987_654_321_098]);
+        let back = BaseConverter::new(&p, &q).convert(&small);
+        assert_eq!(back, q.from_signed(&[-123_456_789_012, 987_654_321_098]));
+    }
+}
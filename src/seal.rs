@@ -0,0 +1,725 @@
+//! Microsoft SEAL (4.x) binary format for BFV encryption parameters, public keys and ciphertexts, so
+//! objects can move between this crate and SEAL based services.
+//!
+//! Every SEAL object is a 16 byte header (magic 0xA15E, header size, version, compression mode, reserved,
+//! total size) followed by its members, little endian. Only uncompressed objects are read or written, save
+//! them on the SEAL side with `compr_mode_type::none`.
+//!
+//! Ciphertexts and keys carry the `parms_id` of their SEAL context, a hash SEAL checks on load.
+//! [`SealParams::parms_id`] computes it the way SEAL does, BLAKE2b-256 over the scheme, the degree, the
+//! coefficient moduli and the plain modulus. With the single coefficient modulus used here the key level
+//! and the first data level share it, so it is what `context.first_parms_id()` returns.
+//!
+//! The layouts follow the SEAL 4.x sources, the tests only check them against bytes written here.
+//!
+//! SEAL decrypts c0 + c1 s, the convention of `BfvCiphertext` with (c0, c1) = (c_1, c_2). Public keys are
+//! stored in SEAL's NTT form, the evaluations at psi^(2 bitrev(i) + 1) with psi the smallest primitive
+//! 2n-th root of unity mod q.
+
+use std::fmt;
+
+use byteorder::{ByteOrder, LittleEndian};
+
+use crate::backend::RingBackend;
+use crate::bfv_pke::{Bfv, BfvCiphertext, BfvPublicKey};
+use crate::ntt::{NttTable, bit_reverse, pow_mod, primitive_root_2n};
+use crate::polynomial::{Element, Polynomial};
+
+pub const SEAL_MAGIC: u16 = 0xA15E;
+/// Version written into headers, any 4.x is accepted on read.
+pub const SEAL_VERSION: (u8, u8) = (4, 1);
+const HEADER_BYTES: usize = 16;
+/// `scheme_type::bfv`
+const SCHEME_BFV: u8 = 1;
+
+/// SEAL's `parms_id_type`.
+pub type ParmsId = [u64; 4];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SealError {
+    Truncated,
+    Magic {
+        got: u16,
+    },
+    HeaderSize {
+        got: u8,
+    },
+    Version {
+        major: u8,
+        minor: u8,
+    },
+    /// Saved with zlib or zstd compression.
+    Compressed {
+        mode: u8,
+    },
+    /// The header claims more bytes than there are.
+    Size {
+        header: u64,
+        got: usize,
+    },
+    /// Bytes left over after the object.
+    Trailing {
+        bytes: usize,
+    },
+    Scheme {
+        scheme: u8,
+    },
+    /// A member differs from the const generics the object is read into.
+    Mismatch {
+        field: &'static str,
+        expected: u64,
+        got: u64,
+    },
+    NttForm {
+        expected: bool,
+    },
+    /// Seed compressed symmetric ciphertext, SEAL only writes these for `encrypt_symmetric`.
+    Seeded,
+    OutOfRange {
+        index: usize,
+        value: u64,
+    },
+    /// Public keys need the NTT mod q.
+    NotNttFriendly {
+        n: usize,
+        q: u64,
+    },
+}
+
+impl fmt::Display for SealError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SealError::Truncated => write!(f, "unexpected end of data"),
+            SealError::Magic { got } => write!(f, "magic {got:#06x} is not a SEAL header"),
+            SealError::HeaderSize { got } => {
+                write!(f, "header size {got}, expected {HEADER_BYTES}")
+            }
+            SealError::Version { major, minor } => {
+                write!(f, "SEAL version {major}.{minor} not supported")
+            }
+            SealError::Compressed { mode } => {
+                write!(
+                    f,
+                    "compression mode {mode} not supported, save uncompressed"
+                )
+            }
+            SealError::Size { header, got } => {
+                write!(f, "header size {header} bytes, only {got} available")
+            }
+            SealError::Trailing { bytes } => write!(f, "{bytes} trailing bytes"),
+            SealError::Scheme { scheme } => write!(f, "scheme {scheme} is not BFV"),
+            SealError::Mismatch {
+                field,
+                expected,
+                got,
+            } => write!(f, "{field} is {got}, expected {expected}"),
+            SealError::NttForm { expected } => {
+                write!(f, "expected NTT form {expected}, got {}", !expected)
+            }
+            SealError::Seeded => write!(f, "seed compressed ciphertexts are not supported"),
+            SealError::OutOfRange { index, value } => {
+                write!(f, "coefficient {index} = {value} is not reduced")
+            }
+            SealError::NotNttFriendly { n, q } => {
+                write!(f, "q = {q} has no negacyclic NTT of size {n}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for SealError {}
+
+/// `EncryptionParameters` of a BFV context.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SealParams {
+    pub poly_modulus_degree: u64,
+    pub coeff_modulus: Vec<u64>,
+    pub plain_modulus: u64,
+}
+
+impl SealParams {
+    /// The parameters of `Bfv<N, Q, T>`, q as the only coefficient modulus.
+    pub fn bfv<const N: usize, const Q: u64, const T: u64>() -> Self {
+        Self {
+            poly_modulus_degree: N as u64,
+            coeff_modulus: vec![Q],
+            plain_modulus: T,
+        }
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut payload = vec![SCHEME_BFV];
+        payload.extend(self.poly_modulus_degree.to_le_bytes());
+        payload.extend((self.coeff_modulus.len() as u64).to_le_bytes());
+        for q in &self.coeff_modulus {
+            payload.extend(write_object(&q.to_le_bytes()));
+        }
+        payload.extend(write_object(&self.plain_modulus.to_le_bytes()));
+        write_object(&payload)
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, SealError> {
+        let mut payload = read_top(bytes)?;
+        let scheme = payload.u8()?;
+        if scheme != SCHEME_BFV {
+            return Err(SealError::Scheme { scheme });
+        }
+        let poly_modulus_degree = payload.u64()?;
+        let count = payload.u64()?;
+        let coeff_modulus = (0..count)
+            .map(|_| read_modulus(&mut payload))
+            .collect::<Result<Vec<_>, _>>()?;
+        let plain_modulus = read_modulus(&mut payload)?;
+        payload.finish()?;
+        Ok(Self {
+            poly_modulus_degree,
+            coeff_modulus,
+            plain_modulus,
+        })
+    }
+
+    /// SEAL's `parms_id` of these parameters: BLAKE2b-256 of the u64 words (scheme, degree, every
+    /// coefficient modulus, plain modulus), little endian, read back as 4 words.
+    pub fn parms_id(&self) -> ParmsId {
+        let mut words = vec![SCHEME_BFV as u64, self.poly_modulus_degree];
+        words.extend(&self.coeff_modulus);
+        words.push(self.plain_modulus);
+        let input = words
+            .iter()
+            .flat_map(|w| w.to_le_bytes())
+            .collect::<Vec<_>>();
+        let hash = blake2b(32, &input);
+        core::array::from_fn(|i| LittleEndian::read_u64(&hash[8 * i..]))
+    }
+
+    /// Errors unless these are the parameters of `Bfv<N, Q, T>`.
+    pub fn check<const N: usize, const Q: u64, const T: u64>(&self) -> Result<(), SealError> {
+        expect("poly_modulus_degree", N as u64, self.poly_modulus_degree)?;
+        expect("coeff_modulus_size", 1, self.coeff_modulus.len() as u64)?;
+        expect("coeff_modulus", Q, self.coeff_modulus[0])?;
+        expect("plain_modulus", T, self.plain_modulus)
+    }
+}
+
+/// `ct` as a SEAL `Ciphertext`.
+pub fn write_ciphertext<const N: usize, const Q: u64, const T: u64, B: RingBackend>(
+    ct: &BfvCiphertext<N, Q, T, B>,
+    parms_id: &ParmsId,
+) -> Vec<u8> {
+    write_ciphertext_object(parms_id, false, [ct.c_1(), ct.c_2()])
+}
+
+/// A SEAL `Ciphertext` as a ciphertext under the key of `bfv`, with its `parms_id`.
+pub fn read_ciphertext<const N: usize, const Q: u64, const T: u64, B: RingBackend>(
+    bfv: &Bfv<N, Q, T, B>,
+    bytes: &[u8],
+) -> Result<(BfvCiphertext<N, Q, T, B>, ParmsId), SealError> {
+    let (parms_id, [c_1, c_2]) = read_ciphertext_object::<N, Q>(bytes, false)?;
+    Ok((
+        BfvCiphertext::new(c_1, c_2, bfv.context().clone()),
+        parms_id,
+    ))
+}
+
+/// `pk` as a SEAL `PublicKey`, which is a ciphertext of zero in NTT form.
+pub fn write_public_key<const N: usize, const Q: u64>(
+    pk: &BfvPublicKey<N, Q>,
+    parms_id: &ParmsId,
+) -> Result<Vec<u8>, SealError> {
+    let ntt = SealNtt::<N, Q>::new()?;
+    let (p_0, p_1) = (ntt.forward(pk.p_0()), ntt.forward(pk.p_1()));
+    Ok(write_ciphertext_object(parms_id, true, [&p_0, &p_1]))
+}
+
+/// A SEAL `PublicKey`, for `Bfv::from_public_key`, with its `parms_id`.
+pub fn read_public_key<const N: usize, const Q: u64>(
+    bytes: &[u8],
+) -> Result<(BfvPublicKey<N, Q>, ParmsId), SealError> {
+    let ntt = SealNtt::<N, Q>::new()?;
+    let (parms_id, [p_0, p_1]) = read_ciphertext_object::<N, Q>(bytes, true)?;
+    Ok((
+        BfvPublicKey::new(ntt.inverse(&p_0), ntt.inverse(&p_1)),
+        parms_id,
+    ))
+}
+
+fn expect(field: &'static str, expected: u64, got: u64) -> Result<(), SealError> {
+    if expected == got {
+        Ok(())
+    } else {
+        Err(SealError::Mismatch {
+            field,
+            expected,
+            got,
+        })
+    }
+}
+
+/// Header plus payload, uncompressed.
+fn write_object(payload: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(HEADER_BYTES + payload.len());
+    out.extend(SEAL_MAGIC.to_le_bytes());
+    out.extend([HEADER_BYTES as u8, SEAL_VERSION.0, SEAL_VERSION.1, 0]);
+    out.extend([0; 2]);
+    out.extend(((HEADER_BYTES + payload.len()) as u64).to_le_bytes());
+    out.extend(payload);
+    out
+}
+
+/// Payload of the object at the front of `reader`, which moves past it.
+fn read_object<'a>(reader: &mut Reader<'a>) -> Result<Reader<'a>, SealError> {
+    let available = reader.bytes.len();
+    let header = reader.take(HEADER_BYTES)?;
+    let magic = LittleEndian::read_u16(&header[0..2]);
+    if magic != SEAL_MAGIC {
+        return Err(SealError::Magic { got: magic });
+    }
+    if header[2] as usize != HEADER_BYTES {
+        return Err(SealError::HeaderSize { got: header[2] });
+    }
+    if header[3] != SEAL_VERSION.0 {
+        return Err(SealError::Version {
+            major: header[3],
+            minor: header[4],
+        });
+    }
+    if header[5] != 0 {
+        return Err(SealError::Compressed { mode: header[5] });
+    }
+    let size = LittleEndian::read_u64(&header[8..16]);
+    if size < HEADER_BYTES as u64 || size > available as u64 {
+        return Err(SealError::Size {
+            header: size,
+            got: available,
+        });
+    }
+    Ok(Reader {
+        bytes: reader.take(size as usize - HEADER_BYTES)?,
+    })
+}
+
+/// Payload of the object filling all of `bytes`.
+fn read_top(bytes: &[u8]) -> Result<Reader<'_>, SealError> {
+    let mut reader = Reader { bytes };
+    let payload = read_object(&mut reader)?;
+    reader.finish()?;
+    Ok(payload)
+}
+
+/// A `Modulus`, its value as the only member.
+fn read_modulus(reader: &mut Reader) -> Result<u64, SealError> {
+    let mut payload = read_object(reader)?;
+    let value = payload.u64()?;
+    payload.finish()?;
+    Ok(value)
+}
+
+/// A size 2 `Ciphertext` over a single modulus: `parms_id`, NTT flag, size, degree, modulus count, scale,
+/// correction factor, then the coefficients as a `DynArray`, polynomial by polynomial.
+fn write_ciphertext_object<const N: usize, const Q: u64>(
+    parms_id: &ParmsId,
+    ntt_form: bool,
+    polys: [&Polynomial<N, Q>; 2],
+) -> Vec<u8> {
+    let mut payload = Vec::new();
+    for w in parms_id {
+        payload.extend(w.to_le_bytes());
+    }
+    payload.push(ntt_form as u8);
+    for member in [2, N as u64, 1] {
+        payload.extend(member.to_le_bytes());
+    }
+    // scale is CKKS only, the correction factor BGV only
+    payload.extend(1f64.to_le_bytes());
+    payload.extend(1u64.to_le_bytes());
+
+    let mut data = ((2 * N) as u64).to_le_bytes().to_vec();
+    for c in polys.iter().flat_map(|p| p.inner.iter()) {
+        data.extend(c.value().to_le_bytes());
+    }
+    payload.extend(write_object(&data));
+    write_object(&payload)
+}
+
+fn read_ciphertext_object<const N: usize, const Q: u64>(
+    bytes: &[u8],
+    ntt_form: bool,
+) -> Result<(ParmsId, [Polynomial<N, Q>; 2]), SealError> {
+    let mut payload = read_top(bytes)?;
+    let parms_id = [
+        payload.u64()?,
+        payload.u64()?,
+        payload.u64()?,
+        payload.u64()?,
+    ];
+    if (payload.u8()? != 0) != ntt_form {
+        return Err(SealError::NttForm { expected: ntt_form });
+    }
+    expect("size", 2, payload.u64()?)?;
+    expect("poly_modulus_degree", N as u64, payload.u64()?)?;
+    expect("coeff_modulus_size", 1, payload.u64()?)?;
+    payload.take(8)?;
+    expect("correction_factor", 1, payload.u64()?)?;
+    let mut data = read_object(&mut payload)?;
+    payload.finish()?;
+
+    // a seeded ciphertext keeps c0 and a marker word, the seed follows the array
+    let len = data.u64()?;
+    if len == N as u64 + 1 {
+        return Err(SealError::Seeded);
+    }
+    expect("data size", 2 * N as u64, len)?;
+    let mut polys = [Polynomial::new([Element::new(0); N]); 2];
+    for (index, c) in polys
+        .iter_mut()
+        .flat_map(|p| p.inner.iter_mut())
+        .enumerate()
+    {
+        let value = data.u64()?;
+        if value >= Q {
+            return Err(SealError::OutOfRange { index, value });
+        }
+        *c = Element::new(value as i64);
+    }
+    data.finish()?;
+    Ok((parms_id, polys))
+}
+
+const BLAKE2B_IV: [u64; 8] = [
+    0x6a09e667f3bcc908,
+    0xbb67ae8584caa73b,
+    0x3c6ef372fe94f82b,
+    0xa54ff53a5f1d36f1,
+    0x510e527fade682d1,
+    0x9b05688c2b3e6c1f,
+    0x1f83d9abfb41bd6b,
+    0x5be0cd19137e2179,
+];
+
+const BLAKE2B_SIGMA: [[usize; 16]; 10] = [
+    [0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15],
+    [14, 10, 4, 8, 9, 15, 13, 6, 1, 12, 0, 2, 11, 7, 5, 3],
+    [11, 8, 12, 0, 5, 2, 15, 13, 10, 14, 3, 6, 7, 1, 9, 4],
+    [7, 9, 3, 1, 13, 12, 11, 14, 2, 6, 5, 10, 4, 0, 15, 8],
+    [9, 0, 5, 7, 2, 4, 10, 15, 14, 1, 11, 12, 6, 8, 3, 13],
+    [2, 12, 6, 10, 0, 11, 8, 3, 4, 13, 7, 5, 15, 14, 1, 9],
+    [12, 5, 1, 15, 14, 13, 4, 10, 0, 7, 6, 3, 9, 2, 8, 11],
+    [13, 11, 7, 14, 12, 1, 3, 9, 5, 0, 15, 4, 8, 6, 2, 10],
+    [6, 15, 14, 9, 11, 3, 0, 8, 12, 2, 13, 7, 1, 4, 10, 5],
+    [10, 2, 8, 4, 7, 6, 1, 5, 15, 11, 9, 14, 3, 12, 13, 0],
+];
+
+/// Unkeyed BLAKE2b (RFC 7693) with an `out_len` byte digest, only used for `parms_id`.
+fn blake2b(out_len: usize, input: &[u8]) -> Vec<u8> {
+    let mut h = BLAKE2B_IV;
+    h[0] ^= 0x0101_0000 ^ out_len as u64;
+
+    let compress = |h: &mut [u64; 8], block: &[u8; 128], counter: u128, last: bool| {
+        let m: [u64; 16] = core::array::from_fn(|i| LittleEndian::read_u64(&block[8 * i..]));
+        let mut v = [0u64; 16];
+        v[..8].copy_from_slice(h);
+        v[8..].copy_from_slice(&BLAKE2B_IV);
+        v[12] ^= counter as u64;
+        v[13] ^= (counter >> 64) as u64;
+        if last {
+            v[14] = !v[14];
+        }
+        let g = |v: &mut [u64; 16], a: usize, b: usize, c: usize, d: usize, x: u64, y: u64| {
+            v[a] = v[a].wrapping_add(v[b]).wrapping_add(x);
+            v[d] = (v[d] ^ v[a]).rotate_right(32);
+            v[c] = v[c].wrapping_add(v[d]);
+            v[b] = (v[b] ^ v[c]).rotate_right(24);
+            v[a] = v[a].wrapping_add(v[b]).wrapping_add(y);
+            v[d] = (v[d] ^ v[a]).rotate_right(16);
+            v[c] = v[c].wrapping_add(v[d]);
+            v[b] = (v[b] ^ v[c]).rotate_right(63);
+        };
+        for round in 0..12 {
+            let s = &BLAKE2B_SIGMA[round % 10];
+            g(&mut v, 0, 4, 8, 12, m[s[0]], m[s[1]]);
+            g(&mut v, 1, 5, 9, 13, m[s[2]], m[s[3]]);
+            g(&mut v, 2, 6, 10, 14, m[s[4]], m[s[5]]);
+            g(&mut v, 3, 7, 11, 15, m[s[6]], m[s[7]]);
+            g(&mut v, 0, 5, 10, 15, m[s[8]], m[s[9]]);
+            g(&mut v, 1, 6, 11, 12, m[s[10]], m[s[11]]);
+            g(&mut v, 2, 7, 8, 13, m[s[12]], m[s[13]]);
+            g(&mut v, 3, 4, 9, 14, m[s[14]], m[s[15]]);
+        }
+        for i in 0..8 {
+            h[i] ^= v[i] ^ v[i + 8];
+        }
+    };
+
+    // every full block but the last goes through as is, the last one (possibly empty) is zero padded
+    let blocks = input.len().div_ceil(128).max(1);
+    for (b, chunk) in input
+        .chunks(128)
+        .chain(input.is_empty().then_some(&[][..]))
+        .enumerate()
+    {
+        let mut block = [0u8; 128];
+        block[..chunk.len()].copy_from_slice(chunk);
+        let counter = (128 * b + chunk.len()) as u128;
+        compress(&mut h, &block, counter, b + 1 == blocks);
+    }
+    h.iter()
+        .flat_map(|w| w.to_le_bytes())
+        .take(out_len)
+        .collect()
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, n: usize) -> Result<&'a [u8], SealError> {
+        if self.bytes.len() < n {
+            return Err(SealError::Truncated);
+        }
+        let (head, tail) = self.bytes.split_at(n);
+        self.bytes = tail;
+        Ok(head)
+    }
+
+    fn u8(&mut self) -> Result<u8, SealError> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u64(&mut self) -> Result<u64, SealError> {
+        Ok(LittleEndian::read_u64(self.take(8)?))
+    }
+
+    fn finish(self) -> Result<(), SealError> {
+        match self.bytes.len() {
+            0 => Ok(()),
+            bytes => Err(SealError::Trailing { bytes }),
+        }
+    }
+}
+
+/// SEAL's NTT layout. `NttTable` evaluates at the same odd powers of a primitive 2n-th root in the same
+/// bit reversed order, only its root differs, so the two outputs are a permutation of each other.
+struct SealNtt<const N: usize, const Q: u64> {
+    table: NttTable<N, Q>,
+    /// SEAL entry i is entry `order[i]` of the `NttTable` output.
+    order: Vec<usize>,
+}
+
+impl<const N: usize, const Q: u64> SealNtt<N, Q> {
+    fn new() -> Result<Self, SealError> {
+        let table = NttTable::new().ok_or(SealError::NotNttFriendly { n: N, q: Q })?;
+        let psi = primitive_root_2n(N, Q).expect("checked by NttTable::new");
+        let two_n = 2 * N as u64;
+        // the primitive 2n-th roots are the odd powers of psi, SEAL takes the smallest
+        let k = (1..two_n)
+            .step_by(2)
+            .min_by_key(|&k| pow_mod(psi, k, Q))
+            .expect("n >= 1");
+        let bits = N.ilog2();
+        let order = (0..N)
+            .map(|i| {
+                let e = k * (2 * bit_reverse(i, bits) as u64 + 1) % two_n;
+                bit_reverse(((e - 1) / 2) as usize, bits)
+            })
+            .collect();
+        Ok(Self { table, order })
+    }
+
+    fn forward(&self, p: &Polynomial<N, Q>) -> Polynomial<N, Q> {
+        let evals = self.table.forward(p);
+        Polynomial::new(core::array::from_fn(|i| evals.inner[self.order[i]]))
+    }
+
+    fn inverse(&self, p: &Polynomial<N, Q>) -> Polynomial<N, Q> {
+        let mut evals = [Element::new(0); N];
+        for (&j, &c) in self.order.iter().zip(p.inner.iter()) {
+            evals[j] = c;
+        }
+        self.table.inverse(&Polynomial::new(evals))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const N: usize = 16;
+    // 12289 ≡ 1 mod 32
+    const Q: u64 = 12_289;
+    const T: u64 = 4;
+    const PARMS_ID: ParmsId = [1, 2, 3, u64::MAX];
+
+    fn header(size: u64) -> Vec<u8> {
+        [&[0x5e, 0xa1, 0x10, 4, 1, 0, 0, 0][..], &size.to_le_bytes()].concat()
+    }
+
+    #[test]
+    fn test_params_layout() {
+        let modulus = |v: u64| [header(24), v.to_le_bytes().to_vec()].concat();
+        let expected = [
+            header(16 + 1 + 8 + 8 + 24 + 24),
+            vec![1],
+            16u64.to_le_bytes().to_vec(),
+            1u64.to_le_bytes().to_vec(),
+            modulus(Q),
+            modulus(T),
+        ]
+        .concat();
+        let params = SealParams::bfv::<N, Q, T>();
+        assert_eq!(params.to_bytes(), expected);
+
+        let back = SealParams::from_bytes(&expected).unwrap();
+        assert_eq!(back, params);
+        assert_eq!(back.check::<N, Q, T>(), Ok(()));
+        assert_eq!(
+            back.check::<N, Q, 3>(),
+            Err(SealError::Mismatch {
+                field: "plain_modulus",
+                expected: 3,
+                got: T
+            })
+        );
+    }
+
+    #[test]
+    fn test_blake2b() {
+        let hex = |b: Vec<u8>| b.iter().map(|x| format!("{x:02x}")).collect::<String>();
+        // RFC 7693 appendix A and the reference implementation's empty input digests
+        assert_eq!(
+            hex(blake2b(64, b"abc")),
+            "ba80a53f981c4d0d6a2797b69f12f6e94c212f14685ac4b74b12bb6fdbffa2d1\
+             7d87c5392aab792dc252d5de4533cc9518d38aa8dbf1925ab92386edd4009923"
+        );
+        assert_eq!(
+            hex(blake2b(64, b"")),
+            "786a02f742015903c6c6fd852552d272912f4740e15847618a86e217f71f5419\
+             d25e1031afee585313896444934eb04b903a685b1448b755d56f701afe9be2ce"
+        );
+        assert_eq!(
+            hex(blake2b(32, b"")),
+            "0e5751c026e543b2e8ab2eb06099daa1d1e5df47778f7787faab45cdf12fe3a8"
+        );
+    }
+
+    #[test]
+    fn test_parms_id() {
+        let params = SealParams::bfv::<N, Q, T>();
+        let words = [1, N as u64, Q, T]
+            .iter()
+            .flat_map(|w| w.to_le_bytes())
+            .collect::<Vec<_>>();
+        let hash = blake2b(32, &words);
+        assert_eq!(
+            params.parms_id(),
+            core::array::from_fn(|i| LittleEndian::read_u64(&hash[8 * i..]))
+        );
+        assert_ne!(params.parms_id(), SealParams::bfv::<N, Q, 3>().parms_id());
+    }
+
+    #[test]
+    fn test_ciphertext_roundtrip() {
+        let (bfv, sk) = Bfv::<N, Q, T>::keygen().unwrap();
+        let m = Polynomial::<N, T>::rand();
+        let ct = bfv.encrypt(m);
+        let bytes = write_ciphertext(&ct, &PARMS_ID);
+        assert_eq!(bytes.len(), 16 + 32 + 1 + 5 * 8 + 16 + 8 + 2 * N * 8);
+
+        let (back, parms_id) = read_ciphertext(&bfv, &bytes).unwrap();
+        assert_eq!(parms_id, PARMS_ID);
+        assert_eq!((back.c_1(), back.c_2()), (ct.c_1(), ct.c_2()));
+        assert_eq!(back.decrypt(&sk), m);
+    }
+
+    #[test]
+    fn test_read_errors() {
+        let (bfv, _) = Bfv::<N, Q, T>::keygen().unwrap();
+        let bytes = write_ciphertext(&bfv.encrypt(Polynomial::rand()), &PARMS_ID);
+        let read = |b: &[u8]| read_ciphertext(&bfv, b).map(|_| ()).unwrap_err();
+        let patched = |at: usize, v: u8| {
+            let mut b = bytes.clone();
+            b[at] = v;
+            b
+        };
+
+        assert_eq!(read(&patched(0, 0)), SealError::Magic { got: 0xa100 });
+        assert_eq!(read(&patched(5, 2)), SealError::Compressed { mode: 2 });
+        assert_eq!(
+            read(&patched(3, 3)),
+            SealError::Version { major: 3, minor: 1 }
+        );
+        assert_eq!(
+            read(&patched(48, 1)),
+            SealError::NttForm { expected: false }
+        );
+        assert_eq!(
+            read(&bytes[..bytes.len() - 1]),
+            SealError::Size {
+                header: bytes.len() as u64,
+                got: bytes.len() - 1
+            }
+        );
+        assert_eq!(
+            read(&[bytes.clone(), vec![0]].concat()),
+            SealError::Trailing { bytes: 1 }
+        );
+        // last coefficient set to q
+        let mut b = bytes.clone();
+        let end = b.len();
+        b[end - 8..].copy_from_slice(&Q.to_le_bytes());
+        assert_eq!(
+            read(&b),
+            SealError::OutOfRange {
+                index: 2 * N - 1,
+                value: Q
+            }
+        );
+        assert_eq!(
+            read_ciphertext(&Bfv::<8, Q, T>::keygen().unwrap().0, &bytes).map(|_| ()),
+            Err(SealError::Mismatch {
+                field: "poly_modulus_degree",
+                expected: 8,
+                got: N as u64
+            })
+        );
+    }
+
+    #[test]
+    fn test_public_key_ntt_form() {
+        let (bfv, sk) = Bfv::<N, Q, T>::keygen().unwrap();
+        let bytes = write_public_key(bfv.public_key(), &PARMS_ID).unwrap();
+
+        // entry i of the stored p_0 is p_0(psi^(2 bitrev(i) + 1)), psi the smallest 2n-th root
+        let psi = (2..Q).find(|&x| pow_mod(x, N as u64, Q) == Q - 1).unwrap();
+        let p_0 = bfv.public_key().p_0();
+        let data = &bytes[bytes.len() - 2 * N * 8..];
+        for i in 0..N {
+            let x = pow_mod(psi, 2 * bit_reverse(i, N.ilog2()) as u64 + 1, Q);
+            let eval = (0..N)
+                .rev()
+                .fold(0, |acc, j| (acc * x + p_0.inner[j].value()) % Q);
+            assert_eq!(LittleEndian::read_u64(&data[8 * i..]), eval, "entry {i}");
+        }
+
+        let (pk, parms_id) = read_public_key::<N, Q>(&bytes).unwrap();
+        assert_eq!(parms_id, PARMS_ID);
+        assert_eq!(&pk, bfv.public_key());
+        let m = Polynomial::<N, T>::rand();
+        assert_eq!(
+            Bfv::<N, Q, T>::from_public_key(pk).encrypt(m).decrypt(&sk),
+            m
+        );
+
+        assert_eq!(
+            read_ciphertext(&bfv, &bytes).map(|_| ()),
+            Err(SealError::NttForm { expected: false })
+        );
+        assert_eq!(
+            write_public_key(
+                &BfvPublicKey::<N, 12_288>::new(Polynomial::rand(), Polynomial::rand()),
+                &PARMS_ID
+            ),
+            Err(SealError::NotNttFriendly { n: N, q: 12_288 })
+        );
+    }
+}
@@ -0,0 +1,293 @@
+//! RLWE security estimates from the tables of the Homomorphic Encryption Standard (Albrecht et al., 2018):
+//! for each ring degree n the largest log q that keeps 128, 192 or 256 bits of classical security.
+//!
+//! The tables assume errors of standard deviation 3.2, `ErrorDist::STANDARD`. Narrower errors make a level
+//! reported here an upper bound. Sparse secrets are not covered, [`estimator`](crate::estimator) computes
+//! estimates for those and other distributions.
+//!
+//! `Bfv::keygen` and `BfvRns::keygen` check their (n, log q) here and, depending on [`set_policy`], let
+//! toy parameters through, keep a warning for [`take_warning`] (the default) or fail with a
+//! [`SecurityError`]. The library never prints, with the `tracing` feature the warning is also logged
+//! once.
+
+use std::fmt;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU8, Ordering};
+
+use crate::polynomial::Ternary;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum SecurityLevel {
+    Bits128,
+    Bits192,
+    Bits256,
+}
+
+impl SecurityLevel {
+    pub const ALL: [Self; 3] = [Self::Bits128, Self::Bits192, Self::Bits256];
+
+    pub fn bits(&self) -> u32 {
+        match self {
+            Self::Bits128 => 128,
+            Self::Bits192 => 192,
+            Self::Bits256 => 256,
+        }
+    }
+}
+
+/// Secret distributions with a table in the standard.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SecretDist {
+    /// Uniform mod q.
+    Uniform,
+    /// Uniform over {-1, 0, 1}.
+    Ternary,
+}
+
+impl SecretDist {
+    /// `None` for distributions the tables don't cover, i.e. anything but `Ternary::UNIFORM`.
+    pub fn from_ternary(dist: &Ternary) -> Option<Self> {
+        (*dist == Ternary::UNIFORM).then_some(Self::Ternary)
+    }
+}
+
+/// (n, max log q at 128, 192, 256 bits).
+const UNIFORM: [(usize, [u32; 3]); 6] = [
+    (1024, [29, 21, 16]),
+    (2048, [56, 39, 31]),
+    (4096, [111, 77, 60]),
+    (8192, [220, 154, 120]),
+    (16384, [440, 307, 239]),
+    (32768, [883, 613, 478]),
+];
+
+const TERNARY: [(usize, [u32; 3]); 6] = [
+    (1024, [27, 19, 14]),
+    (2048, [54, 37, 29]),
+    (4096, [109, 75, 58]),
+    (8192, [218, 152, 118]),
+    (16384, [438, 305, 237]),
+    (32768, [881, 611, 476]),
+];
+
+/// Largest log q at `level` for degree n, read from the largest tabulated degree <= n. `None` below
+/// n = 1024, where no modulus is secure.
+pub fn max_log_q(n: usize, level: SecurityLevel, secret: SecretDist) -> Option<u32> {
+    let table = match secret {
+        SecretDist::Uniform => &UNIFORM,
+        SecretDist::Ternary => &TERNARY,
+    };
+    table
+        .iter()
+        .rev()
+        .find(|(degree, _)| *degree <= n)
+        .map(|(_, bounds)| bounds[level as usize])
+}
+
+/// Highest level (n, log q) reaches, `None` below 128 bits.
+pub fn classify(n: usize, log_q: u32, secret: SecretDist) -> Option<SecurityLevel> {
+    SecurityLevel::ALL
+        .into_iter()
+        .rev()
+        .find(|&level| max_log_q(n, level, secret).is_some_and(|max| log_q <= max))
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SecurityError {
+    /// Below 128 bits.
+    Insecure {
+        n: usize,
+        log_q: u32,
+        max_log_q: Option<u32>,
+    },
+    /// The secret distribution has no table.
+    Uncovered { secret: Ternary },
+}
+
+impl fmt::Display for SecurityError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SecurityError::Insecure {
+                n,
+                log_q,
+                max_log_q: Some(max),
+            } => write!(
+                f,
+                "n = {n}, log q = {log_q} is below 128 bit security (log q <= {max} needed)"
+            ),
+            SecurityError::Insecure {
+                n,
+                log_q,
+                max_log_q: None,
+            } => write!(
+                f,
+                "n = {n}, log q = {log_q} is below 128 bit security (n >= 1024 needed)"
+            ),
+            SecurityError::Uncovered { secret } => {
+                write!(f, "no security estimate for {secret:?} secrets")
+            }
+        }
+    }
+}
+
+impl std::error::Error for SecurityError {}
+
+/// Level of the BFV parameters (N, Q) with secrets from `secret`, log q the bit length of Q.
+pub fn check<const N: usize, const Q: u64>(
+    secret: &Ternary,
+) -> Result<SecurityLevel, SecurityError> {
+    check_log_q(N, 64 - Q.leading_zeros(), secret)
+}
+
+/// Like [`check`] for a modulus only known at runtime, e.g. an RNS product of `log_q` bits.
+pub fn check_log_q(n: usize, log_q: u32, secret: &Ternary) -> Result<SecurityLevel, SecurityError> {
+    let dist =
+        SecretDist::from_ternary(secret).ok_or(SecurityError::Uncovered { secret: *secret })?;
+    classify(n, log_q, dist).ok_or(SecurityError::Insecure {
+        n,
+        log_q,
+        max_log_q: max_log_q(n, SecurityLevel::Bits128, dist),
+    })
+}
+
+/// What keygen does with parameters [`check`] rejects.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SecurityPolicy {
+    Allow,
+    /// Keep the rejection for [`take_warning`], logged through `tracing` with that feature.
+    Warn,
+    /// Fail keygen with the `SecurityError`.
+    Refuse,
+}
+
+static POLICY: AtomicU8 = AtomicU8::new(SecurityPolicy::Warn as u8);
+static WARNING: Mutex<Option<SecurityError>> = Mutex::new(None);
+
+/// Process wide, `Warn` by default.
+pub fn set_policy(policy: SecurityPolicy) {
+    POLICY.store(policy as u8, Ordering::Relaxed);
+}
+
+pub fn policy() -> SecurityPolicy {
+    match POLICY.load(Ordering::Relaxed) {
+        0 => SecurityPolicy::Allow,
+        1 => SecurityPolicy::Warn,
+        _ => SecurityPolicy::Refuse,
+    }
+}
+
+/// The first rejection let through by `Warn` since the last call, e.g. for a CLI to show after keygen.
+pub fn take_warning() -> Option<SecurityError> {
+    WARNING.lock().unwrap_or_else(|e| e.into_inner()).take()
+}
+
+/// Applies the current policy to (N, Q), called by keygen.
+pub(crate) fn enforce<const N: usize, const Q: u64>(secret: &Ternary) -> Result<(), SecurityError> {
+    enforce_log_q(N, 64 - Q.leading_zeros(), secret)
+}
+
+/// Applies the current policy to degree n and a modulus of `log_q` bits.
+pub(crate) fn enforce_log_q(n: usize, log_q: u32, secret: &Ternary) -> Result<(), SecurityError> {
+    enforce_with(policy(), n, log_q, secret)
+}
+
+fn enforce_with(
+    policy: SecurityPolicy,
+    n: usize,
+    log_q: u32,
+    secret: &Ternary,
+) -> Result<(), SecurityError> {
+    let Err(e) = check_log_q(n, log_q, secret) else {
+        return Ok(());
+    };
+    match policy {
+        SecurityPolicy::Allow => {}
+        SecurityPolicy::Warn => {
+            #[cfg(feature = "tracing")]
+            {
+                static WARNED: std::sync::atomic::AtomicBool =
+                    std::sync::atomic::AtomicBool::new(false);
+                if !WARNED.swap(true, Ordering::Relaxed) {
+                    tracing::warn!("{e}, see security::set_policy");
+                }
+            }
+            WARNING
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .get_or_insert(e);
+        }
+        SecurityPolicy::Refuse => return Err(e),
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_classify() {
+        let ternary = SecretDist::Ternary;
+        assert_eq!(classify(4096, 109, ternary), Some(SecurityLevel::Bits128));
+        assert_eq!(classify(4096, 110, ternary), None);
+        assert_eq!(
+            classify(4096, 110, SecretDist::Uniform),
+            Some(SecurityLevel::Bits128)
+        );
+        assert_eq!(classify(8192, 152, ternary), Some(SecurityLevel::Bits192));
+        assert_eq!(classify(8192, 60, ternary), Some(SecurityLevel::Bits256));
+        // between table rows the smaller degree applies
+        assert_eq!(classify(6000, 110, ternary), None);
+        assert_eq!(
+            classify(1 << 16, 881, ternary),
+            Some(SecurityLevel::Bits128)
+        );
+        assert_eq!(classify(512, 10, ternary), None);
+    }
+
+    #[test]
+    fn test_check() {
+        assert_eq!(
+            check::<8192, { (1 << 60) + 1 }>(&Ternary::UNIFORM),
+            Ok(SecurityLevel::Bits256)
+        );
+        assert_eq!(
+            check::<16, 12_289>(&Ternary::UNIFORM),
+            Err(SecurityError::Insecure {
+                n: 16,
+                log_q: 14,
+                max_log_q: None
+            })
+        );
+        assert_eq!(
+            check::<1024, { 1 << 40 }>(&Ternary::UNIFORM),
+            Err(SecurityError::Insecure {
+                n: 1024,
+                log_q: 41,
+                max_log_q: Some(27)
+            })
+        );
+        let sparse = Ternary::HammingWeight(64);
+        assert_eq!(
+            check::<4096, 12_289>(&sparse),
+            Err(SecurityError::Uncovered { secret: sparse })
+        );
+    }
+
+    #[test]
+    fn test_enforce() {
+        assert!(enforce_with(SecurityPolicy::Allow, 16, 14, &Ternary::UNIFORM).is_ok());
+        assert!(enforce_with(SecurityPolicy::Warn, 16, 14, &Ternary::UNIFORM).is_ok());
+        // other tests may have left a rejection of their own
+        assert!(take_warning().is_some());
+        assert!(enforce_with(SecurityPolicy::Refuse, 4096, 14, &Ternary::UNIFORM).is_ok());
+        assert_eq!(
+            enforce_with(SecurityPolicy::Refuse, 16, 14, &Ternary::UNIFORM),
+            Err(SecurityError::Insecure {
+                n: 16,
+                log_q: 14,
+                max_log_q: None
+            })
+        );
+    }
+}
@@ -0,0 +1,275 @@
+//! The whole hybrid flow of [`crate::hybrid`] behind one type. Each step consumes the session and returns
+//! it in the next state, so steps can't be skipped or reordered:
+//!
+//! `Session<Ready>` -> `upload` -> `Session<Uploaded>` -> `transcipher` -> `Session<Transciphered>`
+//! -> `compute` -> `Session<Computed>` -> `decrypt`
+//!
+//! The session holds both parties, which still only exchange the byte messages of `hybrid`: the
+//! [`ServerBundle`] is the setup message, the server is built from it, and the result comes back to the
+//! client as the download message, exactly what a remote client would receive.
+
+use crate::bfv_pke::{BfvCiphertext, Evaluator};
+use crate::cancel::CancellationToken;
+use crate::hybrid::{Client, EncryptedData, HybridError, Server};
+use crate::pasta_plain::{PASTA_R, PASTA_T};
+
+/// Everything the client keeps to itself: the BFV secret key and the Pasta key.
+pub type ClientKeys<const N: usize, const Q: u64, const T: u64, const W: usize, const R: usize> =
+    Client<N, Q, T, W, R>;
+
+/// What the server gets from the client, the `hybrid` setup message.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ServerBundle {
+    setup: Vec<u8>,
+}
+
+impl ServerBundle {
+    /// The bundle of `client_keys`, with a relinearization key of base 2^`relin_base_log`.
+    pub fn new<const N: usize, const Q: u64, const T: u64, const W: usize, const R: usize>(
+        client_keys: &ClientKeys<N, Q, T, W, R>,
+        relin_base_log: u32,
+    ) -> Self {
+        Self {
+            setup: client_keys.setup(relin_base_log),
+        }
+    }
+
+    /// A setup message received elsewhere.
+    pub fn from_bytes(setup: Vec<u8>) -> Self {
+        Self { setup }
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.setup
+    }
+}
+
+/// Fresh session, nothing uploaded yet.
+pub struct Ready;
+
+/// The upload message, the Pasta ciphertext of the data and its block tags.
+pub struct Uploaded {
+    pub message: Vec<u8>,
+}
+
+/// The data as BFV ciphertexts on the server, next to the upload it came from.
+pub struct Transciphered<const N: usize, const Q: u64, const T: u64> {
+    pub upload: Vec<u8>,
+    pub data: EncryptedData<N, Q, T>,
+}
+
+/// Result of the server computation, waiting for the client, which checks it against the upload.
+pub struct Computed<const N: usize, const Q: u64, const T: u64> {
+    pub upload: Vec<u8>,
+    pub data: EncryptedData<N, Q, T>,
+}
+
+pub struct Session<
+    const N: usize,
+    const Q: u64,
+    const T: u64,
+    S,
+    const W: usize = PASTA_T,
+    const R: usize = PASTA_R,
+> {
+    client: ClientKeys<N, Q, T, W, R>,
+    server: Server<N, Q, T, W, R>,
+    pub state: S,
+}
+
+impl<const N: usize, const Q: u64, const T: u64, S, const W: usize, const R: usize>
+    Session<N, Q, T, S, W, R>
+{
+    fn with_state<S2>(self, state: S2) -> Session<N, Q, T, S2, W, R> {
+        Session {
+            client: self.client,
+            server: self.server,
+            state,
+        }
+    }
+
+    pub fn client(&self) -> &ClientKeys<N, Q, T, W, R> {
+        &self.client
+    }
+
+    pub fn server(&self) -> &Server<N, Q, T, W, R> {
+        &self.server
+    }
+}
+
+impl<const N: usize, const Q: u64, const T: u64, const W: usize, const R: usize>
+    Session<N, Q, T, Ready, W, R>
+{
+    /// Sets up the server from `server_bundle`, which has to be made from `client_keys`.
+    pub fn new(
+        client_keys: ClientKeys<N, Q, T, W, R>,
+        server_bundle: &ServerBundle,
+    ) -> Result<Self, HybridError> {
+        let server = Server::from_setup(server_bundle.as_bytes())?;
+        if server.public_key() != client_keys.public_key() {
+            return Err(HybridError::ContextMismatch);
+        }
+        Ok(Self {
+            client: client_keys,
+            server,
+            state: Ready,
+        })
+    }
+
+    /// Client side Pasta encryption under a fresh random nonce, fails with
+    /// `HybridError::WordOutOfRange` unless every word is < t.
+    pub fn upload(self, data: &[u64]) -> Result<Session<N, Q, T, Uploaded, W, R>, HybridError> {
+        let message = self.client.upload(data)?;
+        Ok(self.with_state(Uploaded { message }))
+    }
+}
+
+impl<const N: usize, const Q: u64, const T: u64, const W: usize, const R: usize>
+    Session<N, Q, T, Uploaded, W, R>
+{
+    /// Server side transciphering of the upload into BFV.
+    pub fn transcipher(
+        self,
+    ) -> Result<Session<N, Q, T, Transciphered<N, Q, T>, W, R>, HybridError> {
+        self.transcipher_cancellable(&CancellationToken::new())
+    }
+
+    /// `transcipher` that stops once `token` is cancelled, see [`Server::transcipher_cancellable`].
+    pub fn transcipher_cancellable(
+        self,
+        token: &CancellationToken,
+    ) -> Result<Session<N, Q, T, Transciphered<N, Q, T>, W, R>, HybridError> {
+        let data = self
+            .server
+            .transcipher_cancellable(&self.state.message, token)?;
+        let upload = self.state.message;
+        Ok(Session {
+            client: self.client,
+            server: self.server,
+            state: Transciphered { upload, data },
+        })
+    }
+}
+
+impl<const N: usize, const Q: u64, const T: u64, const W: usize, const R: usize>
+    Session<N, Q, T, Transciphered<N, Q, T>, W, R>
+{
+    /// Server side computation over the transciphered ciphertexts, which keep the layout of
+    /// [`EncryptedData`]: `f` has to return as many ciphertexts as it gets.
+    pub fn compute<F>(self, f: F) -> Session<N, Q, T, Computed<N, Q, T>, W, R>
+    where
+        F: FnOnce(&Evaluator<N, Q, T>, Vec<BfvCiphertext<N, Q, T>>) -> Vec<BfvCiphertext<N, Q, T>>,
+    {
+        let Transciphered { upload, mut data } = self.state;
+        data.ciphertexts = f(self.server.evaluator(), data.ciphertexts);
+        Session {
+            client: self.client,
+            server: self.server,
+            state: Computed { upload, data },
+        }
+    }
+}
+
+impl<const N: usize, const Q: u64, const T: u64, const W: usize, const R: usize>
+    Session<N, Q, T, Computed<N, Q, T>, W, R>
+{
+    /// The server's download message of the result, decrypted by the client after checking its block
+    /// tags against the upload.
+    pub fn decrypt(self) -> Result<Vec<u64>, HybridError> {
+        let message = self.server.download(&self.state.data)?;
+        self.client.download(&self.state.upload, &message)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const N: usize = 8;
+    const Q: u64 = 1 << 59;
+    // 17 ≡ 1 mod 16
+    const T: u64 = 17;
+
+    #[test]
+    fn test_session_roundtrip() {
+        let keys = ClientKeys::<N, Q, T, 2, 2>::generate().unwrap();
+        let bundle = ServerBundle::new(&keys, 8);
+        let session = Session::new(keys, &bundle).unwrap();
+
+        let data = (0..21).map(|i| i * 5 % T).collect::<Vec<_>>();
+        let out = session
+            .upload(&data)
+            .unwrap()
+            .transcipher()
+            .unwrap()
+            .compute(|evaluator, cts| {
+                cts.iter()
+                    .map(|ct| evaluator.mul(ct, ct).unwrap())
+                    .collect()
+            })
+            .decrypt()
+            .unwrap();
+        assert_eq!(out, data.iter().map(|w| w * w % T).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_session_errors() {
+        let keys = ClientKeys::<N, Q, T, 2, 1>::generate().unwrap();
+        let other = ClientKeys::<N, Q, T, 2, 1>::generate().unwrap();
+        assert_eq!(
+            Session::new(keys, &ServerBundle::new(&other, 8)).err(),
+            Some(HybridError::ContextMismatch)
+        );
+        assert!(matches!(
+            Session::new(other, &ServerBundle::from_bytes(vec![0; 7])),
+            Err(HybridError::Decode(_))
+        ));
+
+        let keys = ClientKeys::<N, Q, T, 2, 1>::generate().unwrap();
+        let bundle = ServerBundle::new(&keys, 8);
+        let token = CancellationToken::new();
+        token.cancel();
+        assert!(matches!(
+            Session::new(keys, &bundle)
+                .unwrap()
+                .upload(&[1, 2, 3])
+                .unwrap()
+                .transcipher_cancellable(&token),
+            Err(HybridError::Transcipher(_))
+        ));
+
+        // a computation that drops a ciphertext
+        let keys = ClientKeys::<N, Q, T, 2, 1>::generate().unwrap();
+        let bundle = ServerBundle::new(&keys, 8);
+        let computed = Session::new(keys, &bundle)
+            .unwrap()
+            .upload(&[1, 2, 3])
+            .unwrap()
+            .transcipher()
+            .unwrap()
+            .compute(|_, mut cts| {
+                cts.pop();
+                cts
+            });
+        assert!(matches!(
+            computed.decrypt(),
+            Err(HybridError::CiphertextCount { .. })
+        ));
+
+        // a block dropped along with its tag
+        let keys = ClientKeys::<N, Q, T, 2, 1>::generate().unwrap();
+        let bundle = ServerBundle::new(&keys, 8);
+        let mut transciphered = Session::new(keys, &bundle)
+            .unwrap()
+            .upload(&[1, 2, 3])
+            .unwrap()
+            .transcipher()
+            .unwrap();
+        transciphered.state.data.words = 2;
+        transciphered.state.data.tags.pop();
+        assert_eq!(
+            transciphered.compute(|_, cts| cts).decrypt(),
+            Err(HybridError::BlockMismatch { block: 1 })
+        );
+    }
+}
@@ -0,0 +1,209 @@
+//! Shrink-on-failure harness for plain vs homomorphic mismatches.
+//!
+//! Both sides record a trace of intermediate values (one entry per operation). When the traces disagree,
+//! the run is repeated with fewer blocks, rounds and state words until no smaller configuration still fails,
+//! and the first diverging operation of that minimal run is reported.
+
+use std::fmt;
+
+/// Knobs the harness is allowed to shrink. All of them stay >= 1.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ShrinkConfig {
+    pub rounds: usize,
+    pub state_size: usize,
+    pub blocks: usize,
+}
+
+/// Value of the state after one operation, e.g. `("round 0 mix", l || r)`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TraceStep {
+    pub op: String,
+    pub values: Vec<u64>,
+}
+
+impl TraceStep {
+    pub fn new(op: impl Into<String>, values: Vec<u64>) -> Self {
+        Self {
+            op: op.into(),
+            values,
+        }
+    }
+}
+
+/// First point where the homomorphic trace stops matching the plain one.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Divergence {
+    /// Smallest configuration that still fails.
+    pub config: ShrinkConfig,
+    /// Index of the diverging step in the trace.
+    pub step: usize,
+    pub op: String,
+    pub plain: Vec<u64>,
+    /// `None` when the homomorphic trace ended early.
+    pub homomorphic: Option<Vec<u64>>,
+}
+
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "diverged at step {} ({}) with {:?}",
+            self.step, self.op, self.config
+        )?;
+        writeln!(f, "  plain       = {:?}", self.plain)?;
+        write!(f, "  homomorphic = {:?}", self.homomorphic)
+    }
+}
+
+/// Index of the first step where the traces differ (including one trace being shorter).
+pub fn first_divergence(plain: &[TraceStep], homomorphic: &[TraceStep]) -> Option<usize> {
+    (0..plain.len().max(homomorphic.len())).find(|&i| plain.get(i) != homomorphic.get(i))
+}
+
+/// Runs `run` at `config` and returns `None` if the traces agree. Otherwise shrinks blocks, rounds and
+/// state size (in that order, halving then decrementing) as long as the mismatch persists and returns the
+/// divergence found at the smallest failing configuration. `run` returns `(plain, homomorphic)` traces
+/// and must be deterministic for a given config for the result to be meaningful. Nothing is printed, the
+/// `Display` of the divergence is the report.
+pub fn shrink_on_failure<F>(config: ShrinkConfig, mut run: F) -> Option<Divergence>
+where
+    F: FnMut(&ShrinkConfig) -> (Vec<TraceStep>, Vec<TraceStep>),
+{
+    let mut fails = |c: &ShrinkConfig| {
+        let (plain, homomorphic) = run(c);
+        first_divergence(&plain, &homomorphic).map(|step| (step, plain, homomorphic))
+    };
+
+    let mut failure = fails(&config)?;
+    let mut config = config;
+
+    loop {
+        let candidates = shrink_candidates(&config);
+        let next = candidates
+            .into_iter()
+            .find_map(|c| fails(&c).map(|found| (c, found)));
+        match next {
+            Some((c, found)) => {
+                config = c;
+                failure = found;
+            }
+            None => break,
+        }
+    }
+
+    let (step, plain, homomorphic) = failure;
+    Some(Divergence {
+        config,
+        step,
+        op: plain
+            .get(step)
+            .or(homomorphic.get(step))
+            .map(|s| s.op.clone())
+            .unwrap_or_default(),
+        plain: plain
+            .get(step)
+            .map(|s| s.values.clone())
+            .unwrap_or_default(),
+        homomorphic: homomorphic.get(step).map(|s| s.values.clone()),
+    })
+}
+
+fn shrink_candidates(c: &ShrinkConfig) -> Vec<ShrinkConfig> {
+    let mut out = Vec::new();
+    for n in smaller(c.blocks) {
+        out.push(ShrinkConfig { blocks: n, ..*c });
+    }
+    for n in smaller(c.rounds) {
+        out.push(ShrinkConfig { rounds: n, ..*c });
+    }
+    for n in smaller(c.state_size) {
+        out.push(ShrinkConfig {
+            state_size: n,
+            ..*c
+        });
+    }
+    out
+}
+
+/// Smaller values to try for one knob, most aggressive first.
+fn smaller(n: usize) -> Vec<usize> {
+    let mut out = Vec::new();
+    if n > 1 {
+        out.push(1);
+    }
+    if n / 2 > 1 {
+        out.push(n / 2);
+    }
+    if n - 1 > n / 2 {
+        out.push(n - 1);
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Toy cipher: per block, each round adds the round index to every word.
+    /// The "homomorphic" side is off by one in round 2 on word 3.
+    fn traces(c: &ShrinkConfig, buggy: bool) -> (Vec<TraceStep>, Vec<TraceStep>) {
+        let mut plain = Vec::new();
+        let mut homomorphic = Vec::new();
+        for b in 0..c.blocks {
+            let mut p = vec![b as u64; c.state_size];
+            let mut h = p.clone();
+            for r in 0..c.rounds {
+                for i in 0..c.state_size {
+                    p[i] += r as u64;
+                    h[i] += r as u64;
+                    if buggy && r == 2 && i == 3 {
+                        h[i] += 1;
+                    }
+                }
+                plain.push(TraceStep::new(format!("block {b} round {r}"), p.clone()));
+                homomorphic.push(TraceStep::new(format!("block {b} round {r}"), h.clone()));
+            }
+        }
+        (plain, homomorphic)
+    }
+
+    #[test]
+    fn test_no_divergence() {
+        let config = ShrinkConfig {
+            rounds: 4,
+            state_size: 8,
+            blocks: 3,
+        };
+        assert_eq!(shrink_on_failure(config, |c| traces(c, false)), None);
+    }
+
+    #[test]
+    fn test_shrinks_to_minimal_failing_config() {
+        let config = ShrinkConfig {
+            rounds: 5,
+            state_size: 16,
+            blocks: 4,
+        };
+        let d = shrink_on_failure(config, |c| traces(c, true)).unwrap();
+        assert_eq!(
+            d.config,
+            ShrinkConfig {
+                rounds: 3,
+                state_size: 4,
+                blocks: 1,
+            }
+        );
+        assert_eq!(d.step, 2);
+        assert_eq!(d.op, "block 0 round 2");
+        assert_eq!(d.plain, vec![3, 3, 3, 3]);
+        assert_eq!(d.homomorphic, Some(vec![3, 3, 3, 4]));
+    }
+
+    #[test]
+    fn test_first_divergence_on_truncated_trace() {
+        let plain = vec![TraceStep::new("a", vec![1]), TraceStep::new("b", vec![2])];
+        let homomorphic = vec![TraceStep::new("a", vec![1])];
+        assert_eq!(first_divergence(&plain, &homomorphic), Some(1));
+        assert_eq!(first_divergence(&plain, &plain), None);
+    }
+}
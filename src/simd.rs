@@ -0,0 +1,228 @@
+//! Coefficient-wise mod q kernels over `u64` slices. The AVX2 path is picked at runtime when the cpu
+//! supports it, otherwise (and on other archs) the scalar loop runs. Inputs must already be reduced mod q.
+//!
+//! todo: NEON / AVX-512 paths
+
+/// a[i] = a[i] + b[i] mod q
+pub fn add_assign_mod(a: &mut [u64], b: &[u64], q: u64) {
+    assert_eq!(a.len(), b.len());
+    #[cfg(target_arch = "x86_64")]
+    if q < 1 << 62 && is_x86_feature_detected!("avx2") {
+        // SAFETY: avx2 support checked above
+        unsafe { avx2::add_assign_mod(a, b, q) };
+        return;
+    }
+    scalar::add_assign_mod(a, b, q)
+}
+
+/// a[i] = a[i] - b[i] mod q
+pub fn sub_assign_mod(a: &mut [u64], b: &[u64], q: u64) {
+    assert_eq!(a.len(), b.len());
+    #[cfg(target_arch = "x86_64")]
+    if q < 1 << 62 && is_x86_feature_detected!("avx2") {
+        // SAFETY: avx2 support checked above
+        unsafe { avx2::sub_assign_mod(a, b, q) };
+        return;
+    }
+    scalar::sub_assign_mod(a, b, q)
+}
+
+/// a[i] = a[i] * b[i] mod q
+pub fn mul_assign_mod(a: &mut [u64], b: &[u64], q: u64) {
+    assert_eq!(a.len(), b.len());
+    #[cfg(target_arch = "x86_64")]
+    if q <= 1 << 32 && is_x86_feature_detected!("avx2") {
+        // SAFETY: avx2 support checked above
+        unsafe { avx2::mul_assign_mod(a, b, q) };
+        return;
+    }
+    scalar::mul_assign_mod(a, b, q)
+}
+
+/// a[i] = a[i] * k mod q
+pub fn mul_scalar_assign_mod(a: &mut [u64], k: u64, q: u64) {
+    #[cfg(target_arch = "x86_64")]
+    if q <= 1 << 32 && is_x86_feature_detected!("avx2") {
+        let b = vec![k; a.len()];
+        // SAFETY: avx2 support checked above
+        unsafe { avx2::mul_assign_mod(a, &b, q) };
+        return;
+    }
+    for x in a.iter_mut() {
+        *x = ((*x as u128 * k as u128) % q as u128) as u64;
+    }
+}
+
+/// Brings values in [0, 2q) back to [0, q).
+pub fn reduce_once(a: &mut [u64], q: u64) {
+    #[cfg(target_arch = "x86_64")]
+    if q < 1 << 62 && is_x86_feature_detected!("avx2") {
+        // SAFETY: avx2 support checked above
+        unsafe { avx2::reduce_once(a, q) };
+        return;
+    }
+    scalar::reduce_once(a, q)
+}
+
+mod scalar {
+    pub fn add_assign_mod(a: &mut [u64], b: &[u64], q: u64) {
+        for (x, y) in a.iter_mut().zip(b) {
+            let s = *x + *y;
+            *x = if s >= q { s - q } else { s };
+        }
+    }
+
+    pub fn sub_assign_mod(a: &mut [u64], b: &[u64], q: u64) {
+        for (x, y) in a.iter_mut().zip(b) {
+            *x = if *x >= *y { *x - *y } else { *x + q - *y };
+        }
+    }
+
+    pub fn mul_assign_mod(a: &mut [u64], b: &[u64], q: u64) {
+        for (x, y) in a.iter_mut().zip(b) {
+            *x = ((*x as u128 * *y as u128) % q as u128) as u64;
+        }
+    }
+
+    pub fn reduce_once(a: &mut [u64], q: u64) {
+        for x in a.iter_mut() {
+            if *x >= q {
+                *x -= q;
+            }
+        }
+    }
+}
+
+#[cfg(target_arch = "x86_64")]
+mod avx2 {
+    use std::arch::x86_64::*;
+
+    const LANES: usize = 4;
+
+    /// Lanes are < 2^63 so the signed 64 bit compare is fine.
+    #[target_feature(enable = "avx2")]
+    fn cond_sub(v: __m256i, q: __m256i, q_minus_one: __m256i) -> __m256i {
+        let mask = _mm256_cmpgt_epi64(v, q_minus_one);
+        _mm256_sub_epi64(v, _mm256_and_si256(mask, q))
+    }
+
+    #[target_feature(enable = "avx2")]
+    pub fn add_assign_mod(a: &mut [u64], b: &[u64], q: u64) {
+        let q_v = _mm256_set1_epi64x(q as i64);
+        let q_m1 = _mm256_set1_epi64x(q as i64 - 1);
+        let mut a_chunks = a.chunks_exact_mut(LANES);
+        let mut b_chunks = b.chunks_exact(LANES);
+        for (x, y) in (&mut a_chunks).zip(&mut b_chunks) {
+            // SAFETY: both chunks hold exactly 4 u64s, unaligned load/store
+            unsafe {
+                let x_v = _mm256_loadu_si256(x.as_ptr() as *const __m256i);
+                let y_v = _mm256_loadu_si256(y.as_ptr() as *const __m256i);
+                let s = cond_sub(_mm256_add_epi64(x_v, y_v), q_v, q_m1);
+                _mm256_storeu_si256(x.as_mut_ptr() as *mut __m256i, s);
+            }
+        }
+        super::scalar::add_assign_mod(a_chunks.into_remainder(), b_chunks.remainder(), q);
+    }
+
+    #[target_feature(enable = "avx2")]
+    pub fn sub_assign_mod(a: &mut [u64], b: &[u64], q: u64) {
+        let q_v = _mm256_set1_epi64x(q as i64);
+        let mut a_chunks = a.chunks_exact_mut(LANES);
+        let mut b_chunks = b.chunks_exact(LANES);
+        for (x, y) in (&mut a_chunks).zip(&mut b_chunks) {
+            // SAFETY: both chunks hold exactly 4 u64s, unaligned load/store
+            unsafe {
+                let x_v = _mm256_loadu_si256(x.as_ptr() as *const __m256i);
+                let y_v = _mm256_loadu_si256(y.as_ptr() as *const __m256i);
+                let borrow = _mm256_cmpgt_epi64(y_v, x_v);
+                let d = _mm256_add_epi64(_mm256_sub_epi64(x_v, y_v), _mm256_and_si256(borrow, q_v));
+                _mm256_storeu_si256(x.as_mut_ptr() as *mut __m256i, d);
+            }
+        }
+        super::scalar::sub_assign_mod(a_chunks.into_remainder(), b_chunks.remainder(), q);
+    }
+
+    /// Products are formed 4 at a time with `vpmuludq` (q <= 2^32 so both operands fit in 32 bits),
+    /// the final `% q` is done per lane.
+    #[target_feature(enable = "avx2")]
+    pub fn mul_assign_mod(a: &mut [u64], b: &[u64], q: u64) {
+        let mut a_chunks = a.chunks_exact_mut(LANES);
+        let mut b_chunks = b.chunks_exact(LANES);
+        let mut prod = [0u64; LANES];
+        for (x, y) in (&mut a_chunks).zip(&mut b_chunks) {
+            // SAFETY: both chunks and `prod` hold exactly 4 u64s, unaligned load/store
+            unsafe {
+                let x_v = _mm256_loadu_si256(x.as_ptr() as *const __m256i);
+                let y_v = _mm256_loadu_si256(y.as_ptr() as *const __m256i);
+                let p = _mm256_mul_epu32(x_v, y_v);
+                _mm256_storeu_si256(prod.as_mut_ptr() as *mut __m256i, p);
+            }
+            for (x, p) in x.iter_mut().zip(prod) {
+                *x = p % q;
+            }
+        }
+        super::scalar::mul_assign_mod(a_chunks.into_remainder(), b_chunks.remainder(), q);
+    }
+
+    #[target_feature(enable = "avx2")]
+    pub fn reduce_once(a: &mut [u64], q: u64) {
+        let q_v = _mm256_set1_epi64x(q as i64);
+        let q_m1 = _mm256_set1_epi64x(q as i64 - 1);
+        let mut chunks = a.chunks_exact_mut(LANES);
+        for x in &mut chunks {
+            // SAFETY: the chunk holds exactly 4 u64s, unaligned load/store
+            unsafe {
+                let x_v = _mm256_loadu_si256(x.as_ptr() as *const __m256i);
+                let r = cond_sub(x_v, q_v, q_m1);
+                _mm256_storeu_si256(x.as_mut_ptr() as *mut __m256i, r);
+            }
+        }
+        super::scalar::reduce_once(chunks.into_remainder(), q);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{Rng, rng};
+
+    fn rand_vec(len: usize, q: u64) -> Vec<u64> {
+        let mut rng = rng();
+        (0..len).map(|_| rng.random_range(0..q)).collect()
+    }
+
+    #[test]
+    fn test_kernels_match_scalar() {
+        for q in [2, 3, 97, 65_537, (1 << 32) - 5, 1 << 32, (1 << 61) - 1] {
+            // odd length so the remainder loop is exercised too
+            let a = rand_vec(67, q);
+            let b = rand_vec(67, q);
+
+            let (mut x, mut y) = (a.clone(), a.clone());
+            add_assign_mod(&mut x, &b, q);
+            scalar::add_assign_mod(&mut y, &b, q);
+            assert_eq!(x, y);
+
+            let (mut x, mut y) = (a.clone(), a.clone());
+            sub_assign_mod(&mut x, &b, q);
+            scalar::sub_assign_mod(&mut y, &b, q);
+            assert_eq!(x, y);
+
+            let (mut x, mut y) = (a.clone(), a.clone());
+            mul_assign_mod(&mut x, &b, q);
+            scalar::mul_assign_mod(&mut y, &b, q);
+            assert_eq!(x, y);
+
+            let (mut x, mut y) = (a.clone(), a.clone());
+            mul_scalar_assign_mod(&mut x, b[0], q);
+            scalar::mul_assign_mod(&mut y, &vec![b[0]; 67], q);
+            assert_eq!(x, y);
+
+            let mut x: Vec<u64> = a.iter().zip(&b).map(|(x, y)| x + y).collect();
+            let mut y = x.clone();
+            reduce_once(&mut x, q);
+            scalar::reduce_once(&mut y, q);
+            assert_eq!(x, y);
+        }
+    }
+}
@@ -0,0 +1,150 @@
+//! Sparse polynomials in Z_a[x]/(x^n+1), stored as (index, coefficient) pairs.
+//!
+//! Multiplying a dense polynomial by a sparse one costs O(weight * n) instead of the full product, which is
+//! what you want for sparse (fixed hamming weight) secrets and for monomials x^k.
+
+use std::ops::Mul;
+
+use crate::polynomial::{Element, Polynomial};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SparsePolynomial<const N: usize, const A: u64> {
+    /// Sorted by index, no duplicate indices and no zero coefficients.
+    terms: Vec<(usize, Element<A>)>,
+}
+
+impl<const N: usize, const A: u64> SparsePolynomial<N, A> {
+    /// Terms in any order, coefficients of repeated indices are summed and zeros dropped.
+    pub fn new(terms: impl IntoIterator<Item = (usize, Element<A>)>) -> Self {
+        let mut terms = terms.into_iter().collect::<Vec<_>>();
+        terms.sort_by_key(|(i, _)| *i);
+
+        let mut merged: Vec<(usize, Element<A>)> = Vec::with_capacity(terms.len());
+        for (i, c) in terms {
+            assert!(i < N, "index {i} out of range for ring dimension {N}");
+            match merged.last_mut() {
+                Some((j, acc)) if *j == i => *acc = *acc + c,
+                _ => merged.push((i, c)),
+            }
+        }
+        merged.retain(|(_, c)| c.value() != 0);
+        Self { terms: merged }
+    }
+
+    /// c * x^index
+    pub fn monomial(index: usize, coeff: Element<A>) -> Self {
+        Self::new([(index, coeff)])
+    }
+
+    pub fn from_dense(dense: &Polynomial<N, A>) -> Self {
+        Self {
+            terms: dense
+                .inner
+                .iter()
+                .enumerate()
+                .filter(|(_, c)| c.value() != 0)
+                .map(|(i, c)| (i, *c))
+                .collect(),
+        }
+    }
+
+    pub fn to_dense(&self) -> Polynomial<N, A> {
+        let mut inner = [Element::new(0); N];
+        for &(i, c) in self.terms.iter() {
+            inner[i] = c;
+        }
+        Polynomial::new(inner)
+    }
+
+    pub fn terms(&self) -> &[(usize, Element<A>)] {
+        &self.terms
+    }
+
+    /// Number of nonzero coefficients.
+    pub fn weight(&self) -> usize {
+        self.terms.len()
+    }
+
+    /// self * rhs mod x^n+1, one negacyclic rotation of `rhs` per nonzero term.
+    pub fn mul_dense(&self, rhs: &Polynomial<N, A>) -> Polynomial<N, A> {
+        let mut out = [Element::<A>::new(0); N];
+        for &(k, c) in self.terms.iter() {
+            for (j, b) in rhs.inner.iter().enumerate() {
+                let prod = c * *b;
+                // x^(k+j) with x^n = -1
+                if k + j < N {
+                    out[k + j] = out[k + j] + prod;
+                } else {
+                    out[k + j - N] = out[k + j - N] - prod;
+                }
+            }
+        }
+        Polynomial::new(out)
+    }
+}
+
+impl<const N: usize, const A: u64> Mul<Polynomial<N, A>> for &SparsePolynomial<N, A> {
+    type Output = Polynomial<N, A>;
+
+    fn mul(self, rhs: Polynomial<N, A>) -> Self::Output {
+        self.mul_dense(&rhs)
+    }
+}
+
+impl<const N: usize, const A: u64> Mul<&SparsePolynomial<N, A>> for Polynomial<N, A> {
+    type Output = Polynomial<N, A>;
+
+    fn mul(self, rhs: &SparsePolynomial<N, A>) -> Self::Output {
+        rhs.mul_dense(&self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::polynomial::Ternary;
+
+    #[test]
+    fn test_mul_matches_dense() {
+        const N: usize = 32;
+        const Q: u64 = 12_289;
+
+        let s = Polynomial::<N, 3>::ternary(Ternary::HammingWeight(5))
+            .unwrap()
+            .lift_centered::<Q>();
+        let sparse = SparsePolynomial::from_dense(&s);
+        assert_eq!(sparse.weight(), 5);
+        assert_eq!(sparse.to_dense(), s);
+
+        let a = Polynomial::<N, Q>::rand();
+        assert_eq!(&sparse * a, s * a);
+        assert_eq!(a * &sparse, a * s);
+    }
+
+    #[test]
+    fn test_monomial_wraps_negacyclic() {
+        const N: usize = 8;
+        const Q: u64 = 97;
+
+        let a = Polynomial::<N, Q>::rand();
+        // x^3 * x^5 = x^8 = -1
+        let x3 = SparsePolynomial::<N, Q>::monomial(3, Element::new(1));
+        let x5 = SparsePolynomial::<N, Q>::monomial(5, Element::new(1));
+        assert_eq!(&x5 * (&x3 * a), -a);
+
+        let twice = SparsePolynomial::<N, Q>::monomial(0, Element::new(2));
+        assert_eq!(&twice * a, a + a);
+    }
+
+    #[test]
+    fn test_new_normalizes() {
+        let p = SparsePolynomial::<8, 7>::new([
+            (4, Element::new(3)),
+            (1, Element::new(2)),
+            (4, Element::new(4)),
+            (6, Element::new(0)),
+        ]);
+        // 3 + 4 = 0 mod 7, and the explicit zero is dropped
+        assert_eq!(p.terms(), &[(1, Element::new(2))]);
+    }
+}
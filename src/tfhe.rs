@@ -0,0 +1,337 @@
+//! TFHE / FHEW style gate bootstrapping: bits are LWE ciphertexts under a short binary secret and every
+//! binary gate refreshes its output, so boolean circuits (Rasta, FiLIP) run at any depth.
+//!
+//! A bootstrap blindly rotates a test polynomial by the phase ([`crate::blind_rotation`]), extracts the
+//! constant coefficient as an LWE ciphertext under the ring secret and key switches it back to the short secret.
+//! The test polynomial decides the function of the phase that comes out, [`BootstrapKey::bootstrap`]
+//! takes an arbitrary function and [`BootstrapKey::pbs`] a lookup table over Z_p that can be chained.
+//!
+//! Bits are encrypted as m * q / 4 ([`LweBit`], t = 4). The phase convention is the one of [`LweCipher`].
+
+use std::fmt;
+
+use zeroize::ZeroizeOnDrop;
+
+use crate::blind_rotation::BlindRotationKey;
+use crate::lwe::{LweCipher, LweKeySwitchKey, LweSecretKey};
+use crate::polynomial::{Element, Polynomial};
+
+/// An encrypted bit, m * q / 4 + e.
+pub type LweBit<const L: usize, const Q: u64> = LweCipher<L, Q, 4>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BinaryGate {
+    And,
+    Or,
+    Nand,
+    Nor,
+    Xor,
+    Xnor,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BootstrapError {
+    /// The test polynomial holds 2n / t coefficients per message, so t has to divide 2n.
+    Modulus { t: u64, n: usize },
+    /// A lookup table needs t / 2 entries.
+    TableLength { expected: usize, got: usize },
+    /// Table entries are messages with padding, below t / 2.
+    TableEntry { index: usize, value: u64, p: u64 },
+}
+
+impl fmt::Display for BootstrapError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BootstrapError::Modulus { t, n } => {
+                write!(f, "t = {t} does not divide 2n = {}", 2 * n)
+            }
+            BootstrapError::TableLength { expected, got } => {
+                write!(
+                    f,
+                    "lookup table needs t / 2 = {expected} entries, got {got}"
+                )
+            }
+            BootstrapError::TableEntry { index, value, p } => write!(
+                f,
+                "lookup table entry {index} ({value}) not below t / 2 = {p}"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for BootstrapError {}
+
+/// A binary LWE secret of length l and a ternary ring secret of degree n, whose coefficient vector is the
+/// key of extracted ciphertexts.
+#[derive(Debug, Clone)]
+pub struct TfheSecretKey<const L: usize, const N: usize> {
+    lwe: LweSecretKey<L>,
+    ring: LweSecretKey<N>,
+}
+
+impl<const L: usize, const N: usize> TfheSecretKey<L, N> {
+    pub fn generate() -> Self {
+        Self {
+            lwe: LweSecretKey::binary(),
+            ring: LweSecretKey::generate(),
+        }
+    }
+
+    /// The short secret bits and ciphertexts are encrypted under.
+    pub fn lwe(&self) -> &LweSecretKey<L> {
+        &self.lwe
+    }
+
+    pub fn ring(&self) -> &LweSecretKey<N> {
+        &self.ring
+    }
+
+    pub fn encrypt_bit<const Q: u64>(&self, bit: bool) -> LweBit<L, Q> {
+        self.lwe.encrypt(bit as u64)
+    }
+
+    pub fn decrypt_bit<const Q: u64>(&self, ct: &LweBit<L, Q>) -> bool {
+        self.lwe.decrypt(ct) == 1
+    }
+}
+
+/// Both halves wipe themselves on drop.
+impl<const L: usize, const N: usize> ZeroizeOnDrop for TfheSecretKey<L, N> {}
+
+/// Everything the evaluator needs to bootstrap: a blind rotation key (RGSW in base 2^`base_log`) and a key
+/// switching key from the ring secret back to the LWE secret. (n, q) has to be NTT friendly.
+#[derive(Debug, Clone)]
+pub struct BootstrapKey<const L: usize, const N: usize, const Q: u64> {
+    rotation: BlindRotationKey<L, N, Q>,
+    ksk: LweKeySwitchKey<N, L, Q>,
+}
+
+impl<const L: usize, const N: usize, const Q: u64> BootstrapKey<L, N, Q> {
+    pub fn new(sk: &TfheSecretKey<L, N>, base_log: u32, ks_base_log: u32) -> Self {
+        Self {
+            rotation: BlindRotationKey::new(&sk.lwe, &sk.ring, base_log),
+            ksk: LweKeySwitchKey::new(sk.ring.poly(), sk.lwe.poly(), ks_base_log),
+        }
+    }
+
+    pub fn blind_rotation_key(&self) -> &BlindRotationKey<L, N, Q> {
+        &self.rotation
+    }
+
+    /// Blind rotation, extraction of the constant coefficient and key switching: an encryption under the
+    /// LWE secret of coefficient k of `test`, k the phase of `ct` in units of q / 2n. Phases in [n, 2n)
+    /// wrap around to -test[k - n].
+    fn bootstrap_with<const T: u64>(
+        &self,
+        ct: &LweCipher<L, Q, T>,
+        test: &Polynomial<N, Q>,
+    ) -> LweCipher<L, Q, T> {
+        self.ksk
+            .switch(&self.rotation.rotate(ct, test).extract_lwe::<T>(0))
+    }
+
+    /// Programmable bootstrap: a fresh encryption of f(m) mod t. The input needs a padding bit, m < t / 2,
+    /// and t has to divide 2n.
+    pub fn bootstrap<const T: u64>(
+        &self,
+        ct: &LweCipher<L, Q, T>,
+        f: impl Fn(u64) -> u64,
+    ) -> Result<LweCipher<L, Q, T>, BootstrapError> {
+        if !(2 * N as u64).is_multiple_of(T) {
+            return Err(BootstrapError::Modulus { t: T, n: N });
+        }
+        let delta = Q / T;
+        // m covers the 2n / t coefficients from m * 2n / t on
+        let test = Polynomial::new(core::array::from_fn(|k| {
+            Element::new((delta * (f(k as u64 * T / (2 * N as u64)) % T)) as i64)
+        }));
+        // centers the phase in its window so errors of either sign stay inside
+        let shifted = LweCipher::new(ct.a, ct.b + Element::new((delta / 2) as i64));
+        Ok(self.bootstrap_with(&shifted, &test))
+    }
+
+    /// `pbs(ct, lut)` evaluates the table f: Z_p -> Z_p, p = t / 2 = `lut.len()`, on m in Z_p. Inputs and
+    /// outputs both keep the padding bit, so results can go through another table.
+    pub fn pbs<const T: u64>(
+        &self,
+        ct: &LweCipher<L, Q, T>,
+        lut: &[u64],
+    ) -> Result<LweCipher<L, Q, T>, BootstrapError> {
+        let p = T / 2;
+        if lut.len() as u64 != p {
+            return Err(BootstrapError::TableLength {
+                expected: p as usize,
+                got: lut.len(),
+            });
+        }
+        if let Some((index, &value)) = lut.iter().enumerate().find(|(_, v)| **v >= p) {
+            return Err(BootstrapError::TableEntry { index, value, p });
+        }
+        self.bootstrap(ct, |m| lut[m as usize])
+    }
+
+    /// One bootstrapped gate. The sum of the inputs (doubled for XOR) is shifted so the output bit decides
+    /// its sign, the sign test polynomial turns it into -+q / 8 and adding q / 8 makes it a bit again.
+    pub fn gate(&self, gate: BinaryGate, x: &LweBit<L, Q>, y: &LweBit<L, Q>) -> LweBit<L, Q> {
+        let eighth = Element::new((Q / 8) as i64);
+        let (a, b) = (x.a + y.a, x.b + y.b);
+        let shifted: LweBit<L, Q> = match gate {
+            BinaryGate::And | BinaryGate::Nand => LweCipher::new(a, b - eighth - eighth - eighth),
+            BinaryGate::Or | BinaryGate::Nor => LweCipher::new(a, b - eighth),
+            BinaryGate::Xor | BinaryGate::Xnor => LweCipher::new(a + a, b + b - eighth - eighth),
+        };
+        let sign = self.bootstrap_with(&shifted, &Polynomial::new([eighth; N]));
+        let out = LweCipher::new(sign.a, sign.b + eighth);
+        match gate {
+            BinaryGate::Nand | BinaryGate::Nor | BinaryGate::Xnor => not(&out),
+            _ => out,
+        }
+    }
+}
+
+/// 1 - m, no bootstrap and no extra noise.
+pub fn not<const L: usize, const Q: u64>(x: &LweBit<L, Q>) -> LweBit<L, Q> {
+    LweCipher::new(-x.a, Element::new((Q / 4) as i64) - x.b)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const L: usize = 32;
+    const N: usize = 256;
+    /// Prime, 1 mod 1024.
+    const Q: u64 = 4_294_957_057;
+
+    fn keys() -> (TfheSecretKey<L, N>, BootstrapKey<L, N, Q>) {
+        let sk = TfheSecretKey::generate();
+        let bk = BootstrapKey::new(&sk, 7, 4);
+        (sk, bk)
+    }
+
+    #[test]
+    fn test_gates() {
+        let (sk, bk) = keys();
+        let plain = |gate, x: bool, y: bool| match gate {
+            BinaryGate::And => x & y,
+            BinaryGate::Or => x | y,
+            BinaryGate::Nand => !(x & y),
+            BinaryGate::Nor => !(x | y),
+            BinaryGate::Xor => x ^ y,
+            BinaryGate::Xnor => !(x ^ y),
+        };
+        use BinaryGate::*;
+        for gate in [And, Or, Nand, Nor, Xor, Xnor] {
+            for (x, y) in [(false, false), (false, true), (true, false), (true, true)] {
+                let out = bk.gate(gate, &sk.encrypt_bit(x), &sk.encrypt_bit(y));
+                assert_eq!(
+                    sk.decrypt_bit(&out),
+                    plain(gate, x, y),
+                    "{gate:?}({x}, {y})"
+                );
+            }
+        }
+        for x in [false, true] {
+            assert_eq!(sk.decrypt_bit(&not(&sk.encrypt_bit::<Q>(x))), !x);
+        }
+    }
+
+    #[test]
+    fn test_deep_circuit() {
+        // a ripple carry adder of two 4 bit numbers, the carry passes through 8 levels of gates
+        let (sk, bk) = keys();
+        let (x, y) = (0b1011u8, 0b0111u8);
+        let bits = |v: u8| {
+            (0..4)
+                .map(|i| sk.encrypt_bit::<Q>(v >> i & 1 == 1))
+                .collect::<Vec<_>>()
+        };
+        let (xs, ys) = (bits(x), bits(y));
+        let mut carry = sk.encrypt_bit(false);
+        let mut sum = 0u8;
+        for i in 0..4 {
+            let t = bk.gate(BinaryGate::Xor, &xs[i], &ys[i]);
+            let s = bk.gate(BinaryGate::Xor, &t, &carry);
+            let c_1 = bk.gate(BinaryGate::And, &xs[i], &ys[i]);
+            let c_2 = bk.gate(BinaryGate::And, &t, &carry);
+            carry = bk.gate(BinaryGate::Or, &c_1, &c_2);
+            sum |= (sk.decrypt_bit(&s) as u8) << i;
+        }
+        sum |= (sk.decrypt_bit(&carry) as u8) << 4;
+        assert_eq!(sum, x + y);
+    }
+
+    #[test]
+    fn test_back_to_bfv() {
+        use crate::bfv_pke::{Bfv, Evaluator};
+        use crate::packing::packing_elements;
+
+        // a gate output switched to the coefficient vector of a BFV secret and packed into coefficient 0
+        let (sk, bk) = keys();
+        let (bfv, bfv_sk) = Bfv::<N, Q, 4>::keygen().unwrap();
+        let ksk = LweKeySwitchKey::<L, N, Q>::new(sk.lwe().poly(), bfv_sk.poly(), 4);
+        let evaluator = Evaluator::new().with_galois_keys(
+            bfv.gen_galois_keys(&bfv_sk, &packing_elements::<N>(), 4)
+                .unwrap(),
+        );
+        let bit = bk.gate(
+            BinaryGate::Nand,
+            &sk.encrypt_bit(true),
+            &sk.encrypt_bit(false),
+        );
+        let packed = evaluator.pack_lwes(&[ksk.switch(&bit)]).unwrap();
+        assert_eq!(packed.decrypt(&bfv_sk).inner[0].value(), 1);
+    }
+
+    #[test]
+    fn test_programmable() {
+        let (sk, bk) = keys();
+        for m in 0..4 {
+            let ct = sk.lwe().encrypt::<Q, 8>(m);
+            let out = bk.bootstrap(&ct, |m| 3 * m + 1).unwrap();
+            assert_eq!(sk.lwe().decrypt(&out), (3 * m + 1) % 8);
+        }
+    }
+
+    #[test]
+    fn test_pbs_tables() {
+        // p = 8, values read as 3 bit two's complement for ReLU
+        let (sk, bk) = keys();
+        let relu = [0, 1, 2, 3, 0, 0, 0, 0];
+        let at_least_5 = [0, 0, 0, 0, 0, 1, 1, 1];
+        let sbox = [6, 4, 0, 3, 7, 1, 5, 2];
+        for m in 0..8 {
+            let ct = sk.lwe().encrypt::<Q, 16>(m);
+            let m = m as usize;
+            assert_eq!(sk.lwe().decrypt(&bk.pbs(&ct, &relu).unwrap()), relu[m]);
+            assert_eq!(
+                sk.lwe().decrypt(&bk.pbs(&ct, &at_least_5).unwrap()),
+                at_least_5[m]
+            );
+            let chained = bk.pbs(&bk.pbs(&ct, &sbox).unwrap(), &relu).unwrap();
+            assert_eq!(sk.lwe().decrypt(&chained), relu[sbox[m] as usize]);
+        }
+
+        let ct = sk.lwe().encrypt::<Q, 16>(0);
+        assert_eq!(
+            bk.pbs(&ct, &[0; 16]).unwrap_err(),
+            BootstrapError::TableLength {
+                expected: 8,
+                got: 16
+            }
+        );
+        assert_eq!(
+            bk.pbs(&ct, &[8; 8]).unwrap_err(),
+            BootstrapError::TableEntry {
+                index: 0,
+                value: 8,
+                p: 8
+            }
+        );
+        let wide = sk.lwe().encrypt::<Q, 3>(0);
+        assert_eq!(
+            bk.bootstrap(&wide, |m| m).unwrap_err(),
+            BootstrapError::Modulus { t: 3, n: N }
+        );
+    }
+}
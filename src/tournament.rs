@@ -0,0 +1,365 @@
+//! Maximum and argmax of a small vector by a tournament of comparisons.
+//!
+//! Values are paired up, each pair keeps its larger element through `select(ge(a, b), a, b)`, and the
+//! winners play again until one is left: ceil(log2 n) levels of comparisons. For argmax, every element
+//! carries an indicator bit which is and-ed with its match outcome at every level, so at the end exactly
+//! the winner's indicator is 1.
+//!
+//! The tournament only needs the gadgets in [`CompareOps`], so the same code runs on plaintexts (the
+//! reference `PlainCompare`) and on ciphertexts: [`CircuitCompare`] records the BFV gadgets into a
+//! [`Circuit`], which `Circuit::evaluate` runs and whose depth [`tournament_cost`] predicts. The comparison
+//! is the degree t - 1 polynomial that is 1 on the non-negative half of Z_t, evaluated at a - b.
+
+#[cfg(feature = "bfv")]
+use std::cell::RefCell;
+
+#[cfg(feature = "bfv")]
+use crate::bfv_params::BfvParams;
+#[cfg(feature = "bfv")]
+use crate::bfv_pke::Plaintext;
+#[cfg(feature = "bfv")]
+use crate::circuit::{Circuit, Wire};
+#[cfg(feature = "bfv")]
+use crate::field::{PrimeModulus, Ring};
+#[cfg(feature = "bfv")]
+use crate::polynomial::{Element, Polynomial};
+#[cfg(feature = "bfv")]
+use crate::security::SecurityLevel;
+
+/// Comparison and selection gadgets, bits are 0/1 values of the same type.
+pub trait CompareOps<C> {
+    /// 1 if a >= b, else 0.
+    fn ge(&self, a: &C, b: &C) -> C;
+    /// a if bit is 1, else b.
+    fn select(&self, bit: &C, a: &C, b: &C) -> C;
+    fn and(&self, x: &C, y: &C) -> C;
+    fn not(&self, x: &C) -> C;
+    fn one(&self) -> C;
+    /// Multiplicative depth of one `ge`.
+    fn ge_depth(&self) -> usize;
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Tournament<C> {
+    pub max: C,
+    /// One indicator per input, 1 only at the (first) maximum.
+    pub argmax: Option<Vec<C>>,
+}
+
+/// Depth and gadget counts of a tournament over `n` values.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TournamentCost {
+    pub levels: usize,
+    pub comparisons: usize,
+    /// Multiplicative depth: every level is one `ge` and one `select`. The argmax indicators need the same,
+    /// their `and` runs in parallel with the `select`.
+    pub depth: usize,
+}
+
+#[cfg(feature = "bfv")]
+impl TournamentCost {
+    /// The smallest `BfvParams` preset with the depth, or generated parameters if none has it.
+    pub fn params(&self, security: SecurityLevel, t: u64) -> Option<BfvParams> {
+        let depth = self.depth as u32;
+        BfvParams::select(security, depth, t)
+            .or_else(|| BfvParams::generate(security.bits(), depth, t).ok())
+    }
+}
+
+pub fn tournament_cost(n: usize, ge_depth: usize) -> TournamentCost {
+    let levels = n.max(1).next_power_of_two().trailing_zeros() as usize;
+    TournamentCost {
+        levels,
+        comparisons: n.saturating_sub(1),
+        depth: levels * (ge_depth + 1),
+    }
+}
+
+pub fn tournament_max<C: Clone, O: CompareOps<C>>(
+    ops: &O,
+    values: &[C],
+    with_argmax: bool,
+) -> Tournament<C> {
+    assert!(!values.is_empty(), "tournament over an empty vector");
+
+    // (value, indices of the inputs still behind it)
+    let mut round = values
+        .iter()
+        .enumerate()
+        .map(|(i, v)| (v.clone(), vec![i]))
+        .collect::<Vec<_>>();
+    let mut indicators = with_argmax.then(|| vec![ops.one(); values.len()]);
+
+    while round.len() > 1 {
+        let mut next = Vec::with_capacity(round.len().div_ceil(2));
+        let mut pairs = round.into_iter();
+        while let Some((a, a_idx)) = pairs.next() {
+            let Some((b, b_idx)) = pairs.next() else {
+                // odd one out gets a bye
+                next.push((a, a_idx));
+                break;
+            };
+            let a_wins = ops.ge(&a, &b);
+            if let Some(ind) = indicators.as_mut() {
+                let b_wins = ops.not(&a_wins);
+                for &i in a_idx.iter() {
+                    ind[i] = ops.and(&ind[i], &a_wins);
+                }
+                for &i in b_idx.iter() {
+                    ind[i] = ops.and(&ind[i], &b_wins);
+                }
+            }
+            let winner = ops.select(&a_wins, &a, &b);
+            next.push((winner, [a_idx, b_idx].concat()));
+        }
+        round = next;
+    }
+
+    Tournament {
+        max: round.pop().unwrap().0,
+        argmax: indicators,
+    }
+}
+
+/// Plaintext gadgets, the reference the encrypted ones have to agree with.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PlainCompare;
+
+impl CompareOps<u64> for PlainCompare {
+    fn ge(&self, a: &u64, b: &u64) -> u64 {
+        (a >= b) as u64
+    }
+
+    fn select(&self, bit: &u64, a: &u64, b: &u64) -> u64 {
+        if *bit == 1 { *a } else { *b }
+    }
+
+    fn and(&self, x: &u64, y: &u64) -> u64 {
+        x * y
+    }
+
+    fn not(&self, x: &u64) -> u64 {
+        1 - x
+    }
+
+    fn one(&self) -> u64 {
+        1
+    }
+
+    fn ge_depth(&self) -> usize {
+        0
+    }
+}
+
+/// BFV gadgets over wires of a [`Circuit`], on constant polynomials 0 <= v <= (t - 1) / 2 mod an odd
+/// prime t, so that a - b mod t tells the sign. `ge` costs t - 2 products at depth ceil(log2(t - 1)),
+/// `select` and `and` one product each.
+#[cfg(feature = "bfv")]
+#[derive(Debug)]
+pub struct CircuitCompare<const N: usize, const T: u64> {
+    circuit: RefCell<Circuit<N, T>>,
+    first: RefCell<Option<Wire>>,
+    /// Coefficients of the polynomial that is 1 on [0, (t - 1) / 2] and 0 on the rest of Z_t.
+    sign: Vec<u64>,
+}
+
+#[cfg(feature = "bfv")]
+impl<const N: usize, const T: u64> CircuitCompare<N, T> {
+    /// `None` unless t is an odd prime.
+    pub fn new() -> Option<Self> {
+        let field = PrimeModulus::new(T).filter(|_| T > 2)?;
+        // (x - v)^(t-1) = sum_k x^k v^(t-1-k) mod t, which is 0 at x = v and 1 elsewhere
+        let mut sign = vec![0; T as usize];
+        for v in 0..=(T - 1) / 2 {
+            sign[0] = field.add(sign[0], 1);
+            for (k, c) in sign.iter_mut().enumerate() {
+                *c = field.sub(*c, field.pow(v, T - 1 - k as u64));
+            }
+        }
+        Some(Self {
+            circuit: RefCell::default(),
+            first: RefCell::default(),
+            sign,
+        })
+    }
+
+    pub fn input(&self) -> Wire {
+        let w = self.circuit.borrow_mut().input();
+        self.first.borrow_mut().get_or_insert(w);
+        w
+    }
+
+    pub fn output(&self, w: Wire) {
+        self.circuit.borrow_mut().output(w);
+    }
+
+    pub fn into_circuit(self) -> Circuit<N, T> {
+        self.circuit.into_inner()
+    }
+
+    fn constant(c: i64) -> Plaintext<N, T> {
+        let mut inner = [Element::new(0); N];
+        inner[0] = Element::new(c);
+        Polynomial::new(inner)
+    }
+
+    fn sub(&self, a: Wire, b: Wire) -> Wire {
+        let mut c = self.circuit.borrow_mut();
+        let neg = c.mul_plain(b, Self::constant(-1));
+        c.add(a, neg)
+    }
+}
+
+#[cfg(feature = "bfv")]
+impl<const N: usize, const T: u64> CompareOps<Wire> for CircuitCompare<N, T> {
+    fn ge(&self, a: &Wire, b: &Wire) -> Wire {
+        let d = self.sub(*a, *b);
+        let mut c = self.circuit.borrow_mut();
+        // d^k as d^(2^j) d^(k - 2^j), at depth ceil(log2 k)
+        let mut powers = vec![d; T as usize];
+        for k in 2..T as usize {
+            let high = 1 << (usize::BITS - 1 - k.leading_zeros());
+            let low = if high == k { high / 2 } else { k - high };
+            powers[k] = c.mul(powers[high.min(k - low)], powers[low]);
+        }
+        let mut acc = c.mul_plain(d, Self::constant(self.sign[1] as i64));
+        for (k, &coeff) in self.sign.iter().enumerate().skip(2) {
+            let term = c.mul_plain(powers[k], Self::constant(coeff as i64));
+            acc = c.add(acc, term);
+        }
+        c.add_plain(acc, Self::constant(self.sign[0] as i64))
+    }
+
+    fn select(&self, bit: &Wire, a: &Wire, b: &Wire) -> Wire {
+        let diff = self.sub(*a, *b);
+        let mut c = self.circuit.borrow_mut();
+        let picked = c.mul(*bit, diff);
+        c.add(*b, picked)
+    }
+
+    fn and(&self, x: &Wire, y: &Wire) -> Wire {
+        self.circuit.borrow_mut().mul(*x, *y)
+    }
+
+    fn not(&self, x: &Wire) -> Wire {
+        let mut c = self.circuit.borrow_mut();
+        let neg = c.mul_plain(*x, Self::constant(-1));
+        c.add_plain(neg, Self::constant(1))
+    }
+
+    /// The first input times 0 plus 1, the circuit has no constant wires. Panics before any input.
+    fn one(&self) -> Wire {
+        let first = self.first.borrow().expect("one() needs an input wire");
+        let mut c = self.circuit.borrow_mut();
+        let zero = c.mul_plain(first, Self::constant(0));
+        c.add_plain(zero, Self::constant(1))
+    }
+
+    fn ge_depth(&self) -> usize {
+        (T as usize - 1).next_power_of_two().trailing_zeros() as usize
+    }
+}
+
+/// The tournament over `n` encrypted inputs as a circuit: the maximum, then the argmax indicators if
+/// `with_argmax`. `None` unless t is an odd prime.
+#[cfg(feature = "bfv")]
+pub fn tournament_circuit<const N: usize, const T: u64>(
+    n: usize,
+    with_argmax: bool,
+) -> Option<Circuit<N, T>> {
+    let ops = CircuitCompare::<N, T>::new()?;
+    let inputs = (0..n).map(|_| ops.input()).collect::<Vec<_>>();
+    let res = tournament_max(&ops, &inputs, with_argmax);
+    ops.output(res.max);
+    for w in res.argmax.into_iter().flatten() {
+        ops.output(w);
+    }
+    Some(ops.into_circuit())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_plain_max_argmax() {
+        let values = [3u64, 9, 4, 9, 1];
+        let res = tournament_max(&PlainCompare, &values, true);
+        assert_eq!(res.max, 9);
+        // ties go to the left operand, so the first 9 wins
+        assert_eq!(res.argmax, Some(vec![0, 1, 0, 0, 0]));
+
+        let res = tournament_max(&PlainCompare, &[7u64], false);
+        assert_eq!(
+            res,
+            Tournament {
+                max: 7,
+                argmax: None
+            }
+        );
+    }
+
+    #[test]
+    fn test_cost() {
+        let cost = tournament_cost(5, 4);
+        assert_eq!(cost.levels, 3);
+        assert_eq!(cost.comparisons, 4);
+        assert_eq!(cost.depth, 15);
+        assert_eq!(tournament_cost(1, 4).levels, 0);
+        assert_eq!(tournament_cost(8, 0).levels, 3);
+    }
+
+    #[test]
+    #[cfg(feature = "bfv")]
+    fn test_encrypted_max_argmax() {
+        use crate::bfv_pke::{Bfv, Evaluator};
+
+        const N: usize = 16;
+        const Q: u64 = 1 << 59;
+        const T: u64 = 5;
+
+        let field = PrimeModulus::new(T).unwrap();
+        let ops = CircuitCompare::<N, T>::new().unwrap();
+        // the sign polynomial in the clear agrees with `PlainCompare` on [0, (t - 1) / 2]
+        for a in 0..=(T - 1) / 2 {
+            for b in 0..=(T - 1) / 2 {
+                let x = field.sub(a, b);
+                let ge = ops
+                    .sign
+                    .iter()
+                    .rev()
+                    .fold(0, |acc, &c| field.add(field.mul(acc, x), c));
+                assert_eq!(ge, PlainCompare.ge(&a, &b), "{a} >= {b}");
+            }
+        }
+
+        let (bfv, sk) = Bfv::<N, Q, T>::keygen().unwrap();
+        let evaluator = Evaluator::new().with_relin_key(bfv.gen_relin_key(&sk, 8));
+        for values in [[1u64, 2, 0], [2, 2, 1], [0, 0, 0]] {
+            let circuit = tournament_circuit::<N, T>(values.len(), true).unwrap();
+            let cost = tournament_cost(values.len(), ops.ge_depth());
+            assert_eq!(circuit.depth() as usize, cost.depth);
+
+            let inputs = values
+                .iter()
+                .map(|&v| bfv.encrypt(CircuitCompare::<N, T>::constant(v as i64)))
+                .collect::<Vec<_>>();
+            let out = circuit
+                .evaluate(&evaluator, &inputs)
+                .unwrap()
+                .iter()
+                .map(|ct| ct.decrypt(&sk).inner[0].value())
+                .collect::<Vec<_>>();
+            let plain = tournament_max(&PlainCompare, &values, true);
+            assert_eq!(out[0], plain.max, "{values:?}");
+            assert_eq!(out[1..].to_vec(), plain.argmax.unwrap(), "{values:?}");
+        }
+
+        assert!(CircuitCompare::<N, 2>::new().is_none());
+        assert!(CircuitCompare::<N, 9>::new().is_none());
+        let params = tournament_cost(2, 1)
+            .params(SecurityLevel::Bits128, 2)
+            .unwrap();
+        assert!(params.depth >= 2);
+    }
+}
@@ -0,0 +1,160 @@
+//! wasm-bindgen bindings for the client half of hybrid HE (`wasm` feature): Pasta key generation and
+//! encryption, and the Pasta key encrypted under the server's BFV public key. The server stays native
+//! Rust, reads the key back with [`read_encrypted_key`] and transciphers with [`Transcipher`].
+//!
+//! **A demo, not secure.** Const generics can't cross into JavaScript, so the bindings fix one
+//! instantiation, the toy sizes the `hybrid` tests transcipher at: n = 8 and a 2 word, 2 round Pasta
+//! give no security at all. Secure sizes (PASTA-3 and a ring from `bfv_params`) are out of reach of the
+//! single 64 bit q the transciphering server runs at, see `pasta_bfv`.
+//!
+//! The cdylib comes from the wrapper crate in `wasm/`, so consumers of `rlattice` only build an rlib:
+//! `wasm-pack build wasm --target web`.
+//!
+//! [`Transcipher`]: crate::pasta_bfv::Transcipher
+
+use wasm_bindgen::prelude::*;
+
+use crate::bfv_pke::{Bfv, BfvCiphertext, BfvPublicKey};
+use crate::error::Error;
+use crate::pasta_bfv::EncryptedPastaKey;
+use crate::pasta_plain::{Pasta, PastaKey};
+use crate::polynomial::DecodeError;
+
+/// BFV ring dimension, toy sized.
+pub const N: usize = 8;
+/// BFV ciphertext modulus.
+pub const Q: u64 = 1 << 59;
+/// BFV plaintext modulus and Pasta prime, 1 mod 2n for batching.
+pub const T: u64 = 17;
+/// Pasta block size in words.
+pub const W: usize = 2;
+/// Pasta rounds.
+pub const R: usize = 2;
+
+/// The browser side: a Pasta key mod t, at the demo sizes above.
+#[wasm_bindgen]
+pub struct PastaClient {
+    key: PastaKey<W>,
+}
+
+#[wasm_bindgen]
+impl PastaClient {
+    /// Fresh random key.
+    #[wasm_bindgen(constructor)]
+    pub fn generate() -> PastaClient {
+        Self {
+            key: PastaKey::generate(&mut rand::rng(), T),
+        }
+    }
+
+    /// Key from its 2W words, e.g. one the page stored earlier.
+    #[wasm_bindgen(js_name = fromKey)]
+    pub fn from_key(words: Vec<u64>) -> Result<PastaClient, JsError> {
+        Ok(Self {
+            key: PastaKey::try_new(words, T)?,
+        })
+    }
+
+    pub fn key(&self) -> Vec<u64> {
+        self.key.words().to_vec()
+    }
+
+    /// Pasta ciphertext of `plaintext` under `nonce`, every word must be < t.
+    pub fn encrypt(&self, nonce: u64, plaintext: Vec<u64>) -> Result<Vec<u64>, JsError> {
+        Ok(self.apply(nonce, &plaintext, true)?)
+    }
+
+    pub fn decrypt(&self, nonce: u64, ciphertext: Vec<u64>) -> Result<Vec<u64>, JsError> {
+        Ok(self.apply(nonce, &ciphertext, false)?)
+    }
+
+    /// The key words encrypted under the BFV public key `public_key` (`BfvPublicKey::to_bytes`), as
+    /// concatenated `BfvCiphertext::to_bytes`.
+    #[wasm_bindgen(js_name = encryptKey)]
+    pub fn encrypt_key(&self, public_key: &[u8]) -> Result<Vec<u8>, JsError> {
+        Ok(self.encrypted_key(public_key)?)
+    }
+}
+
+impl PastaClient {
+    fn apply(&self, nonce: u64, words: &[u64], encrypt: bool) -> Result<Vec<u64>, Error> {
+        if let Some(&w) = words.iter().find(|&&w| w >= T) {
+            return Err(DecodeError::InvalidField {
+                field: "word",
+                value: w,
+            }
+            .into());
+        }
+        let mut pasta = Pasta::<W, R>::with_key(self.key.clone(), T);
+        Ok(if encrypt {
+            pasta.encrypt_with_nonce(nonce, words)
+        } else {
+            pasta.decrypt_with_nonce(nonce, words)
+        })
+    }
+
+    fn encrypted_key(&self, public_key: &[u8]) -> Result<Vec<u8>, Error> {
+        let bfv = Bfv::<N, Q, T>::from_public_key(BfvPublicKey::from_bytes(public_key)?);
+        let key = EncryptedPastaKey::<N, Q, T, W>::encrypt(&bfv, &self.key)?;
+        Ok(key.words().iter().flat_map(|ct| ct.to_bytes()).collect())
+    }
+}
+
+/// Server side of [`PastaClient::encrypt_key`].
+pub fn read_encrypted_key(
+    bfv: &Bfv<N, Q, T>,
+    bytes: &[u8],
+) -> Result<EncryptedPastaKey<N, Q, T, W>, Error> {
+    let len = BfvCiphertext::<N, Q, T>::BYTES;
+    if bytes.len() != 2 * W * len {
+        return Err(DecodeError::Length {
+            expected: 2 * W * len,
+            got: bytes.len(),
+        }
+        .into());
+    }
+    let words = bytes
+        .chunks(len)
+        .map(|ct| bfv.ciphertext_from_bytes(ct))
+        .collect::<Result<_, _>>()?;
+    Ok(EncryptedPastaKey::from_ciphertexts(words)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::batch::BatchEncoder;
+    use crate::bfv_pke::{Decryptor, Evaluator};
+    use crate::encoding::Encoder;
+    use crate::pasta_bfv::Transcipher;
+
+    #[test]
+    fn test_client_to_native_server() {
+        let (bfv, sk) = Bfv::<N, Q, T>::keygen().unwrap();
+        let client = PastaClient::generate();
+        let restored = PastaClient::from_key(client.key()).unwrap();
+
+        let data = (0..2 * W as u64).map(|i| i * 5 % T).collect::<Vec<_>>();
+        let ct = restored.encrypt(9, data.clone()).unwrap();
+        assert_eq!(client.decrypt(9, ct.clone()).unwrap(), data);
+
+        let key = client.encrypt_key(&bfv.public_key().to_bytes()).unwrap();
+        let relin = bfv.gen_relin_key(&sk, 8);
+        let transcipher = Transcipher::<N, Q, T, Pasta<W, R>>::new(
+            read_encrypted_key(&bfv, &key).unwrap().into_words(),
+            Evaluator::new().with_relin_key(relin),
+        )
+        .unwrap();
+        let (decryptor, encoder) = (Decryptor::new(sk), BatchEncoder::<N, T>::new().unwrap());
+        let words = transcipher
+            .decrypt(9, 0, &ct)
+            .unwrap()
+            .iter()
+            .map(|c| encoder.decode(&decryptor.decrypt(c)))
+            .collect::<Vec<_>>();
+        for (k, &w) in data.iter().enumerate() {
+            assert_eq!(words[k % W][k / W], w);
+        }
+        assert!(read_encrypted_key(&bfv, &key[1..]).is_err());
+    }
+}
@@ -0,0 +1,3 @@
+//! The `rlattice::wasm` bindings as a cdylib for wasm-pack: `wasm-pack build wasm --target web`.
+
+pub use rlattice::wasm::*;